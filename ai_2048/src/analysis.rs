@@ -0,0 +1,293 @@
+use crate::expectimax::{SearchConfig, best_move};
+use crate::heuristic::LOST_PENALTY;
+use serde::{Deserialize, Serialize};
+use twothousand_forty_eight::board::Board;
+use twothousand_forty_eight::direction::Direction;
+use twothousand_forty_eight::unified::reconstruction::Reconstructable;
+use twothousand_forty_eight::v2::recording::SeededRecording;
+use twothousand_forty_eight::v2::replay::MoveReplayError;
+use web_time::Instant;
+
+/// Expected value a move can give up and still count as good, in units of [LOST_PENALTY]
+const GOOD_LOSS: f64 = 0.01;
+/// Past this a move is a mistake
+const MISTAKE_LOSS: f64 = 0.05;
+/// Past this a move is a blunder, like pulling the biggest tiles out of their row
+const BLUNDER_LOSS: f64 = 0.15;
+
+/// What the engine thought of a move, chess style
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verdict {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Verdict {
+    fn from_loss(loss: f64) -> Self {
+        if loss <= 0.0 {
+            Verdict::Best
+        } else if loss <= GOOD_LOSS {
+            Verdict::Good
+        } else if loss <= MISTAKE_LOSS {
+            Verdict::Inaccuracy
+        } else if loss <= BLUNDER_LOSS {
+            Verdict::Mistake
+        } else {
+            Verdict::Blunder
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MoveAnalysis {
+    /// Index of the move in the recording
+    pub move_index: usize,
+    pub played: Direction,
+    pub best: Direction,
+    /// Expected value the played move gave up against the best one, 0 for the best move. In units
+    /// of the heuristic's lost penalty rather than a share of the best move's value, which carries
+    /// that penalty once per row and column and so hides even big losses
+    pub loss: f64,
+    pub verdict: Verdict,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameAnalysis {
+    /// Every move with more than one option. Forced moves and breaks are left out
+    pub moves: Vec<MoveAnalysis>,
+    /// 0 to 100, how close the moves were to the engine's on average
+    pub accuracy: f64,
+}
+
+impl GameAnalysis {
+    pub fn count(&self, verdict: Verdict) -> usize {
+        self.moves
+            .iter()
+            .filter(|analysis| analysis.verdict == verdict)
+            .count()
+    }
+}
+
+/// 1 for the best move, falling off quickly so a few good looking moves do not hide the bad ones
+fn move_accuracy(loss: f64) -> f64 {
+    (-loss / GOOD_LOSS).exp()
+}
+
+/// Scores the move played on the board against the engine's. `None` for forced moves and breaks
+fn analyze_move(
+    board: &Board,
+    played: Direction,
+    move_index: usize,
+    search: &SearchConfig,
+) -> Option<MoveAnalysis> {
+    let hint = best_move(board, search)?;
+    if hint.evaluations.len() < 2 {
+        return None;
+    }
+    let best_value = hint.evaluations[0].1;
+    //Not in the evaluations for breaks and the rare 6x6 board the engine sees differently
+    let (_, played_value) = hint
+        .evaluations
+        .iter()
+        .find(|(direction, _)| *direction == played)?;
+    let loss = ((best_value - played_value) / LOST_PENALTY).max(0.0);
+    Some(MoveAnalysis {
+        move_index,
+        played,
+        best: hint.direction,
+        loss,
+        verdict: Verdict::from_loss(loss),
+    })
+}
+
+/// How much of a game [analyze_within] looks at, for callers that can not wait out a long one
+#[derive(Clone, Copy, Debug)]
+pub struct AnalysisBudget {
+    /// Games with more moves than this have this many spread evenly over them scored
+    pub max_positions: usize,
+    /// Analysis gives up once this passes
+    pub deadline: Instant,
+}
+
+/// Replays the recording and scores every move against what the engine would have played
+pub fn analyze(
+    recording: &SeededRecording,
+    search: &SearchConfig,
+) -> Result<GameAnalysis, MoveReplayError> {
+    //Without a deadline every move gets scored
+    analyze_positions(recording, search, 1, None).map(Option::unwrap_or_default)
+}
+
+/// Like [analyze], but scores a sample of the moves when there are more than the budget allows.
+/// `None` when the deadline passed before every sampled move was scored
+pub fn analyze_within(
+    recording: &SeededRecording,
+    search: &SearchConfig,
+    budget: &AnalysisBudget,
+) -> Result<Option<GameAnalysis>, MoveReplayError> {
+    let step = recording
+        .moves
+        .len()
+        .div_ceil(budget.max_positions.max(1))
+        .max(1);
+    analyze_positions(recording, search, step, Some(budget.deadline))
+}
+
+fn analyze_positions(
+    recording: &SeededRecording,
+    search: &SearchConfig,
+    step: usize,
+    deadline: Option<Instant>,
+) -> Result<Option<GameAnalysis>, MoveReplayError> {
+    let reconstruction = recording.reconstruct()?;
+    let mut moves = vec![];
+    for (move_index, (board, played)) in reconstruction
+        .history
+        .iter()
+        .zip(recording.moves.iter().copied())
+        .enumerate()
+        .step_by(step)
+    {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Ok(None);
+        }
+        moves.extend(analyze_move(board, played, move_index, search));
+    }
+    let accuracy = if moves.is_empty() {
+        100.0
+    } else {
+        moves
+            .iter()
+            .map(|analysis| move_accuracy(analysis.loss))
+            .sum::<f64>()
+            / moves.len() as f64
+            * 100.0
+    };
+    Ok(Some(GameAnalysis { moves, accuracy }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deep enough to see past the next tile, with time to spare so the depth is always reached
+    const SEARCH: SearchConfig = SearchConfig {
+        depth: 2,
+        time_budget_ms: 60_000,
+    };
+
+    fn board(rows: [[usize; 4]; 4]) -> Board {
+        let mut board = Board::new(4, 4, 1);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, value) in row.iter().enumerate() {
+                if *value > 0 {
+                    board.set_tile(x, y, *value);
+                }
+            }
+        }
+        board
+    }
+
+    // The top row is built up from the corner. Down pulls all of it but the corner tile out of
+    // the row, the classic way to throw a game away
+    fn built_up_top_row() -> Board {
+        board([[128, 64, 32, 16], [4, 0, 0, 0], [2, 8, 0, 0], [4, 2, 8, 4]])
+    }
+
+    #[test]
+    fn pulling_the_top_row_apart_is_a_blunder() {
+        let analysis = analyze_move(&built_up_top_row(), Direction::DOWN, 0, &SEARCH).unwrap();
+        assert_eq!(analysis.verdict, Verdict::Blunder);
+        assert_ne!(analysis.best, Direction::DOWN);
+    }
+
+    #[test]
+    fn keeping_the_top_row_together_is_not_a_mistake() {
+        for played in [Direction::UP, Direction::RIGHT] {
+            let analysis = analyze_move(&built_up_top_row(), played, 0, &SEARCH).unwrap();
+            assert!(
+                matches!(analysis.verdict, Verdict::Best | Verdict::Good),
+                "{:?} was a {:?}",
+                played,
+                analysis.verdict
+            );
+        }
+    }
+
+    #[test]
+    fn the_engines_move_is_best() {
+        let board = built_up_top_row();
+        let best = best_move(&board, &SEARCH).unwrap().direction;
+        let analysis = analyze_move(&board, best, 0, &SEARCH).unwrap();
+        assert_eq!(analysis.verdict, Verdict::Best);
+        assert_eq!(analysis.loss, 0.0);
+    }
+
+    // Plays the engine's moves at a shallow depth, quick to make
+    fn engine_game(moves: usize) -> SeededRecording {
+        let mut recording = SeededRecording::empty(7, 4, 4);
+        for _ in 0..moves {
+            let board = *recording.reconstruct().unwrap().history.last().unwrap();
+            let Some(hint) = crate::best_move_at_depth(&board, 1) else {
+                break;
+            };
+            recording.moves.push(hint.direction);
+        }
+        recording
+    }
+
+    #[test]
+    fn long_games_are_sampled() {
+        let recording = engine_game(60);
+        let budget = AnalysisBudget {
+            max_positions: 10,
+            deadline: Instant::now() + web_time::Duration::from_secs(60),
+        };
+        let analysis = analyze_within(&recording, &SEARCH, &budget)
+            .unwrap()
+            .unwrap();
+        assert!(!analysis.moves.is_empty());
+        assert!(analysis.moves.len() <= 10);
+        assert!(
+            analysis
+                .moves
+                .iter()
+                .all(|analysis| analysis.move_index % 6 == 0)
+        );
+    }
+
+    #[test]
+    fn short_games_are_analysed_in_full() {
+        let recording = engine_game(20);
+        let budget = AnalysisBudget {
+            max_positions: 100,
+            deadline: Instant::now() + web_time::Duration::from_secs(60),
+        };
+        let within = analyze_within(&recording, &SEARCH, &budget).unwrap();
+        assert_eq!(within, Some(analyze(&recording, &SEARCH).unwrap()));
+    }
+
+    #[test]
+    fn analysis_stops_at_the_deadline() {
+        let budget = AnalysisBudget {
+            max_positions: 100,
+            deadline: Instant::now(),
+        };
+        assert_eq!(
+            analyze_within(&engine_game(20), &SEARCH, &budget).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn verdicts_follow_the_loss() {
+        assert_eq!(Verdict::from_loss(0.0), Verdict::Best);
+        assert_eq!(Verdict::from_loss(GOOD_LOSS), Verdict::Good);
+        assert_eq!(Verdict::from_loss(MISTAKE_LOSS), Verdict::Inaccuracy);
+        assert_eq!(Verdict::from_loss(BLUNDER_LOSS), Verdict::Mistake);
+        assert_eq!(Verdict::from_loss(BLUNDER_LOSS * 2.0), Verdict::Blunder);
+    }
+}
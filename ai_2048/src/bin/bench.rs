@@ -0,0 +1,238 @@
+//! Plays a batch of seeded games with each bot and prints how they did.
+//!
+//! cargo run --release -p ai_2048 --bin bench -- --games 50 --strategy all --export-dir fixtures
+//!
+//! Every game is checked by replaying its seeded recording the same way the app does, and the
+//! recordings can be exported one per line to use as fixtures.
+
+use ai_2048::SearchConfig;
+use ai_2048::strategy::{CornerStrategy, ExpectimaxStrategy, RandomStrategy, Strategy, play_game};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use twothousand_forty_eight::unified::game::GameState;
+use twothousand_forty_eight::v2::recording::SeededRecording;
+
+const STRATEGIES: [&str; 3] = ["random", "corner", "expectimax"];
+
+const USAGE: &str = "Usage: bench [options]
+  --games <n>            Games per strategy (default 20)
+  --strategy <name>      random, corner, expectimax or all (default all)
+  --size <n>             Board width and height, 3 to 6 (default 4)
+  --seed <n>             Seed of the first game, each game after adds one (default 1)
+  --max-moves <n>        Stop a game after this many moves
+  --depth <n>            Expectimax search depth
+  --time-budget <ms>     Expectimax thinking time per move
+  --export-dir <path>    Write each strategy's seeded recordings to <path>/<strategy>.txt";
+
+struct Options {
+    games: u32,
+    strategies: Vec<&'static str>,
+    size: usize,
+    seed: u32,
+    max_moves: Option<usize>,
+    search: SearchConfig,
+    export_dir: Option<PathBuf>,
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("{} is not a valid value for {}", value, flag))
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        games: 20,
+        strategies: STRATEGIES.to_vec(),
+        size: 4,
+        seed: 1,
+        max_moves: None,
+        search: SearchConfig::default(),
+        export_dir: None,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--games" => options.games = parse_value(&flag, args.next())?,
+            "--strategy" => {
+                let name: String = parse_value(&flag, args.next())?;
+                options.strategies = match STRATEGIES.iter().find(|strategy| **strategy == name) {
+                    Some(strategy) => vec![*strategy],
+                    None if name == "all" => STRATEGIES.to_vec(),
+                    None => return Err(format!("Unknown strategy {}", name)),
+                };
+            }
+            "--size" => options.size = parse_value(&flag, args.next())?,
+            "--seed" => options.seed = parse_value(&flag, args.next())?,
+            "--max-moves" => options.max_moves = Some(parse_value(&flag, args.next())?),
+            "--depth" => options.search.depth = parse_value(&flag, args.next())?,
+            "--time-budget" => options.search.time_budget_ms = parse_value(&flag, args.next())?,
+            "--export-dir" => options.export_dir = Some(parse_value(&flag, args.next())?),
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ => return Err(format!("Unknown option {}\n{}", flag, USAGE)),
+        }
+    }
+    if !(3..=twothousand_forty_eight::board::MAX_WIDTH).contains(&options.size) {
+        return Err(format!(
+            "--size has to be between 3 and {}",
+            twothousand_forty_eight::board::MAX_WIDTH
+        ));
+    }
+    Ok(options)
+}
+
+fn new_strategy(name: &str, seed: u32, search: SearchConfig) -> Box<dyn Strategy> {
+    match name {
+        "random" => Box::new(RandomStrategy::new(seed)),
+        "corner" => Box::new(CornerStrategy),
+        _ => Box::new(ExpectimaxStrategy { search }),
+    }
+}
+
+/// A finished game after its recording was replayed
+struct GameResult {
+    recording: String,
+    score: usize,
+    max_tile: usize,
+    moves: usize,
+    won: bool,
+}
+
+fn verify(recording: &SeededRecording) -> Result<GameResult, String> {
+    let gamestate = GameState::from_reconstructable_ruleset(recording).map_err(|err| {
+        format!(
+            "Recording for seed {} does not replay: {}",
+            recording.seed, err
+        )
+    })?;
+    let max_tile = gamestate
+        .board
+        .get_occupied_tiles()
+        .iter()
+        .map(|tile| tile.value)
+        .max()
+        .unwrap_or(0);
+    Ok(GameResult {
+        recording: recording.into(),
+        score: gamestate.score_max,
+        max_tile,
+        moves: recording.moves.len(),
+        won: gamestate.won,
+    })
+}
+
+/// Spreads the games over every core since the expectimax games take a while
+fn run_strategy(name: &'static str, options: &Options) -> Result<Vec<GameResult>, String> {
+    let seeds: Vec<u32> = (0..options.games)
+        .map(|game| options.seed.wrapping_add(game))
+        .collect();
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk_size = seeds.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = seeds
+            .chunks(chunk_size)
+            .map(|seeds| {
+                scope.spawn(move || {
+                    seeds
+                        .iter()
+                        .map(|seed| {
+                            let mut strategy = new_strategy(name, *seed, options.search);
+                            let recording = play_game(
+                                strategy.as_mut(),
+                                *seed,
+                                options.size,
+                                options.max_moves,
+                            );
+                            verify(&recording)
+                        })
+                        .collect::<Result<Vec<_>, String>>()
+                })
+            })
+            .collect();
+        let mut results = vec![];
+        for handle in handles {
+            let chunk = handle
+                .join()
+                .map_err(|_| format!("A {} game panicked", name))??;
+            results.extend(chunk);
+        }
+        Ok(results)
+    })
+}
+
+fn print_summary(name: &str, results: &[GameResult]) {
+    let mut scores: Vec<usize> = results.iter().map(|result| result.score).collect();
+    scores.sort_unstable();
+    let games = results.len().max(1);
+    let average_score = scores.iter().sum::<usize>() / games;
+    let median_score = scores.get(scores.len() / 2).copied().unwrap_or(0);
+    let best_score = scores.last().copied().unwrap_or(0);
+    let average_moves = results.iter().map(|result| result.moves).sum::<usize>() / games;
+    let wins = results.iter().filter(|result| result.won).count();
+    println!(
+        "{:<12}{:>7}{:>12}{:>10}{:>10}{:>11}{:>11.1}%",
+        name,
+        results.len(),
+        average_score,
+        median_score,
+        best_score,
+        average_moves,
+        wins as f64 / games as f64 * 100.0
+    );
+
+    let mut max_tiles: Vec<usize> = results.iter().map(|result| result.max_tile).collect();
+    max_tiles.sort_unstable();
+    max_tiles.dedup();
+    let distribution = max_tiles
+        .iter()
+        .map(|max_tile| {
+            let count = results
+                .iter()
+                .filter(|result| result.max_tile == *max_tile)
+                .count();
+            format!("{}: {}", max_tile, count)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    println!("{:<12}max tiles {}", "", distribution);
+}
+
+fn main() -> Result<(), String> {
+    let options = parse_options()?;
+    if let Some(export_dir) = &options.export_dir {
+        fs::create_dir_all(export_dir)
+            .map_err(|e| format!("Could not create {}: {}", export_dir.display(), e))?;
+    }
+
+    println!(
+        "{} games per strategy on a {}x{} board, seeds {} to {}",
+        options.games,
+        options.size,
+        options.size,
+        options.seed,
+        options.seed.wrapping_add(options.games.saturating_sub(1))
+    );
+    println!(
+        "{:<12}{:>7}{:>12}{:>10}{:>10}{:>11}{:>12}",
+        "strategy", "games", "avg score", "median", "best", "avg moves", "win rate"
+    );
+    for name in &options.strategies {
+        let results = run_strategy(name, &options)?;
+        print_summary(name, &results);
+
+        if let Some(export_dir) = &options.export_dir {
+            let path = export_dir.join(format!("{}.txt", name));
+            let recordings = results
+                .iter()
+                .map(|result| result.recording.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            fs::write(&path, recordings + "\n")
+                .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use crate::grid::Grid;
+use crate::heuristic;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use twothousand_forty_eight::board::Board;
+use twothousand_forty_eight::direction::{Direction, MOVE_DIRECTIONS};
+use web_time::{Duration, Instant};
+
+/// The deepest search the settings allow. Past this a search rarely finishes inside its time budget
+pub const MAX_DEPTH: u8 = 6;
+/// Odds of a new tile being a 2 instead of a 4
+const TWO_PROBABILITY: f64 = 0.9;
+/// Spawns less likely than this are scored as they are instead of searched any deeper
+const MIN_PROBABILITY: f64 = 0.0001;
+
+/// How hard the engine looks for a move
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchConfig {
+    /// How many moves ahead to look
+    pub depth: u8,
+    /// Once this many milliseconds have passed the deepest finished search is used
+    pub time_budget_ms: u64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            depth: 3,
+            time_budget_ms: 250,
+        }
+    }
+}
+
+/// The move the engine would make
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hint {
+    pub direction: Direction,
+    /// How many moves ahead the search got before running out of depth or time
+    pub depth: u8,
+    /// The expected value of every move that changes the board, best first
+    pub evaluations: Vec<(Direction, f64)>,
+}
+
+struct Search {
+    /// `None` for the first pass so there is always a move to give back
+    deadline: Option<Instant>,
+    cache: HashMap<(Grid, u8), f64>,
+}
+
+impl Search {
+    fn out_of_time(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// The player's turn, they take the best move. `None` when the search ran out of time
+    fn player_turn(&mut self, grid: &Grid, depth: u8, probability: f64) -> Option<f64> {
+        let mut best = 0.0;
+        for direction in MOVE_DIRECTIONS {
+            if let Some((moved, _)) = grid.shift(direction) {
+                best = f64::max(best, self.tile_spawn(&moved, depth - 1, probability)?);
+            }
+        }
+        //Boards with no moves left stay at 0, the worst a board can score
+        Some(best)
+    }
+
+    /// The game's turn, a 2 or 4 shows up on any empty cell
+    fn tile_spawn(&mut self, grid: &Grid, depth: u8, probability: f64) -> Option<f64> {
+        if depth == 0 || probability < MIN_PROBABILITY {
+            return Some(heuristic::score(grid));
+        }
+        if let Some(value) = self.cache.get(&(*grid, depth)) {
+            return Some(*value);
+        }
+        if self.out_of_time() {
+            return None;
+        }
+
+        let empty_cells = grid.empty_cells();
+        let cell_probability = probability / empty_cells.len() as f64;
+        let mut total = 0.0;
+        for cell in &empty_cells {
+            total += TWO_PROBABILITY
+                * self.player_turn(
+                    &grid.with_tile(*cell, 1),
+                    depth,
+                    cell_probability * TWO_PROBABILITY,
+                )?;
+            total += (1.0 - TWO_PROBABILITY)
+                * self.player_turn(
+                    &grid.with_tile(*cell, 2),
+                    depth,
+                    cell_probability * (1.0 - TWO_PROBABILITY),
+                )?;
+        }
+        let value = total / empty_cells.len() as f64;
+        self.cache.insert((*grid, depth), value);
+        Some(value)
+    }
+
+    fn evaluate_moves(&mut self, grid: &Grid, depth: u8) -> Option<Vec<(Direction, f64)>> {
+        let mut evaluations = vec![];
+        for direction in MOVE_DIRECTIONS {
+            if let Some((moved, _)) = grid.shift(direction) {
+                evaluations.push((direction, self.tile_spawn(&moved, depth - 1, 1.0)?));
+            }
+        }
+        evaluations.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Some(evaluations)
+    }
+}
+
+/// Expectimax search for the best move on `board`. Searches one move deeper at a time until it
+/// hits the configured depth or runs out of time. `None` when there are no moves left
+pub fn best_move(board: &Board, config: &SearchConfig) -> Option<Hint> {
+    let grid = Grid::from(board);
+    let deadline = Instant::now() + Duration::from_millis(config.time_budget_ms);
+    let mut hint: Option<Hint> = None;
+    for depth in 1..=config.depth.clamp(1, MAX_DEPTH) {
+        let mut search = Search {
+            deadline: hint.is_some().then_some(deadline),
+            cache: HashMap::new(),
+        };
+        let Some(evaluations) = search.evaluate_moves(&grid, depth) else {
+            break;
+        };
+        //Only the first pass can find no moves, the board is the same for every pass
+        let (direction, _) = evaluations.first()?;
+        hint = Some(Hint {
+            direction: *direction,
+            depth,
+            evaluations,
+        });
+        if Instant::now() >= deadline {
+            break;
+        }
+    }
+    hint
+}
+
+/// Expectimax search straight to `depth` with no time limit, so the same board always gets the
+/// same move back however busy the machine is. `None` when there are no moves left
+pub fn best_move_at_depth(board: &Board, depth: u8) -> Option<Hint> {
+    let depth = depth.clamp(1, MAX_DEPTH);
+    let mut search = Search {
+        deadline: None,
+        cache: HashMap::new(),
+    };
+    let evaluations = search.evaluate_moves(&Grid::from(board), depth)?;
+    let (direction, _) = evaluations.first()?;
+    Some(Hint {
+        direction: *direction,
+        depth,
+        evaluations,
+    })
+}
@@ -0,0 +1,105 @@
+use twothousand_forty_eight::board::{Board, MAX_HEIGHT, MAX_WIDTH};
+use twothousand_forty_eight::direction::Direction;
+
+/// A board boiled down to the exponent of each tile, with 0 being an empty cell.
+/// Much cheaper to copy, move and hash than a [Board] while searching
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Grid {
+    pub width: usize,
+    pub height: usize,
+    pub cells: [[u8; MAX_WIDTH]; MAX_HEIGHT],
+}
+
+impl From<&Board> for Grid {
+    fn from(board: &Board) -> Self {
+        let mut cells = [[0; MAX_WIDTH]; MAX_HEIGHT];
+        for tile in board.get_occupied_tiles() {
+            cells[tile.y][tile.x] = tile.value.trailing_zeros() as u8;
+        }
+        Self {
+            width: board.width,
+            height: board.height,
+            cells,
+        }
+    }
+}
+
+impl Grid {
+    pub fn empty_cells(&self) -> Vec<(usize, usize)> {
+        let mut empty = vec![];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.cells[y][x] == 0 {
+                    empty.push((x, y));
+                }
+            }
+        }
+        empty
+    }
+
+    /// The cells of every row and column, which is what the heuristic scores
+    pub fn lines(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let rows = (0..self.height).map(|y| self.cells[y][..self.width].to_vec());
+        let columns = (0..self.width).map(|x| (0..self.height).map(|y| self.cells[y][x]).collect());
+        rows.chain(columns)
+    }
+
+    /// The coordinates of each line tiles slide along, starting at the edge they slide towards
+    fn slide_lines(&self, direction: Direction) -> Vec<Vec<(usize, usize)>> {
+        match direction {
+            Direction::LEFT => (0..self.height)
+                .map(|y| (0..self.width).map(|x| (x, y)).collect())
+                .collect(),
+            Direction::RIGHT => (0..self.height)
+                .map(|y| (0..self.width).rev().map(|x| (x, y)).collect())
+                .collect(),
+            Direction::UP => (0..self.width)
+                .map(|x| (0..self.height).map(|y| (x, y)).collect())
+                .collect(),
+            Direction::DOWN => (0..self.width)
+                .map(|x| (0..self.height).rev().map(|y| (x, y)).collect())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Slides and merges the tiles like the game does. Returns the new grid and the score gained,
+    /// or `None` when the move would not change anything. The game gives up sliding after a set
+    /// number of passes, so on crowded 6x6 boards it can very rarely leave a gap this does not
+    pub fn shift(&self, direction: Direction) -> Option<(Grid, u64)> {
+        let mut shifted = *self;
+        let mut score_gain = 0;
+        for line in self.slide_lines(direction) {
+            let ranks = line
+                .iter()
+                .map(|(x, y)| self.cells[*y][*x])
+                .filter(|rank| *rank != 0);
+            let mut merged: Vec<u8> = Vec::with_capacity(line.len());
+            //A tile that was just made by a merge can not merge again in the same move
+            let mut can_merge = false;
+            for rank in ranks {
+                match merged.last_mut() {
+                    Some(last) if can_merge && *last == rank => {
+                        *last += 1;
+                        score_gain += 1 << *last;
+                        can_merge = false;
+                    }
+                    _ => {
+                        merged.push(rank);
+                        can_merge = true;
+                    }
+                }
+            }
+            for (i, (x, y)) in line.iter().enumerate() {
+                shifted.cells[*y][*x] = merged.get(i).copied().unwrap_or(0);
+            }
+        }
+        (shifted != *self).then_some((shifted, score_gain))
+    }
+
+    pub fn with_tile(&self, (x, y): (usize, usize), rank: u8) -> Grid {
+        let mut grid = *self;
+        grid.cells[y][x] = rank;
+        grid
+    }
+}
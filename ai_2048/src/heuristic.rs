@@ -0,0 +1,58 @@
+use crate::grid::Grid;
+
+//Weights from nneonneo's 2048-ai, tuned for 4x4 boards but they hold up on the other sizes too
+pub(crate) const LOST_PENALTY: f64 = 200_000.0;
+const MONOTONICITY_POWER: i32 = 4;
+const MONOTONICITY_WEIGHT: f64 = 47.0;
+const SUM_POWER: f64 = 3.5;
+const SUM_WEIGHT: f64 = 11.0;
+const MERGES_WEIGHT: f64 = 700.0;
+const EMPTY_WEIGHT: f64 = 270.0;
+
+fn line_score(line: &[u8]) -> f64 {
+    let mut sum = 0.0;
+    let mut empty = 0;
+    let mut merges = 0;
+    let mut previous = 0;
+    let mut run = 0;
+    for &rank in line {
+        sum += (rank as f64).powf(SUM_POWER);
+        if rank == 0 {
+            empty += 1;
+            continue;
+        }
+        if previous == rank {
+            run += 1;
+        } else if run > 0 {
+            merges += 1 + run;
+            run = 0;
+        }
+        previous = rank;
+    }
+    if run > 0 {
+        merges += 1 + run;
+    }
+
+    //How far the line is from only going up or only going down
+    let mut monotonicity_left = 0.0;
+    let mut monotonicity_right = 0.0;
+    for pair in line.windows(2) {
+        let left = (pair[0] as f64).powi(MONOTONICITY_POWER);
+        let right = (pair[1] as f64).powi(MONOTONICITY_POWER);
+        if pair[0] > pair[1] {
+            monotonicity_left += left - right;
+        } else {
+            monotonicity_right += right - left;
+        }
+    }
+
+    LOST_PENALTY + EMPTY_WEIGHT * empty as f64 + MERGES_WEIGHT * merges as f64
+        - MONOTONICITY_WEIGHT * monotonicity_left.min(monotonicity_right)
+        - SUM_WEIGHT * sum
+}
+
+/// How good a board looks, higher is better. Rewards empty cells, tiles lined up to merge and
+/// rows and columns that go in one direction
+pub(crate) fn score(grid: &Grid) -> f64 {
+    grid.lines().map(|line| line_score(&line)).sum()
+}
@@ -0,0 +1,8 @@
+//! Engines that play at://2048. Powers the hints and autoplay demo in the app
+pub mod expectimax;
+mod grid;
+mod heuristic;
+pub mod strategy;
+
+pub use expectimax::{Hint, MAX_DEPTH, SearchConfig, best_move, best_move_at_depth};
+pub mod analysis;
@@ -0,0 +1,126 @@
+use crate::expectimax::{SearchConfig, best_move};
+use twothousand_forty_eight::board::{Board, check_move};
+use twothousand_forty_eight::direction::{Direction, MOVE_DIRECTIONS};
+use twothousand_forty_eight::v1::validator::initialize_board;
+use twothousand_forty_eight::v2::recording::SeededRecording;
+
+/// Something that can play a game of 2048 on its own
+pub trait Strategy {
+    fn name(&self) -> &'static str;
+
+    /// The move to make on `board`, `None` once there is nothing left to do
+    fn next_move(&mut self, board: &Board) -> Option<Direction>;
+}
+
+fn allowed_moves(board: &Board) -> impl Iterator<Item = Direction> + '_ {
+    MOVE_DIRECTIONS
+        .into_iter()
+        .filter(|direction| check_move(*board, *direction).is_ok())
+}
+
+/// Mashes the arrow keys. The floor every other strategy should beat
+pub struct RandomStrategy {
+    /// xorshift state, seeded per game so runs can be repeated
+    state: u64,
+}
+
+impl RandomStrategy {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            //xorshift gets stuck on 0
+            state: (seed as u64) << 1 | 1,
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn next_move(&mut self, board: &Board) -> Option<Direction> {
+        let moves: Vec<Direction> = allowed_moves(board).collect();
+        if moves.is_empty() {
+            return None;
+        }
+        let index = (self.next_random() % moves.len() as u64) as usize;
+        Some(moves[index])
+    }
+}
+
+/// The classic beginner trick of keeping the big tiles in the bottom left corner
+pub struct CornerStrategy;
+
+impl CornerStrategy {
+    /// Only moves up when nothing else works
+    const PREFERENCE: [Direction; 4] = [
+        Direction::DOWN,
+        Direction::LEFT,
+        Direction::RIGHT,
+        Direction::UP,
+    ];
+}
+
+impl Strategy for CornerStrategy {
+    fn name(&self) -> &'static str {
+        "corner"
+    }
+
+    fn next_move(&mut self, board: &Board) -> Option<Direction> {
+        Self::PREFERENCE
+            .into_iter()
+            .find(|direction| check_move(*board, *direction).is_ok())
+    }
+}
+
+/// Plays the move the hint engine suggests
+pub struct ExpectimaxStrategy {
+    pub search: SearchConfig,
+}
+
+impl Strategy for ExpectimaxStrategy {
+    fn name(&self) -> &'static str {
+        "expectimax"
+    }
+
+    fn next_move(&mut self, board: &Board) -> Option<Direction> {
+        let hint = best_move(board, &self.search)?;
+        //Falls back to any move the game allows on the rare board where the engine disagrees
+        hint.evaluations
+            .into_iter()
+            .map(|(direction, _)| direction)
+            .find(|direction| check_move(*board, *direction).is_ok())
+            .or_else(|| allowed_moves(board).next())
+    }
+}
+
+/// Plays a game on a `size` by `size` board till the strategy runs out of moves or `max_moves`
+/// is hit. Follows the same rules the game is replayed with, so the recording always verifies
+pub fn play_game(
+    strategy: &mut dyn Strategy,
+    seed: u32,
+    size: usize,
+    max_moves: Option<usize>,
+) -> SeededRecording {
+    let mut recording = SeededRecording::empty(seed, size, size);
+    let mut board = initialize_board(size, size, seed, 2);
+    while max_moves.is_none_or(|max_moves| recording.moves.len() < max_moves) {
+        let Some(direction) = strategy.next_move(&board) else {
+            break;
+        };
+        match check_move(board, direction) {
+            Ok(result) => board = result.board,
+            Err(_) => break,
+        }
+        board.add_random_tile();
+        recording.moves.push(direction);
+    }
+    recording
+}
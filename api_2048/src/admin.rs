@@ -0,0 +1,297 @@
+use crate::atproto::AtprotoClient;
+use crate::config::{Config, Features};
+use crate::{anticheat, image_routes, moderation, openapi, ranked, storage, tournaments};
+use atrium_api::types::Collection;
+use chrono::Utc;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use twothousand_forty_eight::{unified::game::GameState, v2::recording::SeededRecording};
+use types_2048::blue;
+use types_2048::blue::_2048::game;
+
+const USAGE: &str = "Usage: api_2048 [COMMAND]
+
+Without a command the API server starts. The commands work on the same data files as the
+server, so run them where it runs, with the same config file and environment.
+
+Commands:
+  backfill <did or handle>      Analyse every game on the player's PDS for the moderation queue
+  reverify <did or handle>      Replay every game on the player's PDS and check its score adds up,
+                                storing the results and the player's stats in the database
+  leaderboard                   Rebuild every player's stats, and so the global leaderboards,
+                                from the games and verifications in the database
+  tournament-standings <tournament id>
+                                Recompute a tournament's standings from the players' PDSs
+  render <recording> <file>     Draw a game's final board to a PNG, like its share image
+  rotate-key                    Deal ranked seeds from a new key, keeping the old ones to check
+                                seeds already dealt. Restart the server to pick it up
+  openapi [--check]             Write the OpenAPI document for every endpoint to openapi.json, or
+                                with --check fail if the one there is out of date
+  help                          Show this";
+
+/// Runs the admin command in the arguments, everything after the binary's name
+pub async fn run(config: &Config, args: &[String]) -> Result<(), String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["backfill", player] => backfill(config, player).await,
+        ["reverify", player] => reverify(config, player).await,
+        ["leaderboard"] => leaderboard(config).await,
+        ["tournament-standings", id] => tournament_standings(config, id).await,
+        ["render", recording, file] => render(config, recording, file),
+        ["rotate-key"] => rotate_key(config),
+        ["openapi"] => write_openapi(),
+        ["openapi", "--check"] => check_openapi(),
+        ["help" | "--help" | "-h"] => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            Err(format!("Unknown command {}", args.join(" ")))
+        }
+    }
+}
+
+async fn backfill(config: &Config, player: &str) -> Result<(), String> {
+    let client = AtprotoClient::default();
+    let did = client
+        .resolve_player(player)
+        .await
+        .map_err(|e| e.to_string())?;
+    let storage = &config.storage;
+    let anticheat = anticheat::AnticheatStore::load(storage.anticheat.clone())?;
+    let moderation = moderation::ModerationStore::load(
+        storage.moderation.clone(),
+        storage.moderation_audit.clone(),
+    )?;
+    let ranked_seeds = ranked::RankedSeeds::load_or_create(config.keys.ranked_seeds.clone())?;
+
+    let reports = anticheat
+        .scan_player(&client, &did)
+        .await
+        .map_err(|e| e.to_string())?;
+    // Ranked games are screened again knowing which seeds were dealt, as the server does
+    let ranked = ranked_seeds
+        .ranked_player(&client, &anticheat, &moderation, None, &did)
+        .await
+        .map_err(|e| e.to_string())?;
+    let ineligible = reports
+        .iter()
+        .filter(|report| !report.is_eligible())
+        .count();
+    let queued = reports
+        .iter()
+        .filter(|report| report.needs_review())
+        .count();
+    println!("{}", did.as_str());
+    println!("  analysed:   {}", reports.len());
+    println!("  queued:     {}", queued);
+    println!("  ineligible: {}", ineligible);
+    println!(
+        "  ranked:     {} ({} held back)",
+        ranked.games.len(),
+        ranked.ineligible
+    );
+    Ok(())
+}
+
+async fn reverify(config: &Config, player: &str) -> Result<(), String> {
+    let client = AtprotoClient::default();
+    let did = client
+        .resolve_player(player)
+        .await
+        .map_err(|e| e.to_string())?;
+    let storage = &config.storage;
+    let database = storage::Database::open(&storage.database).map_err(|e| e.to_string())?;
+    let anticheat = anticheat::AnticheatStore::load(storage.anticheat.clone())?;
+    let moderation = moderation::ModerationStore::load(
+        storage.moderation.clone(),
+        storage.moderation_audit.clone(),
+    )?;
+    let ranked_seeds = ranked::RankedSeeds::load_or_create(config.keys.ranked_seeds.clone())?;
+    // The games that count for leaderboards, screened as the server screens them
+    let eligible: HashSet<String> = ranked_seeds
+        .ranked_player(&client, &anticheat, &moderation, None, &did)
+        .await
+        .map_err(|e| e.to_string())?
+        .games
+        .into_iter()
+        .map(|game| game.uri)
+        .collect();
+    let records = client
+        .list_records::<serde_json::Value>(&did, blue::_2048::Game::NSID)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let now = Utc::now();
+    let mut checked = vec![];
+    let mut failed = 0;
+    for (uri, value) in &records {
+        let game: game::RecordData = match serde_json::from_value(value.clone()) {
+            Ok(game) => game,
+            Err(e) => {
+                failed += 1;
+                println!("unreadable  {}: {}", uri, e);
+                continue;
+            }
+        };
+        let replayed = game
+            .seeded_recording
+            .parse::<SeededRecording>()
+            .map_err(|e| e.to_string())
+            .and_then(|recording| {
+                GameState::from_reconstructable_ruleset(&recording).map_err(|e| e.to_string())
+            });
+        let (replayed_score, error) = match replayed {
+            Ok(gamestate) if gamestate.score_current as i64 == game.current_score => {
+                println!("ok          {} {}", uri, game.current_score);
+                (Some(gamestate.score_current as i64), None)
+            }
+            Ok(gamestate) => {
+                failed += 1;
+                println!(
+                    "mismatch    {} claims {} but replays to {}",
+                    uri, game.current_score, gamestate.score_current
+                );
+                (
+                    Some(gamestate.score_current as i64),
+                    Some("The score does not match the replay".to_string()),
+                )
+            }
+            Err(e) => {
+                failed += 1;
+                println!("unplayable  {}: {}", uri, e);
+                (None, Some(e))
+            }
+        };
+        // A recording that does not parse has nothing to store a game from
+        if let Ok(stored) = storage::games::Game::from_record(
+            uri.clone(),
+            did.as_str().to_string(),
+            &game,
+            &ranked_seeds,
+            eligible.contains(uri),
+        ) {
+            let verification = storage::verifications::Verification {
+                game_uri: uri.clone(),
+                verified: error.is_none(),
+                replayed_score,
+                error,
+                verified_at: now,
+            };
+            checked.push((stored, verification));
+        }
+    }
+
+    let player = storage::players::Player {
+        did: did.as_str().to_string(),
+        handle: (player != did.as_str()).then(|| player.to_string()),
+        updated_at: now,
+    };
+    database
+        .transaction(move |tx| {
+            tx.players().upsert(&player)?;
+            for (game, verification) in &checked {
+                tx.games().upsert(game)?;
+                tx.verifications().record(verification)?;
+            }
+            tx.stats().refresh(&player.did, now)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+    println!(
+        "{} of {} games verified",
+        records.len() - failed,
+        records.len()
+    );
+    if failed > 0 {
+        return Err(format!("{} games did not verify", failed));
+    }
+    Ok(())
+}
+
+async fn leaderboard(config: &Config) -> Result<(), String> {
+    let storage = &config.storage;
+    let database = storage::Database::open(&storage.database).map_err(|e| e.to_string())?;
+    let refreshed = database
+        .transaction(|tx| tx.stats().refresh_all(Utc::now()))
+        .await
+        .map_err(|e| e.to_string())?;
+    println!("Rebuilt the stats of {} players", refreshed);
+    Ok(())
+}
+
+async fn tournament_standings(config: &Config, id: &str) -> Result<(), String> {
+    let storage = &config.storage;
+    let tournament_store = tournaments::TournamentStore::load(storage.tournaments.clone())?;
+    let anticheat = Arc::new(anticheat::AnticheatStore::load(storage.anticheat.clone())?);
+    let moderation = Arc::new(moderation::ModerationStore::load(
+        storage.moderation.clone(),
+        storage.moderation_audit.clone(),
+    )?);
+    // A fresh store has nothing cached, so these are read from the PDSs now
+    let results = tournament_store
+        .results(&AtprotoClient::default(), &anticheat, &moderation, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&results).map_err(|e| e.to_string())?;
+    println!("{}", json);
+    if !results.unreachable.is_empty() {
+        eprintln!(
+            "Could not read the games of {}",
+            results.unreachable.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn render(config: &Config, recording: &str, file: &str) -> Result<(), String> {
+    let recording: SeededRecording = recording
+        .parse()
+        .map_err(|e| format!("Invalid seeded_recording: {}", e))?;
+    let gamestate = GameState::from_reconstructable_ruleset(&recording)
+        .map_err(|e| format!("Could not replay the game: {}", e))?;
+    let font_path = &config.images.font_path;
+    let font_bytes = std::fs::read(font_path)
+        .map_err(|e| format!("Error reading {}: {}", font_path.display(), e))?;
+    let font = ab_glyph::FontArc::try_from_vec(font_bytes)
+        .map_err(|e| format!("Error parsing {}: {}", font_path.display(), e))?;
+    let png = image_routes::render_board_png(&gamestate, &font)?;
+    std::fs::write(file, png).map_err(|e| format!("Error writing {}: {}", file, e))?;
+    println!("Wrote {} ({} points)", file, gamestate.score_current);
+    Ok(())
+}
+
+fn rotate_key(config: &Config) -> Result<(), String> {
+    let path = config.keys.ranked_seeds.clone();
+    let ranked_seeds = ranked::RankedSeeds::rotate(path.clone())?;
+    println!(
+        "{} now deals from a new key and checks seeds against {} keys. Restart the server to use it",
+        path.display(),
+        ranked_seeds.key_count()
+    );
+    Ok(())
+}
+
+// Every feature on, so the document does not depend on how this server is configured
+fn write_openapi() -> Result<(), String> {
+    let document = openapi::document(&crate::api_description(&Features::default()))?;
+    std::fs::write(openapi::SPEC_PATH, document)
+        .map_err(|e| format!("Error writing {}: {}", openapi::SPEC_PATH, e))?;
+    println!("Wrote {}", openapi::SPEC_PATH);
+    Ok(())
+}
+
+fn check_openapi() -> Result<(), String> {
+    let api = crate::api_description(&Features::default());
+    if !openapi::is_current(&api, Path::new(openapi::SPEC_PATH))? {
+        return Err(format!(
+            "{} does not match the endpoints, run `api_2048 openapi` and commit it",
+            openapi::SPEC_PATH
+        ));
+    }
+    println!("{} is up to date", openapi::SPEC_PATH);
+    Ok(())
+}
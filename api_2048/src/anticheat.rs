@@ -0,0 +1,776 @@
+use crate::atproto::{AtprotoClient, AtprotoError};
+use crate::limits::{LimitError, Limits};
+use ai_2048::best_move_at_depth;
+use atrium_api::types::Collection;
+use atrium_api::types::string::Did;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use twothousand_forty_eight::unified::reconstruction::Reconstructable;
+use twothousand_forty_eight::{unified::game::GameState, v2::recording::SeededRecording};
+use types_2048::blue;
+use types_2048::blue::_2048::game;
+
+// Games this suspicious go in the moderation queue
+pub const QUEUE_AT: f64 = 0.3;
+// Games this suspicious are kept off leaderboards till a moderator clears them
+pub const INELIGIBLE_AT: f64 = 0.6;
+
+// The engine is asked about this many positions spread over the game, not every move
+const SAMPLED_POSITIONS: usize = 120;
+// Agreement over fewer positions than this says nothing
+const MIN_SAMPLED_POSITIONS: usize = 40;
+// A shallow search is plenty to tell if someone plays exactly like the engine does. It runs with
+// no time limit, so a game gets the same score however busy the server is
+const SEARCH_DEPTH: u8 = 2;
+// Strong players agree with the engine on most moves, but not on nearly all of them
+const HIGH_AGREEMENT: f64 = 0.92;
+const NEAR_TOTAL_AGREEMENT: f64 = 0.97;
+
+// Timing is only judged on games long enough to have a rhythm
+const MIN_TIMED_MOVES: usize = 50;
+// Faster than a person can read the board and press a key, move after move
+const ROBOTIC_MEDIAN_MS: f64 = 60.0;
+// People speed up and slow down, scripts with a fixed delay do not
+const ROBOTIC_VARIATION: f64 = 0.2;
+
+// How often one player can play the same seed before it looks like practising the spawns
+const REPEATED_SEED_GAMES: usize = 10;
+const GROUND_SEED_GAMES: usize = 30;
+// A first try on a seed scoring this many times the player's usual looks like a seed picked offline
+const OUTLIER_RATIO: f64 = 3.0;
+const MIN_GAMES_FOR_USUAL: usize = 5;
+
+#[derive(Debug)]
+pub enum AnticheatError {
+    NotFound(String),
+    Storage(String),
+    Atproto(AtprotoError),
+}
+
+impl std::fmt::Display for AnticheatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnticheatError::NotFound(uri) => write!(f, "{} has not been analysed", uri),
+            AnticheatError::Storage(err) => write!(f, "Could not save the analyses: {}", err),
+            AnticheatError::Atproto(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalKind {
+    /// Nearly every move is the one the engine would have made
+    EngineAgreement,
+    /// Moves come too fast or too evenly spaced for a person
+    RoboticTiming,
+    /// The move times do not line up with the moves, so the record was edited
+    EditedTiming,
+    /// The player has played this seed a suspicious number of times
+    RepeatedSeed,
+    /// A one off seed scoring far above the player's usual, as if it was searched for offline
+    BruteForcedSeed,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Signal {
+    pub kind: SignalKind,
+    /// 0 to 1, how much this alone makes the game look like cheating
+    pub weight: f64,
+    pub detail: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewVerdict {
+    /// A person played it, it counts no matter the suspicion
+    Cleared,
+    /// It never counts
+    Cheating,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Review {
+    pub verdict: ReviewVerdict,
+    pub note: Option<String>,
+    pub reviewed_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SuspicionReport {
+    pub uri: String,
+    pub did: String,
+    pub seed: u32,
+    pub size: usize,
+    pub score: i64,
+    pub moves: usize,
+    /// 0 to 1, the signals combined
+    pub suspicion: f64,
+    pub signals: Vec<Signal>,
+    pub analyzed_at: DateTime<Utc>,
+    pub review: Option<Review>,
+}
+
+impl SuspicionReport {
+    /// Whether the game can show up on leaderboards and in tournament standings
+    pub fn is_eligible(&self) -> bool {
+        match &self.review {
+            Some(review) => review.verdict == ReviewVerdict::Cleared,
+            None => self.suspicion < INELIGIBLE_AT,
+        }
+    }
+
+    /// Whether the game is in the moderation queue
+    pub fn needs_review(&self) -> bool {
+        self.review.is_none() && self.suspicion >= QUEUE_AT
+    }
+
+    // A record rewritten under the same key has to be looked at again
+    fn matches(&self, game: &game::RecordData, moves: usize) -> bool {
+        self.score == game.current_score && self.moves == moves
+    }
+}
+
+/// What the rest of a player's games say about one of them
+#[derive(Clone)]
+pub struct PlayerHistory {
+    games_per_seed: HashMap<(u32, usize), usize>,
+    usual_score: Option<f64>,
+    // Seeds handed to the player, by a tournament or ranked play, that they could not have searched for
+    dealt_seeds: HashSet<(u32, usize)>,
+}
+
+impl PlayerHistory {
+    pub fn new<'a>(games: impl IntoIterator<Item = &'a game::RecordData>) -> Self {
+        let mut games_per_seed = HashMap::new();
+        let mut scores = vec![];
+        for game in games {
+            if let Ok(recording) = game.seeded_recording.parse::<SeededRecording>() {
+                *games_per_seed
+                    .entry((recording.seed, recording.width))
+                    .or_insert(0) += 1;
+                scores.push(game.current_score);
+            }
+        }
+        scores.sort_unstable();
+        let usual_score =
+            (scores.len() >= MIN_GAMES_FOR_USUAL).then(|| scores[scores.len() / 2] as f64);
+        PlayerHistory {
+            games_per_seed,
+            usual_score,
+            dealt_seeds: HashSet::new(),
+        }
+    }
+
+    pub fn with_dealt_seeds(mut self, seeds: impl IntoIterator<Item = (u32, usize)>) -> Self {
+        self.dealt_seeds.extend(seeds);
+        self
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    values[values.len() / 2]
+}
+
+/// Share of sampled positions where the player made the engine's move, and how many were sampled
+fn engine_agreement(recording: &SeededRecording) -> Option<(f64, usize)> {
+    let reconstruction = recording.reconstruct().ok()?;
+    let positions = reconstruction.history.len().min(recording.moves.len());
+    let step = positions.div_ceil(SAMPLED_POSITIONS).max(1);
+    let mut sampled = 0;
+    let mut agreed = 0;
+    for index in (0..positions).step_by(step) {
+        let Some(hint) = best_move_at_depth(&reconstruction.history[index], SEARCH_DEPTH) else {
+            continue;
+        };
+        // Forced moves say nothing about who is playing
+        if hint.evaluations.len() < 2 {
+            continue;
+        }
+        sampled += 1;
+        if hint.direction == recording.moves[index] {
+            agreed += 1;
+        }
+    }
+    (sampled > 0).then(|| (agreed as f64 / sampled as f64, sampled))
+}
+
+fn timing_signals(move_times: &[i64], moves: usize) -> Vec<Signal> {
+    if move_times.len() != moves {
+        return vec![Signal {
+            kind: SignalKind::EditedTiming,
+            weight: 0.5,
+            detail: format!("{} move times for {} moves", move_times.len(), moves),
+        }];
+    }
+    if move_times.windows(2).any(|pair| pair[1] < pair[0]) {
+        return vec![Signal {
+            kind: SignalKind::EditedTiming,
+            weight: 0.5,
+            detail: "Move times go backwards".to_string(),
+        }];
+    }
+    if moves < MIN_TIMED_MOVES {
+        return vec![];
+    }
+    let mut gaps: Vec<f64> = move_times
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]) as f64)
+        .collect();
+    let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+    let variation = if mean > 0.0 {
+        (gaps.iter().map(|gap| (gap - mean).powi(2)).sum::<f64>() / gaps.len() as f64).sqrt() / mean
+    } else {
+        0.0
+    };
+    let median_gap = median(&mut gaps);
+    let mut signals = vec![];
+    if median_gap < ROBOTIC_MEDIAN_MS {
+        signals.push(Signal {
+            kind: SignalKind::RoboticTiming,
+            weight: 0.5,
+            detail: format!("{:.0}ms between moves on average", median_gap),
+        });
+    }
+    if variation < ROBOTIC_VARIATION {
+        signals.push(Signal {
+            kind: SignalKind::RoboticTiming,
+            weight: 0.3,
+            detail: format!(
+                "Moves are evenly spaced, varying by only {:.0}%",
+                variation * 100.0
+            ),
+        });
+    }
+    signals
+}
+
+/// Looks a game over for signs it was played by a bot or edited. Slow, the engine runs on it,
+/// so it belongs on a blocking thread. Nothing comes back for a recording that does not replay
+pub fn analyze(
+    uri: String,
+    did: String,
+    game: &game::RecordData,
+    history: &PlayerHistory,
+) -> Option<SuspicionReport> {
+    let recording: SeededRecording = game.seeded_recording.parse().ok()?;
+    GameState::from_reconstructable_ruleset(&recording).ok()?;
+    let moves = recording.moves.len();
+    let mut signals = vec![];
+
+    if let Some((agreement, sampled)) = engine_agreement(&recording)
+        && sampled >= MIN_SAMPLED_POSITIONS
+    {
+        let weight = if agreement >= NEAR_TOTAL_AGREEMENT {
+            0.6
+        } else if agreement >= HIGH_AGREEMENT {
+            0.35
+        } else {
+            0.0
+        };
+        if weight > 0.0 {
+            signals.push(Signal {
+                kind: SignalKind::EngineAgreement,
+                weight,
+                detail: format!(
+                    "Played the engine's move {:.0}% of the time",
+                    agreement * 100.0
+                ),
+            });
+        }
+    }
+
+    if let Some(move_times) = &game.move_times {
+        signals.extend(timing_signals(move_times, moves));
+    }
+
+    let seed_games = history
+        .games_per_seed
+        .get(&(recording.seed, recording.width))
+        .copied()
+        .unwrap_or(1);
+    if seed_games >= REPEATED_SEED_GAMES {
+        signals.push(Signal {
+            kind: SignalKind::RepeatedSeed,
+            weight: if seed_games >= GROUND_SEED_GAMES {
+                0.35
+            } else {
+                0.2
+            },
+            detail: format!("{} games on this seed", seed_games),
+        });
+    }
+    if seed_games == 1
+        && !history
+            .dealt_seeds
+            .contains(&(recording.seed, recording.width))
+        && let Some(usual_score) = history.usual_score
+        && usual_score > 0.0
+        && game.current_score as f64 >= usual_score * OUTLIER_RATIO
+    {
+        signals.push(Signal {
+            kind: SignalKind::BruteForcedSeed,
+            weight: 0.3,
+            detail: format!(
+                "Scored {:.1}x their usual {} on a seed they never played before",
+                game.current_score as f64 / usual_score,
+                usual_score
+            ),
+        });
+    }
+
+    // Each signal is treated as independent evidence, so two weak ones add up to more than either
+    let suspicion = 1.0
+        - signals
+            .iter()
+            .map(|signal| 1.0 - signal.weight)
+            .product::<f64>();
+    Some(SuspicionReport {
+        uri,
+        did,
+        seed: recording.seed,
+        size: recording.width,
+        score: game.current_score,
+        moves,
+        suspicion,
+        signals,
+        analyzed_at: Utc::now(),
+        review: None,
+    })
+}
+
+fn analyze_all(
+    did: &str,
+    history: &PlayerHistory,
+    games: Vec<(String, game::RecordData)>,
+) -> Vec<SuspicionReport> {
+    games
+        .into_iter()
+        .filter_map(|(uri, game)| analyze(uri, did.to_string(), &game, history))
+        .collect()
+}
+
+/// Suspicion reports kept in a JSON file next to the server, keyed by game URI
+pub struct AnticheatStore {
+    path: PathBuf,
+    reports: RwLock<HashMap<String, SuspicionReport>>,
+}
+
+impl AnticheatStore {
+    /// Starts empty when the file does not exist yet
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let reports: Vec<SuspicionReport> = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("Error parsing {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(format!("Error reading {}: {}", path.display(), e)),
+        };
+        Ok(AnticheatStore {
+            path,
+            reports: RwLock::new(
+                reports
+                    .into_iter()
+                    .map(|report| (report.uri.clone(), report))
+                    .collect(),
+            ),
+        })
+    }
+
+    async fn save(&self, reports: &HashMap<String, SuspicionReport>) -> Result<(), AnticheatError> {
+        let mut sorted: Vec<&SuspicionReport> = reports.values().collect();
+        sorted.sort_by(|a, b| a.uri.cmp(&b.uri));
+        let contents = serde_json::to_string_pretty(&sorted)
+            .map_err(|e| AnticheatError::Storage(e.to_string()))?;
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| AnticheatError::Storage(e.to_string()))?;
+        }
+        // Written next to the real file first so a crash mid write can not lose every review
+        let temp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&temp_path, contents)
+            .await
+            .map_err(|e| AnticheatError::Storage(e.to_string()))?;
+        tokio::fs::rename(&temp_path, &self.path)
+            .await
+            .map_err(|e| AnticheatError::Storage(e.to_string()))
+    }
+
+    // The games with no report, or one from before the game was rewritten
+    async fn stale(&self, games: &[(String, game::RecordData)]) -> Vec<(String, game::RecordData)> {
+        let reports = self.reports.read().await;
+        games
+            .iter()
+            .filter(|(uri, game)| {
+                let moves = game
+                    .seeded_recording
+                    .parse::<SeededRecording>()
+                    .map_or(0, |recording| recording.moves.len());
+                !reports
+                    .get(uri)
+                    .is_some_and(|report| report.matches(game, moves))
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Keeps the new reports, only writing the file when there are some
+    async fn store(&self, fresh: Vec<SuspicionReport>) {
+        if fresh.is_empty() {
+            return;
+        }
+        let mut reports = self.reports.write().await;
+        for report in fresh {
+            if report.needs_review() {
+                log::info!(
+                    "{} queued for moderation at {:.2} suspicion",
+                    report.uri,
+                    report.suspicion
+                );
+            }
+            reports.insert(report.uri.clone(), report);
+        }
+        if let Err(e) = self.save(&reports).await {
+            log::error!("{}", e);
+        }
+    }
+
+    async fn stored(&self, games: &[(String, game::RecordData)]) -> Vec<SuspicionReport> {
+        let reports = self.reports.read().await;
+        games
+            .iter()
+            .filter_map(|(uri, _)| reports.get(uri).cloned())
+            .collect()
+    }
+
+    /// Reports for the given games, analysing any that are new or were rewritten since last time.
+    /// Reviews are kept as long as the game has not changed
+    pub async fn screen(
+        &self,
+        did: &str,
+        history: &PlayerHistory,
+        games: &[(String, game::RecordData)],
+    ) -> Vec<SuspicionReport> {
+        let stale = self.stale(games).await;
+        if !stale.is_empty() {
+            let did_owned = did.to_string();
+            let history = history.clone();
+            let fresh =
+                tokio::task::spawn_blocking(move || analyze_all(&did_owned, &history, stale))
+                    .await
+                    .unwrap_or_else(|e| {
+                        log::error!("Analysing games for {} panicked: {}", did, e);
+                        vec![]
+                    });
+            self.store(fresh).await;
+        }
+        self.stored(games).await
+    }
+
+    /// Like `screen`, for requests anyone can make. The stored reports are served as they are and
+    /// only new games are analysed, within the request's budget
+    pub async fn screen_within(
+        &self,
+        limits: &Limits,
+        did: &str,
+        history: &PlayerHistory,
+        games: &[(String, game::RecordData)],
+    ) -> Result<Vec<SuspicionReport>, LimitError> {
+        let stale = self.stale(games).await;
+        if !stale.is_empty() {
+            let did_owned = did.to_string();
+            let history = history.clone();
+            let fresh = limits
+                .run(move || analyze_all(&did_owned, &history, stale))
+                .await?;
+            self.store(fresh).await;
+        }
+        Ok(self.stored(games).await)
+    }
+
+    /// Reads every game the player has on their PDS and screens all of them
+    pub async fn scan_player(
+        &self,
+        client: &AtprotoClient,
+        did: &Did,
+    ) -> Result<Vec<SuspicionReport>, AnticheatError> {
+        let records = client
+            .list_records::<serde_json::Value>(did, blue::_2048::Game::NSID)
+            .await
+            .map_err(AnticheatError::Atproto)?;
+        // Read loosely so one odd record does not hide the rest of the player's games
+        let games: Vec<(String, game::RecordData)> = records
+            .into_iter()
+            .filter_map(|(uri, value)| Some((uri, serde_json::from_value(value).ok()?)))
+            .collect();
+        let history = PlayerHistory::new(games.iter().map(|(_, game)| game));
+        Ok(self.screen(did.as_str(), &history, &games).await)
+    }
+
+    /// Games analysed so far, whatever came of it
+    pub async fn report_count(&self) -> usize {
+        self.reports.read().await.len()
+    }
+
+    /// Games waiting on a moderator, most suspicious first
+    pub async fn queue(&self) -> Vec<SuspicionReport> {
+        let mut queue: Vec<SuspicionReport> = self
+            .reports
+            .read()
+            .await
+            .values()
+            .filter(|report| report.needs_review())
+            .cloned()
+            .collect();
+        queue.sort_by(|a, b| b.suspicion.total_cmp(&a.suspicion));
+        queue
+    }
+
+    pub async fn review(
+        &self,
+        uri: &str,
+        verdict: ReviewVerdict,
+        note: Option<String>,
+    ) -> Result<SuspicionReport, AnticheatError> {
+        let mut reports = self.reports.write().await;
+        let report = reports
+            .get_mut(uri)
+            .ok_or_else(|| AnticheatError::NotFound(uri.to_string()))?;
+        report.review = Some(Review {
+            verdict,
+            note,
+            reviewed_at: Utc::now(),
+        });
+        let report = report.clone();
+        self.save(&reports).await?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atrium_api::types::string::Datetime;
+    use twothousand_forty_eight::board::Board;
+    use twothousand_forty_eight::direction::{Direction, MOVE_DIRECTIONS};
+    use types_2048::blue::_2048::defs::SyncStatusData;
+
+    const SEED: u32 = 2048;
+
+    fn record(recording: &SeededRecording, move_times: Option<Vec<i64>>) -> game::RecordData {
+        let gamestate = GameState::from_reconstructable_ruleset(recording).unwrap();
+        game::RecordData {
+            completed: true,
+            created_at: Datetime::now(),
+            current_score: gamestate.score_current as i64,
+            hints_used: Some(0),
+            max_tile: None,
+            mode: Some("classic".to_string()),
+            mode_limit: None,
+            move_times,
+            play_time: None,
+            score_to_beat: None,
+            seed_issued_at: None,
+            seeded_recording: recording.into(),
+            started_at: None,
+            sync_status: SyncStatusData {
+                created_at: Datetime::now(),
+                hash: "".to_string(),
+                synced_with_at_repo: true,
+                updated_at: Datetime::now(),
+            }
+            .into(),
+            time_to_find_twenty_forty_eight: None,
+            undo_count: Some(0),
+            won: false,
+            won_at_move: None,
+        }
+    }
+
+    // Plays `moves` moves on the seed, each picked by `pick` from the board and the moves so far
+    fn play(moves: usize, pick: impl Fn(&Board, &[Direction]) -> Direction) -> SeededRecording {
+        let mut recording = SeededRecording::empty(SEED, 4, 4);
+        for _ in 0..moves {
+            let gamestate = GameState::from_reconstructable_ruleset(&recording).unwrap();
+            if gamestate.over {
+                break;
+            }
+            let direction = pick(&gamestate.board, &recording.moves);
+            recording.moves.push(direction);
+        }
+        recording
+    }
+
+    fn engine_game() -> SeededRecording {
+        play(120, |board, _| {
+            best_move_at_depth(board, SEARCH_DEPTH).unwrap().direction
+        })
+    }
+
+    // Goes round the directions in turn, skipping the ones that do nothing
+    fn rotating_game() -> SeededRecording {
+        play(120, |board, moves| {
+            (0..4)
+                .map(|offset| MOVE_DIRECTIONS[(moves.len() + offset) % 4])
+                .find(|direction| {
+                    let mut tried = SeededRecording::new(SEED, 4, 4, moves.to_vec());
+                    tried.moves.push(*direction);
+                    GameState::from_reconstructable_ruleset(&tried)
+                        .is_ok_and(|gamestate| gamestate.board != *board)
+                })
+                .unwrap()
+        })
+    }
+
+    fn report(game: &game::RecordData, history: &PlayerHistory) -> SuspicionReport {
+        analyze(
+            "at://game".to_string(),
+            "did:plc:a".to_string(),
+            game,
+            history,
+        )
+        .unwrap()
+    }
+
+    fn kinds(report: &SuspicionReport) -> Vec<SignalKind> {
+        report.signals.iter().map(|signal| signal.kind).collect()
+    }
+
+    #[test]
+    fn analysis_is_deterministic() {
+        for recording in [engine_game(), rotating_game()] {
+            let game = record(&recording, None);
+            let history = PlayerHistory::new([&game]);
+            let first = report(&game, &history);
+            let second = report(&game, &history);
+            assert_eq!(first.signals, second.signals);
+            assert_eq!(first.suspicion, second.suspicion);
+        }
+    }
+
+    #[test]
+    fn playing_like_the_engine_is_kept_off_leaderboards() {
+        let game = record(&engine_game(), None);
+        let report = report(&game, &PlayerHistory::new([&game]));
+        assert_eq!(kinds(&report), [SignalKind::EngineAgreement]);
+        assert!(report.needs_review());
+        assert!(!report.is_eligible());
+    }
+
+    #[test]
+    fn a_game_unlike_the_engines_is_clean() {
+        let game = record(&rotating_game(), None);
+        let report = report(&game, &PlayerHistory::new([&game]));
+        assert!(report.signals.is_empty());
+        assert_eq!(report.suspicion, 0.0);
+        assert!(report.is_eligible());
+    }
+
+    #[test]
+    fn robotic_timing_is_flagged() {
+        let signals = timing_signals(&(0..100).map(|index| index * 40).collect::<Vec<_>>(), 100);
+        assert_eq!(signals.len(), 2);
+        assert!(
+            signals
+                .iter()
+                .all(|signal| signal.kind == SignalKind::RoboticTiming)
+        );
+
+        // A person's pace, quick and slow moves mixed
+        let human: Vec<i64> = (0..100)
+            .scan(0, |time, index| {
+                *time += if index % 3 == 0 { 900 } else { 250 };
+                Some(*time)
+            })
+            .collect();
+        assert!(timing_signals(&human, 100).is_empty());
+    }
+
+    #[test]
+    fn edited_timing_is_flagged() {
+        let signals = timing_signals(&[0, 100, 200], 4);
+        assert_eq!(signals[0].kind, SignalKind::EditedTiming);
+        let signals = timing_signals(&[0, 300, 200, 400], 4);
+        assert_eq!(signals[0].kind, SignalKind::EditedTiming);
+    }
+
+    #[test]
+    fn replaying_a_seed_over_and_over_is_flagged() {
+        let game = record(&rotating_game(), None);
+        let history = PlayerHistory::new(vec![&game; GROUND_SEED_GAMES]);
+        let report = report(&game, &history);
+        assert_eq!(kinds(&report), [SignalKind::RepeatedSeed]);
+        assert_eq!(report.signals[0].weight, 0.35);
+        // Worth a look, but not enough on its own to keep the game off leaderboards
+        assert!(report.needs_review());
+        assert!(report.is_eligible());
+    }
+
+    #[test]
+    fn a_far_better_first_try_is_flagged_unless_the_seed_was_dealt() {
+        let game = record(&rotating_game(), None);
+        let mut usual = record(&SeededRecording::empty(1, 4, 4), None);
+        usual.current_score = game.current_score / 4;
+        let games: Vec<&game::RecordData> = std::iter::repeat_n(&usual, MIN_GAMES_FOR_USUAL)
+            .chain([&game])
+            .collect();
+
+        let history = PlayerHistory::new(games.iter().copied());
+        assert_eq!(
+            kinds(&report(&game, &history)),
+            [SignalKind::BruteForcedSeed]
+        );
+
+        let history = history.with_dealt_seeds([(SEED, 4)]);
+        assert!(report(&game, &history).signals.is_empty());
+    }
+
+    #[test]
+    fn signals_add_up_as_independent_evidence() {
+        let game = record(
+            &engine_game(),
+            Some((0..5).map(|index| index * 100).collect()),
+        );
+        let report = report(&game, &PlayerHistory::new([&game]));
+        assert_eq!(
+            kinds(&report),
+            [SignalKind::EngineAgreement, SignalKind::EditedTiming]
+        );
+        assert!((report.suspicion - (1.0 - 0.4 * 0.5)).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn budgeted_screens_serve_stored_reports_without_analysing() {
+        let path = std::env::temp_dir().join(format!("anticheat-{}.json", rand::random::<u64>()));
+        let store = AnticheatStore::load(path.clone()).unwrap();
+        let no_budget = Limits::new(crate::config::LimitsConfig {
+            work_budget_ms: 0,
+            ..Default::default()
+        });
+        let game = record(&rotating_game(), None);
+        let games = vec![("at://game".to_string(), game.clone())];
+        let history = PlayerHistory::new([&game]);
+
+        // Nothing stored yet, so the game has to be analysed and there is no time to
+        assert!(matches!(
+            store
+                .screen_within(&no_budget, "did:plc:a", &history, &games)
+                .await,
+            Err(LimitError::OverBudget)
+        ));
+
+        let screened = store.screen("did:plc:a", &history, &games).await;
+        let served = store
+            .screen_within(&no_budget, "did:plc:a", &history, &games)
+            .await
+            .unwrap();
+        assert_eq!(served.len(), 1);
+        assert_eq!(served[0].analyzed_at, screened[0].analyzed_at);
+        let _ = std::fs::remove_file(path);
+    }
+}
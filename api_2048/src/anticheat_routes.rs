@@ -0,0 +1,110 @@
+use crate::anticheat::{AnticheatError, ReviewVerdict, SuspicionReport};
+use crate::{ApiContext, require_bearer_token};
+use atrium_api::types::Collection;
+use atrium_api::types::string::Did;
+use dropshot::{HttpError, HttpResponseOk, Path, RequestContext, TypedBody};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use types_2048::blue;
+
+impl From<AnticheatError> for HttpError {
+    fn from(err: AnticheatError) -> Self {
+        match err {
+            AnticheatError::NotFound(_) => HttpError::for_not_found(None, err.to_string()),
+            AnticheatError::Storage(_) => HttpError::for_internal_error(err.to_string()),
+            AnticheatError::Atproto(_) => HttpError::for_unavail(None, err.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PlayerPath {
+    pub did: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GamePath {
+    pub did: String,
+    pub rkey: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GameReview {
+    pub verdict: ReviewVerdict,
+    /// Why, for the other moderators
+    pub note: Option<String>,
+}
+
+/// Everything under /api/moderation needs the moderator token as a bearer token
+pub fn require_moderator(rqctx: &RequestContext<ApiContext>) -> Result<(), HttpError> {
+    require_bearer_token(
+        rqctx,
+        rqctx.context().config.tokens.moderator.as_deref(),
+        "Moderation is not set up on this server",
+    )
+}
+
+fn parse_did(did: &str) -> Result<Did, HttpError> {
+    did.parse()
+        .map_err(|e: &str| HttpError::for_bad_request(None, format!("Invalid DID: {}", e)))
+}
+
+/// Games suspicious enough to need a look that nobody has reviewed yet, most suspicious first
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/moderation/queue",
+}]
+pub async fn get_moderation_queue(
+    rqctx: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<Vec<SuspicionReport>>, HttpError> {
+    require_moderator(&rqctx)?;
+    Ok(HttpResponseOk(rqctx.context().anticheat.queue().await))
+}
+
+/// Analyse every game on the player's PDS. Games that were analysed before and have not changed
+/// keep their report and review
+#[dropshot::endpoint {
+    method = POST,
+    path = "/api/moderation/players/{did}/scan",
+}]
+pub async fn scan_player_games(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<PlayerPath>,
+) -> Result<HttpResponseOk<Vec<SuspicionReport>>, HttpError> {
+    require_moderator(&rqctx)?;
+    let did = parse_did(&path.into_inner().did)?;
+    let api_context = rqctx.context();
+    let reports = api_context
+        .anticheat
+        .scan_player(&api_context.atproto, &did)
+        .await?;
+    Ok(HttpResponseOk(reports))
+}
+
+/// Clear a game so it counts no matter how suspicious it looks, or mark it as cheating so it never does
+#[dropshot::endpoint {
+    method = PUT,
+    path = "/api/moderation/games/{did}/{rkey}",
+}]
+pub async fn review_game(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<GamePath>,
+    body: TypedBody<GameReview>,
+) -> Result<HttpResponseOk<SuspicionReport>, HttpError> {
+    require_moderator(&rqctx)?;
+    let path = path.into_inner();
+    let did = parse_did(&path.did)?;
+    let uri = format!(
+        "at://{}/{}/{}",
+        did.as_str(),
+        blue::_2048::Game::NSID,
+        path.rkey
+    );
+    let review = body.into_inner();
+    let report = rqctx
+        .context()
+        .anticheat
+        .review(&uri, review.verdict, review.note)
+        .await?;
+    Ok(HttpResponseOk(report))
+}
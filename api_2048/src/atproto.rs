@@ -0,0 +1,329 @@
+use atrium_api::types::string::Did;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+const PLC_DIRECTORY_URL: &str = "https://plc.directory";
+// Any AppView answers resolveHandle, so the public Bluesky one is used instead of DNS lookups
+const HANDLE_RESOLVER_URL: &str = "https://public.api.bsky.app";
+// Most records a PDS hands back per listRecords page
+const LIST_RECORDS_LIMIT: usize = 100;
+
+#[derive(Debug)]
+pub enum AtprotoError {
+    // The handle or DID could not be turned into a PDS
+    Resolve(String),
+    // The PDS or directory did not answer with what was asked for
+    Fetch(String),
+    // Logging in to the authority's repo failed
+    Session(String),
+}
+
+impl std::fmt::Display for AtprotoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtprotoError::Resolve(err) => write!(f, "Could not resolve the player: {}", err),
+            AtprotoError::Fetch(err) => write!(f, "Could not fetch from the PDS: {}", err),
+            AtprotoError::Session(err) => {
+                write!(f, "Could not log in to the authority repo: {}", err)
+            }
+        }
+    }
+}
+
+// The repo this server publishes its own records to, logged in with an app password. Built
+// from the authority section of the config
+#[derive(Clone)]
+pub struct AuthorityConfig {
+    pub pds: String,
+    pub identifier: String,
+    pub app_password: String,
+}
+
+#[derive(Deserialize)]
+struct ResolvedHandle {
+    did: Did,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidService {
+    id: String,
+    service_endpoint: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerificationMethod {
+    id: String,
+    public_key_multibase: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidDocument {
+    #[serde(default)]
+    service: Vec<DidService>,
+    #[serde(default)]
+    verification_method: Vec<VerificationMethod>,
+}
+
+#[derive(Deserialize)]
+struct PublicRecord<R> {
+    uri: String,
+    value: R,
+}
+
+#[derive(Deserialize)]
+struct PublicRecordPage<R> {
+    cursor: Option<String>,
+    records: Vec<PublicRecord<R>>,
+}
+
+#[derive(Serialize)]
+struct CreateSessionInput<'a> {
+    identifier: &'a str,
+    password: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Session {
+    access_jwt: String,
+    did: Did,
+}
+
+#[derive(Serialize)]
+struct PutRecordInput<'a, R> {
+    repo: &'a str,
+    collection: &'a str,
+    rkey: &'a str,
+    record: &'a R,
+}
+
+#[derive(Serialize)]
+struct DeleteRecordInput<'a> {
+    repo: &'a str,
+    collection: &'a str,
+    rkey: &'a str,
+}
+
+#[derive(Deserialize)]
+pub struct PutRecordOutput {
+    pub uri: String,
+    pub cid: String,
+}
+
+// Reads players' public records straight from their PDSs, there is no indexer to ask
+#[derive(Clone, Default)]
+pub struct AtprotoClient {
+    http: reqwest::Client,
+}
+
+impl AtprotoClient {
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, AtprotoError> {
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| AtprotoError::Fetch(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(AtprotoError::Fetch(format!(
+                "{} returned {}",
+                url,
+                response.status()
+            )));
+        }
+        response
+            .json::<T>()
+            .await
+            .map_err(|e| AtprotoError::Fetch(e.to_string()))
+    }
+
+    // Takes a handle (with or without the @) or a DID and gives back the DID
+    pub async fn resolve_player(&self, handle_or_did: &str) -> Result<Did, AtprotoError> {
+        let handle_or_did = handle_or_did.trim().trim_start_matches('@');
+        if let Ok(did) = handle_or_did.parse::<Did>() {
+            return Ok(did);
+        }
+        let url = format!(
+            "{}/xrpc/com.atproto.identity.resolveHandle?handle={}",
+            HANDLE_RESOLVER_URL,
+            urlencoding::encode(handle_or_did)
+        );
+        self.get_json::<ResolvedHandle>(&url)
+            .await
+            .map(|resolved| resolved.did)
+            .map_err(|e| AtprotoError::Resolve(format!("{}: {}", handle_or_did, e)))
+    }
+
+    async fn did_document(&self, did: &Did) -> Result<DidDocument, AtprotoError> {
+        let url = match did.as_str().strip_prefix("did:web:") {
+            Some(host) => format!("https://{}/.well-known/did.json", host.replace("%3A", ":")),
+            None => format!("{}/{}", PLC_DIRECTORY_URL, did.as_str()),
+        };
+        self.get_json(&url)
+            .await
+            .map_err(|e| AtprotoError::Resolve(e.to_string()))
+    }
+
+    pub async fn pds_endpoint(&self, did: &Did) -> Result<String, AtprotoError> {
+        self.did_document(did)
+            .await?
+            .service
+            .into_iter()
+            .find(|service| service.id.ends_with("#atproto_pds"))
+            .map(|service| service.service_endpoint.trim_end_matches('/').to_string())
+            .ok_or_else(|| AtprotoError::Resolve(format!("{} has no PDS", did.as_str())))
+    }
+
+    // The Multikey the player's repo and service auth tokens are signed with
+    pub async fn signing_key(&self, did: &Did) -> Result<String, AtprotoError> {
+        self.did_document(did)
+            .await?
+            .verification_method
+            .into_iter()
+            .find(|method| method.id.ends_with("#atproto"))
+            .and_then(|method| method.public_key_multibase)
+            .ok_or_else(|| AtprotoError::Resolve(format!("{} has no signing key", did.as_str())))
+    }
+
+    // Every record in one of a player's collections, with its at:// uri
+    pub async fn list_records<R: DeserializeOwned>(
+        &self,
+        did: &Did,
+        collection: &str,
+    ) -> Result<Vec<(String, R)>, AtprotoError> {
+        let pds = self.pds_endpoint(did).await?;
+        let mut records = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut url = format!(
+                "{}/xrpc/com.atproto.repo.listRecords?repo={}&collection={}&limit={}",
+                pds,
+                urlencoding::encode(did.as_str()),
+                collection,
+                LIST_RECORDS_LIMIT
+            );
+            if let Some(cursor) = &cursor {
+                url.push_str(&format!("&cursor={}", urlencoding::encode(cursor)));
+            }
+            let page: PublicRecordPage<R> = self.get_json(&url).await?;
+            let page_size = page.records.len();
+            records.extend(
+                page.records
+                    .into_iter()
+                    .map(|record| (record.uri, record.value)),
+            );
+            match page.cursor {
+                Some(next) if page_size > 0 => cursor = Some(next),
+                _ => break,
+            }
+        }
+        Ok(records)
+    }
+
+    async fn create_authority_session(
+        &self,
+        authority: &AuthorityConfig,
+    ) -> Result<Session, AtprotoError> {
+        let response = self
+            .http
+            .post(format!(
+                "{}/xrpc/com.atproto.server.createSession",
+                authority.pds.trim_end_matches('/')
+            ))
+            .json(&CreateSessionInput {
+                identifier: &authority.identifier,
+                password: &authority.app_password,
+            })
+            .send()
+            .await
+            .map_err(|e| AtprotoError::Session(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(AtprotoError::Session(format!(
+                "createSession returned {}",
+                response.status()
+            )));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| AtprotoError::Session(e.to_string()))
+    }
+
+    // Logs in as the authority and writes the record under the given key, replacing any
+    // record already there. The record needs its $type, so pass a KnownRecord
+    pub async fn put_authority_record<R: Serialize>(
+        &self,
+        authority: &AuthorityConfig,
+        collection: &str,
+        rkey: &str,
+        record: &R,
+    ) -> Result<PutRecordOutput, AtprotoError> {
+        let session = self.create_authority_session(authority).await?;
+        let response = self
+            .http
+            .post(format!(
+                "{}/xrpc/com.atproto.repo.putRecord",
+                authority.pds.trim_end_matches('/')
+            ))
+            .bearer_auth(&session.access_jwt)
+            .json(&PutRecordInput {
+                repo: session.did.as_str(),
+                collection,
+                rkey,
+                record,
+            })
+            .send()
+            .await
+            .map_err(|e| AtprotoError::Fetch(e.to_string()))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(AtprotoError::Fetch(format!(
+                "putRecord returned {}: {}",
+                status, body
+            )));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| AtprotoError::Fetch(e.to_string()))
+    }
+
+    // Logs in as the authority and removes the record under the given key. Removing a record
+    // that is already gone succeeds
+    pub async fn delete_authority_record(
+        &self,
+        authority: &AuthorityConfig,
+        collection: &str,
+        rkey: &str,
+    ) -> Result<(), AtprotoError> {
+        let session = self.create_authority_session(authority).await?;
+        let response = self
+            .http
+            .post(format!(
+                "{}/xrpc/com.atproto.repo.deleteRecord",
+                authority.pds.trim_end_matches('/')
+            ))
+            .bearer_auth(&session.access_jwt)
+            .json(&DeleteRecordInput {
+                repo: session.did.as_str(),
+                collection,
+                rkey,
+            })
+            .send()
+            .await
+            .map_err(|e| AtprotoError::Fetch(e.to_string()))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(AtprotoError::Fetch(format!(
+                "deleteRecord returned {}: {}",
+                status, body
+            )));
+        }
+        Ok(())
+    }
+}
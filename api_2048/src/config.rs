@@ -0,0 +1,485 @@
+use crate::atproto::AuthorityConfig;
+use ab_glyph::FontRef;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// Environment variable naming the config file, and where it is looked for without one
+const CONFIG_FILE_VAR: &str = "API_CONFIG";
+const DEFAULT_CONFIG_FILE: &str = "Config.toml";
+
+/// Everything the server can be set up with. Read from the TOML file, then from environment
+/// variables, which win. Secrets are never sent back out through /api/server-config
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub site: SiteConfig,
+    pub images: ImageConfig,
+    pub storage: StorageConfig,
+    pub keys: KeyConfig,
+    pub features: Features,
+    pub limits: LimitsConfig,
+    #[serde(skip_serializing)]
+    #[schemars(skip)]
+    pub tokens: TokenConfig,
+    pub authority: AuthoritySettings,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// `BIND_ADDRESS`, and `PORT` for just the port
+    pub bind_address: SocketAddr,
+    /// `REQUEST_BODY_MAX_BYTES`
+    pub request_body_max_bytes: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: SocketAddr::from(([127, 0, 0, 1], 8081)),
+            request_body_max_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteConfig {
+    /// `BASE_URL`, where this server is reached, for the links in share cards. Players' service
+    /// auth tokens are made out to its did:web, so it is the origin the app calls the api on
+    pub base_url: String,
+    /// `APP_URL`, where the game is played, for the links sending players there
+    pub app_url: String,
+    /// `OG_TITLE`
+    pub og_title: String,
+    /// `OG_DESCRIPTION`
+    pub og_description: String,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        SiteConfig {
+            base_url: "https://2048.symm.app".to_string(),
+            app_url: "https://2048.symm.app".to_string(),
+            og_title: "2048 Game".to_string(),
+            og_description: "Play 2048!".to_string(),
+        }
+    }
+}
+
+impl SiteConfig {
+    /// The did:web of the base url, what players' PDSs make service auth tokens out to
+    pub fn service_did(&self) -> String {
+        let host = reqwest::Url::parse(&self.base_url).ok().and_then(|url| {
+            let host = url.host_str()?.to_string();
+            Some(match url.port() {
+                Some(port) => format!("{}%3A{}", host, port),
+                None => host,
+            })
+        });
+        format!("did:web:{}", host.unwrap_or_default())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageConfig {
+    /// `FONT_PATH`, the font tile numbers are drawn in
+    pub font_path: PathBuf,
+    /// `IMAGE_MEMORY_CACHE_ENTRIES`, share images kept in memory
+    pub memory_cache_entries: usize,
+    /// `IMAGE_DISK_CACHE_DIR`
+    pub disk_cache_dir: PathBuf,
+    /// `IMAGE_DISK_CACHE_ENTRIES`, share images kept on disk
+    pub disk_cache_entries: usize,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        ImageConfig {
+            font_path: PathBuf::from("assets/DejaVuSans.ttf"),
+            memory_cache_entries: 256,
+            disk_cache_dir: PathBuf::from("data/share_images"),
+            disk_cache_entries: 4096,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// `DATABASE_PATH`, the SQLite database. `:memory:` keeps it in memory, empty on every start
+    pub database: PathBuf,
+    /// `TOURNAMENTS_FILE`
+    pub tournaments: PathBuf,
+    /// `ANTICHEAT_FILE`
+    pub anticheat: PathBuf,
+    /// `MODERATION_FILE`
+    pub moderation: PathBuf,
+    /// `MODERATION_AUDIT_FILE`
+    pub moderation_audit: PathBuf,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            database: PathBuf::from("data/2048.sqlite"),
+            tournaments: PathBuf::from("data/tournaments.json"),
+            anticheat: PathBuf::from("data/anticheat.json"),
+            moderation: PathBuf::from("data/moderation.json"),
+            moderation_audit: PathBuf::from("data/moderation_audit.jsonl"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyConfig {
+    /// `RANKED_SEED_KEY_FILE`, the keys ranked seeds are signed with. Made on first start
+    pub ranked_seeds: PathBuf,
+}
+
+impl Default for KeyConfig {
+    fn default() -> Self {
+        KeyConfig {
+            ranked_seeds: PathBuf::from("data/ranked_seed.key"),
+        }
+    }
+}
+
+/// Parts of the API that can be switched off, their endpoints are not served at all
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    /// `FEATURE_TOURNAMENTS`
+    pub tournaments: bool,
+    /// `FEATURE_LIVE`, spectating games as they are played
+    pub live: bool,
+    /// `FEATURE_RACES`
+    pub races: bool,
+    /// `FEATURE_RANKED`, dealing ranked seeds
+    pub ranked: bool,
+    /// `FEATURE_MODERATION`, the moderation queue, reviews and bans
+    pub moderation: bool,
+    /// `FEATURE_SHARE_ANALYSIS`, the engine's accuracy on share cards
+    pub share_analysis: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features {
+            tournaments: true,
+            live: true,
+            races: true,
+            ranked: true,
+            moderation: true,
+            share_analysis: true,
+        }
+    }
+}
+
+/// What one client can ask of the public endpoints that replay and draw games
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// `REQUESTS_PER_MINUTE`, per IP, for the share page and image
+    pub requests_per_minute: u32,
+    /// `RATE_LIMIT_BURST`, requests an IP can make at once before the per minute rate applies
+    pub burst: u32,
+    /// `FORWARDED_HOPS`, how many reverse proxies in front add to X-Forwarded-For. The client's
+    /// IP is taken that many entries from its end. 0 uses the connection's address, anything
+    /// more trusts the proxies, anyone can send the header
+    pub forwarded_hops: usize,
+    /// `MAX_RECORDING_BYTES`
+    pub max_recording_bytes: usize,
+    /// `MAX_RECORDING_MOVES`
+    pub max_moves: usize,
+    /// `WORK_BUDGET_MS`, how long one request's replaying and drawing can take, waiting included
+    pub work_budget_ms: u64,
+    /// `MAX_CONCURRENT_WORK`, replays and drawings running at once
+    pub max_concurrent_work: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            requests_per_minute: 60,
+            burst: 20,
+            forwarded_hops: 0,
+            max_recording_bytes: 64 * 1024,
+            max_moves: 50_000,
+            work_budget_ms: 2_000,
+            max_concurrent_work: 4,
+        }
+    }
+}
+
+/// Bearer tokens for the endpoints that change things, never sent back out
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenConfig {
+    /// `MODERATOR_TOKEN`, moderation is off without it
+    pub moderator: Option<String>,
+    /// `ORGANISER_TOKEN`, organising tournaments is off without it
+    pub organiser: Option<String>,
+}
+
+/// The repo bans and tournament results are published to
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthoritySettings {
+    /// `AUTHORITY_PDS`
+    pub pds: String,
+    /// `AUTHORITY_IDENTIFIER`, publishing is off without it
+    pub identifier: Option<String>,
+    /// `AUTHORITY_APP_PASSWORD`
+    #[serde(skip_serializing)]
+    #[schemars(skip)]
+    pub app_password: Option<String>,
+}
+
+impl Default for AuthoritySettings {
+    fn default() -> Self {
+        AuthoritySettings {
+            pds: "https://bsky.social".to_string(),
+            identifier: None,
+            app_password: None,
+        }
+    }
+}
+
+fn override_with<T: FromStr>(value: &mut T, var: &str, errors: &mut Vec<String>)
+where
+    T::Err: Display,
+{
+    if let Ok(raw) = env::var(var) {
+        match raw.parse() {
+            Ok(parsed) => *value = parsed,
+            Err(e) => errors.push(format!("{}={:?}: {}", var, raw, e)),
+        }
+    }
+}
+
+fn override_optional(value: &mut Option<String>, var: &str) {
+    if let Ok(raw) = env::var(var) {
+        *value = Some(raw);
+    }
+}
+
+fn check_url(name: &str, url: &mut String, errors: &mut Vec<String>) {
+    // Links are built by adding a path straight on
+    url.truncate(url.trim_end_matches('/').len());
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        Ok(_) => errors.push(format!("{}: {} is not an http or https URL", name, url)),
+        Err(e) => errors.push(format!("{}: {} is not a URL: {}", name, url, e)),
+    }
+}
+
+fn check_token(name: &str, token: &Option<String>, errors: &mut Vec<String>) {
+    if token
+        .as_deref()
+        .is_some_and(|token| token.trim().is_empty())
+    {
+        errors.push(format!("{} is set but empty", name));
+    }
+}
+
+impl Config {
+    /// Reads the file named by API_CONFIG, or Config.toml when that is not set, applies the
+    /// environment on top and checks the result. Every problem found is listed in the error
+    pub fn load() -> Result<Self, String> {
+        let (path, required) = match env::var(CONFIG_FILE_VAR) {
+            Ok(path) => (PathBuf::from(path), true),
+            Err(_) => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        };
+        let (mut config, source): (Config, String) = match std::fs::read_to_string(&path) {
+            Ok(contents) => (
+                toml::from_str(&contents)
+                    .map_err(|e| format!("Error parsing {}: {}", path.display(), e))?,
+                path.display().to_string(),
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => (
+                Config::default(),
+                format!("the defaults, there is no {}", path.display()),
+            ),
+            Err(e) => return Err(format!("Error reading {}: {}", path.display(), e)),
+        };
+
+        let mut errors = vec![];
+        config.apply_env(&mut errors);
+        config.validate(&mut errors);
+        if !errors.is_empty() {
+            return Err(format!(
+                "Invalid config from {}:\n  {}",
+                source,
+                errors.join("\n  ")
+            ));
+        }
+        Ok(config)
+    }
+
+    fn apply_env(&mut self, errors: &mut Vec<String>) {
+        override_with(&mut self.server.bind_address, "BIND_ADDRESS", errors);
+        let mut port = self.server.bind_address.port();
+        override_with(&mut port, "PORT", errors);
+        self.server.bind_address.set_port(port);
+        override_with(
+            &mut self.server.request_body_max_bytes,
+            "REQUEST_BODY_MAX_BYTES",
+            errors,
+        );
+
+        override_with(&mut self.site.base_url, "BASE_URL", errors);
+        override_with(&mut self.site.app_url, "APP_URL", errors);
+        override_with(&mut self.site.og_title, "OG_TITLE", errors);
+        override_with(&mut self.site.og_description, "OG_DESCRIPTION", errors);
+
+        override_with(&mut self.images.font_path, "FONT_PATH", errors);
+        override_with(
+            &mut self.images.memory_cache_entries,
+            "IMAGE_MEMORY_CACHE_ENTRIES",
+            errors,
+        );
+        override_with(
+            &mut self.images.disk_cache_dir,
+            "IMAGE_DISK_CACHE_DIR",
+            errors,
+        );
+        override_with(
+            &mut self.images.disk_cache_entries,
+            "IMAGE_DISK_CACHE_ENTRIES",
+            errors,
+        );
+
+        override_with(&mut self.storage.database, "DATABASE_PATH", errors);
+        override_with(&mut self.storage.tournaments, "TOURNAMENTS_FILE", errors);
+        override_with(&mut self.storage.anticheat, "ANTICHEAT_FILE", errors);
+        override_with(&mut self.storage.moderation, "MODERATION_FILE", errors);
+        override_with(
+            &mut self.storage.moderation_audit,
+            "MODERATION_AUDIT_FILE",
+            errors,
+        );
+
+        override_with(&mut self.keys.ranked_seeds, "RANKED_SEED_KEY_FILE", errors);
+
+        override_with(
+            &mut self.features.tournaments,
+            "FEATURE_TOURNAMENTS",
+            errors,
+        );
+        override_with(&mut self.features.live, "FEATURE_LIVE", errors);
+        override_with(&mut self.features.races, "FEATURE_RACES", errors);
+        override_with(&mut self.features.ranked, "FEATURE_RANKED", errors);
+        override_with(&mut self.features.moderation, "FEATURE_MODERATION", errors);
+        override_with(
+            &mut self.features.share_analysis,
+            "FEATURE_SHARE_ANALYSIS",
+            errors,
+        );
+
+        override_with(
+            &mut self.limits.requests_per_minute,
+            "REQUESTS_PER_MINUTE",
+            errors,
+        );
+        override_with(&mut self.limits.burst, "RATE_LIMIT_BURST", errors);
+        override_with(&mut self.limits.forwarded_hops, "FORWARDED_HOPS", errors);
+        override_with(
+            &mut self.limits.max_recording_bytes,
+            "MAX_RECORDING_BYTES",
+            errors,
+        );
+        override_with(&mut self.limits.max_moves, "MAX_RECORDING_MOVES", errors);
+        override_with(&mut self.limits.work_budget_ms, "WORK_BUDGET_MS", errors);
+        override_with(
+            &mut self.limits.max_concurrent_work,
+            "MAX_CONCURRENT_WORK",
+            errors,
+        );
+
+        override_optional(&mut self.tokens.moderator, "MODERATOR_TOKEN");
+        override_optional(&mut self.tokens.organiser, "ORGANISER_TOKEN");
+
+        override_with(&mut self.authority.pds, "AUTHORITY_PDS", errors);
+        override_optional(&mut self.authority.identifier, "AUTHORITY_IDENTIFIER");
+        override_optional(&mut self.authority.app_password, "AUTHORITY_APP_PASSWORD");
+    }
+
+    fn validate(&mut self, errors: &mut Vec<String>) {
+        if self.server.request_body_max_bytes == 0 {
+            errors.push("server.request_body_max_bytes has to be above 0".to_string());
+        }
+        let limits = &self.limits;
+        for (name, value) in [
+            (
+                "limits.requests_per_minute",
+                limits.requests_per_minute as u64,
+            ),
+            ("limits.burst", limits.burst as u64),
+            (
+                "limits.max_recording_bytes",
+                limits.max_recording_bytes as u64,
+            ),
+            ("limits.max_moves", limits.max_moves as u64),
+            ("limits.work_budget_ms", limits.work_budget_ms),
+            (
+                "limits.max_concurrent_work",
+                limits.max_concurrent_work as u64,
+            ),
+        ] {
+            if value == 0 {
+                errors.push(format!("{} has to be above 0", name));
+            }
+        }
+        check_url("site.base_url", &mut self.site.base_url, errors);
+        check_url("site.app_url", &mut self.site.app_url, errors);
+        check_url("authority.pds", &mut self.authority.pds, errors);
+
+        // A font that is missing or broken would otherwise only show up on the first share image
+        match std::fs::read(&self.images.font_path) {
+            Ok(bytes) if FontRef::try_from_slice(&bytes).is_err() => {
+                errors.push(format!(
+                    "images.font_path: {} is not a font",
+                    self.images.font_path.display()
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => errors.push(format!(
+                "images.font_path: {}: {}",
+                self.images.font_path.display(),
+                e
+            )),
+        }
+
+        check_token("tokens.moderator", &self.tokens.moderator, errors);
+        check_token("tokens.organiser", &self.tokens.organiser, errors);
+        if self.authority.identifier.is_some() != self.authority.app_password.is_some() {
+            errors.push(
+                "authority.identifier and authority.app_password have to be set together"
+                    .to_string(),
+            );
+        }
+        check_token(
+            "authority.app_password",
+            &self.authority.app_password,
+            errors,
+        );
+    }
+
+    /// The authority's credentials, none when publishing is not set up
+    pub fn authority(&self) -> Option<AuthorityConfig> {
+        Some(AuthorityConfig {
+            pds: self.authority.pds.clone(),
+            identifier: self.authority.identifier.clone()?,
+            app_password: self.authority.app_password.clone()?,
+        })
+    }
+}
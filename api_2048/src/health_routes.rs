@@ -0,0 +1,361 @@
+use crate::ApiContext;
+use crate::config::StorageConfig;
+use crate::metrics::write_header;
+use crate::storage::Database;
+use dropshot::{Body as DropshotBody, HttpError, HttpResponseOk, RequestContext};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+// Written and removed again in every data directory at startup, to check they take writes
+const PROBE_FILE: &str = ".readyz";
+
+/// One thing the server needs before it can take traffic
+#[derive(Serialize, JsonSchema)]
+pub struct ReadinessCheck {
+    pub name: String,
+    pub ok: bool,
+    /// What is wrong, when something is
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+fn check(name: &str, result: Result<(), String>) -> ReadinessCheck {
+    ReadinessCheck {
+        name: name.to_string(),
+        ok: result.is_ok(),
+        error: result.err(),
+    }
+}
+
+// The directories the data files are written to
+fn data_dirs(storage: &StorageConfig) -> BTreeSet<&Path> {
+    [
+        &storage.tournaments,
+        &storage.anticheat,
+        &storage.moderation,
+        &storage.moderation_audit,
+    ]
+    .into_iter()
+    .map(|path| {
+        path.parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+    })
+    .collect()
+}
+
+async fn check_writable(dir: &Path) -> Result<(), String> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| format!("Error creating {}: {}", dir.display(), e))?;
+    let probe = dir.join(PROBE_FILE);
+    tokio::fs::write(&probe, b"ok")
+        .await
+        .map_err(|e| format!("Error writing to {}: {}", dir.display(), e))?;
+    let _ = tokio::fs::remove_file(&probe).await;
+    Ok(())
+}
+
+/// Writes a file to every data directory and removes it again. The data files are only written
+/// when something changes, so a full disk or a volume mounted read only would otherwise show up
+/// on the first write. Run once at startup, /readyz reports what it found
+pub async fn probe_storage(storage: &StorageConfig) -> Result<(), String> {
+    for dir in data_dirs(storage) {
+        check_writable(dir).await?;
+    }
+    Ok(())
+}
+
+// The startup probe, then a look at each directory so one that goes away or read only since
+// shows up without writing to it on every call
+async fn check_storage(api_context: &ApiContext) -> Result<(), String> {
+    api_context.storage_probe.clone()?;
+    for dir in data_dirs(&api_context.config.storage) {
+        let metadata = tokio::fs::metadata(dir)
+            .await
+            .map_err(|e| format!("Error reading {}: {}", dir.display(), e))?;
+        if !metadata.is_dir() {
+            return Err(format!("{} is not a directory", dir.display()));
+        }
+        if metadata.permissions().readonly() {
+            return Err(format!("{} is read only", dir.display()));
+        }
+    }
+    Ok(())
+}
+
+async fn check_database(api_context: &ApiContext) -> Result<(), String> {
+    let version = api_context
+        .database
+        .schema_version()
+        .await
+        .map_err(|e| e.to_string())?;
+    if version != Database::latest_version() {
+        return Err(format!(
+            "The database is at version {}, expected {}",
+            version,
+            Database::latest_version()
+        ));
+    }
+    Ok(())
+}
+
+async fn check_keys(api_context: &ApiContext) -> Result<(), String> {
+    if api_context.ranked_seeds.key_count() == 0 {
+        return Err("No ranked seed keys are loaded".to_string());
+    }
+    let path = &api_context.config.keys.ranked_seeds;
+    tokio::fs::metadata(path)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Error reading {}: {}", path.display(), e))
+}
+
+/// Answers as long as the server is running, for restarting it when it stops
+#[dropshot::endpoint {
+    method = GET,
+    path = "/healthz",
+}]
+pub async fn get_health(
+    _rqctx: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<String>, HttpError> {
+    Ok(HttpResponseOk("ok".to_string()))
+}
+
+/// 200 when the database answers, the data directories took writes at startup and are still
+/// there and the ranked seed keys are loaded, 503 with what failed otherwise. The body is a Readiness either way
+#[dropshot::endpoint {
+    method = GET,
+    path = "/readyz",
+}]
+pub async fn get_readiness(
+    rqctx: RequestContext<ApiContext>,
+) -> Result<http::Response<DropshotBody>, HttpError> {
+    let api_context = rqctx.context();
+    let checks = vec![
+        check("database", check_database(api_context).await),
+        check("storage", check_storage(api_context).await),
+        check("keys", check_keys(api_context).await),
+    ];
+    let readiness = Readiness {
+        ready: checks.iter().all(|check| check.ok),
+        checks,
+    };
+    let status = if readiness.ready {
+        http::StatusCode::OK
+    } else {
+        log::warn!(
+            "Not ready: {}",
+            readiness
+                .checks
+                .iter()
+                .filter_map(|check| check.error.as_deref())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        http::StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = serde_json::to_string(&readiness)
+        .map_err(|e| HttpError::for_internal_error(e.to_string()))?;
+    http::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(DropshotBody::from(body))
+        .map_err(|e| HttpError::for_internal_error(e.to_string()))
+}
+
+fn write_value(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    write_header(out, name, kind, help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Request counts and latencies per endpoint, share image render times and cache hits, requests
+/// turned away by the limits and the sizes of the stores and database tables, in the Prometheus
+/// text format
+#[dropshot::endpoint {
+    method = GET,
+    path = "/metrics",
+}]
+pub async fn get_metrics(
+    rqctx: RequestContext<ApiContext>,
+) -> Result<http::Response<DropshotBody>, HttpError> {
+    let api_context = rqctx.context();
+    let mut out = String::new();
+    api_context.request_metrics.write(&mut out);
+
+    let images = api_context.share_images.stats().await;
+    write_header(
+        &mut out,
+        "api_share_image_cache_hits_total",
+        "counter",
+        "Share images served from a cache",
+    );
+    let _ = writeln!(
+        out,
+        "api_share_image_cache_hits_total{{cache=\"memory\"}} {}",
+        images.memory_hits
+    );
+    let _ = writeln!(
+        out,
+        "api_share_image_cache_hits_total{{cache=\"disk\"}} {}",
+        images.disk_hits
+    );
+    write_value(
+        &mut out,
+        "api_share_image_cache_misses_total",
+        "counter",
+        "Share images replayed and drawn because no cache had them",
+        images.misses,
+    );
+    write_value(
+        &mut out,
+        "api_share_image_not_modified_total",
+        "counter",
+        "Share image requests answered with 304 Not Modified",
+        images.not_modified,
+    );
+    write_header(
+        &mut out,
+        "api_share_image_cache_entries",
+        "gauge",
+        "Share images in each cache",
+    );
+    let _ = writeln!(
+        out,
+        "api_share_image_cache_entries{{cache=\"memory\"}} {}",
+        images.memory_entries
+    );
+    let _ = writeln!(
+        out,
+        "api_share_image_cache_entries{{cache=\"disk\"}} {}",
+        images.disk_entries
+    );
+    write_header(
+        &mut out,
+        "api_share_image_cache_evictions_total",
+        "counter",
+        "Share images pushed out of each cache to make room",
+    );
+    let _ = writeln!(
+        out,
+        "api_share_image_cache_evictions_total{{cache=\"memory\"}} {}",
+        images.memory_evictions
+    );
+    let _ = writeln!(
+        out,
+        "api_share_image_cache_evictions_total{{cache=\"disk\"}} {}",
+        images.disk_evictions
+    );
+    write_header(
+        &mut out,
+        "api_share_image_render_duration_seconds",
+        "histogram",
+        "Time to replay and draw a share image",
+    );
+    api_context.share_images.render_times().await.write(
+        &mut out,
+        "api_share_image_render_duration_seconds",
+        "",
+    );
+
+    let limited = api_context.limits.counts();
+    write_header(
+        &mut out,
+        "api_limited_requests_total",
+        "counter",
+        "Requests turned away, by the limit they hit",
+    );
+    let _ = writeln!(
+        out,
+        "api_limited_requests_total{{limit=\"rate\"}} {}",
+        limited.rate_limited
+    );
+    let _ = writeln!(
+        out,
+        "api_limited_requests_total{{limit=\"size\"}} {}",
+        limited.too_large
+    );
+    let _ = writeln!(
+        out,
+        "api_limited_requests_total{{limit=\"budget\"}} {}",
+        limited.over_budget
+    );
+
+    match api_context.database.row_counts().await {
+        Ok(rows) => {
+            write_value(
+                &mut out,
+                "api_stored_games",
+                "gauge",
+                "Games in the database",
+                rows.games,
+            );
+            write_value(
+                &mut out,
+                "api_stored_players",
+                "gauge",
+                "Players in the database",
+                rows.players,
+            );
+            write_value(
+                &mut out,
+                "api_stored_verifications",
+                "gauge",
+                "Replay verifications in the database",
+                rows.verifications,
+            );
+        }
+        // The rest still gets scraped, /readyz says what is wrong with the database
+        Err(e) => log::warn!("Error counting the database's rows: {}", e),
+    }
+
+    write_value(
+        &mut out,
+        "api_analysed_games",
+        "gauge",
+        "Games read from players' PDSs and analysed for the moderation queue",
+        api_context.anticheat.report_count().await as u64,
+    );
+    write_value(
+        &mut out,
+        "api_tournaments",
+        "gauge",
+        "Tournaments set up",
+        api_context.tournaments.list().await.len() as u64,
+    );
+    write_value(
+        &mut out,
+        "api_sanctions",
+        "gauge",
+        "Bans and hides in force",
+        api_context.moderation.list().await.len() as u64,
+    );
+    write_value(
+        &mut out,
+        "api_live_games",
+        "gauge",
+        "Games being streamed to spectators",
+        api_context.live.list().await.len() as u64,
+    );
+    write_value(
+        &mut out,
+        "api_ranked_seed_keys",
+        "gauge",
+        "Keys ranked seeds are checked against",
+        api_context.ranked_seeds.key_count() as u64,
+    );
+
+    http::Response::builder()
+        .status(http::StatusCode::OK)
+        .header(http::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(DropshotBody::from(out))
+        .map_err(|e| HttpError::for_internal_error(e.to_string()))
+}
@@ -1,17 +1,24 @@
-use dropshot::{HttpError, Query, RequestContext, Body as DropshotBody};
+use dropshot::{HttpError, HttpResponseOk, Query, RequestContext, Body as DropshotBody};
 use image::{Rgba, RgbaImage, ImageFormat};
 use imageproc::drawing::{draw_filled_rect_mut, draw_text_mut};
 use imageproc::rect::Rect;
-use ab_glyph::{FontRef, PxScale, Font, ScaleFont as _};
+use ab_glyph::{FontArc, PxScale, Font, ScaleFont as _};
 use serde::Deserialize;
 use schemars::JsonSchema;
 use twothousand_forty_eight::{unified::game::GameState, v2::recording::SeededRecording};
 use crate::ApiContext;
+use crate::share_images::CacheStats;
 use urlencoding;
 
 const TILE_SIZE: u32 = 100;
 const PADDING: u32 = 10;
-const FONT_PATH: &str = "assets/DejaVuSans.ttf";
+// A day, then crawlers check back with the ETag in case the drawing changed
+const CACHE_CONTROL: &str = "public, max-age=86400";
+
+/// Width or height in pixels of the image for a board with this many tiles across
+pub fn board_image_dimension(tiles: usize) -> u32 {
+    (tiles as u32 * TILE_SIZE) + ((tiles as u32 + 1) * PADDING)
+}
 
 fn get_tile_color(value: usize) -> Rgba<u8> {
     match value {
@@ -38,80 +45,20 @@ fn get_text_color(value: usize) -> Rgba<u8> {
     }
 }
 
-#[derive(Deserialize, JsonSchema)]
-pub struct GenerateImageQuery {
-    pub seeded_recording: String,
-}
-
-#[dropshot::endpoint {
-    method = GET,
-    path = "/share/game/image.png",
-}]
-pub async fn generate_board_image(
-    _rqctx: RequestContext<ApiContext>,
-    query: Query<GenerateImageQuery>,
-) -> Result<http::Response<DropshotBody>, HttpError> {
-    let original_seeded_recording_param = query.into_inner().seeded_recording;
-
-    // Decode the potentially double-encoded seeded_recording string for parsing
-    let string_to_parse = match urlencoding::decode(&original_seeded_recording_param) {
-        Ok(cow_str) => cow_str.into_owned(),
-        Err(e) => {
-            log::warn!(
-                "ImageGen: Failed to URL-decode seeded_recording string: {}. Error: {}",
-                original_seeded_recording_param,
-                e
-            );
-            return Err(HttpError::for_bad_request(
-                None,
-                format!("ImageGen: Invalid seeded_recording parameter: could not decode. Error: {}", e),
-            ));
-        }
-    };
-
-    let seeded_recording: SeededRecording = match string_to_parse.parse() { // Use decoded string
-        Ok(rec) => rec,
-        Err(e) => {
-            log::error!("ImageGen: Failed to parse seeded_recording from '{}': {}", string_to_parse, e);
-            return Err(HttpError::for_bad_request(None, format!("ImageGen: Invalid seeded_recording: {}", e)));
-        }
-    };
-
-    let game_state: GameState = match GameState::from_reconstructable_ruleset(&seeded_recording) {
-        Ok(gs) => gs,
-        Err(e) => {
-            log::error!("Failed to reconstruct game state: {}", e);
-            return Err(HttpError::for_internal_error(format!("Could not reconstruct game state: {}", e)));
-        }
-    };
-
-    let board_dim = game_state.board.width;
-    if game_state.board.height != board_dim {
-        log::warn!("Board width and height differ, using width for square image generation.");
-    }
+/// Draws the board as it stands at the end of the game as a PNG, the same image the share links show
+pub fn render_board_png(game_state: &GameState, font: &FontArc) -> Result<Vec<u8>, String> {
+    let board_width = game_state.board.width;
+    let board_height = game_state.board.height;
 
-    let img_dimension = (board_dim as u32 * TILE_SIZE) + ((board_dim as u32 + 1) * PADDING);
-    let mut img = RgbaImage::new(img_dimension, img_dimension);
+    let img_width = board_image_dimension(board_width);
+    let img_height = board_image_dimension(board_height);
+    let mut img = RgbaImage::new(img_width, img_height);
     let board_bg_color = Rgba([187, 173, 160, 255]);
-    draw_filled_rect_mut(&mut img, Rect::at(0, 0).of_size(img_dimension, img_dimension), board_bg_color);
+    draw_filled_rect_mut(&mut img, Rect::at(0, 0).of_size(img_width, img_height), board_bg_color);
 
-    let font_bytes = match std::fs::read(FONT_PATH) {
-        Ok(bytes) => bytes,
-        Err(e) => {
-            log::error!("Failed to load font file '{}': {}", FONT_PATH, e);
-            return Err(HttpError::for_internal_error("Internal server error: font file missing".to_string()));
-        }
-    };
-    let font = match FontRef::try_from_slice(&font_bytes) {
-        Ok(f) => f,
-        Err(e) => {
-            log::error!("Failed to parse font file '{}' with ab_glyph: {}", FONT_PATH, e);
-            return Err(HttpError::for_internal_error("Internal server error: font parsing failed".to_string()));
-        }
-    };
     
-    for r in 0..board_dim {
-        for c in 0..board_dim {
+    for r in 0..board_height {
+        for c in 0..board_width {
             let tile_opt = game_state.board.tiles.get(r).and_then(|row| row.get(c)).and_then(|&t| t);
             let x_offset = PADDING + (c as u32 * (TILE_SIZE + PADDING));
             let y_offset = PADDING + (r as u32 * (TILE_SIZE + PADDING));
@@ -168,7 +115,7 @@ pub async fn generate_board_image(
                     text_x as i32, 
                     text_y as i32, 
                     scale,
-                    &font,
+                    font,
                     &text,
                 );
             }
@@ -176,18 +123,121 @@ pub async fn generate_board_image(
     }
 
     let mut buffer = Vec::new();
-    if let Err(e) = img.write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png) {
-        log::error!("Failed to encode image to PNG: {}", e);
-        return Err(HttpError::for_internal_error("Failed to generate image".to_string()));
+    img.write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode image to PNG: {}", e))?;
+    Ok(buffer)
+}
+
+// Whether an If-None-Match header names the ETag. Weak tags count, the image is the same either way
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GenerateImageQuery {
+    pub seeded_recording: String,
+}
+
+#[dropshot::endpoint {
+    method = GET,
+    path = "/share/game/image.png",
+}]
+pub async fn generate_board_image(
+    rqctx: RequestContext<ApiContext>,
+    query: Query<GenerateImageQuery>,
+) -> Result<http::Response<DropshotBody>, HttpError> {
+    crate::limits::check_rate_limit(&rqctx)?;
+    let limits = &rqctx.context().limits;
+    let original_seeded_recording_param = query.into_inner().seeded_recording;
+    limits.check_recording_size(&original_seeded_recording_param)?;
+
+    // Decode the potentially double-encoded seeded_recording string for parsing
+    let string_to_parse = match urlencoding::decode(&original_seeded_recording_param) {
+        Ok(cow_str) => cow_str.into_owned(),
+        Err(e) => {
+            log::warn!(
+                "ImageGen: Failed to URL-decode seeded_recording string: {}. Error: {}",
+                original_seeded_recording_param,
+                e
+            );
+            return Err(HttpError::for_bad_request(
+                None,
+                format!("ImageGen: Invalid seeded_recording parameter: could not decode. Error: {}", e),
+            ));
+        }
+    };
+
+    let seeded_recording: SeededRecording = match string_to_parse.parse() { // Use decoded string
+        Ok(rec) => rec,
+        Err(e) => {
+            log::error!("ImageGen: Failed to parse seeded_recording from '{}': {}", string_to_parse, e);
+            return Err(HttpError::for_bad_request(None, format!("ImageGen: Invalid seeded_recording: {}", e)));
+        }
+    };
+    limits.check_moves(&seeded_recording)?;
+
+    let api_context = rqctx.context();
+    let share_images = &api_context.share_images;
+    let key = share_images.key(&seeded_recording);
+    let etag = format!("\"{}\"", key);
+
+    // The image only depends on the recording, so a crawler that has it already can keep it
+    let if_none_match = rqctx
+        .request
+        .headers()
+        .get(http::header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok());
+    if if_none_match.is_some_and(|tags| etag_matches(tags, &etag)) {
+        share_images.count_not_modified();
+        return http::Response::builder()
+            .status(http::StatusCode::NOT_MODIFIED)
+            .header(http::header::ETAG, &etag)
+            .header(http::header::CACHE_CONTROL, CACHE_CONTROL)
+            .body(DropshotBody::empty())
+            .map_err(|e| {
+                log::error!("Failed to create response: {}", e);
+                HttpError::for_internal_error("Failed to create image response".to_string())
+            });
     }
 
+    let font = api_context.font.clone();
+    let render = async {
+        let png = limits
+            .run(move || {
+                let game_state = GameState::from_reconstructable_ruleset(&seeded_recording)
+                    .map_err(|e| format!("Could not reconstruct game state: {}", e))?;
+                render_board_png(&game_state, &font)
+            })
+            .await?;
+        png.map_err(|e| {
+            log::error!("ImageGen: {}", e);
+            HttpError::for_internal_error("Failed to generate image".to_string())
+        })
+    };
+    let buffer = share_images.get_or_render(&key, render).await?;
+
     let response = http::Response::builder()
         .status(http::StatusCode::OK)
         .header(http::header::CONTENT_TYPE, "image/png")
+        .header(http::header::ETAG, &etag)
+        .header(http::header::CACHE_CONTROL, CACHE_CONTROL)
         .body(DropshotBody::from(buffer))
         .map_err(|e| {
             log::error!("Failed to create response: {}", e);
             HttpError::for_internal_error("Failed to create image response".to_string())
         })?;
     Ok(response)
-} 
\ No newline at end of file
+} 
+/// Hits, misses and sizes of the share image cache, for tuning its capacity
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/share/image-cache",
+}]
+pub async fn get_share_image_cache_stats(
+    rqctx: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<CacheStats>, HttpError> {
+    Ok(HttpResponseOk(rqctx.context().share_images.stats().await))
+}
@@ -0,0 +1,101 @@
+use crate::ApiContext;
+use crate::storage::StorageError;
+use crate::storage::stats::PlayerStats;
+use crate::storage::verifications::Verification;
+use dropshot::{HttpError, HttpResponseOk, Query, RequestContext};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+const DEFAULT_BOARD_SIZE: usize = 4;
+const DEFAULT_LEADERBOARD_LENGTH: usize = 50;
+const MAX_LEADERBOARD_LENGTH: usize = 200;
+
+impl From<StorageError> for HttpError {
+    fn from(err: StorageError) -> Self {
+        match err {
+            StorageError::Invalid(_) => HttpError::for_bad_request(None, err.to_string()),
+            _ => {
+                log::error!("{}", err);
+                HttpError::for_internal_error(err.to_string())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct LeaderboardQuery {
+    /// Width and height of the board, 4 when missing
+    pub size: Option<usize>,
+    /// How many players to list, 50 when missing and 200 at most
+    pub limit: Option<usize>,
+}
+
+/// Players by their best score on boards of a size, best first. Only verified ranked games count:
+/// the first classic game on each seed the server dealt, without hints or undos, not held back by
+/// the anti-cheat checks or a moderator
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/leaderboard",
+}]
+pub async fn get_leaderboard(
+    rqctx: RequestContext<ApiContext>,
+    query: Query<LeaderboardQuery>,
+) -> Result<HttpResponseOk<Vec<PlayerStats>>, HttpError> {
+    let query = query.into_inner();
+    let size = query.size.unwrap_or(DEFAULT_BOARD_SIZE);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_LEADERBOARD_LENGTH)
+        .min(MAX_LEADERBOARD_LENGTH);
+    let api_context = rqctx.context();
+    // Stats are only totalled again when the player is reverified, so sanctions since then are
+    // checked here. Each one can knock out at most one row, reading that many more fills the list
+    let sanctions = api_context.moderation.list().await.len();
+    let rows = api_context
+        .database
+        .transaction(move |tx| tx.stats().leaderboard(size, limit + sanctions))
+        .await?;
+    let mut leaderboard = Vec::with_capacity(limit);
+    for stats in rows {
+        if leaderboard.len() == limit {
+            break;
+        }
+        // A player whose best game was pulled is left off till they are reverified, rather than
+        // shown with a best score that is not theirs
+        if !api_context
+            .moderation
+            .exclusions(&stats.did)
+            .await
+            .excludes(&stats.best_game_uri)
+        {
+            leaderboard.push(stats);
+        }
+    }
+    Ok(HttpResponseOk(leaderboard))
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct VerificationQuery {
+    /// The game record's at:// URI
+    pub uri: String,
+}
+
+/// Whether the game replayed to the score it claims, the last time it was checked
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/verifications",
+}]
+pub async fn get_verification(
+    rqctx: RequestContext<ApiContext>,
+    query: Query<VerificationQuery>,
+) -> Result<HttpResponseOk<Verification>, HttpError> {
+    let uri = query.into_inner().uri;
+    let lookup = uri.clone();
+    rqctx
+        .context()
+        .database
+        .transaction(move |tx| tx.verifications().get(&lookup))
+        .await?
+        .map(HttpResponseOk)
+        .ok_or_else(|| HttpError::for_not_found(None, format!("{} has not been verified", uri)))
+}
@@ -0,0 +1,372 @@
+use crate::ApiContext;
+use crate::config::LimitsConfig;
+use dropshot::{ClientErrorStatusCode, HttpError, RequestContext, RequestInfo};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use twothousand_forty_eight::v2::recording::SeededRecording;
+
+// Past this many clients the buckets that have filled back up are dropped, they are the same as
+// no bucket at all
+const PRUNE_ABOVE: usize = 10_000;
+// What a client turned away for the server being busy is told to wait
+const BUSY_RETRY_AFTER_SECS: u64 = 5;
+
+#[derive(Debug)]
+pub enum LimitError {
+    TooLarge(String),
+    RateLimited { retry_after_secs: u64 },
+    OverBudget,
+    Failed(String),
+}
+
+impl std::fmt::Display for LimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitError::TooLarge(err) => write!(f, "{}", err),
+            LimitError::RateLimited { retry_after_secs } => {
+                write!(
+                    f,
+                    "Too many requests, try again in {} seconds",
+                    retry_after_secs
+                )
+            }
+            LimitError::OverBudget => write!(
+                f,
+                "The server is too busy to replay this game, try again shortly"
+            ),
+            LimitError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+fn with_retry_after(err: HttpError, secs: u64) -> HttpError {
+    err.with_header(http::header::RETRY_AFTER, secs.to_string())
+        .unwrap_or_else(|e| HttpError::for_internal_error(e.to_string()))
+}
+
+impl From<LimitError> for HttpError {
+    fn from(err: LimitError) -> Self {
+        match err {
+            LimitError::TooLarge(_) => HttpError::for_client_error(
+                None,
+                ClientErrorStatusCode::PAYLOAD_TOO_LARGE,
+                err.to_string(),
+            ),
+            LimitError::RateLimited { retry_after_secs } => with_retry_after(
+                HttpError::for_client_error(
+                    None,
+                    ClientErrorStatusCode::TOO_MANY_REQUESTS,
+                    err.to_string(),
+                ),
+                retry_after_secs,
+            ),
+            LimitError::OverBudget => with_retry_after(
+                HttpError::for_unavail(None, err.to_string()),
+                BUSY_RETRY_AFTER_SECS,
+            ),
+            LimitError::Failed(e) => {
+                log::error!("{}", e);
+                HttpError::for_internal_error(e)
+            }
+        }
+    }
+}
+
+/// Requests turned away by each limit since the server started
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LimitCounts {
+    pub rate_limited: u64,
+    pub too_large: u64,
+    pub over_budget: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    rate_limited: AtomicU64,
+    too_large: AtomicU64,
+    over_budget: AtomicU64,
+}
+
+// The entry `hops` from the end of an X-Forwarded-For value, when there is one and it is an IP
+fn forwarded_ip(value: &str, hops: usize) -> Option<IpAddr> {
+    if hops == 0 {
+        return None;
+    }
+    let entries: Vec<&str> = value.split(',').collect();
+    let index = entries.len().checked_sub(hops)?;
+    entries[index].trim().parse().ok()
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per IP rate limits, recording size caps and a budget for the replaying and drawing one
+/// request can ask for
+pub struct Limits {
+    config: LimitsConfig,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+    // Caps the blocking threads busy with replays, so a burst waits instead of piling up
+    workers: Arc<Semaphore>,
+    counters: Counters,
+}
+
+impl Limits {
+    pub fn new(config: LimitsConfig) -> Self {
+        Limits {
+            workers: Arc::new(Semaphore::new(config.max_concurrent_work)),
+            buckets: Mutex::new(HashMap::new()),
+            counters: Counters::default(),
+            config,
+        }
+    }
+
+    /// The client's IP, from X-Forwarded-For when there are proxies in front to trust. Each
+    /// adds the address it got the request from at the end, so the entry `forwarded_hops` from
+    /// the end is the one the outermost proxy saw. Anything before it came from the client
+    pub fn client_ip(&self, request: &RequestInfo) -> IpAddr {
+        request
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| forwarded_ip(value, self.config.forwarded_hops))
+            .unwrap_or_else(|| request.remote_addr().ip())
+    }
+
+    /// Takes a token from the IP's bucket, which holds `burst` and refills at
+    /// `requests_per_minute`
+    pub fn check_rate(&self, ip: IpAddr) -> Result<(), LimitError> {
+        self.check_rate_at(ip, Instant::now())
+    }
+
+    fn check_rate_at(&self, ip: IpAddr, now: Instant) -> Result<(), LimitError> {
+        let capacity = self.config.burst as f64;
+        let per_second = self.config.requests_per_minute as f64 / 60.0;
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() > PRUNE_ABOVE {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_second
+                    < capacity
+            });
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let refilled = now.duration_since(bucket.updated).as_secs_f64() * per_second;
+        bucket.tokens = (bucket.tokens + refilled).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            self.counters.rate_limited.fetch_add(1, Ordering::Relaxed);
+            let retry_after_secs = ((1.0 - bucket.tokens) / per_second).ceil() as u64;
+            Err(LimitError::RateLimited {
+                retry_after_secs: retry_after_secs.max(1),
+            })
+        }
+    }
+
+    /// Turns away recordings too long to be worth parsing
+    pub fn check_recording_size(&self, raw: &str) -> Result<(), LimitError> {
+        if raw.len() > self.config.max_recording_bytes {
+            self.counters.too_large.fetch_add(1, Ordering::Relaxed);
+            return Err(LimitError::TooLarge(format!(
+                "The seeded_recording is {} bytes, the most accepted is {}",
+                raw.len(),
+                self.config.max_recording_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn check_moves(&self, recording: &SeededRecording) -> Result<(), LimitError> {
+        if recording.moves.len() > self.config.max_moves {
+            self.counters.too_large.fetch_add(1, Ordering::Relaxed);
+            return Err(LimitError::TooLarge(format!(
+                "The game has {} moves, the most accepted is {}",
+                recording.moves.len(),
+                self.config.max_moves
+            )));
+        }
+        Ok(())
+    }
+
+    /// Both checks, for callers that parse the recording themselves. One that does not parse
+    /// passes here and is left for the caller to reject
+    pub fn check_recording(&self, raw: &str) -> Result<(), LimitError> {
+        self.check_recording_size(raw)?;
+        match raw.parse::<SeededRecording>() {
+            Ok(recording) => self.check_moves(&recording),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// When work starting now runs out of budget, for work that can stop itself rather than
+    /// keep its worker after the request gives up on it
+    pub fn work_deadline(&self) -> Instant {
+        Instant::now() + Duration::from_millis(self.config.work_budget_ms)
+    }
+
+    /// Runs CPU bound work off the async workers, within the budget. Waiting for a free worker
+    /// counts against it. Work that runs over can not be stopped, but the request stops waiting
+    /// for it and its worker stays taken till it is done
+    pub async fn run<T, F>(&self, work: F) -> Result<T, LimitError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let workers = self.workers.clone();
+        let budget = Duration::from_millis(self.config.work_budget_ms);
+        tokio::time::timeout(budget, async move {
+            let permit = workers
+                .acquire_owned()
+                .await
+                .map_err(|e| LimitError::Failed(e.to_string()))?;
+            tokio::task::spawn_blocking(move || {
+                let result = work();
+                drop(permit);
+                result
+            })
+            .await
+            .map_err(|e| LimitError::Failed(format!("Replaying panicked: {}", e)))
+        })
+        .await
+        .unwrap_or_else(|_| {
+            self.counters.over_budget.fetch_add(1, Ordering::Relaxed);
+            Err(LimitError::OverBudget)
+        })
+    }
+
+    pub fn counts(&self) -> LimitCounts {
+        LimitCounts {
+            rate_limited: self.counters.rate_limited.load(Ordering::Relaxed),
+            too_large: self.counters.too_large.load(Ordering::Relaxed),
+            over_budget: self.counters.over_budget.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Rate limits the request by its client's IP
+pub fn check_rate_limit(rqctx: &RequestContext<ApiContext>) -> Result<(), HttpError> {
+    let limits = &rqctx.context().limits;
+    let ip = limits.client_ip(&rqctx.request);
+    limits.check_rate(ip).map_err(|err| {
+        log::warn!("Rate limited {}", ip);
+        HttpError::from(err)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(requests_per_minute: u32, burst: u32) -> Limits {
+        Limits::new(LimitsConfig {
+            requests_per_minute,
+            burst,
+            ..LimitsConfig::default()
+        })
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn the_burst_goes_through_then_the_rate_applies() {
+        let limits = limits(60, 3);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limits.check_rate_at(ip("10.0.0.1"), start).is_ok());
+        }
+        match limits.check_rate_at(ip("10.0.0.1"), start) {
+            Err(LimitError::RateLimited { retry_after_secs }) => assert_eq!(retry_after_secs, 1),
+            other => panic!("Expected to be rate limited, got {:?}", other),
+        }
+        assert_eq!(limits.counts().rate_limited, 1);
+
+        // One a second at 60 a minute
+        let later = start + Duration::from_secs(1);
+        assert!(limits.check_rate_at(ip("10.0.0.1"), later).is_ok());
+        assert!(limits.check_rate_at(ip("10.0.0.1"), later).is_err());
+    }
+
+    #[test]
+    fn buckets_refill_no_higher_than_the_burst() {
+        let limits = limits(60, 2);
+        let start = Instant::now();
+        assert!(limits.check_rate_at(ip("10.0.0.1"), start).is_ok());
+        let later = start + Duration::from_secs(600);
+        for _ in 0..2 {
+            assert!(limits.check_rate_at(ip("10.0.0.1"), later).is_ok());
+        }
+        assert!(limits.check_rate_at(ip("10.0.0.1"), later).is_err());
+    }
+
+    #[test]
+    fn each_ip_has_its_own_bucket() {
+        let limits = limits(1, 1);
+        let start = Instant::now();
+        assert!(limits.check_rate_at(ip("10.0.0.1"), start).is_ok());
+        match limits.check_rate_at(ip("10.0.0.1"), start) {
+            Err(LimitError::RateLimited { retry_after_secs }) => assert_eq!(retry_after_secs, 60),
+            other => panic!("Expected to be rate limited, got {:?}", other),
+        }
+        assert!(limits.check_rate_at(ip("10.0.0.2"), start).is_ok());
+        assert!(limits.check_rate_at(ip("::1"), start).is_ok());
+    }
+
+    #[test]
+    fn forwarded_ips_are_taken_hops_from_the_end() {
+        let value = "1.1.1.1, 2.2.2.2, 3.3.3.3";
+        assert_eq!(forwarded_ip(value, 1), Some(ip("3.3.3.3")));
+        assert_eq!(forwarded_ip(value, 2), Some(ip("2.2.2.2")));
+        assert_eq!(forwarded_ip(value, 3), Some(ip("1.1.1.1")));
+        assert_eq!(
+            forwarded_ip("2001:db8::1,10.0.0.1", 2),
+            Some(ip("2001:db8::1"))
+        );
+    }
+
+    // X-Forwarded-For as it reaches the api in production: whatever the client sent, the
+    // client's address from Railway's edge, then the edge's address from Caddy
+    #[test]
+    fn the_shipped_proxy_chain_gives_the_client() {
+        let hops: usize = include_str!("../../dockerfiles/Api.Dockerfile")
+            .lines()
+            .find_map(|line| line.strip_prefix("ENV FORWARDED_HOPS="))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        // Without trusted_proxies Caddy swaps the header for the one address it saw, which is
+        // too short for the hops and leaves every client in Caddy's bucket
+        assert!(include_str!("../../production_configs/Caddyfile").contains("trusted_proxies"));
+        assert_eq!(forwarded_ip("100.64.0.2", hops), None);
+        assert_eq!(
+            forwarded_ip("203.0.113.7, 100.64.0.2", hops),
+            Some(ip("203.0.113.7"))
+        );
+        assert_eq!(
+            forwarded_ip("6.6.6.6, 203.0.113.7, 100.64.0.2", hops),
+            Some(ip("203.0.113.7"))
+        );
+    }
+
+    #[test]
+    fn forwarded_ips_fall_back_when_the_header_can_not_be_trusted() {
+        // No proxies to trust, so the header is whatever the client sent
+        assert_eq!(forwarded_ip("1.1.1.1", 0), None);
+        // Fewer entries than proxies means a proxy did not add one
+        assert_eq!(forwarded_ip("1.1.1.1", 2), None);
+        assert_eq!(forwarded_ip("", 1), None);
+        assert_eq!(forwarded_ip("not an ip, 2.2.2.2", 2), None);
+    }
+}
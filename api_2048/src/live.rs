@@ -0,0 +1,186 @@
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, broadcast};
+use twothousand_forty_eight::{unified::game::GameState, v2::recording::SeededRecording};
+
+// A stream nobody has moved in for this long can be taken over, say after a page reload
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+// Every update carries the whole recording, so a spectator that falls behind only needs the newest
+const SPECTATOR_BUFFER: usize = 16;
+// Spectators can wait on players who are not streaming yet, for this many players at most
+const MAX_WAITING_CHANNELS: usize = 256;
+
+#[derive(Debug)]
+pub enum LiveError {
+    Invalid(String),
+    // Someone else is streaming for this DID and has not gone idle
+    Taken,
+    // Too many players who are not streaming are being waited on
+    Busy,
+}
+
+impl std::fmt::Display for LiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LiveError::Invalid(err) => write!(f, "Invalid live game: {}", err),
+            LiveError::Taken => write!(f, "A game is already being streamed for this player"),
+            LiveError::Busy => write!(f, "Too many players are being waited on, try again later"),
+        }
+    }
+}
+
+/// The game a player is streaming, as spectators see it
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct LiveGame {
+    pub did: String,
+    /// The moves so far. Spectators replay it to get the board
+    pub seeded_recording: String,
+    pub score: usize,
+    /// The board filled up, or the player ended or ran out of their mode's limit
+    pub finished: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+struct Stream {
+    token: String,
+    last_update: Instant,
+    game: LiveGame,
+}
+
+impl Stream {
+    fn is_active(&self) -> bool {
+        !self.game.finished && self.last_update.elapsed() < IDLE_TIMEOUT
+    }
+}
+
+// Spectators subscribe per DID so they stay connected from one game to the next
+struct Channel {
+    sender: broadcast::Sender<LiveGame>,
+    stream: Option<Stream>,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Channel {
+            sender: broadcast::channel(SPECTATOR_BUFFER).0,
+            stream: None,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.stream.as_ref().is_some_and(Stream::is_active)
+    }
+
+    // Channels nobody streams to or watches are dropped as they are noticed
+    fn is_needed(&self) -> bool {
+        self.is_active() || self.sender.receiver_count() > 0
+    }
+}
+
+#[derive(Default)]
+pub struct LiveGames {
+    channels: Mutex<HashMap<String, Channel>>,
+}
+
+impl LiveGames {
+    /// Replaces the player's live game with the recording so far. The first update of a stream
+    /// has no token and gets one back that the rest of its updates have to send
+    pub async fn update(
+        &self,
+        did: &str,
+        token: Option<&str>,
+        seeded_recording: String,
+        finished: bool,
+    ) -> Result<String, LiveError> {
+        let recording: SeededRecording = seeded_recording
+            .parse()
+            .map_err(|e| LiveError::Invalid(format!("{:?}", e)))?;
+        let gamestate = GameState::from_reconstructable_ruleset(&recording)
+            .map_err(|e| LiveError::Invalid(format!("{:?}", e)))?;
+        let game = LiveGame {
+            did: did.to_string(),
+            seeded_recording,
+            score: gamestate.score_current,
+            finished: finished || gamestate.over,
+            updated_at: Utc::now(),
+        };
+
+        let mut channels = self.channels.lock().await;
+        channels.retain(|_, channel| channel.is_needed());
+        let channel = channels.entry(did.to_string()).or_insert_with(Channel::new);
+        let token = match &channel.stream {
+            Some(stream) if token == Some(stream.token.as_str()) => stream.token.clone(),
+            Some(stream) if stream.is_active() => return Err(LiveError::Taken),
+            _ => uuid::Uuid::new_v4().to_string(),
+        };
+        // Sending only fails when nobody is watching
+        let _ = channel.sender.send(game.clone());
+        channel.stream = Some(Stream {
+            token: token.clone(),
+            last_update: Instant::now(),
+            game,
+        });
+        Ok(token)
+    }
+
+    /// Whether the token is the one the player's stream was given, which stands in for the
+    /// player on the rest of the stream's updates
+    pub async fn holds_stream(&self, did: &str, token: Option<&str>) -> bool {
+        let Some(token) = token else {
+            return false;
+        };
+        self.channels
+            .lock()
+            .await
+            .get(did)
+            .and_then(|channel| channel.stream.as_ref())
+            .is_some_and(|stream| stream.token == token)
+    }
+
+    /// The player's game so far, if they are streaming one, and the updates that follow it
+    pub async fn watch(
+        &self,
+        did: &str,
+    ) -> Result<(Option<LiveGame>, broadcast::Receiver<LiveGame>), LiveError> {
+        let mut channels = self.channels.lock().await;
+        channels.retain(|_, channel| channel.is_needed());
+        if !channels.contains_key(did)
+            && channels
+                .values()
+                .filter(|channel| channel.stream.is_none())
+                .count()
+                >= MAX_WAITING_CHANNELS
+        {
+            return Err(LiveError::Busy);
+        }
+        let channel = channels.entry(did.to_string()).or_insert_with(Channel::new);
+        let current = channel.stream.as_ref().map(|stream| stream.game.clone());
+        Ok((current, channel.sender.subscribe()))
+    }
+
+    pub async fn get(&self, did: &str) -> Option<LiveGame> {
+        self.channels
+            .lock()
+            .await
+            .get(did)
+            .and_then(|channel| channel.stream.as_ref())
+            .map(|stream| stream.game.clone())
+    }
+
+    /// Games being played right now, most recently moved first
+    pub async fn list(&self) -> Vec<LiveGame> {
+        let mut games: Vec<LiveGame> = self
+            .channels
+            .lock()
+            .await
+            .values()
+            .filter_map(|channel| channel.stream.as_ref().filter(|stream| stream.is_active()))
+            .map(|stream| stream.game.clone())
+            .collect();
+        games.sort_by_key(|game| std::cmp::Reverse(game.updated_at));
+        games
+    }
+}
@@ -0,0 +1,167 @@
+use crate::live::{LiveError, LiveGame};
+use crate::{ApiContext, require_player};
+use atrium_api::types::string::Did;
+use dropshot::{
+    ClientErrorStatusCode, HttpError, HttpResponseOk, Path, RequestContext, TypedBody,
+    WebsocketChannelResult, WebsocketConnection, channel,
+};
+use futures::{SinkExt, StreamExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::Role;
+
+// The method players' service auth tokens name to start streaming
+const UPDATE_LIVE_GAME_METHOD: &str = "blue.2048.live.updateGame";
+
+impl From<LiveError> for HttpError {
+    fn from(err: LiveError) -> Self {
+        match err {
+            LiveError::Invalid(_) => HttpError::for_bad_request(None, err.to_string()),
+            LiveError::Taken => {
+                HttpError::for_client_error(None, ClientErrorStatusCode::CONFLICT, err.to_string())
+            }
+            LiveError::Busy => HttpError::for_unavail(None, err.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct LivePath {
+    pub did: String,
+}
+
+impl LivePath {
+    fn did(&self) -> Result<Did, HttpError> {
+        self.did
+            .parse()
+            .map_err(|e: &str| HttpError::for_bad_request(None, format!("Invalid DID: {}", e)))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct LiveUpdate {
+    /// Given back by the first update of a stream, missing on that first update
+    pub token: Option<String>,
+    pub seeded_recording: String,
+    /// The player ended the game or ran out of their mode's limit
+    #[serde(default)]
+    pub finished: bool,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct LiveToken {
+    pub token: String,
+}
+
+/// Games being streamed right now
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/live",
+}]
+pub async fn list_live_games(
+    rqctx: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<Vec<LiveGame>>, HttpError> {
+    Ok(HttpResponseOk(rqctx.context().live.list().await))
+}
+
+/// The player's live game so far
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/live/{did}",
+}]
+pub async fn get_live_game(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<LivePath>,
+) -> Result<HttpResponseOk<LiveGame>, HttpError> {
+    let did = path.into_inner().did()?;
+    rqctx
+        .context()
+        .live
+        .get(did.as_str())
+        .await
+        .map(HttpResponseOk)
+        .ok_or_else(|| HttpError::for_not_found(None, format!("{} is not streaming", did.as_str())))
+}
+
+/// Sent by the player's app after every move with the whole recording so far. Starting a stream
+/// needs a service auth token for blue.2048.live.updateGame from the player's PDS as the bearer
+/// token, after that the stream's token is enough
+#[dropshot::endpoint {
+    method = PUT,
+    path = "/api/live/{did}",
+}]
+pub async fn update_live_game(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<LivePath>,
+    body: TypedBody<LiveUpdate>,
+) -> Result<HttpResponseOk<LiveToken>, HttpError> {
+    let did = path.into_inner().did()?;
+    let update = body.into_inner();
+    rqctx
+        .context()
+        .limits
+        .check_recording(&update.seeded_recording)?;
+    if !rqctx
+        .context()
+        .live
+        .holds_stream(did.as_str(), update.token.as_deref())
+        .await
+    {
+        require_player(&rqctx, &did, UPDATE_LIVE_GAME_METHOD).await?;
+    }
+    let token = rqctx
+        .context()
+        .live
+        .update(
+            did.as_str(),
+            update.token.as_deref(),
+            update.seeded_recording,
+            update.finished,
+        )
+        .await?;
+    Ok(HttpResponseOk(LiveToken { token }))
+}
+
+/// Sends the player's game so far as soon as a spectator connects, then again after every
+/// move. Each message is a LiveGame as JSON
+#[channel {
+    protocol = WEBSOCKETS,
+    path = "/api/live/{did}/watch",
+}]
+pub async fn watch_live_game(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<LivePath>,
+    upgraded: WebsocketConnection,
+) -> WebsocketChannelResult {
+    let did = path.into_inner().did()?;
+    let (current, mut updates) = rqctx
+        .context()
+        .live
+        .watch(did.as_str())
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut ws = WebSocketStream::from_raw_socket(upgraded.into_inner(), Role::Server, None).await;
+
+    if let Some(game) = current {
+        ws.send(Message::Text(serde_json::to_string(&game)?.into()))
+            .await?;
+    }
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(game) => ws.send(Message::Text(serde_json::to_string(&game)?.into())).await?,
+                // The next update has the whole recording, so skipped ones are not missed
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            incoming = ws.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        }
+    }
+    Ok(())
+}
@@ -2,92 +2,211 @@
 
 //! Example using Dropshot to serve files
 
+use dropshot::ClientErrorStatusCode;
 use dropshot::ConfigLogging;
 use dropshot::ConfigLoggingLevel;
 use dropshot::HttpError;
 use dropshot::RequestContext;
 use dropshot::ServerBuilder;
-use dropshot::{ApiDescription, ConfigDropshot, HttpResponseOk};
-use dropshot::endpoint;
-use serde::Deserialize;
-use serde::Serialize;
-use std::fs;
-use schemars::JsonSchema;
+use dropshot::{ApiDescription, ConfigDropshot};
+use atrium_api::types::string::Did;
 use std::env;
-use std::net::SocketAddr;
+use std::sync::Arc;
 
+pub mod admin;
+pub mod anticheat;
+// dropshot 0.16's #[endpoint] expands to a NeedRequestContext struct that newer toolchains flag
+// as never constructed, once per endpoint, so the endpoint modules allow it
+#[allow(dead_code)]
+pub mod anticheat_routes;
+pub mod atproto;
+pub mod config;
+#[allow(dead_code)]
+pub mod health_routes;
+#[allow(dead_code)]
 pub mod image_routes;
+#[allow(dead_code)]
+pub mod leaderboard_routes;
+pub mod limits;
+pub mod live;
+#[allow(dead_code)]
+pub mod live_routes;
+pub mod metrics;
+pub mod moderation;
+#[allow(dead_code)]
+pub mod moderation_routes;
+#[allow(dead_code)]
+pub mod openapi;
+pub mod race;
+#[allow(dead_code)]
+pub mod race_routes;
+pub mod ranked;
+#[allow(dead_code)]
+pub mod ranked_routes;
+#[allow(dead_code)]
+pub mod server_routes;
+pub mod service_auth;
+pub mod share_images;
+#[allow(dead_code)]
 pub mod share_routes;
-
-// Define Config, ApiContext, and ServerConfigSchema
-#[derive(Clone, Debug)]
-pub struct Config {
-    pub base_url: String,
-    pub default_og_title: String,
-    pub default_og_description: String,
-}
+pub mod storage;
+#[allow(dead_code)]
+pub mod tournament_routes;
+pub mod tournaments;
 
 #[derive(Clone)]
 pub struct ApiContext {
-    pub config: Config,
+    pub config: config::Config,
+    // Tile numbers are drawn in it, read once at startup
+    pub font: ab_glyph::FontArc,
+    pub share_images: Arc<share_images::ShareImageCache>,
+    pub database: Arc<storage::Database>,
+    pub limits: Arc<limits::Limits>,
+    // The document describing the endpoints served, built once at startup
+    pub openapi: Arc<serde_json::Value>,
+    pub request_metrics: Arc<metrics::RequestMetrics>,
+    pub tournaments: Arc<tournaments::TournamentStore>,
+    pub atproto: atproto::AtprotoClient,
+    pub live: Arc<live::LiveGames>,
+    pub races: Arc<race::Races>,
+    pub anticheat: Arc<anticheat::AnticheatStore>,
+    pub moderation: Arc<moderation::ModerationStore>,
+    pub ranked_seeds: Arc<ranked::RankedSeeds>,
+    // Whether the data directories took a write at startup, /readyz fails when they did not
+    pub storage_probe: Result<(), String>,
+    // Where tournament results are published, publishing is off without it
+    pub authority: Option<atproto::AuthorityConfig>,
     // Potentially other shared states
 }
 
-#[derive(Serialize, JsonSchema, Debug)]
-pub struct ServerConfigSchema {
-    pub og_title: String,
-    pub og_description: String,
-    // pub base_url: String, // Let's keep this out for now until actually used
+fn bearer_token(rqctx: &RequestContext<ApiContext>) -> Option<&str> {
+    rqctx
+        .request
+        .headers()
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Checks the request carries the token as a bearer token. Without a token configured the
+/// feature is off and every request is turned away with the message
+pub fn require_bearer_token(
+    rqctx: &RequestContext<ApiContext>,
+    token: Option<&str>,
+    disabled_message: &str,
+) -> Result<(), HttpError> {
+    let Some(token) = token else {
+        return Err(HttpError::for_client_error(
+            None,
+            ClientErrorStatusCode::FORBIDDEN,
+            disabled_message.to_string(),
+        ));
+    };
+    match bearer_token(rqctx) {
+        Some(provided) if provided == token => Ok(()),
+        _ => Err(HttpError::for_client_error_with_status(None, ClientErrorStatusCode::UNAUTHORIZED)),
+    }
+}
+
+impl From<service_auth::ServiceAuthError> for HttpError {
+    fn from(err: service_auth::ServiceAuthError) -> Self {
+        match err {
+            service_auth::ServiceAuthError::Atproto(_) => HttpError::for_unavail(None, err.to_string()),
+            _ => HttpError::for_client_error(None, ClientErrorStatusCode::UNAUTHORIZED, err.to_string()),
+        }
+    }
+}
+
+/// Checks the request carries a service auth token the player's PDS signed for this server and
+/// method, which only someone signed in as the player can get
+pub async fn require_player(
+    rqctx: &RequestContext<ApiContext>,
+    did: &Did,
+    method: &str,
+) -> Result<(), HttpError> {
+    let token = bearer_token(rqctx).ok_or(service_auth::ServiceAuthError::Missing)?;
+    let api_context = rqctx.context();
+    service_auth::verify(
+        &api_context.atproto,
+        token,
+        did,
+        &api_context.config.site.service_did(),
+        method,
+    )
+    .await?;
+    Ok(())
 }
 
-#[derive(Deserialize)]
-struct MyAppConfig {
-    http_api_server: ConfigDropshot,
+/// Every endpoint the features turned on serve
+pub fn api_description(features: &config::Features) -> ApiDescription<ApiContext> {
+    let mut api = ApiDescription::new();
+    api.register(server_routes::example_api_get_counter).unwrap();
+    api.register(image_routes::generate_board_image).unwrap();
+    api.register(image_routes::get_share_image_cache_stats).unwrap();
+    api.register(share_routes::serve_shared_game_page).unwrap();
+    api.register(share_routes::create_share_code).unwrap();
+    api.register(share_routes::follow_share_code).unwrap();
+    api.register(leaderboard_routes::get_leaderboard).unwrap();
+    api.register(leaderboard_routes::get_verification).unwrap();
+    api.register(server_routes::get_server_config).unwrap();
+    api.register(openapi::get_openapi).unwrap();
+    api.register(health_routes::get_health).unwrap();
+    api.register(health_routes::get_readiness).unwrap();
+    api.register(health_routes::get_metrics).unwrap();
+    if features.tournaments {
+        api.register(tournament_routes::list_tournaments).unwrap();
+        api.register(tournament_routes::create_tournament).unwrap();
+        api.register(tournament_routes::update_tournament).unwrap();
+        api.register(tournament_routes::get_tournament_results).unwrap();
+        api.register(tournament_routes::publish_tournament_results).unwrap();
+        api.register(tournament_routes::serve_tournament_page).unwrap();
+    }
+    if features.live {
+        api.register(live_routes::list_live_games).unwrap();
+        api.register(live_routes::get_live_game).unwrap();
+        api.register(live_routes::update_live_game).unwrap();
+        api.register(live_routes::watch_live_game).unwrap();
+    }
+    if features.races {
+        api.register(race_routes::list_races).unwrap();
+        api.register(race_routes::create_race).unwrap();
+        api.register(race_routes::get_race).unwrap();
+        api.register(race_routes::race_socket).unwrap();
+    }
+    if features.moderation {
+        api.register(anticheat_routes::get_moderation_queue).unwrap();
+        api.register(anticheat_routes::scan_player_games).unwrap();
+        api.register(anticheat_routes::review_game).unwrap();
+        api.register(moderation_routes::list_sanctions).unwrap();
+        api.register(moderation_routes::apply_sanction).unwrap();
+        api.register(moderation_routes::lift_sanction).unwrap();
+        api.register(moderation_routes::get_moderation_audit).unwrap();
+    }
+    if features.ranked {
+        api.register(ranked_routes::issue_ranked_seed).unwrap();
+        api.register(ranked_routes::get_ranked_player).unwrap();
+    }
+    // api.register(static_content).unwrap();
+    api
 }
 
 #[tokio::main]
 async fn main() -> Result<(), String> {
-    // Determine bind address based on environment
-    let port = env::var("PORT").unwrap_or_else(|_| "8081".to_string());
-    let host = if env::var("RAILWAY_ENVIRONMENT").is_ok() {
-        "0.0.0.0"
-    } else {
-        "127.0.0.1"
-    };
-    let bind_address_str = format!("{}:{}", host, port);
-    
-    let dropshot_config: ConfigDropshot = match env::var("RAILWAY_ENVIRONMENT") {
-        Ok(_) => {
-            // Railway environment: construct config from env vars
-            let bind_address: SocketAddr = bind_address_str
-                .parse()
-                .map_err(|e| format!("Failed to parse bind address '{}': {}", bind_address_str, e))?;
-            ConfigDropshot {
-                bind_address,
-                default_request_body_max_bytes: 1024 * 1024 * 10, // 10MB, example
-                ..Default::default()
-            }
-        }
-        Err(_) => {
-            // Local environment: read from Dev.toml
-            match fs::read_to_string("Dev.toml") {
-                Ok(config_str) => {
-                    let app_config: MyAppConfig = toml::from_str(&config_str)
-                        .map_err(|e| format!("Error parsing Dev.toml: {}", e))?;
-                    app_config.http_api_server
-                }
-                Err(e) => {
-                    println!("Error reading Dev.toml ({}). Falling back to default local config.", e);
-                    let bind_address: SocketAddr = bind_address_str
-                        .parse()
-                        .map_err(|e| format!("Failed to parse bind address '{}': {}", bind_address_str, e))?;
-                    ConfigDropshot {
-                        bind_address,
-                        ..Default::default()
-                    }
-                }
-            }
-        }
+    let config = config::Config::load().map_err(|e| {
+        eprintln!("{}", e);
+        "Could not start with this config".to_string()
+    })?;
+
+    // Anything after the binary's name is an admin command, run in place of the server
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        return admin::run(&config, &args).await;
+    }
+
+    let dropshot_config = ConfigDropshot {
+        bind_address: config.server.bind_address,
+        default_request_body_max_bytes: config.server.request_body_max_bytes,
+        ..Default::default()
     };
 
     let config_logging = ConfigLogging::StderrTerminal {
@@ -97,19 +216,57 @@ async fn main() -> Result<(), String> {
         .to_logger("example-basic")
         .map_err(|error| format!("failed to create logger: {}", error))?;
 
-    let mut api = ApiDescription::new();
-    api.register(example_api_get_counter).unwrap();
-    api.register(image_routes::generate_board_image).unwrap();
-    api.register(share_routes::serve_shared_game_page).unwrap();
-    api.register(get_server_config).unwrap();
-    // api.register(static_content).unwrap();
+    let api = api_description(&config.features);
+    let openapi = openapi::spec(&api)?;
+    // Counted off Dropshot's log of each request, under the endpoints' paths
+    let paths = openapi["paths"].as_object().into_iter().flat_map(|paths| paths.keys());
+    let request_metrics = Arc::new(metrics::RequestMetrics::new(paths.map(String::as_str)));
+    let log = slog::Logger::root(
+        metrics::RequestMetricsDrain::new(log, request_metrics.clone()),
+        slog::o!(),
+    );
+
+    let storage = &config.storage;
+    let database = storage::Database::open(&storage.database)
+        .map_err(|e| format!("Error opening {}: {}", storage.database.display(), e))?;
+    let tournament_store = tournaments::TournamentStore::load(storage.tournaments.clone())?;
+    let anticheat_store = anticheat::AnticheatStore::load(storage.anticheat.clone())?;
+    let moderation_store =
+        moderation::ModerationStore::load(storage.moderation.clone(), storage.moderation_audit.clone())?;
+    let ranked_seeds = ranked::RankedSeeds::load_or_create(config.keys.ranked_seeds.clone())?;
+    let storage_probe = health_routes::probe_storage(storage).await;
+    if let Err(e) = &storage_probe {
+        log::warn!("{}", e);
+    }
+    let images = &config.images;
+    let font_bytes = std::fs::read(&images.font_path)
+        .map_err(|e| format!("Error reading {}: {}", images.font_path.display(), e))?;
+    let share_images = share_images::ShareImageCache::load(
+        &font_bytes,
+        images.memory_cache_entries,
+        images.disk_cache_dir.clone(),
+        images.disk_cache_entries,
+    )?;
+    let font = ab_glyph::FontArc::try_from_vec(font_bytes)
+        .map_err(|e| format!("Error parsing {}: {}", images.font_path.display(), e))?;
 
     let app_context = ApiContext {
-        config: Config {
-            base_url: "https://2048.symm.app".to_string(),
-            default_og_title: "2048 Game".to_string(),
-            default_og_description: "Play 2048!".to_string(),
-        },
+        authority: config.authority(),
+        limits: Arc::new(limits::Limits::new(config.limits.clone())),
+        openapi: Arc::new(openapi),
+        request_metrics,
+        config,
+        font,
+        share_images: Arc::new(share_images),
+        database: Arc::new(database),
+        tournaments: Arc::new(tournament_store),
+        atproto: atproto::AtprotoClient::default(),
+        live: Arc::new(live::LiveGames::default()),
+        races: Arc::new(race::Races::default()),
+        anticheat: Arc::new(anticheat_store),
+        moderation: Arc::new(moderation_store),
+        ranked_seeds: Arc::new(ranked_seeds),
+        storage_probe,
     };
 
     let server = ServerBuilder::new(api, app_context, log)
@@ -119,31 +276,3 @@ async fn main() -> Result<(), String> {
 
     server.await
 }
-
-/// Fetch the current value of the counter.
-#[endpoint {
-    method = GET,
-    path = "/api/test",
-    }]
-async fn example_api_get_counter(
-    request_context: RequestContext<ApiContext>,
-) -> Result<HttpResponseOk<String>, HttpError> {
-    let _api_context = request_context.context();
-
-    Ok(HttpResponseOk("Nice".to_string()))
-}
-
-#[dropshot::endpoint {
-    method = GET,
-    path = "/api/server-config"
-}]
-async fn get_server_config(
-    request_context: RequestContext<ApiContext>,
-) -> Result<HttpResponseOk<ServerConfigSchema>, HttpError> {
-    let api_context = request_context.context();
-    Ok(HttpResponseOk(ServerConfigSchema {
-        og_title: api_context.config.default_og_title.clone(),
-        og_description: api_context.config.default_og_description.clone(),
-        // base_url: api_context.config.base_url.clone(),
-    }))
-}
@@ -0,0 +1,209 @@
+use slog::{Drain, KV, Key, OwnedKVList, Record, Serializer};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Upper bounds in seconds, the same ones the Prometheus clients default to
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+// Requests for paths the API does not serve all count under this one, so scanners probing for
+// random paths can not add a series each
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Durations counted into the usual Prometheus buckets
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    // Not cumulative, each holds the observations for its bucket only. The last is +Inf
+    counts: [u64; BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = BUCKETS
+            .iter()
+            .position(|le| seconds <= *le)
+            .unwrap_or(BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    /// Writes it out as `name`, `labels` being what goes between the braces, if anything
+    pub fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (le, count) in BUCKETS.iter().zip(self.counts) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, le, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, separator, self.count
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+/// Writes the HELP and TYPE lines every metric starts with
+pub fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+#[derive(Default)]
+struct EndpointStats {
+    responses: BTreeMap<u16, u64>,
+    latency: Histogram,
+}
+
+/// Counts and latencies of the requests answered, by endpoint
+pub struct RequestMetrics {
+    // The endpoints' paths, like /api/live/{did}, split at the slashes
+    routes: Vec<(String, Vec<String>)>,
+    endpoints: Mutex<BTreeMap<(String, String), EndpointStats>>,
+}
+
+impl RequestMetrics {
+    /// `paths` are the endpoints' path templates, as the OpenAPI document lists them
+    pub fn new<'a>(paths: impl IntoIterator<Item = &'a str>) -> Self {
+        let routes = paths
+            .into_iter()
+            .map(|path| {
+                (
+                    path.to_string(),
+                    path.split('/').map(str::to_string).collect(),
+                )
+            })
+            .collect();
+        RequestMetrics {
+            routes,
+            endpoints: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    // The endpoint's path for a request's, so every DID or id shares one series
+    fn route(&self, uri: &str) -> &str {
+        let path = uri.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.split('/').collect();
+        self.routes
+            .iter()
+            .find(|(_, template)| {
+                template.len() == segments.len()
+                    && template.iter().zip(&segments).all(|(expected, segment)| {
+                        expected == segment
+                            || (expected.starts_with('{') && expected.ends_with('}'))
+                    })
+            })
+            .map_or(UNMATCHED_ROUTE, |(route, _)| route.as_str())
+    }
+
+    pub fn record(&self, method: &str, uri: &str, status: u16, latency: Duration) {
+        let key = (method.to_string(), self.route(uri).to_string());
+        let mut endpoints = self.endpoints.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = endpoints.entry(key).or_default();
+        *stats.responses.entry(status).or_default() += 1;
+        stats.latency.observe(latency);
+    }
+
+    pub fn write(&self, out: &mut String) {
+        let endpoints = self.endpoints.lock().unwrap_or_else(|e| e.into_inner());
+        write_header(
+            out,
+            "api_requests_total",
+            "counter",
+            "Requests answered, by endpoint and status",
+        );
+        for ((method, route), stats) in endpoints.iter() {
+            for (status, count) in &stats.responses {
+                let _ = writeln!(
+                    out,
+                    "api_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                    method, route, status, count
+                );
+            }
+        }
+        write_header(
+            out,
+            "api_request_duration_seconds",
+            "histogram",
+            "Time from a request arriving to its response, by endpoint",
+        );
+        for ((method, route), stats) in endpoints.iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", method, route);
+            stats
+                .latency
+                .write(out, "api_request_duration_seconds", &labels);
+        }
+    }
+}
+
+// The fields of Dropshot's "request completed" log line the metrics need
+#[derive(Default)]
+struct CompletedRequest {
+    method: Option<String>,
+    uri: Option<String>,
+    status: Option<u16>,
+    latency_us: Option<u64>,
+}
+
+impl Serializer for CompletedRequest {
+    fn emit_arguments(&mut self, key: Key, value: &std::fmt::Arguments) -> slog::Result {
+        match AsRef::<str>::as_ref(&key) {
+            "method" => self.method = Some(value.to_string()),
+            "uri" => self.uri = Some(value.to_string()),
+            "response_code" => self.status = value.to_string().parse().ok(),
+            "latency_us" => self.latency_us = value.to_string().parse().ok(),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Passes every log record on, counting the requests Dropshot logs as completed. Dropshot has
+/// no hook around its handlers, but it logs every request it answers, those turned away before
+/// reaching one included
+pub struct RequestMetricsDrain<D> {
+    inner: D,
+    metrics: std::sync::Arc<RequestMetrics>,
+}
+
+impl<D> RequestMetricsDrain<D> {
+    pub fn new(inner: D, metrics: std::sync::Arc<RequestMetrics>) -> Self {
+        RequestMetricsDrain { inner, metrics }
+    }
+}
+
+impl<D: Drain> Drain for RequestMetricsDrain<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        if record.msg().as_str() == Some("request completed") {
+            let mut completed = CompletedRequest::default();
+            let _ = values.serialize(record, &mut completed);
+            let _ = record.kv().serialize(record, &mut completed);
+            if let CompletedRequest {
+                method: Some(method),
+                uri: Some(uri),
+                status: Some(status),
+                latency_us: Some(latency_us),
+            } = completed
+            {
+                self.metrics
+                    .record(&method, &uri, status, Duration::from_micros(latency_us));
+            }
+        }
+        self.inner.log(record, values)
+    }
+}
@@ -0,0 +1,442 @@
+use crate::atproto::{AtprotoClient, AtprotoError, AuthorityConfig};
+use atrium_api::types::Collection;
+use atrium_api::types::string::{Datetime, Did};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+use types_2048::blue;
+use types_2048::blue::_2048::ban;
+use types_2048::record::KnownRecord;
+
+// Longest record key a PDS takes, a game's key has to fit after the player's DID
+const MAX_RECORD_KEY_LENGTH: usize = 512;
+
+#[derive(Debug)]
+pub enum ModerationError {
+    Invalid(String),
+    NotFound(String),
+    Storage(String),
+    Atproto(AtprotoError),
+}
+
+impl std::fmt::Display for ModerationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModerationError::Invalid(err) => write!(f, "{}", err),
+            ModerationError::NotFound(subject) => write!(f, "{} is not banned or hidden", subject),
+            ModerationError::Storage(err) => {
+                write!(f, "Could not save the moderation list: {}", err)
+            }
+            ModerationError::Atproto(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// A player, or one of their games
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Subject {
+    pub did: String,
+    /// Record key of the blue.2048.game, none for every game by the player
+    pub game: Option<String>,
+}
+
+fn is_record_key(rkey: &str) -> bool {
+    !rkey.is_empty()
+        && rkey.len() <= MAX_RECORD_KEY_LENGTH
+        && rkey != "."
+        && rkey != ".."
+        && rkey
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':' | '~'))
+}
+
+impl Subject {
+    /// Reads a DID for the whole player, or the at://uri of one of their games
+    pub fn parse(subject: &str) -> Result<Self, ModerationError> {
+        let subject = subject.trim();
+        let (did, game) = match subject.strip_prefix("at://") {
+            Some(uri) => {
+                let mut parts = uri.split('/');
+                let (Some(did), Some(collection), Some(rkey), None) =
+                    (parts.next(), parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(ModerationError::Invalid(format!(
+                        "{} is not a game's at://uri",
+                        subject
+                    )));
+                };
+                if collection != blue::_2048::Game::NSID || !is_record_key(rkey) {
+                    return Err(ModerationError::Invalid(format!(
+                        "{} is not a game's at://uri",
+                        subject
+                    )));
+                }
+                (did, Some(rkey.to_string()))
+            }
+            None => (subject, None),
+        };
+        let did: Did = did
+            .parse()
+            .map_err(|e: &str| ModerationError::Invalid(format!("Invalid DID: {}", e)))?;
+        Ok(Subject {
+            did: did.to_string(),
+            game,
+        })
+    }
+
+    /// The at://uri of the game, none for a player
+    pub fn game_uri(&self) -> Option<String> {
+        self.game
+            .as_ref()
+            .map(|rkey| format!("at://{}/{}/{}", self.did, blue::_2048::Game::NSID, rkey))
+    }
+
+    // The blue.2048.ban record for the subject lives under this key, so banning again replaces it
+    fn record_key(&self) -> String {
+        match &self.game {
+            Some(rkey) => format!("{}:{}", self.did, rkey),
+            None => self.did.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for Subject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.game_uri() {
+            Some(uri) => write!(f, "{}", uri),
+            None => write!(f, "{}", self.did),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SanctionKind {
+    /// Kept off leaderboards and standings, and published to the authority repo as a blue.2048.ban
+    Ban,
+    /// Kept off leaderboards and standings like a ban, but never published so the player is not told
+    Hide,
+}
+
+/// A ban or hide in force
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Sanction {
+    pub subject: Subject,
+    pub kind: SanctionKind,
+    pub reason: String,
+    pub moderator: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// The blue.2048.ban record in the authority repo, for bans made while one was configured
+    pub published: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Ban,
+    Hide,
+    Lift,
+}
+
+/// One thing a moderator did. The audit log is only ever appended to
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub action: AuditAction,
+    pub subject: Subject,
+    pub reason: String,
+    pub moderator: Option<String>,
+    /// The blue.2048.ban record written, or removed when lifting
+    pub published: Option<String>,
+}
+
+/// What is kept off leaderboards for one player
+pub struct Exclusions {
+    player: bool,
+    games: HashSet<String>,
+}
+
+impl Exclusions {
+    /// Whether the player's game with this at://uri is banned or hidden
+    pub fn excludes(&self, uri: &str) -> bool {
+        self.player || self.games.contains(uri)
+    }
+
+    /// Whether every game by the player is
+    pub fn excludes_player(&self) -> bool {
+        self.player
+    }
+}
+
+/// Banned and hidden players and games, kept in a JSON file, with every change to them in an
+/// append only JSON lines audit log next to it
+pub struct ModerationStore {
+    path: PathBuf,
+    audit_path: PathBuf,
+    sanctions: RwLock<Vec<Sanction>>,
+}
+
+fn reason(reason: &str) -> Result<String, ModerationError> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(ModerationError::Invalid("Give a reason".to_string()));
+    }
+    Ok(reason.to_string())
+}
+
+impl ModerationStore {
+    /// Starts empty when the file does not exist yet
+    pub fn load(path: PathBuf, audit_path: PathBuf) -> Result<Self, String> {
+        let sanctions: Vec<Sanction> = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("Error parsing {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(format!("Error reading {}: {}", path.display(), e)),
+        };
+        Ok(ModerationStore {
+            path,
+            audit_path,
+            sanctions: RwLock::new(sanctions),
+        })
+    }
+
+    async fn save(&self, sanctions: &[Sanction]) -> Result<(), ModerationError> {
+        let contents = serde_json::to_string_pretty(sanctions)
+            .map_err(|e| ModerationError::Storage(e.to_string()))?;
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| ModerationError::Storage(e.to_string()))?;
+        }
+        // Written next to the real file first so a crash mid write can not lift every ban
+        let temp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&temp_path, contents)
+            .await
+            .map_err(|e| ModerationError::Storage(e.to_string()))?;
+        tokio::fs::rename(&temp_path, &self.path)
+            .await
+            .map_err(|e| ModerationError::Storage(e.to_string()))
+    }
+
+    async fn append_audit(&self, entry: &AuditEntry) -> Result<(), ModerationError> {
+        let mut line =
+            serde_json::to_string(entry).map_err(|e| ModerationError::Storage(e.to_string()))?;
+        line.push('\n');
+        if let Some(parent) = self.audit_path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| ModerationError::Storage(e.to_string()))?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.audit_path)
+            .await
+            .map_err(|e| ModerationError::Storage(e.to_string()))?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|e| ModerationError::Storage(e.to_string()))
+    }
+
+    /// Every ban and hide in force, newest first
+    pub async fn list(&self) -> Vec<Sanction> {
+        let mut sanctions = self.sanctions.read().await.clone();
+        sanctions.sort_by_key(|sanction| std::cmp::Reverse(sanction.created_at));
+        sanctions
+    }
+
+    /// The audit log, newest first
+    pub async fn audit(&self) -> Result<Vec<AuditEntry>, ModerationError> {
+        let contents = match tokio::fs::read_to_string(&self.audit_path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(ModerationError::Storage(e.to_string())),
+        };
+        let mut entries = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<AuditEntry>, _>>()
+            .map_err(|e| ModerationError::Storage(e.to_string()))?;
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// What is banned or hidden for the player
+    pub async fn exclusions(&self, did: &str) -> Exclusions {
+        let sanctions = self.sanctions.read().await;
+        let mut exclusions = Exclusions {
+            player: false,
+            games: HashSet::new(),
+        };
+        for sanction in sanctions
+            .iter()
+            .filter(|sanction| sanction.subject.did == did)
+        {
+            match sanction.subject.game_uri() {
+                Some(uri) => {
+                    exclusions.games.insert(uri);
+                }
+                None => exclusions.player = true,
+            }
+        }
+        exclusions
+    }
+
+    /// Bans or hides the subject, replacing what was in force for it before. Bans are published
+    /// to the authority repo when there is one, and turning a ban into a hide takes its record down
+    pub async fn sanction(
+        &self,
+        client: &AtprotoClient,
+        authority: Option<&AuthorityConfig>,
+        subject: Subject,
+        kind: SanctionKind,
+        reason_given: &str,
+        moderator: Option<String>,
+    ) -> Result<Sanction, ModerationError> {
+        let reason = reason(reason_given)?;
+        let now = Utc::now();
+        // Held over the publishing too, so two moderators can not leave the record and the list disagreeing
+        let mut sanctions = self.sanctions.write().await;
+        let previous = sanctions
+            .iter()
+            .position(|sanction| sanction.subject == subject)
+            .map(|index| sanctions.remove(index));
+
+        let published = match (kind, authority) {
+            (SanctionKind::Ban, Some(authority)) => {
+                let record = KnownRecord::from(ban::RecordData {
+                    created_at: Datetime::new(now.fixed_offset()),
+                    game: subject.game_uri(),
+                    reason: reason.clone(),
+                    subject: subject
+                        .did
+                        .parse()
+                        .map_err(|e: &str| ModerationError::Invalid(e.to_string()))?,
+                });
+                let output = client
+                    .put_authority_record(
+                        authority,
+                        blue::_2048::Ban::NSID,
+                        &subject.record_key(),
+                        &record,
+                    )
+                    .await;
+                match output {
+                    Ok(output) => Some(output.uri),
+                    Err(e) => {
+                        sanctions.extend(previous);
+                        return Err(ModerationError::Atproto(e));
+                    }
+                }
+            }
+            (SanctionKind::Ban, None) => {
+                log::warn!(
+                    "Banned {} without publishing it, no authority repo is set up",
+                    subject
+                );
+                None
+            }
+            (SanctionKind::Hide, _) => None,
+        };
+        // A hide is never published, so a ban it replaces has to come down from the authority repo
+        if kind == SanctionKind::Hide
+            && previous
+                .as_ref()
+                .is_some_and(|previous| previous.published.is_some())
+        {
+            let unpublished = match authority {
+                Some(authority) => client
+                    .delete_authority_record(
+                        authority,
+                        blue::_2048::Ban::NSID,
+                        &subject.record_key(),
+                    )
+                    .await
+                    .map_err(ModerationError::Atproto),
+                None => Err(ModerationError::Invalid(format!(
+                    "{} is published, it can only be changed with the authority repo set up",
+                    subject
+                ))),
+            };
+            if let Err(e) = unpublished {
+                sanctions.extend(previous);
+                return Err(e);
+            }
+        }
+
+        let sanction = Sanction {
+            subject,
+            kind,
+            reason,
+            moderator,
+            created_at: now,
+            published,
+        };
+        sanctions.push(sanction.clone());
+        self.save(&sanctions).await?;
+        self.append_audit(&AuditEntry {
+            at: now,
+            action: match kind {
+                SanctionKind::Ban => AuditAction::Ban,
+                SanctionKind::Hide => AuditAction::Hide,
+            },
+            subject: sanction.subject.clone(),
+            reason: sanction.reason.clone(),
+            moderator: sanction.moderator.clone(),
+            published: sanction.published.clone(),
+        })
+        .await?;
+        log::info!("{:?} on {}: {}", kind, sanction.subject, sanction.reason);
+        Ok(sanction)
+    }
+
+    /// Lifts the ban or hide on the subject, taking its published record down. Gives back what was lifted
+    pub async fn lift(
+        &self,
+        client: &AtprotoClient,
+        authority: Option<&AuthorityConfig>,
+        subject: Subject,
+        reason_given: &str,
+        moderator: Option<String>,
+    ) -> Result<Sanction, ModerationError> {
+        let reason = reason(reason_given)?;
+        let mut sanctions = self.sanctions.write().await;
+        let index = sanctions
+            .iter()
+            .position(|sanction| sanction.subject == subject)
+            .ok_or_else(|| ModerationError::NotFound(subject.to_string()))?;
+        let published = sanctions[index].published.clone();
+        if published.is_some() {
+            // Left up, other clients would go on honouring a ban lifted here
+            let Some(authority) = authority else {
+                return Err(ModerationError::Invalid(format!(
+                    "{} is published, it can only be lifted with the authority repo set up",
+                    subject
+                )));
+            };
+            client
+                .delete_authority_record(authority, blue::_2048::Ban::NSID, &subject.record_key())
+                .await
+                .map_err(ModerationError::Atproto)?;
+        }
+
+        let lifted = sanctions.remove(index);
+        self.save(&sanctions).await?;
+        self.append_audit(&AuditEntry {
+            at: Utc::now(),
+            action: AuditAction::Lift,
+            subject: lifted.subject.clone(),
+            reason,
+            moderator,
+            published,
+        })
+        .await?;
+        log::info!("Lifted the {:?} on {}", lifted.kind, lifted.subject);
+        Ok(lifted)
+    }
+}
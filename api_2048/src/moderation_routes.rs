@@ -0,0 +1,114 @@
+use crate::ApiContext;
+use crate::anticheat_routes::require_moderator;
+use crate::moderation::{AuditEntry, ModerationError, Sanction, SanctionKind, Subject};
+use dropshot::{HttpError, HttpResponseOk, RequestContext, TypedBody};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+impl From<ModerationError> for HttpError {
+    fn from(err: ModerationError) -> Self {
+        match err {
+            ModerationError::Invalid(_) => HttpError::for_bad_request(None, err.to_string()),
+            ModerationError::NotFound(_) => HttpError::for_not_found(None, err.to_string()),
+            ModerationError::Storage(_) => HttpError::for_internal_error(err.to_string()),
+            ModerationError::Atproto(_) => HttpError::for_unavail(None, err.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct NewSanction {
+    /// A DID to cover every game by the player, or the at://uri of one blue.2048.game
+    pub subject: String,
+    pub kind: SanctionKind,
+    /// Kept in the audit log, and on the published record for bans
+    pub reason: String,
+    /// Who did it, for the audit log
+    pub moderator: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct LiftSanction {
+    /// The DID or at://uri the ban or hide was made on
+    pub subject: String,
+    pub reason: String,
+    pub moderator: Option<String>,
+}
+
+/// Every ban and hide in force, newest first
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/moderation/sanctions",
+}]
+pub async fn list_sanctions(
+    rqctx: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<Vec<Sanction>>, HttpError> {
+    require_moderator(&rqctx)?;
+    Ok(HttpResponseOk(rqctx.context().moderation.list().await))
+}
+
+/// Ban or shadow hide a player or one of their games, replacing what was in force for it.
+/// Bans are published to the authority repo as blue.2048.ban records when one is set up
+#[dropshot::endpoint {
+    method = PUT,
+    path = "/api/moderation/sanctions",
+}]
+pub async fn apply_sanction(
+    rqctx: RequestContext<ApiContext>,
+    body: TypedBody<NewSanction>,
+) -> Result<HttpResponseOk<Sanction>, HttpError> {
+    require_moderator(&rqctx)?;
+    let body = body.into_inner();
+    let subject = Subject::parse(&body.subject)?;
+    let api_context = rqctx.context();
+    let sanction = api_context
+        .moderation
+        .sanction(
+            &api_context.atproto,
+            api_context.authority.as_ref(),
+            subject,
+            body.kind,
+            &body.reason,
+            body.moderator,
+        )
+        .await?;
+    Ok(HttpResponseOk(sanction))
+}
+
+/// Lift a ban or hide, taking down its published record. Answers with what was lifted
+#[dropshot::endpoint {
+    method = POST,
+    path = "/api/moderation/sanctions/lift",
+}]
+pub async fn lift_sanction(
+    rqctx: RequestContext<ApiContext>,
+    body: TypedBody<LiftSanction>,
+) -> Result<HttpResponseOk<Sanction>, HttpError> {
+    require_moderator(&rqctx)?;
+    let body = body.into_inner();
+    let subject = Subject::parse(&body.subject)?;
+    let api_context = rqctx.context();
+    let lifted = api_context
+        .moderation
+        .lift(
+            &api_context.atproto,
+            api_context.authority.as_ref(),
+            subject,
+            &body.reason,
+            body.moderator,
+        )
+        .await?;
+    Ok(HttpResponseOk(lifted))
+}
+
+/// Every ban, hide and lift with its reason, newest first
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/moderation/audit",
+}]
+pub async fn get_moderation_audit(
+    rqctx: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<Vec<AuditEntry>>, HttpError> {
+    require_moderator(&rqctx)?;
+    Ok(HttpResponseOk(rqctx.context().moderation.audit().await?))
+}
@@ -0,0 +1,47 @@
+use crate::ApiContext;
+use dropshot::{ApiDescription, HttpError, HttpResponseOk, RequestContext};
+use std::path::Path;
+
+// Committed next to Cargo.toml, the app's client is generated from it
+pub const SPEC_PATH: &str = "openapi.json";
+const TITLE: &str = "at://2048";
+
+fn version() -> semver::Version {
+    env!("CARGO_PKG_VERSION")
+        .parse()
+        .expect("the crate version is semver")
+}
+
+/// The OpenAPI document for the endpoints, as served
+pub fn spec(api: &ApiDescription<ApiContext>) -> Result<serde_json::Value, String> {
+    api.openapi(TITLE, version())
+        .json()
+        .map_err(|e| format!("Error building the OpenAPI document: {}", e))
+}
+
+/// The OpenAPI document for the endpoints, as written to `SPEC_PATH`
+pub fn document(api: &ApiDescription<ApiContext>) -> Result<String, String> {
+    let mut document = vec![];
+    api.openapi(TITLE, version())
+        .write(&mut document)
+        .map_err(|e| format!("Error building the OpenAPI document: {}", e))?;
+    String::from_utf8(document).map_err(|e| e.to_string())
+}
+
+/// Whether the document at the path is the one the code describes now
+pub fn is_current(api: &ApiDescription<ApiContext>, path: &Path) -> Result<bool, String> {
+    let committed = std::fs::read_to_string(path)
+        .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    Ok(committed == document(api)?)
+}
+
+/// The OpenAPI document describing this server's endpoints
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/openapi.json",
+}]
+pub async fn get_openapi(
+    rqctx: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<serde_json::Value>, HttpError> {
+    Ok(HttpResponseOk(rqctx.context().openapi.as_ref().clone()))
+}
@@ -0,0 +1,520 @@
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, broadcast};
+use twothousand_forty_eight::board::Board;
+use twothousand_forty_eight::{unified::game::GameState, v2::recording::SeededRecording};
+
+pub const MIN_PLAYERS: usize = 2;
+pub const MAX_PLAYERS: usize = 4;
+const DEFAULT_MINUTES: u32 = 3;
+const MAX_MINUTES: u32 = 15;
+const DEFAULT_BOARD_SIZE: usize = 4;
+const MIN_BOARD_SIZE: usize = 3;
+const MAX_BOARD_SIZE: usize = twothousand_forty_eight::board::MAX_WIDTH;
+const COUNTDOWN: Duration = Duration::from_secs(3);
+// Races are dropped this long after they were made, finished or not
+const RACE_LIFETIME: chrono::Duration = chrono::Duration::hours(2);
+// Every view has the whole race in it, so a socket that falls behind only needs the newest
+const VIEW_BUFFER: usize = 16;
+// Players send every move as they make it. A few can pile up behind one that was turned away,
+// more than this in one message were not played in the race
+const MAX_NEW_MOVES: usize = 16;
+
+#[derive(Debug)]
+pub enum RaceError {
+    Invalid(String),
+    NotFound(String),
+    Full,
+}
+
+impl std::fmt::Display for RaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RaceError::Invalid(err) => write!(f, "{}", err),
+            RaceError::NotFound(id) => write!(f, "No race called {}", id),
+            RaceError::Full => write!(f, "The race has already started or is full"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// Someone made a 2048 tile
+    TwentyFortyEight,
+    /// The race ran out of time
+    Time,
+    /// Every player ran out of moves before time was up
+    AllOut,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RacePhase {
+    /// Waiting for players to join and ready up
+    Lobby,
+    Countdown {
+        starts_at: DateTime<Utc>,
+    },
+    Racing {
+        ends_at: DateTime<Utc>,
+    },
+    /// The winner is missing when the best scores tie
+    Finished {
+        winner: Option<String>,
+        reason: FinishReason,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct RacePlayer {
+    pub id: String,
+    pub name: String,
+    pub ready: bool,
+    pub connected: bool,
+    pub score: usize,
+    pub moves: usize,
+    /// Tile values row by row, 0 for an empty cell
+    pub board: Vec<Vec<usize>>,
+    /// No moves left
+    pub over: bool,
+    /// The player's game so far, checked by replaying it on the race's seed
+    pub seeded_recording: Option<String>,
+}
+
+/// Everything a player or spectator needs to draw the race, sent after every change
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct RaceView {
+    pub id: String,
+    /// Missing till the race starts, so nobody can look ahead on the seed during the countdown
+    pub seed: Option<u32>,
+    pub size: usize,
+    pub minutes: u32,
+    pub created_at: DateTime<Utc>,
+    pub phase: RacePhase,
+    pub players: Vec<RacePlayer>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct NewRace {
+    /// How long the race lasts if nobody makes a 2048 tile first. 3 when missing
+    pub minutes: Option<u32>,
+    /// Width and height of the board, 4 when missing
+    pub size: Option<usize>,
+}
+
+/// What a player's socket sends
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RaceCommand {
+    Ready {
+        ready: bool,
+    },
+    /// The whole recording after the player's latest move
+    Move {
+        seeded_recording: String,
+    },
+}
+
+/// What the race's sockets receive
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RaceMessage {
+    /// Sent once to a player's socket so it knows which player it is
+    Joined {
+        player_id: String,
+    },
+    Race(RaceView),
+    Error {
+        message: String,
+    },
+}
+
+fn board_rows(board: &Board) -> Vec<Vec<usize>> {
+    let mut rows = vec![vec![0; board.width]; board.height];
+    for tile in board.get_occupied_tiles() {
+        rows[tile.y][tile.x] = tile.value;
+    }
+    rows
+}
+
+pub struct Race {
+    seed: u32,
+    sender: broadcast::Sender<RaceView>,
+    view: Mutex<RaceView>,
+}
+
+impl Race {
+    pub async fn view(&self) -> RaceView {
+        self.view.lock().await.clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<RaceView> {
+        self.sender.subscribe()
+    }
+
+    // Sending only fails when no sockets are open
+    fn broadcast(&self, view: &RaceView) {
+        let _ = self.sender.send(view.clone());
+    }
+
+    pub async fn join(&self, name: String) -> Result<String, RaceError> {
+        let mut view = self.view.lock().await;
+        if view.phase != RacePhase::Lobby || view.players.len() >= MAX_PLAYERS {
+            return Err(RaceError::Full);
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        let empty_board = vec![vec![0; view.size]; view.size];
+        view.players.push(RacePlayer {
+            id: id.clone(),
+            name,
+            ready: false,
+            connected: true,
+            score: 0,
+            moves: 0,
+            board: empty_board,
+            over: false,
+            seeded_recording: None,
+        });
+        self.broadcast(&view);
+        Ok(id)
+    }
+
+    /// Players that leave the lobby give up their spot, ones that leave mid race keep their score
+    pub async fn leave(&self, player_id: &str) {
+        let mut view = self.view.lock().await;
+        match view.phase {
+            RacePhase::Lobby => view.players.retain(|player| player.id != player_id),
+            _ => {
+                if let Some(player) = view
+                    .players
+                    .iter_mut()
+                    .find(|player| player.id == player_id)
+                {
+                    player.connected = false;
+                }
+            }
+        }
+        self.broadcast(&view);
+    }
+
+    /// The countdown starts once every player in the lobby is ready
+    pub async fn set_ready(self: &Arc<Self>, player_id: &str, ready: bool) {
+        let mut view = self.view.lock().await;
+        if view.phase != RacePhase::Lobby {
+            return;
+        }
+        if let Some(player) = view
+            .players
+            .iter_mut()
+            .find(|player| player.id == player_id)
+        {
+            player.ready = ready;
+        }
+        if view.players.len() >= MIN_PLAYERS && view.players.iter().all(|player| player.ready) {
+            let starts_at = Utc::now() + COUNTDOWN;
+            view.phase = RacePhase::Countdown { starts_at };
+            let race = self.clone();
+            let minutes = view.minutes;
+            tokio::spawn(async move { race.run_clock(minutes).await });
+        }
+        self.broadcast(&view);
+    }
+
+    async fn run_clock(&self, minutes: u32) {
+        tokio::time::sleep(COUNTDOWN).await;
+        let race_length = Duration::from_secs(minutes as u64 * 60);
+        {
+            let mut view = self.view.lock().await;
+            view.phase = RacePhase::Racing {
+                ends_at: Utc::now() + race_length,
+            };
+            view.seed = Some(self.seed);
+            self.broadcast(&view);
+        }
+        tokio::time::sleep(race_length).await;
+        let mut view = self.view.lock().await;
+        if matches!(view.phase, RacePhase::Racing { .. }) {
+            finish(&mut view, FinishReason::Time, None);
+            self.broadcast(&view);
+        }
+    }
+
+    /// Replays the player's recording on the race's seed and updates their board with it
+    pub async fn record_move(
+        &self,
+        player_id: &str,
+        seeded_recording: String,
+    ) -> Result<(), RaceError> {
+        let recording: SeededRecording = seeded_recording
+            .parse()
+            .map_err(|e| RaceError::Invalid(format!("Invalid recording: {:?}", e)))?;
+        let gamestate = GameState::from_reconstructable_ruleset(&recording)
+            .map_err(|e| RaceError::Invalid(format!("Invalid recording: {:?}", e)))?;
+
+        let mut view = self.view.lock().await;
+        if !matches!(view.phase, RacePhase::Racing { .. }) {
+            return Err(RaceError::Invalid("The race is not running".to_string()));
+        }
+        if recording.seed != self.seed
+            || recording.width != view.size
+            || recording.height != view.size
+        {
+            return Err(RaceError::Invalid(
+                "That game is not on the race's seed".to_string(),
+            ));
+        }
+        let Some(player) = view
+            .players
+            .iter_mut()
+            .find(|player| player.id == player_id)
+        else {
+            return Err(RaceError::Invalid("You are not in this race".to_string()));
+        };
+        // There are no undos in a race, the game so far has to carry on from the last one sent
+        let played: Vec<_> = player
+            .seeded_recording
+            .as_deref()
+            .and_then(|played| played.parse::<SeededRecording>().ok())
+            .map(|played| played.moves)
+            .unwrap_or_default();
+        if !recording.moves.starts_with(&played) {
+            return Err(RaceError::Invalid(
+                "Moves can not be taken back in a race".to_string(),
+            ));
+        }
+        if recording.moves.len() - played.len() > MAX_NEW_MOVES {
+            return Err(RaceError::Invalid(
+                "Too many moves at once, send each move as it is made".to_string(),
+            ));
+        }
+        player.score = gamestate.score_current;
+        player.moves = recording.moves.len();
+        player.board = board_rows(&gamestate.board);
+        player.over = gamestate.over;
+        player.seeded_recording = Some(seeded_recording);
+
+        if gamestate.won {
+            let winner = player.id.clone();
+            finish(&mut view, FinishReason::TwentyFortyEight, Some(winner));
+        } else if view.players.iter().all(|player| player.over) {
+            finish(&mut view, FinishReason::AllOut, None);
+        }
+        self.broadcast(&view);
+        Ok(())
+    }
+}
+
+/// Ends the race. Without a 2048 the best score wins, and a tie at the top has no winner
+fn finish(view: &mut RaceView, reason: FinishReason, winner: Option<String>) {
+    let winner = winner.or_else(|| {
+        let best = view.players.iter().map(|player| player.score).max()?;
+        match view
+            .players
+            .iter()
+            .filter(|player| player.score == best)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [only] => Some(only.id.clone()),
+            _ => None,
+        }
+    });
+    view.phase = RacePhase::Finished { winner, reason };
+}
+
+#[derive(Default)]
+pub struct Races {
+    races: Mutex<HashMap<String, Arc<Race>>>,
+}
+
+impl Races {
+    pub async fn create(&self, new_race: NewRace) -> Result<RaceView, RaceError> {
+        let minutes = new_race.minutes.unwrap_or(DEFAULT_MINUTES);
+        if !(1..=MAX_MINUTES).contains(&minutes) {
+            return Err(RaceError::Invalid(format!(
+                "Races last from 1 to {} minutes",
+                MAX_MINUTES
+            )));
+        }
+        let size = new_race.size.unwrap_or(DEFAULT_BOARD_SIZE);
+        if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size) {
+            return Err(RaceError::Invalid(
+                "That board size does not exist".to_string(),
+            ));
+        }
+        // A v4 uuid is random, so its bytes double as the seed and a short code for the race
+        let uuid = uuid::Uuid::new_v4();
+        let bytes = uuid.as_bytes();
+        let seed = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let id = uuid.simple().to_string()[..8].to_string();
+        let view = RaceView {
+            id: id.clone(),
+            seed: None,
+            size,
+            minutes,
+            created_at: Utc::now(),
+            phase: RacePhase::Lobby,
+            players: vec![],
+        };
+        let race = Arc::new(Race {
+            seed,
+            sender: broadcast::channel(VIEW_BUFFER).0,
+            view: Mutex::new(view.clone()),
+        });
+
+        let mut races = self.races.lock().await;
+        let cutoff = Utc::now() - RACE_LIFETIME;
+        let mut stale = vec![];
+        for (race_id, race) in races.iter() {
+            if race.view().await.created_at < cutoff {
+                stale.push(race_id.clone());
+            }
+        }
+        for race_id in stale {
+            races.remove(&race_id);
+        }
+        races.insert(id, race);
+        Ok(view)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Arc<Race>, RaceError> {
+        self.races
+            .lock()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| RaceError::NotFound(id.to_string()))
+    }
+
+    /// Races still waiting for players, newest first
+    pub async fn open_lobbies(&self) -> Vec<RaceView> {
+        let races: Vec<Arc<Race>> = self.races.lock().await.values().cloned().collect();
+        let mut lobbies = vec![];
+        for race in races {
+            let view = race.view().await;
+            if view.phase == RacePhase::Lobby && view.players.len() < MAX_PLAYERS {
+                lobbies.push(view);
+            }
+        }
+        lobbies.sort_by_key(|view| std::cmp::Reverse(view.created_at));
+        lobbies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twothousand_forty_eight::direction::{Direction, MOVE_DIRECTIONS};
+
+    // A race with one player in it that has skipped the lobby and countdown
+    async fn racing() -> (Arc<Race>, String) {
+        let races = Races::default();
+        let view = races
+            .create(NewRace {
+                minutes: None,
+                size: None,
+            })
+            .await
+            .unwrap();
+        let race = races.get(&view.id).await.unwrap();
+        let player_id = race.join("a".to_string()).await.unwrap();
+        let mut view = race.view.lock().await;
+        view.phase = RacePhase::Racing {
+            ends_at: Utc::now() + chrono::Duration::minutes(3),
+        };
+        view.seed = Some(race.seed);
+        drop(view);
+        (race, player_id)
+    }
+
+    // Moves that each change the board on the race's seed
+    fn playable_moves(race: &Race, count: usize) -> Vec<Direction> {
+        let mut moves = vec![];
+        while moves.len() < count {
+            let next = MOVE_DIRECTIONS
+                .into_iter()
+                .find(|direction| {
+                    let mut tried = moves.clone();
+                    tried.push(*direction);
+                    let recording = SeededRecording::new(race.seed, 4, 4, tried);
+                    GameState::from_reconstructable_ruleset(&recording)
+                        .is_ok_and(|gamestate| !gamestate.over)
+                })
+                .expect("the board has a move left");
+            moves.push(next);
+        }
+        moves
+    }
+
+    fn recording(race: &Race, moves: &[Direction]) -> String {
+        (&SeededRecording::new(race.seed, 4, 4, moves.to_vec())).into()
+    }
+
+    #[tokio::test]
+    async fn the_seed_is_hidden_till_the_race_starts() {
+        let races = Races::default();
+        let view = races
+            .create(NewRace {
+                minutes: None,
+                size: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(view.seed, None);
+        assert_eq!(races.open_lobbies().await[0].seed, None);
+    }
+
+    #[tokio::test]
+    async fn moves_carry_on_from_the_last_ones_sent() {
+        let (race, player_id) = racing().await;
+        let moves = playable_moves(&race, 4);
+        race.record_move(&player_id, recording(&race, &moves[..3]))
+            .await
+            .unwrap();
+
+        // Taking the last move back
+        assert!(
+            race.record_move(&player_id, recording(&race, &moves[..2]))
+                .await
+                .is_err()
+        );
+        // Swapping an earlier move out for another
+        let mut rewritten = moves.clone();
+        rewritten[0] = MOVE_DIRECTIONS
+            .into_iter()
+            .find(|direction| *direction != moves[0])
+            .unwrap();
+        assert!(
+            race.record_move(&player_id, recording(&race, &rewritten))
+                .await
+                .is_err()
+        );
+
+        race.record_move(&player_id, recording(&race, &moves))
+            .await
+            .unwrap();
+        assert_eq!(race.view().await.players[0].moves, 4);
+    }
+
+    #[tokio::test]
+    async fn a_message_only_adds_a_few_moves() {
+        let (race, player_id) = racing().await;
+        let moves = playable_moves(&race, MAX_NEW_MOVES + 1);
+        assert!(
+            race.record_move(&player_id, recording(&race, &moves))
+                .await
+                .is_err()
+        );
+        race.record_move(&player_id, recording(&race, &moves[..MAX_NEW_MOVES]))
+            .await
+            .unwrap();
+        race.record_move(&player_id, recording(&race, &moves))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+use crate::ApiContext;
+use crate::limits::Limits;
+use crate::race::{NewRace, Race, RaceCommand, RaceError, RaceMessage, RaceView};
+use dropshot::{
+    ClientErrorStatusCode, HttpError, HttpResponseCreated, HttpResponseOk, Path, Query,
+    RequestContext, TypedBody, WebsocketChannelResult, WebsocketConnection, WebsocketConnectionRaw,
+    channel,
+};
+use futures::{SinkExt, StreamExt};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::Role;
+
+// Names show up next to boards, anything longer is cut
+const MAX_NAME_LENGTH: usize = 40;
+
+impl From<RaceError> for HttpError {
+    fn from(err: RaceError) -> Self {
+        match err {
+            RaceError::Invalid(_) => HttpError::for_bad_request(None, err.to_string()),
+            RaceError::NotFound(_) => HttpError::for_not_found(None, err.to_string()),
+            RaceError::Full => {
+                HttpError::for_client_error(None, ClientErrorStatusCode::CONFLICT, err.to_string())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RacePath {
+    pub id: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RaceSocketQuery {
+    /// Joins the race as a player under this name. Without one the socket only watches
+    pub name: Option<String>,
+}
+
+/// Races still waiting for players
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/races",
+}]
+pub async fn list_races(
+    rqctx: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<Vec<RaceView>>, HttpError> {
+    Ok(HttpResponseOk(rqctx.context().races.open_lobbies().await))
+}
+
+/// Open a lobby on a new random seed
+#[dropshot::endpoint {
+    method = POST,
+    path = "/api/races",
+}]
+pub async fn create_race(
+    rqctx: RequestContext<ApiContext>,
+    body: TypedBody<NewRace>,
+) -> Result<HttpResponseCreated<RaceView>, HttpError> {
+    let view = rqctx.context().races.create(body.into_inner()).await?;
+    Ok(HttpResponseCreated(view))
+}
+
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/races/{id}",
+}]
+pub async fn get_race(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<RacePath>,
+) -> Result<HttpResponseOk<RaceView>, HttpError> {
+    let race = rqctx.context().races.get(&path.into_inner().id).await?;
+    Ok(HttpResponseOk(race.view().await))
+}
+
+/// The race's socket. Players send {"type": "ready", "ready": true} in the lobby and
+/// {"type": "move", "seeded_recording": "..."} after every move once it starts. Every socket
+/// gets the whole race as {"type": "race", ...} after each change
+#[channel {
+    protocol = WEBSOCKETS,
+    path = "/api/races/{id}/play",
+}]
+pub async fn race_socket(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<RacePath>,
+    query: Query<RaceSocketQuery>,
+    upgraded: WebsocketConnection,
+) -> WebsocketChannelResult {
+    let race = rqctx
+        .context()
+        .races
+        .get(&path.into_inner().id)
+        .await
+        .map_err(HttpError::from)?;
+    let mut ws = WebSocketStream::from_raw_socket(upgraded.into_inner(), Role::Server, None).await;
+
+    // Subscribed before joining so the view with this player in it is not missed
+    let updates = race.subscribe();
+    let joined = match query.into_inner().name {
+        Some(name) => {
+            let name: String = name.trim().chars().take(MAX_NAME_LENGTH).collect();
+            race.join(name).await.map(Some)
+        }
+        None => Ok(None),
+    };
+    let player_id = joined.as_ref().ok().cloned().flatten();
+    let greeting = match joined {
+        Ok(Some(player_id)) => Some(RaceMessage::Joined { player_id }),
+        Ok(None) => None,
+        // Still let them watch when the race is full or already going
+        Err(err) => Some(RaceMessage::Error {
+            message: err.to_string(),
+        }),
+    };
+    let limits = &rqctx.context().limits;
+    let result = relay(
+        &race,
+        limits,
+        &mut ws,
+        player_id.as_deref(),
+        greeting,
+        updates,
+    )
+    .await;
+    // Leaving even when the socket broke, so the lobby does not wait on a player that is gone
+    if let Some(player_id) = &player_id {
+        race.leave(player_id).await;
+    }
+    result
+}
+
+fn to_message(message: &RaceMessage) -> Result<Message, serde_json::Error> {
+    serde_json::to_string(message).map(|text| Message::Text(text.into()))
+}
+
+/// Passes race updates out to the socket and the player's commands in till it closes
+async fn relay(
+    race: &Arc<Race>,
+    limits: &Limits,
+    ws: &mut WebSocketStream<WebsocketConnectionRaw>,
+    player_id: Option<&str>,
+    greeting: Option<RaceMessage>,
+    mut updates: broadcast::Receiver<RaceView>,
+) -> WebsocketChannelResult {
+    if let Some(greeting) = greeting {
+        ws.send(to_message(&greeting)?).await?;
+    }
+    ws.send(to_message(&RaceMessage::Race(race.view().await))?)
+        .await?;
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(view) => ws.send(to_message(&RaceMessage::Race(view))?).await?,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            incoming = ws.next() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    // Spectators can not do anything but watch
+                    let Some(player_id) = player_id else {
+                        continue;
+                    };
+                    let result = match serde_json::from_str::<RaceCommand>(&text) {
+                        Ok(RaceCommand::Ready { ready }) => {
+                            race.set_ready(player_id, ready).await;
+                            Ok(())
+                        }
+                        Ok(RaceCommand::Move { seeded_recording }) => match limits.check_recording(&seeded_recording) {
+                            Ok(()) => race.record_move(player_id, seeded_recording).await,
+                            Err(err) => Err(RaceError::Invalid(err.to_string())),
+                        },
+                        Err(err) => Err(RaceError::Invalid(err.to_string())),
+                    };
+                    if let Err(err) = result {
+                        ws.send(to_message(&RaceMessage::Error { message: err.to_string() })?).await?;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,544 @@
+use crate::anticheat::{AnticheatStore, PlayerHistory};
+use crate::atproto::{AtprotoClient, AtprotoError};
+use crate::limits::{LimitError, Limits};
+use crate::moderation::ModerationStore;
+use atrium_api::types::Collection;
+use atrium_api::types::string::Did;
+use chrono::{DateTime, DurationRound, Utc};
+use hmac::{Hmac, Mac};
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use twothousand_forty_eight::{unified::game::GameState, v2::recording::SeededRecording};
+use types_2048::blue;
+use types_2048::blue::_2048::game;
+
+// Each player is dealt one seed per board size for this long. Asking again inside it gets the
+// same seed, so searching for a lucky one means waiting out a window per try
+const WINDOW: chrono::Duration = chrono::Duration::hours(1);
+const KEY_LENGTH: usize = 32;
+const DEFAULT_BOARD_SIZE: usize = 4;
+const MIN_BOARD_SIZE: usize = 3;
+const MAX_BOARD_SIZE: usize = twothousand_forty_eight::board::MAX_WIDTH;
+// Ranked games pull the player's games from their PDS, so they are reused for a bit
+const PLAYER_TTL: Duration = Duration::from_secs(60);
+// Past this many players the stale entries are dropped
+const PRUNE_PLAYERS_ABOVE: usize = 10_000;
+
+#[derive(Debug)]
+pub enum RankedError {
+    Invalid(String),
+    Atproto(AtprotoError),
+    Limit(LimitError),
+}
+
+impl std::fmt::Display for RankedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RankedError::Invalid(err) => write!(f, "{}", err),
+            RankedError::Atproto(err) => write!(f, "{}", err),
+            RankedError::Limit(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// A seed the server dealt a player. The game record carries `issued_at` as `seedIssuedAt` so
+/// the seed can be checked again later
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct IssuedSeed {
+    pub seed: u32,
+    pub size: usize,
+    pub issued_at: DateTime<Utc>,
+    /// When asking again deals a new seed
+    pub next_seed_at: DateTime<Utc>,
+}
+
+/// A game that counts for global leaderboards
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct RankedGame {
+    pub uri: String,
+    pub seed: u32,
+    pub size: usize,
+    pub score: i64,
+    pub issued_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct RankedPlayer {
+    pub did: String,
+    /// Best score first
+    pub games: Vec<RankedGame>,
+    /// Games on seeds the player picked, shared seeds and casual games, or replays of an issued seed
+    pub unranked: usize,
+    /// Games on issued seeds held back by the anti-cheat checks or a moderator
+    pub ineligible: usize,
+}
+
+/// When the game's first move was made. Games from before the app recorded it fall back to
+/// createdAt, which the app sets when the game ends, so those have to finish inside the window
+pub fn started_at(game: &game::RecordData) -> DateTime<Utc> {
+    game.started_at
+        .as_ref()
+        .unwrap_or(&game.created_at)
+        .as_ref()
+        .with_timezone(&Utc)
+}
+
+/// The keys ranked seeds are derived from. Seeds are never stored, the same key, player, size
+/// and window always give the same seed
+pub struct RankedSeeds {
+    // Newest first. Only the newest deals seeds, the rest still check old ones
+    keys: Vec<[u8; KEY_LENGTH]>,
+    // Players' ranked games as last served, by DID
+    players: Mutex<HashMap<String, (Instant, RankedPlayer)>>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<[u8; KEY_LENGTH]> {
+    let hex = hex.trim();
+    if hex.len() != KEY_LENGTH * 2 {
+        return None;
+    }
+    let mut key = [0; KEY_LENGTH];
+    for (index, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(index * 2..index * 2 + 2)?, 16).ok()?;
+    }
+    Some(key)
+}
+
+impl RankedSeeds {
+    fn with_keys(keys: Vec<[u8; KEY_LENGTH]>) -> Self {
+        RankedSeeds {
+            keys,
+            players: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads the key file, one hex key per line with the newest first. A new key is made and
+    /// saved when the file does not exist yet
+    pub fn load_or_create(path: PathBuf) -> Result<Self, String> {
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                let keys = contents
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| {
+                        from_hex(line).ok_or_else(|| format!("Invalid key in {}", path.display()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if keys.is_empty() {
+                    return Err(format!("No keys in {}", path.display()));
+                }
+                Ok(RankedSeeds::with_keys(keys))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let ranked_seeds = RankedSeeds::with_keys(vec![rand::random()]);
+                ranked_seeds.save(&path)?;
+                Ok(ranked_seeds)
+            }
+            Err(e) => Err(format!("Error reading {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Deals seeds from a new key from now on. The old keys stay in the file, newest first, so
+    /// games on seeds they dealt stay ranked
+    pub fn rotate(path: PathBuf) -> Result<Self, String> {
+        let mut ranked_seeds = Self::load_or_create(path.clone())?;
+        ranked_seeds.keys.insert(0, rand::random());
+        ranked_seeds.save(&path)?;
+        Ok(ranked_seeds)
+    }
+
+    /// How many keys seeds are checked against, the newest included
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Error creating {}: {}", parent.display(), e))?;
+        }
+        let contents: String = self.keys.iter().map(|key| to_hex(key) + "\n").collect();
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e))
+    }
+
+    fn derive(key: &[u8; KEY_LENGTH], did: &str, size: usize, window_start: DateTime<Utc>) -> u32 {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+        mac.update(format!("{}:{}:{}", did, size, window_start.timestamp()).as_bytes());
+        let digest = mac.finalize().into_bytes();
+        u32::from_le_bytes([digest[0], digest[1], digest[2], digest[3]])
+    }
+
+    fn window_start(at: DateTime<Utc>) -> DateTime<Utc> {
+        at.duration_trunc(WINDOW).unwrap_or(at)
+    }
+
+    /// The player's seed for this window
+    pub fn issue(
+        &self,
+        did: &Did,
+        size: Option<usize>,
+        now: DateTime<Utc>,
+    ) -> Result<IssuedSeed, RankedError> {
+        let size = size.unwrap_or(DEFAULT_BOARD_SIZE);
+        if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size) {
+            return Err(RankedError::Invalid(
+                "That board size does not exist".to_string(),
+            ));
+        }
+        let issued_at = Self::window_start(now);
+        Ok(IssuedSeed {
+            seed: Self::derive(&self.keys[0], did.as_str(), size, issued_at),
+            size,
+            issued_at,
+            next_seed_at: issued_at + WINDOW,
+        })
+    }
+
+    /// Whether this server dealt the seed to the player at that time, under any of its keys, and
+    /// the game on it was started inside the window it was dealt for. A game can finish after the
+    /// window, but one started later can not be passed off as played back when the seed was dealt
+    pub fn is_issued(
+        &self,
+        did: &str,
+        seed: u32,
+        size: usize,
+        issued_at: DateTime<Utc>,
+        started_at: DateTime<Utc>,
+    ) -> bool {
+        Self::window_start(issued_at) == issued_at
+            && issued_at <= started_at
+            && started_at <= issued_at + WINDOW
+            && self
+                .keys
+                .iter()
+                .any(|key| Self::derive(key, did, size, issued_at) == seed)
+    }
+
+    /// The issued seed a game was played on, when it follows ranked rules. Only classic games
+    /// played to the end without hints or undos are ranked
+    fn ranked_seed(
+        &self,
+        did: &str,
+        game: &game::RecordData,
+    ) -> Option<(SeededRecording, DateTime<Utc>)> {
+        let issued_at = game.seed_issued_at.as_ref()?.as_ref().with_timezone(&Utc);
+        let classic = game.mode.as_deref().is_none_or(|mode| mode == "classic");
+        if !classic
+            || !game.completed
+            || game.hints_used.unwrap_or(0) > 0
+            || game.undo_count.unwrap_or(0) > 0
+        {
+            return None;
+        }
+        let recording: SeededRecording = game.seeded_recording.parse().ok()?;
+        let started_at = started_at(game);
+        if recording.width != recording.height
+            || !self.is_issued(did, recording.seed, recording.width, issued_at, started_at)
+        {
+            return None;
+        }
+        // A record claiming a score its moves do not add up to was edited after the fact
+        let gamestate = GameState::from_reconstructable_ruleset(&recording).ok()?;
+        (gamestate.score_current as i64 == game.current_score).then_some((recording, issued_at))
+    }
+
+    /// `ranked_player` for requests anyone can make. The answer is reused for a minute, and games
+    /// the anti-cheat checks have not seen are analysed within the request's budget
+    pub async fn cached_ranked_player(
+        &self,
+        client: &AtprotoClient,
+        anticheat: &AnticheatStore,
+        moderation: &ModerationStore,
+        limits: &Limits,
+        did: &Did,
+    ) -> Result<RankedPlayer, RankedError> {
+        if let Some((computed, player)) = self.players.lock().await.get(did.as_str())
+            && computed.elapsed() < PLAYER_TTL
+        {
+            return Ok(player.clone());
+        }
+        let player = self
+            .ranked_player(client, anticheat, moderation, Some(limits), did)
+            .await?;
+        let mut players = self.players.lock().await;
+        if players.len() > PRUNE_PLAYERS_ABOVE {
+            players.retain(|_, (computed, _)| computed.elapsed() < PLAYER_TTL);
+        }
+        players.insert(did.to_string(), (Instant::now(), player.clone()));
+        Ok(player)
+    }
+
+    /// Sorts the player's games into ranked and unranked. Only the first game on each issued seed
+    /// counts, so replaying a seed till it goes well does not help. New games are analysed within
+    /// the budget of `limits` when given
+    pub async fn ranked_player(
+        &self,
+        client: &AtprotoClient,
+        anticheat: &AnticheatStore,
+        moderation: &ModerationStore,
+        limits: Option<&Limits>,
+        did: &Did,
+    ) -> Result<RankedPlayer, RankedError> {
+        let records = client
+            .list_records::<serde_json::Value>(did, blue::_2048::Game::NSID)
+            .await
+            .map_err(RankedError::Atproto)?;
+        // Read loosely so one odd record does not hide the rest of the player's games
+        let mut games: Vec<(String, game::RecordData)> = records
+            .into_iter()
+            .filter_map(|(uri, value)| Some((uri, serde_json::from_value(value).ok()?)))
+            .collect();
+        games.sort_by(|(_, a), (_, b)| a.created_at.as_ref().cmp(b.created_at.as_ref()));
+
+        let mut first_games: HashMap<(u32, usize), (RankedGame, game::RecordData)> = HashMap::new();
+        for (uri, game) in &games {
+            let Some((recording, issued_at)) = self.ranked_seed(did.as_str(), game) else {
+                continue;
+            };
+            first_games
+                .entry((recording.seed, recording.width))
+                .or_insert_with(|| {
+                    let ranked = RankedGame {
+                        uri: uri.clone(),
+                        seed: recording.seed,
+                        size: recording.width,
+                        score: game.current_score,
+                        issued_at,
+                    };
+                    (ranked, game.clone())
+                });
+        }
+
+        // Games that look played by a bot, or that a moderator found cheating, banned or hid, do not count
+        let exclusions = moderation.exclusions(did.as_str()).await;
+        let history = PlayerHistory::new(games.iter().map(|(_, game)| game))
+            .with_dealt_seeds(first_games.keys().copied());
+        let candidates: Vec<(String, game::RecordData)> = first_games
+            .values()
+            .map(|(ranked, game)| (ranked.uri.clone(), game.clone()))
+            .collect();
+        let reports = match limits {
+            Some(limits) => anticheat
+                .screen_within(limits, did.as_str(), &history, &candidates)
+                .await
+                .map_err(RankedError::Limit)?,
+            None => anticheat.screen(did.as_str(), &history, &candidates).await,
+        };
+        let eligible: HashMap<String, bool> = reports
+            .into_iter()
+            .map(|report| (report.uri.clone(), report.is_eligible()))
+            .collect();
+        let (mut ranked, ineligible): (Vec<RankedGame>, Vec<RankedGame>) = first_games
+            .into_values()
+            .map(|(ranked, _)| ranked)
+            .partition(|ranked| {
+                eligible.get(&ranked.uri).copied().unwrap_or(true)
+                    && !exclusions.excludes(&ranked.uri)
+            });
+        ranked.sort_by_key(|ranked| std::cmp::Reverse(ranked.score));
+        Ok(RankedPlayer {
+            did: did.to_string(),
+            unranked: games.len() - ranked.len() - ineligible.len(),
+            ineligible: ineligible.len(),
+            games: ranked,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; KEY_LENGTH] = [1; KEY_LENGTH];
+    const OLD_KEY: [u8; KEY_LENGTH] = [2; KEY_LENGTH];
+    const DID: &str = "did:plc:ewvi7nxzyoun6zhxrhs64oiz";
+
+    fn at(time: &str) -> DateTime<Utc> {
+        format!("2026-03-01T{}Z", time).parse().unwrap()
+    }
+
+    fn seeds() -> RankedSeeds {
+        RankedSeeds::with_keys(vec![KEY, OLD_KEY])
+    }
+
+    #[test]
+    fn derived_seeds_depend_on_every_input() {
+        let window = at("12:00:00");
+        let seed = RankedSeeds::derive(&KEY, DID, 4, window);
+        assert_eq!(seed, RankedSeeds::derive(&KEY, DID, 4, window));
+        for other in [
+            RankedSeeds::derive(&OLD_KEY, DID, 4, window),
+            RankedSeeds::derive(&KEY, "did:plc:someoneelse", 4, window),
+            RankedSeeds::derive(&KEY, DID, 5, window),
+            RankedSeeds::derive(&KEY, DID, 4, window + WINDOW),
+        ] {
+            assert_ne!(seed, other);
+        }
+    }
+
+    #[test]
+    fn one_seed_is_dealt_per_window() {
+        let did: Did = DID.parse().unwrap();
+        let seeds = seeds();
+        let first = seeds.issue(&did, None, at("12:05:00")).unwrap();
+        assert_eq!(first.size, DEFAULT_BOARD_SIZE);
+        assert_eq!(first.issued_at, at("12:00:00"));
+        assert_eq!(first.next_seed_at, at("13:00:00"));
+        let again = seeds.issue(&did, None, at("12:59:59")).unwrap();
+        assert_eq!(again.seed, first.seed);
+        let next = seeds.issue(&did, None, at("13:00:00")).unwrap();
+        assert_ne!(next.seed, first.seed);
+
+        assert!(
+            seeds
+                .issue(&did, Some(MAX_BOARD_SIZE + 1), at("12:00:00"))
+                .is_err()
+        );
+        assert!(
+            seeds
+                .issue(&did, Some(MIN_BOARD_SIZE - 1), at("12:00:00"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn issued_seeds_check_out() {
+        let did: Did = DID.parse().unwrap();
+        let seeds = seeds();
+        let issued = seeds.issue(&did, Some(5), at("12:05:00")).unwrap();
+        let check = |did: &str, seed: u32, size: usize, issued_at, played_at| {
+            seeds.is_issued(did, seed, size, issued_at, played_at)
+        };
+        let (seed, issued_at) = (issued.seed, issued.issued_at);
+        assert!(check(DID, seed, 5, issued_at, at("12:05:00")));
+
+        assert!(!check(
+            "did:plc:someoneelse",
+            seed,
+            5,
+            issued_at,
+            at("12:05:00")
+        ));
+        assert!(!check(
+            DID,
+            seed.wrapping_add(1),
+            5,
+            issued_at,
+            at("12:05:00")
+        ));
+        assert!(!check(DID, seed, 4, issued_at, at("12:05:00")));
+        // Only the start of a window is ever handed out
+        assert!(!check(DID, seed, 5, at("12:05:00"), at("12:05:00")));
+    }
+
+    #[test]
+    fn games_started_outside_the_window_do_not_count() {
+        let did: Did = DID.parse().unwrap();
+        let seeds = seeds();
+        let issued = seeds.issue(&did, None, at("12:05:00")).unwrap();
+        let check = |started_at| seeds.is_issued(DID, issued.seed, 4, issued.issued_at, started_at);
+        assert!(check(at("12:00:00")));
+        assert!(check(at("13:00:00")));
+        assert!(!check(at("13:00:01")));
+        assert!(!check(at("11:59:59")));
+    }
+
+    // A finished classic game on the seed, started and ended at the times given
+    fn ranked_game(
+        issued: &IssuedSeed,
+        started_at: Option<&str>,
+        ended_at: &str,
+    ) -> game::RecordData {
+        use atrium_api::types::string::Datetime;
+        use twothousand_forty_eight::direction::Direction;
+        use types_2048::blue::_2048::defs::SyncStatusData;
+
+        let datetime = |time: &str| Datetime::new(at(time).fixed_offset());
+        let recording = SeededRecording::new(
+            issued.seed,
+            4,
+            4,
+            vec![
+                Direction::UP,
+                Direction::LEFT,
+                Direction::DOWN,
+                Direction::RIGHT,
+            ],
+        );
+        let gamestate = GameState::from_reconstructable_ruleset(&recording).unwrap();
+        game::RecordData {
+            completed: true,
+            created_at: datetime(ended_at),
+            current_score: gamestate.score_current as i64,
+            hints_used: Some(0),
+            max_tile: None,
+            mode: Some("classic".to_string()),
+            mode_limit: None,
+            move_times: None,
+            play_time: None,
+            score_to_beat: None,
+            seed_issued_at: Some(Datetime::new(issued.issued_at.fixed_offset())),
+            seeded_recording: (&recording).into(),
+            started_at: started_at.map(datetime),
+            sync_status: SyncStatusData {
+                created_at: datetime(ended_at),
+                hash: "".to_string(),
+                synced_with_at_repo: true,
+                updated_at: datetime(ended_at),
+            }
+            .into(),
+            time_to_find_twenty_forty_eight: None,
+            undo_count: Some(0),
+            won: false,
+            won_at_move: None,
+        }
+    }
+
+    #[test]
+    fn games_started_inside_the_window_count_however_late_they_finish() {
+        let did: Did = DID.parse().unwrap();
+        let seeds = seeds();
+        let issued = seeds.issue(&did, None, at("12:05:00")).unwrap();
+        let long_game = ranked_game(&issued, Some("12:50:00"), "15:30:00");
+        assert!(seeds.ranked_seed(DID, &long_game).is_some());
+
+        let late_start = ranked_game(&issued, Some("13:10:00"), "13:20:00");
+        assert!(seeds.ranked_seed(DID, &late_start).is_none());
+    }
+
+    #[test]
+    fn games_without_a_start_time_have_to_finish_inside_the_window() {
+        let did: Did = DID.parse().unwrap();
+        let seeds = seeds();
+        let issued = seeds.issue(&did, None, at("12:05:00")).unwrap();
+        assert!(
+            seeds
+                .ranked_seed(DID, &ranked_game(&issued, None, "12:40:00"))
+                .is_some()
+        );
+        assert!(
+            seeds
+                .ranked_seed(DID, &ranked_game(&issued, None, "13:40:00"))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn seeds_from_old_keys_still_check_out() {
+        let window = at("12:00:00");
+        let seed = RankedSeeds::derive(&OLD_KEY, DID, 4, window);
+        assert!(seeds().is_issued(DID, seed, 4, window, window));
+        let without_old_key = RankedSeeds::with_keys(vec![KEY]);
+        assert!(!without_old_key.is_issued(DID, seed, 4, window, window));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::ranked::{IssuedSeed, RankedError, RankedPlayer};
+use crate::{ApiContext, require_player};
+use atrium_api::types::string::Did;
+use dropshot::{HttpError, HttpResponseOk, Path, RequestContext, TypedBody};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+impl From<RankedError> for HttpError {
+    fn from(err: RankedError) -> Self {
+        match err {
+            RankedError::Invalid(_) => HttpError::for_bad_request(None, err.to_string()),
+            RankedError::Atproto(_) => HttpError::for_unavail(None, err.to_string()),
+            RankedError::Limit(err) => HttpError::from(err),
+        }
+    }
+}
+
+// The method players' service auth tokens name to be dealt a seed
+const ISSUE_RANKED_SEED_METHOD: &str = "blue.2048.ranked.issueSeed";
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SeedRequest {
+    pub did: String,
+    /// Width and height of the board, 4 when missing
+    pub size: Option<usize>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PlayerPath {
+    pub did: String,
+}
+
+fn parse_did(did: &str) -> Result<Did, HttpError> {
+    did.parse()
+        .map_err(|e: &str| HttpError::for_bad_request(None, format!("Invalid DID: {}", e)))
+}
+
+/// Deal the player a seed for a ranked game. They get the same one till the window is up. Needs a
+/// service auth token for blue.2048.ranked.issueSeed from the player's PDS as the bearer token
+#[dropshot::endpoint {
+    method = POST,
+    path = "/api/ranked/seeds",
+}]
+pub async fn issue_ranked_seed(
+    rqctx: RequestContext<ApiContext>,
+    body: TypedBody<SeedRequest>,
+) -> Result<HttpResponseOk<IssuedSeed>, HttpError> {
+    let request = body.into_inner();
+    let did = parse_did(&request.did)?;
+    require_player(&rqctx, &did, ISSUE_RANKED_SEED_METHOD).await?;
+    let issued = rqctx
+        .context()
+        .ranked_seeds
+        .issue(&did, request.size, chrono::Utc::now())?;
+    Ok(HttpResponseOk(issued))
+}
+
+/// The player's games that count for global leaderboards, read from their PDS at most once a
+/// minute
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/ranked/players/{did}",
+}]
+pub async fn get_ranked_player(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<PlayerPath>,
+) -> Result<HttpResponseOk<RankedPlayer>, HttpError> {
+    crate::limits::check_rate_limit(&rqctx)?;
+    let did = parse_did(&path.into_inner().did)?;
+    let api_context = rqctx.context();
+    let player = api_context
+        .ranked_seeds
+        .cached_ranked_player(
+            &api_context.atproto,
+            &api_context.anticheat,
+            &api_context.moderation,
+            &api_context.limits,
+            &did,
+        )
+        .await?;
+    Ok(HttpResponseOk(player))
+}
@@ -0,0 +1,27 @@
+use crate::ApiContext;
+use crate::config;
+use dropshot::{HttpError, HttpResponseOk, RequestContext, endpoint};
+
+/// Fetch the current value of the counter.
+#[endpoint {
+    method = GET,
+    path = "/api/test",
+    }]
+pub async fn example_api_get_counter(
+    request_context: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<String>, HttpError> {
+    let _api_context = request_context.context();
+
+    Ok(HttpResponseOk("Nice".to_string()))
+}
+
+/// How this server is set up, without its tokens and passwords
+#[endpoint {
+    method = GET,
+    path = "/api/server-config"
+}]
+pub async fn get_server_config(
+    request_context: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<config::Config>, HttpError> {
+    Ok(HttpResponseOk(request_context.context().config.clone()))
+}
@@ -0,0 +1,337 @@
+use crate::atproto::{AtprotoClient, AtprotoError};
+use atrium_api::types::string::Did;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use k256::ecdsa::signature::Verifier;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+// Multicodec prefixes of the compressed public keys a DID document's Multikey holds
+const SECP256K1_PREFIX: [u8; 2] = [0xe7, 0x01];
+const P256_PREFIX: [u8; 2] = [0x80, 0x24];
+
+#[derive(Debug)]
+pub enum ServiceAuthError {
+    // The request has no bearer token
+    Missing,
+    Malformed(String),
+    // The token is for another player, server or method
+    Claims(String),
+    Expired,
+    // The signature does not match the player's signing key
+    Signature,
+    // The player's DID document could not be read or has no usable signing key
+    Key(String),
+    Atproto(AtprotoError),
+}
+
+impl std::fmt::Display for ServiceAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceAuthError::Missing => {
+                write!(f, "Sign in first, the request has no service auth token")
+            }
+            ServiceAuthError::Malformed(err) => write!(f, "Invalid service auth token: {}", err),
+            ServiceAuthError::Claims(err) => {
+                write!(f, "The service auth token is not for this request: {}", err)
+            }
+            ServiceAuthError::Expired => write!(f, "The service auth token has expired"),
+            ServiceAuthError::Signature => {
+                write!(f, "The service auth token is not signed by the player")
+            }
+            ServiceAuthError::Key(err) => {
+                write!(f, "Could not read the player's signing key: {}", err)
+            }
+            ServiceAuthError::Atproto(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    iss: String,
+    aud: String,
+    exp: i64,
+    lxm: Option<String>,
+}
+
+fn decode_part<T: DeserializeOwned>(part: &str) -> Result<T, ServiceAuthError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(part)
+        .map_err(|e| ServiceAuthError::Malformed(e.to_string()))?;
+    serde_json::from_slice(&bytes).map_err(|e| ServiceAuthError::Malformed(e.to_string()))
+}
+
+/// A service auth JWT a player's PDS signed, taken apart but not checked yet
+pub struct ServiceAuthToken {
+    alg: String,
+    claims: Claims,
+    // The header and claims as they were signed
+    signed: String,
+    signature: Vec<u8>,
+}
+
+impl ServiceAuthToken {
+    pub fn parse(token: &str) -> Result<Self, ServiceAuthError> {
+        let parts: Vec<&str> = token.split('.').collect();
+        let [header, claims, signature] = parts.as_slice() else {
+            return Err(ServiceAuthError::Malformed(
+                "a JWT has three parts".to_string(),
+            ));
+        };
+        let header: Header = decode_part(header)?;
+        Ok(ServiceAuthToken {
+            alg: header.alg,
+            claims: decode_part(claims)?,
+            signed: format!("{}.{}", parts[0], claims),
+            signature: URL_SAFE_NO_PAD
+                .decode(signature)
+                .map_err(|e| ServiceAuthError::Malformed(e.to_string()))?,
+        })
+    }
+
+    /// Checks the token was issued by the player, made out to this server for the method and has
+    /// not expired. `now` is in seconds since the epoch
+    pub fn check_claims(
+        &self,
+        did: &Did,
+        audience: &str,
+        method: &str,
+        now: i64,
+    ) -> Result<(), ServiceAuthError> {
+        // Services sign with a fragment on their DID, a player's own token has none
+        if self.claims.iss != did.as_str() {
+            return Err(ServiceAuthError::Claims(format!(
+                "it was issued by {}",
+                self.claims.iss
+            )));
+        }
+        if self.claims.aud != audience {
+            return Err(ServiceAuthError::Claims(format!(
+                "it is made out to {}",
+                self.claims.aud
+            )));
+        }
+        if self.claims.lxm.as_deref() != Some(method) {
+            return Err(ServiceAuthError::Claims(format!(
+                "it is not for {}",
+                method
+            )));
+        }
+        if self.claims.exp <= now {
+            return Err(ServiceAuthError::Expired);
+        }
+        Ok(())
+    }
+
+    /// Checks the signature against the Multikey from the player's DID document
+    pub fn verify_signature(&self, public_key_multibase: &str) -> Result<(), ServiceAuthError> {
+        let (_, key) = multibase::decode(public_key_multibase)
+            .map_err(|e| ServiceAuthError::Key(e.to_string()))?;
+        let Some((prefix, key)) = key.split_at_checked(SECP256K1_PREFIX.len()) else {
+            return Err(ServiceAuthError::Key("the key is empty".to_string()));
+        };
+        let verified = match self.alg.as_str() {
+            "ES256K" if prefix == SECP256K1_PREFIX => {
+                let key = k256::ecdsa::VerifyingKey::from_sec1_bytes(key)
+                    .map_err(|e| ServiceAuthError::Key(e.to_string()))?;
+                k256::ecdsa::Signature::from_slice(&self.signature)
+                    .is_ok_and(|signature| key.verify(self.signed.as_bytes(), &signature).is_ok())
+            }
+            "ES256" if prefix == P256_PREFIX => {
+                let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(key)
+                    .map_err(|e| ServiceAuthError::Key(e.to_string()))?;
+                p256::ecdsa::Signature::from_slice(&self.signature)
+                    .is_ok_and(|signature| key.verify(self.signed.as_bytes(), &signature).is_ok())
+            }
+            // Signed with another kind of key than the player has
+            _ => false,
+        };
+        if verified {
+            Ok(())
+        } else {
+            Err(ServiceAuthError::Signature)
+        }
+    }
+}
+
+/// Checks a service auth token proves the request comes from whoever controls the DID. The
+/// player's PDS only signs them for a signed in player, made out to `audience` for `method`
+pub async fn verify(
+    client: &AtprotoClient,
+    token: &str,
+    did: &Did,
+    audience: &str,
+    method: &str,
+) -> Result<(), ServiceAuthError> {
+    let token = ServiceAuthToken::parse(token)?;
+    // The claims go first so a made up token does not send the server off resolving DIDs
+    token.check_claims(did, audience, method, chrono::Utc::now().timestamp())?;
+    let key = client
+        .signing_key(did)
+        .await
+        .map_err(ServiceAuthError::Atproto)?;
+    token.verify_signature(&key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::signature::Signer;
+
+    const DID: &str = "did:plc:ewvi7nxzyoun6zhxrhs64oiz";
+    const AUDIENCE: &str = "did:web:2048.symm.app";
+    const METHOD: &str = "blue.2048.live.updateGame";
+    const NOW: i64 = 1_700_000_000;
+
+    fn encode(value: serde_json::Value) -> String {
+        URL_SAFE_NO_PAD.encode(value.to_string())
+    }
+
+    fn claims() -> serde_json::Value {
+        serde_json::json!({
+            "iss": DID,
+            "aud": AUDIENCE,
+            "exp": NOW + 60,
+            "iat": NOW,
+            "lxm": METHOD,
+        })
+    }
+
+    fn secp256k1_key() -> k256::ecdsa::SigningKey {
+        k256::ecdsa::SigningKey::from_slice(&[7; 32]).unwrap()
+    }
+
+    fn secp256k1_multikey(key: &k256::ecdsa::SigningKey) -> String {
+        let public = key.verifying_key().to_encoded_point(true);
+        multibase::encode(
+            multibase::Base::Base58Btc,
+            [&SECP256K1_PREFIX[..], public.as_bytes()].concat(),
+        )
+    }
+
+    fn secp256k1_token(key: &k256::ecdsa::SigningKey, claims: serde_json::Value) -> String {
+        let signed = format!(
+            "{}.{}",
+            encode(serde_json::json!({"typ": "JWT", "alg": "ES256K"})),
+            encode(claims)
+        );
+        let signature: k256::ecdsa::Signature = key.sign(signed.as_bytes());
+        format!(
+            "{}.{}",
+            signed,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        )
+    }
+
+    fn check(token: &str) -> Result<(), ServiceAuthError> {
+        ServiceAuthToken::parse(token)?.check_claims(&DID.parse().unwrap(), AUDIENCE, METHOD, NOW)
+    }
+
+    #[test]
+    fn a_players_token_is_accepted() {
+        let key = secp256k1_key();
+        let token = secp256k1_token(&key, claims());
+        check(&token).unwrap();
+        ServiceAuthToken::parse(&token)
+            .unwrap()
+            .verify_signature(&secp256k1_multikey(&key))
+            .unwrap();
+    }
+
+    #[test]
+    fn p256_keys_are_accepted() {
+        let key = p256::ecdsa::SigningKey::from_slice(&[9; 32]).unwrap();
+        let signed = format!(
+            "{}.{}",
+            encode(serde_json::json!({"typ": "JWT", "alg": "ES256"})),
+            encode(claims())
+        );
+        let signature: p256::ecdsa::Signature = key.sign(signed.as_bytes());
+        let token = format!(
+            "{}.{}",
+            signed,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        );
+        let public = key.verifying_key().to_encoded_point(true);
+        let multikey = multibase::encode(
+            multibase::Base::Base58Btc,
+            [&P256_PREFIX[..], public.as_bytes()].concat(),
+        );
+        ServiceAuthToken::parse(&token)
+            .unwrap()
+            .verify_signature(&multikey)
+            .unwrap();
+    }
+
+    #[test]
+    fn another_key_is_turned_away() {
+        let token = secp256k1_token(&secp256k1_key(), claims());
+        let other = k256::ecdsa::SigningKey::from_slice(&[8; 32]).unwrap();
+        assert!(matches!(
+            ServiceAuthToken::parse(&token)
+                .unwrap()
+                .verify_signature(&secp256k1_multikey(&other)),
+            Err(ServiceAuthError::Signature)
+        ));
+    }
+
+    #[test]
+    fn edited_claims_break_the_signature() {
+        let key = secp256k1_key();
+        let token = secp256k1_token(&key, claims());
+        let parts: Vec<&str> = token.split('.').collect();
+        let mut edited = claims();
+        edited["iss"] = "did:plc:someoneelse".into();
+        let forged = format!("{}.{}.{}", parts[0], encode(edited), parts[2]);
+        assert!(matches!(
+            ServiceAuthToken::parse(&forged)
+                .unwrap()
+                .verify_signature(&secp256k1_multikey(&key)),
+            Err(ServiceAuthError::Signature)
+        ));
+    }
+
+    #[test]
+    fn tokens_for_something_else_are_turned_away() {
+        let key = secp256k1_key();
+        for (field, value) in [
+            ("iss", "did:plc:someoneelse"),
+            ("aud", "did:web:example.com"),
+            ("lxm", "blue.2048.ranked.issueSeed"),
+        ] {
+            let mut claims = claims();
+            claims[field] = value.into();
+            assert!(
+                matches!(
+                    check(&secp256k1_token(&key, claims)),
+                    Err(ServiceAuthError::Claims(_))
+                ),
+                "{} was not checked",
+                field
+            );
+        }
+
+        let mut claims = claims();
+        claims["exp"] = NOW.into();
+        assert!(matches!(
+            check(&secp256k1_token(&key, claims)),
+            Err(ServiceAuthError::Expired)
+        ));
+    }
+
+    #[test]
+    fn malformed_tokens_are_turned_away() {
+        for token in ["", "a.b", "a.b.c.d", "!!.??.**"] {
+            assert!(matches!(
+                ServiceAuthToken::parse(token),
+                Err(ServiceAuthError::Malformed(_))
+            ));
+        }
+    }
+}
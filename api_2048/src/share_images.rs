@@ -0,0 +1,289 @@
+use crate::metrics::Histogram;
+use bytes::Bytes;
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime};
+use tokio::sync::Mutex;
+use twothousand_forty_eight::v2::recording::SeededRecording;
+
+// Bump when the drawing changes, so images cached on disk by an older build are not served
+const RENDER_VERSION: u32 = 1;
+
+/// How the share image cache is doing, for sizing it
+#[derive(Clone, Debug, Default, Serialize, JsonSchema)]
+pub struct CacheStats {
+    pub memory_entries: usize,
+    pub memory_capacity: usize,
+    pub disk_entries: usize,
+    pub disk_capacity: usize,
+    pub memory_hits: u64,
+    pub disk_hits: u64,
+    pub misses: u64,
+    /// Requests answered with 304 Not Modified, no image needed
+    pub not_modified: u64,
+    pub memory_evictions: u64,
+    pub disk_evictions: u64,
+    /// Time spent replaying and drawing the misses
+    pub render_ms: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    memory_hits: AtomicU64,
+    disk_hits: AtomicU64,
+    misses: AtomicU64,
+    not_modified: AtomicU64,
+    memory_evictions: AtomicU64,
+    disk_evictions: AtomicU64,
+    render_ms: AtomicU64,
+}
+
+// Least recently used goes first. The caches hold a few thousand entries at most, so finding
+// it by a scan is cheaper than keeping a second ordering in step
+struct Lru<V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, (u64, V)>,
+}
+
+impl<V: Clone> Lru<V> {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<V> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|(used, value)| {
+            *used = tick;
+            value.clone()
+        })
+    }
+
+    // Gives back the keys pushed out to make room
+    fn insert(&mut self, key: String, value: V) -> Vec<String> {
+        self.tick += 1;
+        self.entries.insert(key, (self.tick, value));
+        let mut evicted = vec![];
+        while self.entries.len() > self.capacity {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            evicted.push(oldest);
+        }
+        evicted
+    }
+}
+
+/// Rendered share images, kept in memory and on disk by a hash of what went into drawing them
+pub struct ShareImageCache {
+    // Part of every key, so changing the font does not serve images drawn with the old one
+    font_digest: [u8; 32],
+    memory: Mutex<Lru<Bytes>>,
+    disk_dir: PathBuf,
+    disk: Mutex<Lru<()>>,
+    // The engine's accuracy for games shared with analysis, under the same keys as their images
+    accuracies: Mutex<Lru<f64>>,
+    counters: Counters,
+    render_times: Mutex<Histogram>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl ShareImageCache {
+    /// Picks up the images already on disk, the most recently written counting as the most
+    /// recently used
+    pub fn load(
+        font_bytes: &[u8],
+        memory_capacity: usize,
+        disk_dir: PathBuf,
+        disk_capacity: usize,
+    ) -> Result<Self, String> {
+        let mut on_disk: Vec<(SystemTime, String)> = vec![];
+        match std::fs::read_dir(&disk_dir) {
+            Ok(entries) => {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if path.extension().is_some_and(|extension| extension == "png")
+                        && let Some(key) = path.file_stem().and_then(|stem| stem.to_str())
+                    {
+                        let modified = entry
+                            .metadata()
+                            .and_then(|metadata| metadata.modified())
+                            .unwrap_or(SystemTime::UNIX_EPOCH);
+                        on_disk.push((modified, key.to_string()));
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Error reading {}: {}", disk_dir.display(), e)),
+        }
+        on_disk.sort();
+        let mut disk = Lru::new(disk_capacity);
+        for (_, key) in on_disk {
+            for evicted in disk.insert(key, ()) {
+                let _ = std::fs::remove_file(disk_dir.join(format!("{}.png", evicted)));
+            }
+        }
+
+        Ok(ShareImageCache {
+            font_digest: Sha256::digest(font_bytes).into(),
+            memory: Mutex::new(Lru::new(memory_capacity)),
+            disk_dir,
+            disk: Mutex::new(disk),
+            accuracies: Mutex::new(Lru::new(memory_capacity)),
+            counters: Counters::default(),
+            render_times: Mutex::new(Histogram::default()),
+        })
+    }
+
+    /// The key for a recording's image, also used as its ETag. Only the game itself goes in, so
+    /// the same game written with a comment or encoded differently shares the image
+    pub fn key(&self, recording: &SeededRecording) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(RENDER_VERSION.to_le_bytes());
+        hasher.update(self.font_digest);
+        hasher.update(String::from(recording).as_bytes());
+        to_hex(&hasher.finalize())
+    }
+
+    pub fn count_not_modified(&self) {
+        self.counters.not_modified.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn disk_path(&self, key: &str) -> PathBuf {
+        self.disk_dir.join(format!("{}.png", key))
+    }
+
+    /// The image under the key, drawn with `render` when neither cache has it. Where the drawing
+    /// runs, and for how long, is left to the caller
+    pub async fn get_or_render<R, E>(&self, key: &str, render: R) -> Result<Bytes, E>
+    where
+        R: Future<Output = Result<Vec<u8>, E>>,
+    {
+        if let Some(png) = self.memory.lock().await.get(key) {
+            self.counters.memory_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(png);
+        }
+
+        let on_disk = self.disk.lock().await.get(key).is_some();
+        if on_disk {
+            match tokio::fs::read(self.disk_path(key)).await {
+                Ok(png) => {
+                    self.counters.disk_hits.fetch_add(1, Ordering::Relaxed);
+                    let png = Bytes::from(png);
+                    self.remember(key, png.clone()).await;
+                    return Ok(png);
+                }
+                // Removed from under us, draw it again
+                Err(e) => log::warn!("Cached share image {} could not be read: {}", key, e),
+            }
+        }
+
+        self.counters.misses.fetch_add(1, Ordering::Relaxed);
+        let started = Instant::now();
+        let png = render.await?;
+        let elapsed = started.elapsed();
+        self.counters
+            .render_ms
+            .fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+        self.render_times.lock().await.observe(elapsed);
+        let png = Bytes::from(png);
+        self.remember(key, png.clone()).await;
+        self.write_to_disk(key, &png).await;
+        Ok(png)
+    }
+
+    async fn remember(&self, key: &str, png: Bytes) {
+        let evicted = self.memory.lock().await.insert(key.to_string(), png);
+        self.counters
+            .memory_evictions
+            .fetch_add(evicted.len() as u64, Ordering::Relaxed);
+    }
+
+    // Failing to cache is not worth failing the request over, the image is drawn again next time
+    async fn write_to_disk(&self, key: &str, png: &[u8]) {
+        if self.disk.lock().await.capacity == 0 {
+            return;
+        }
+        if let Err(e) = tokio::fs::create_dir_all(&self.disk_dir).await {
+            log::warn!("Could not create {}: {}", self.disk_dir.display(), e);
+            return;
+        }
+        let path = self.disk_path(key);
+        // Written next to the real file first so a crash mid write can not leave half an image
+        let temp_path = path.with_extension("png.tmp");
+        let written = match tokio::fs::write(&temp_path, png).await {
+            Ok(()) => tokio::fs::rename(&temp_path, &path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            log::warn!("Could not cache share image {}: {}", key, e);
+            return;
+        }
+        let evicted = self.disk.lock().await.insert(key.to_string(), ());
+        self.counters
+            .disk_evictions
+            .fetch_add(evicted.len() as u64, Ordering::Relaxed);
+        for evicted in evicted {
+            let _ = tokio::fs::remove_file(self.disk_path(&evicted)).await;
+        }
+    }
+
+    /// The accuracy worked out for the game under the key before, if it is still cached
+    pub async fn accuracy(&self, key: &str) -> Option<f64> {
+        self.accuracies.lock().await.get(key)
+    }
+
+    pub async fn remember_accuracy(&self, key: &str, accuracy: f64) {
+        self.accuracies
+            .lock()
+            .await
+            .insert(key.to_string(), accuracy);
+    }
+
+    /// How long the misses took to replay and draw
+    pub async fn render_times(&self) -> Histogram {
+        self.render_times.lock().await.clone()
+    }
+
+    pub async fn stats(&self) -> CacheStats {
+        let (memory_entries, memory_capacity) = {
+            let memory = self.memory.lock().await;
+            (memory.entries.len(), memory.capacity)
+        };
+        let (disk_entries, disk_capacity) = {
+            let disk = self.disk.lock().await;
+            (disk.entries.len(), disk.capacity)
+        };
+        CacheStats {
+            memory_entries,
+            memory_capacity,
+            disk_entries,
+            disk_capacity,
+            memory_hits: self.counters.memory_hits.load(Ordering::Relaxed),
+            disk_hits: self.counters.disk_hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            not_modified: self.counters.not_modified.load(Ordering::Relaxed),
+            memory_evictions: self.counters.memory_evictions.load(Ordering::Relaxed),
+            disk_evictions: self.counters.disk_evictions.load(Ordering::Relaxed),
+            render_ms: self.counters.render_ms.load(Ordering::Relaxed),
+        }
+    }
+}
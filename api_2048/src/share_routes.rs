@@ -1,17 +1,54 @@
-use dropshot::{HttpError, Query, RequestContext, HttpResponseOk};
+use dropshot::{HttpError, HttpResponseFound, Path, Query, RequestContext, HttpResponseOk, TypedBody, http_response_found};
 use schemars::JsonSchema;
-use serde::Deserialize;
-use twothousand_forty_eight::{unified::game::GameState, v2::recording::SeededRecording};
+use serde::{Deserialize, Serialize};
+use twothousand_forty_eight::{
+    unified::{game::GameState, reconstruction::Reconstructable},
+    v2::recording::SeededRecording,
+};
+use ai_2048::SearchConfig;
+use ai_2048::analysis::{AnalysisBudget, analyze_within};
 use crate::ApiContext;
+use crate::image_routes::board_image_dimension;
 
-// Assuming the image dimensions are known (e.g., for a 4x4 board)
-// These should match the output of the image generation endpoint
-const OG_IMAGE_WIDTH: u32 = 450; // (4 * 100px tiles) + (5 * 10px padding)
-const OG_IMAGE_HEIGHT: u32 = 450;
+// Used when the recording can't be read, matches a classic 4x4 board
+const DEFAULT_BOARD_SIZE: usize = 4;
+// Shallow and quick, the sampled moves get searched before the page is served
+const ANALYSIS_SEARCH: SearchConfig = SearchConfig {
+    depth: 2,
+    time_budget_ms: 10,
+};
+// Longer games have this many moves spread over them analysed, like the anti-cheat checks
+const ANALYSED_POSITIONS: usize = 200;
+
+// The final board, and for won games how fast 2048 showed up and how far past it the game went
+fn replay(recording: &SeededRecording) -> Option<(GameState, Option<String>)> {
+    let gs = match GameState::from_reconstructable_ruleset(recording) {
+        Ok(gs) => gs,
+        Err(e) => {
+            log::warn!("Failed to create GameState from ruleset for {}. Error: {:?}", String::from(recording), e);
+            return None;
+        }
+    };
+    let win_summary = if gs.won {
+        recording.reconstruct().ok().and_then(|reconstruction| {
+            // history[n] is the board after n moves
+            let won_at_move = reconstruction.history.iter().position(|board| {
+                board.get_occupied_tiles().iter().any(|tile| tile.value >= 2048)
+            })?;
+            let max_tile = gs.board.get_occupied_tiles().iter().map(|tile| tile.value).max()?;
+            Some(format!(" Reached 2048 in {} moves and finished with a {} tile.", won_at_move, max_tile))
+        })
+    } else {
+        None
+    };
+    Some((gs, win_summary))
+}
 
 #[derive(Deserialize, JsonSchema, Debug)]
 pub struct ShareGameQuery {
     pub seeded_recording: String,
+    /// Adds the accuracy from the engine's per move analysis to the card
+    pub analysis: Option<bool>,
 }
 
 #[dropshot::endpoint {
@@ -25,6 +62,10 @@ pub async fn serve_shared_game_page(
     let api_context = rqctx.context();
     let query_params = query.into_inner();
     let original_seeded_recording_param = query_params.seeded_recording;
+    let with_analysis = query_params.analysis.unwrap_or(false) && api_context.config.features.share_analysis;
+    crate::limits::check_rate_limit(&rqctx)?;
+    let limits = &api_context.limits;
+    limits.check_recording_size(&original_seeded_recording_param)?;
 
     // Decode the potentially double-encoded seeded_recording string for parsing
     let string_to_parse = match urlencoding::decode(&original_seeded_recording_param) {
@@ -43,18 +84,15 @@ pub async fn serve_shared_game_page(
     };
 
     // Attempt to parse the recording to get game details
-    let game_details = match string_to_parse.parse::<SeededRecording>() {
-        Ok(rec) => match GameState::from_reconstructable_ruleset(&rec) {
-            Ok(gs) => Some(gs),
-            Err(e) => {
-                log::warn!(
-                    "Failed to create GameState from ruleset for decoded string: {}. Error: {:?}",
-                    string_to_parse,
-                    e
-                );
-                None
-            }
-        },
+    let recording = string_to_parse.parse::<SeededRecording>();
+    if let Ok(rec) = &recording {
+        limits.check_moves(rec)?;
+    }
+    let replayed = match &recording {
+        Ok(rec) => {
+            let rec = rec.clone();
+            limits.run(move || replay(&rec)).await?
+        }
         Err(e) => {
             log::warn!(
                 "Failed to parse SeededRecording from decoded string: {}. Error: {:?}",
@@ -64,21 +102,75 @@ pub async fn serve_shared_game_page(
             None
         }
     };
+    let (game_details, win_summary) = match replayed {
+        Some((gs, win_summary)) => (Some(gs), win_summary),
+        None => (None, None),
+    };
+    let win_summary = win_summary.unwrap_or_default();
+
+    // The image is sized to the board, so the OG tags need to match it
+    let (board_width, board_height) = game_details
+        .as_ref()
+        .map_or((DEFAULT_BOARD_SIZE, DEFAULT_BOARD_SIZE), |gs| (gs.board.width, gs.board.height));
+    let og_image_width = board_image_dimension(board_width);
+    let og_image_height = board_image_dimension(board_height);
+
+    // The page is still worth serving without the accuracy, so running out of budget drops it.
+    // The analysis stops itself at the deadline so it does not hold a worker past the request
+    let accuracy = match (with_analysis, recording) {
+        (true, Ok(rec)) if game_details.is_some() => {
+            let key = api_context.share_images.key(&rec);
+            match api_context.share_images.accuracy(&key).await {
+                Some(accuracy) => Some(accuracy),
+                None => {
+                    let budget = AnalysisBudget {
+                        max_positions: ANALYSED_POSITIONS,
+                        deadline: limits.work_deadline(),
+                    };
+                    let accuracy = limits
+                        .run(move || {
+                            analyze_within(&rec, &ANALYSIS_SEARCH, &budget)
+                                .ok()
+                                .flatten()
+                                .map(|analysis| analysis.accuracy)
+                        })
+                        .await
+                        .unwrap_or_else(|e| {
+                            log::warn!("Analysis of a shared game failed: {}", e);
+                            None
+                        });
+                    if let Some(accuracy) = accuracy {
+                        api_context.share_images.remember_accuracy(&key, accuracy).await;
+                    }
+                    accuracy
+                }
+            }
+        }
+        _ => None,
+    };
 
     let score_str = game_details.map_or("a game".to_string(), |gs| gs.score_current.to_string());
     let title = format!("My 2048 Game Result - Score: {}", score_str);
-    let description = format!(
-        "I played a game of at://2048 and scored {}. Can you beat it?",
-        score_str
-    );
+    let description = match accuracy {
+        Some(accuracy) => format!(
+            "I played a {}x{} game of at://2048 and scored {} with {:.1}% accuracy.{} Can you beat it?",
+            board_width, board_height, score_str, accuracy, win_summary
+        ),
+        None => format!(
+            "I played a {}x{} game of at://2048 and scored {}.{} Can you beat it?",
+            board_width, board_height, score_str, win_summary
+        ),
+    };
 
-    let base_url = &api_context.config.base_url;
+    let base_url = &api_context.config.site.base_url;
+    let app_url = &api_context.config.site.app_url;
 
     // For the URLs in meta tags, use the original, once-encoded parameter string
     let page_url = format!(
-        "{}/share/game?seeded_recording={}",
+        "{}/share/game?seeded_recording={}{}",
         base_url,
-        original_seeded_recording_param // Use the original param value here
+        original_seeded_recording_param, // Use the original param value here
+        if with_analysis { "&analysis=true" } else { "" }
     );
     // Corrected image URL path and use original param value
     let image_url = format!(
@@ -111,21 +203,89 @@ pub async fn serve_shared_game_page(
             <meta property="twitter:description" content="{}">
             <meta property="twitter:image" content="{}">
             
-            <!-- Optional: Redirect to the main game page if not a crawler -->
-            <!-- <meta http-equiv="refresh" content="0;url=http://127.0.0.1:8080/"> --> <!-- Assuming Yew app runs on 8080 -->
         </head>
         <body>
             <h1>Sharing Game Result...</h1>
-            <p>If you are not redirected, <a href="http://127.0.0.1:8080/">click here to play</a>.</p> <!-- Assuming Yew app runs on 8080 -->
+            <p><a href="{}/">Click here to play</a>.</p>
             <p>Score: {}</p>
             <img src="{}" alt="Game Board Preview" width="{}" height="{}"/>
         </body>
         </html>"#,
         title, description, // head title, meta description
-        page_url, title, description, image_url, OG_IMAGE_WIDTH, OG_IMAGE_HEIGHT, // OG tags
+        page_url, title, description, image_url, og_image_width, og_image_height, // OG tags
         page_url, title, description, image_url, // Twitter tags
-        score_str, image_url, OG_IMAGE_WIDTH, OG_IMAGE_HEIGHT // body content
+        app_url, score_str, image_url, og_image_width, og_image_height // body content
     );
 
     Ok(HttpResponseOk(html_content))
-} 
\ No newline at end of file
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct NewShareCode {
+    pub seeded_recording: String,
+}
+
+/// A short link standing in for a recording's share page
+#[derive(Serialize, JsonSchema)]
+pub struct ShareCode {
+    pub code: String,
+    pub url: String,
+}
+
+/// Gives the recording a short code, the same one every time it is shared
+#[dropshot::endpoint {
+    method = POST,
+    path = "/api/share/codes",
+}]
+pub async fn create_share_code(
+    rqctx: RequestContext<ApiContext>,
+    body: TypedBody<NewShareCode>,
+) -> Result<HttpResponseOk<ShareCode>, HttpError> {
+    crate::limits::check_rate_limit(&rqctx)?;
+    let api_context = rqctx.context();
+    let seeded_recording = body.into_inner().seeded_recording;
+    api_context.limits.check_recording_size(&seeded_recording)?;
+    let recording: SeededRecording = seeded_recording
+        .parse()
+        .map_err(|e| HttpError::for_bad_request(None, format!("Invalid seeded_recording: {}", e)))?;
+    api_context.limits.check_moves(&recording)?;
+    // Stored the way it is written back out, so the same game always gets the same code
+    let seeded_recording = String::from(&recording);
+    let code = api_context
+        .database
+        .transaction(move |tx| tx.share_codes().create(&seeded_recording, chrono::Utc::now()))
+        .await?;
+    Ok(HttpResponseOk(ShareCode {
+        url: format!("{}/s/{}", api_context.config.site.base_url, code),
+        code,
+    }))
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ShareCodePath {
+    pub code: String,
+}
+
+/// Sends a short share link on to the recording's share page
+#[dropshot::endpoint {
+    method = GET,
+    path = "/s/{code}",
+}]
+pub async fn follow_share_code(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<ShareCodePath>,
+) -> Result<HttpResponseFound, HttpError> {
+    let code = path.into_inner().code;
+    let lookup = code.clone();
+    let api_context = rqctx.context();
+    let seeded_recording = api_context
+        .database
+        .transaction(move |tx| tx.share_codes().resolve(&lookup))
+        .await?
+        .ok_or_else(|| HttpError::for_not_found(None, format!("No game is shared as {}", code)))?;
+    http_response_found(format!(
+        "{}/share/game?seeded_recording={}",
+        api_context.config.site.base_url,
+        urlencoding::encode(&seeded_recording)
+    ))
+}
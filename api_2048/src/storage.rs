@@ -0,0 +1,329 @@
+use rusqlite::{Connection, Transaction};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub mod games;
+pub mod players;
+pub mod share_codes;
+pub mod stats;
+pub mod verifications;
+
+// Where `DATABASE_PATH` asks for a database that only lives as long as the server
+pub const IN_MEMORY: &str = ":memory:";
+
+// Applied in order, the database's user_version being how many have been. Only ever add to the
+// end, a database already migrated past one does not see changes to it
+const MIGRATIONS: &[&str] = &["CREATE TABLE players (
+        did TEXT PRIMARY KEY,
+        handle TEXT,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE games (
+        uri TEXT PRIMARY KEY,
+        did TEXT NOT NULL REFERENCES players (did) ON DELETE CASCADE,
+        seed INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        score INTEGER NOT NULL,
+        won INTEGER NOT NULL,
+        moves INTEGER NOT NULL,
+        mode TEXT,
+        hints_used INTEGER NOT NULL,
+        undo_count INTEGER NOT NULL,
+        ranked INTEGER NOT NULL,
+        eligible INTEGER NOT NULL,
+        seeded_recording TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX games_by_player ON games (did);
+    CREATE INDEX games_by_seed ON games (seed, size, score DESC);
+    CREATE TABLE verifications (
+        game_uri TEXT PRIMARY KEY REFERENCES games (uri) ON DELETE CASCADE,
+        verified INTEGER NOT NULL,
+        replayed_score INTEGER,
+        error TEXT,
+        verified_at TEXT NOT NULL
+    );
+    CREATE TABLE stats (
+        did TEXT NOT NULL REFERENCES players (did) ON DELETE CASCADE,
+        size INTEGER NOT NULL,
+        games INTEGER NOT NULL,
+        wins INTEGER NOT NULL,
+        best_score INTEGER NOT NULL,
+        best_game_uri TEXT NOT NULL,
+        total_score INTEGER NOT NULL,
+        updated_at TEXT NOT NULL,
+        PRIMARY KEY (did, size)
+    );
+    CREATE INDEX stats_by_best_score ON stats (size, best_score DESC);
+    CREATE TABLE share_codes (
+        code TEXT PRIMARY KEY,
+        seeded_recording TEXT NOT NULL UNIQUE,
+        created_at TEXT NOT NULL
+    );"];
+
+#[derive(Debug)]
+pub enum StorageError {
+    Sqlite(rusqlite::Error),
+    Migration {
+        version: usize,
+        error: rusqlite::Error,
+    },
+    TooNew {
+        version: usize,
+    },
+    Invalid(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::Sqlite(err) => write!(f, "Database error: {}", err),
+            StorageError::Migration { version, error } => {
+                write!(
+                    f,
+                    "Error migrating the database to version {}: {}",
+                    version, error
+                )
+            }
+            StorageError::TooNew { version } => write!(
+                f,
+                "The database is at version {}, newer than the {} this build knows. Was it opened by a newer server?",
+                version,
+                MIGRATIONS.len()
+            ),
+            StorageError::Invalid(err) => write!(f, "{}", err),
+            StorageError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> Self {
+        StorageError::Sqlite(err)
+    }
+}
+
+/// The repositories, over one transaction. Everything done through them commits together
+pub struct Tx<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Tx<'a> {
+    pub fn players(&self) -> players::Players<'a> {
+        players::Players::new(self.conn)
+    }
+
+    pub fn games(&self) -> games::Games<'a> {
+        games::Games::new(self.conn)
+    }
+
+    pub fn stats(&self) -> stats::Stats<'a> {
+        stats::Stats::new(self.conn)
+    }
+
+    pub fn verifications(&self) -> verifications::Verifications<'a> {
+        verifications::Verifications::new(self.conn)
+    }
+
+    pub fn share_codes(&self) -> share_codes::ShareCodes<'a> {
+        share_codes::ShareCodes::new(self.conn)
+    }
+}
+
+/// How many rows the main tables hold
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RowCounts {
+    pub games: u64,
+    pub players: u64,
+    pub verifications: u64,
+}
+
+/// The SQLite database, migrated to the latest schema when opened
+pub struct Database {
+    // SQLite serialises writers anyway, so one connection is shared. It is only locked off the
+    // async workers, in `transaction`
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Database {
+    /// Opens the file, creating it and its directory when missing. `:memory:` opens an empty
+    /// database in memory instead, see `in_memory`
+    pub fn open(path: &Path) -> Result<Self, StorageError> {
+        if path == Path::new(IN_MEMORY) {
+            return Self::in_memory();
+        }
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                StorageError::Failed(format!("Error creating {}: {}", parent.display(), e))
+            })?;
+        }
+        let conn = Connection::open(path)?;
+        // Lets other processes, like an admin command or the sqlite3 shell, read the file while the
+        // server writes. The server's own reads still wait their turn on its one connection
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::from_connection(conn)
+    }
+
+    /// A fresh database that goes away with it, for tests and trying the server out
+    pub fn in_memory() -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut conn: Connection) -> Result<Self, StorageError> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        migrate(&mut conn)?;
+        Ok(Database {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs `work` in a transaction on a blocking thread, committing when it returns Ok and
+    /// rolling back when it returns an error
+    pub async fn transaction<T, F>(&self, work: F) -> Result<T, StorageError>
+    where
+        F: FnOnce(&Tx) -> Result<T, StorageError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+            let transaction = conn.transaction()?;
+            let result = work(&Tx { conn: &transaction })?;
+            transaction.commit()?;
+            Ok(result)
+        })
+        .await
+        .map_err(|e| StorageError::Failed(format!("Database work panicked: {}", e)))?
+    }
+
+    /// How many migrations the database has had, checking it still answers
+    pub async fn schema_version(&self) -> Result<usize, StorageError> {
+        self.transaction(|tx| user_version(tx.conn)).await
+    }
+
+    /// The sizes of the tables, for the metrics
+    pub async fn row_counts(&self) -> Result<RowCounts, StorageError> {
+        self.transaction(|tx| {
+            Ok(RowCounts {
+                games: tx.games().count()?,
+                players: tx.players().count()?,
+                verifications: tx.verifications().count()?,
+            })
+        })
+        .await
+    }
+
+    /// The schema version this build migrates databases to
+    pub fn latest_version() -> usize {
+        MIGRATIONS.len()
+    }
+}
+
+fn user_version(conn: &Connection) -> Result<usize, StorageError> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    Ok(version as usize)
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), StorageError> {
+    let current = user_version(conn)?;
+    if current > MIGRATIONS.len() {
+        return Err(StorageError::TooNew { version: current });
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+        let version = index + 1;
+        let apply = |transaction: &Transaction| -> Result<(), rusqlite::Error> {
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", version as i64)
+        };
+        let transaction = conn.transaction()?;
+        apply(&transaction).map_err(|error| StorageError::Migration { version, error })?;
+        transaction.commit()?;
+        log::info!("Migrated the database to version {}", version);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn new_databases_are_migrated_to_the_latest_version() {
+        let database = Database::in_memory().unwrap();
+        assert_eq!(
+            database.schema_version().await.unwrap(),
+            Database::latest_version()
+        );
+    }
+
+    #[tokio::test]
+    async fn row_counts_follow_the_tables() {
+        let database = Database::in_memory().unwrap();
+        assert_eq!(database.row_counts().await.unwrap(), RowCounts::default());
+        database
+            .transaction(|tx| {
+                tx.players().upsert(&players::Player {
+                    did: "did:plc:counted".to_string(),
+                    handle: None,
+                    updated_at: chrono::Utc::now(),
+                })
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            database.row_counts().await.unwrap(),
+            RowCounts {
+                players: 1,
+                ..RowCounts::default()
+            }
+        );
+    }
+
+    #[test]
+    fn migrating_again_changes_nothing() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn databases_from_newer_builds_are_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        let version = MIGRATIONS.len() + 1;
+        conn.pragma_update(None, "user_version", version as i64)
+            .unwrap();
+        match Database::from_connection(conn) {
+            Err(StorageError::TooNew { version: found }) => assert_eq!(found, version),
+            Err(err) => panic!("Expected TooNew, got {}", err),
+            Ok(_) => panic!("Expected TooNew, the database opened"),
+        }
+    }
+
+    #[tokio::test]
+    async fn transactions_roll_back_on_an_error() {
+        let database = Database::in_memory().unwrap();
+        let result: Result<(), StorageError> = database
+            .transaction(|tx| {
+                tx.share_codes().create("recording", chrono::Utc::now())?;
+                Err(StorageError::Invalid("Changed my mind".to_string()))
+            })
+            .await;
+        assert!(matches!(result, Err(StorageError::Invalid(_))));
+
+        let count: i64 = database
+            .transaction(|tx| {
+                Ok(tx
+                    .conn
+                    .query_row("SELECT count(*) FROM share_codes", [], |row| row.get(0))?)
+            })
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
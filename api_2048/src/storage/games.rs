@@ -0,0 +1,129 @@
+use super::StorageError;
+use crate::ranked::{self, RankedSeeds};
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, params};
+use twothousand_forty_eight::v2::recording::SeededRecording;
+use types_2048::blue::_2048::game;
+
+/// A game record read from a player's PDS
+#[derive(Clone, Debug)]
+pub struct Game {
+    pub uri: String,
+    pub did: String,
+    pub seed: u32,
+    pub size: usize,
+    /// The score the record claims, its verification says whether the replay agrees
+    pub score: i64,
+    pub won: bool,
+    pub moves: usize,
+    /// Missing for classic games
+    pub mode: Option<String>,
+    pub hints_used: i64,
+    pub undo_count: i64,
+    /// Played on a seed this server dealt the player, in the window it was dealt for
+    pub ranked: bool,
+    /// The first game on its seed, and not held back by the anti-cheat checks or a moderator
+    pub eligible: bool,
+    pub seeded_recording: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Game {
+    /// Ranked when the seed checks out against the keys, a `seedIssuedAt` alone is only the
+    /// record's word for it. Whether it is eligible is up to the caller
+    pub fn from_record(
+        uri: String,
+        did: String,
+        record: &game::RecordData,
+        ranked_seeds: &RankedSeeds,
+        eligible: bool,
+    ) -> Result<Self, StorageError> {
+        let recording: SeededRecording = record.seeded_recording.parse().map_err(|e| {
+            StorageError::Invalid(format!("Invalid seeded_recording on {}: {}", uri, e))
+        })?;
+        let created_at = record.created_at.as_ref().with_timezone(&Utc);
+        let ranked = record.seed_issued_at.as_ref().is_some_and(|issued_at| {
+            recording.width == recording.height
+                && ranked_seeds.is_issued(
+                    &did,
+                    recording.seed,
+                    recording.width,
+                    issued_at.as_ref().with_timezone(&Utc),
+                    ranked::started_at(record),
+                )
+        });
+        Ok(Game {
+            did,
+            seed: recording.seed,
+            size: recording.width,
+            score: record.current_score,
+            won: record.won,
+            moves: recording.moves.len(),
+            mode: record.mode.clone(),
+            hints_used: record.hints_used.unwrap_or(0),
+            undo_count: record.undo_count.unwrap_or(0),
+            ranked,
+            eligible,
+            seeded_recording: record.seeded_recording.clone(),
+            created_at,
+            uri,
+        })
+    }
+}
+
+pub struct Games<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Games<'a> {
+    pub(super) fn new(conn: &'a Connection) -> Self {
+        Games { conn }
+    }
+
+    /// Adds the game or replaces it, the record may have been edited since it was last read
+    pub fn upsert(&self, game: &Game) -> Result<(), StorageError> {
+        self.conn.execute(
+            "INSERT INTO games (uri, did, seed, size, score, won, moves, mode, hints_used, undo_count,
+                ranked, eligible, seeded_recording, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+             ON CONFLICT (uri) DO UPDATE SET
+                seed = excluded.seed,
+                size = excluded.size,
+                score = excluded.score,
+                won = excluded.won,
+                moves = excluded.moves,
+                mode = excluded.mode,
+                hints_used = excluded.hints_used,
+                undo_count = excluded.undo_count,
+                ranked = excluded.ranked,
+                eligible = excluded.eligible,
+                seeded_recording = excluded.seeded_recording,
+                created_at = excluded.created_at",
+            params![
+                game.uri,
+                game.did,
+                game.seed,
+                game.size as i64,
+                game.score,
+                game.won,
+                game.moves as i64,
+                game.mode,
+                game.hints_used,
+                game.undo_count,
+                game.ranked,
+                game.eligible,
+                game.seeded_recording,
+                game.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// How many there are, for the metrics
+    pub fn count(&self) -> Result<u64, StorageError> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT count(*) FROM games", [], |row| row.get(0))?;
+        Ok(count as u64)
+    }
+}
@@ -0,0 +1,42 @@
+use super::StorageError;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, params};
+
+/// Someone with games in the database
+#[derive(Clone, Debug)]
+pub struct Player {
+    pub did: String,
+    /// The handle they were last looked up by, if it was by handle
+    pub handle: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct Players<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Players<'a> {
+    pub(super) fn new(conn: &'a Connection) -> Self {
+        Players { conn }
+    }
+
+    /// Adds the player or updates them, keeping the handle already known when there is no new one
+    pub fn upsert(&self, player: &Player) -> Result<(), StorageError> {
+        self.conn.execute(
+            "INSERT INTO players (did, handle, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (did) DO UPDATE SET
+                handle = coalesce(excluded.handle, players.handle),
+                updated_at = excluded.updated_at",
+            params![player.did, player.handle, player.updated_at],
+        )?;
+        Ok(())
+    }
+
+    /// How many there are, for the metrics
+    pub fn count(&self) -> Result<u64, StorageError> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT count(*) FROM players", [], |row| row.get(0))?;
+        Ok(count as u64)
+    }
+}
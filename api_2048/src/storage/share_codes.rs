@@ -0,0 +1,103 @@
+use super::StorageError;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use rusqlite::{Connection, OptionalExtension, params};
+
+const CODE_LENGTH: usize = 8;
+const CODE_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+// 62^8 codes make a clash unlikely, running into this many in a row means something is wrong
+const MAX_ATTEMPTS: usize = 5;
+
+/// Short codes standing in for recordings in share links
+pub struct ShareCodes<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> ShareCodes<'a> {
+    pub(super) fn new(conn: &'a Connection) -> Self {
+        ShareCodes { conn }
+    }
+
+    /// The recording's code, made the first time it is shared
+    pub fn create(
+        &self,
+        seeded_recording: &str,
+        now: DateTime<Utc>,
+    ) -> Result<String, StorageError> {
+        let existing = self
+            .conn
+            .query_row(
+                "SELECT code FROM share_codes WHERE seeded_recording = ?1",
+                params![seeded_recording],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(code) = existing {
+            return Ok(code);
+        }
+        let mut rng = rand::thread_rng();
+        for _ in 0..MAX_ATTEMPTS {
+            let code: String = (0..CODE_LENGTH)
+                .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+                .collect();
+            let inserted = self.conn.execute(
+                "INSERT OR IGNORE INTO share_codes (code, seeded_recording, created_at) VALUES (?1, ?2, ?3)",
+                params![code, seeded_recording, now],
+            )?;
+            if inserted == 1 {
+                return Ok(code);
+            }
+        }
+        Err(StorageError::Failed(
+            "Could not find a free share code".to_string(),
+        ))
+    }
+
+    pub fn resolve(&self, code: &str) -> Result<Option<String>, StorageError> {
+        let recording = self
+            .conn
+            .query_row(
+                "SELECT seeded_recording FROM share_codes WHERE code = ?1",
+                params![code],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(recording)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Database;
+
+    #[tokio::test]
+    async fn a_recording_keeps_its_code() {
+        let database = Database::in_memory().unwrap();
+        let (first, again, other) = database
+            .transaction(|tx| {
+                let now = chrono::Utc::now();
+                let codes = tx.share_codes();
+                Ok((
+                    codes.create("first", now)?,
+                    codes.create("first", now)?,
+                    codes.create("other", now)?,
+                ))
+            })
+            .await
+            .unwrap();
+        assert_eq!(first, again);
+        assert_ne!(first, other);
+        assert_eq!(first.len(), CODE_LENGTH);
+
+        let (resolved, missing) = database
+            .transaction(move |tx| {
+                let codes = tx.share_codes();
+                Ok((codes.resolve(&first)?, codes.resolve("nothing")?))
+            })
+            .await
+            .unwrap();
+        assert_eq!(resolved.as_deref(), Some("first"));
+        assert_eq!(missing, None);
+    }
+}
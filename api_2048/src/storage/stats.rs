@@ -0,0 +1,283 @@
+use super::StorageError;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, params};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// The games that count: verified ranked classic games without hints or undos, that were the
+// first on their seed and were not held back by the anti-cheat checks or a moderator
+const COUNTED: &str = "verifications.verified AND games.ranked AND games.eligible
+    AND coalesce(games.mode, 'classic') = 'classic' AND games.hints_used = 0 AND games.undo_count = 0";
+
+/// A player's totals over the games that count for leaderboards on one board size
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct PlayerStats {
+    pub did: String,
+    pub handle: Option<String>,
+    /// Width and height of the board
+    pub size: usize,
+    pub games: i64,
+    pub wins: i64,
+    pub best_score: i64,
+    pub best_game_uri: String,
+    pub total_score: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct Stats<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Stats<'a> {
+    pub(super) fn new(conn: &'a Connection) -> Self {
+        Stats { conn }
+    }
+
+    /// Totals the player's games again, per board size. Games never checked, that failed to
+    /// verify or that are not ranked are left out, and so are sizes left with no games
+    pub fn refresh(&self, did: &str, now: DateTime<Utc>) -> Result<(), StorageError> {
+        self.conn
+            .execute("DELETE FROM stats WHERE did = ?1", params![did])?;
+        // SQLite takes the bare uri from the row max(score) picked
+        self.conn.execute(
+            &format!(
+                "INSERT INTO stats (did, size, games, wins, best_score, best_game_uri, total_score, updated_at)
+                 SELECT ?1, games.size, count(*), sum(games.won), max(games.score), games.uri,
+                    sum(games.score), ?2
+                 FROM games JOIN verifications ON verifications.game_uri = games.uri
+                 WHERE games.did = ?1 AND {}
+                 GROUP BY games.size",
+                COUNTED
+            ),
+            params![did, now],
+        )?;
+        Ok(())
+    }
+
+    /// Totals every player's games again, like `refresh` for each of them. Gives back how many
+    /// players there were
+    pub fn refresh_all(&self, now: DateTime<Utc>) -> Result<usize, StorageError> {
+        let dids = self
+            .conn
+            .prepare("SELECT did FROM players ORDER BY did")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for did in &dids {
+            self.refresh(did, now)?;
+        }
+        Ok(dids.len())
+    }
+
+    /// The players with the best scores on boards of this size, best first
+    pub fn leaderboard(&self, size: usize, limit: usize) -> Result<Vec<PlayerStats>, StorageError> {
+        let mut statement = self.conn.prepare(
+            "SELECT stats.did, players.handle, stats.size, stats.games, stats.wins, stats.best_score,
+                stats.best_game_uri, stats.total_score, stats.updated_at
+             FROM stats JOIN players ON players.did = stats.did
+             WHERE stats.size = ?1
+             ORDER BY stats.best_score DESC, stats.did
+             LIMIT ?2",
+        )?;
+        let rows = statement.query_map(params![size as i64, limit as i64], |row| {
+            Ok(PlayerStats {
+                did: row.get(0)?,
+                handle: row.get(1)?,
+                size: row.get::<_, i64>(2)? as usize,
+                games: row.get(3)?,
+                wins: row.get(4)?,
+                best_score: row.get(5)?,
+                best_game_uri: row.get(6)?,
+                total_score: row.get(7)?,
+                updated_at: row.get(8)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::games::Game;
+    use crate::storage::players::Player;
+    use crate::storage::verifications::Verification;
+    use crate::storage::{Database, StorageError, Tx};
+    use chrono::Utc;
+
+    // A verified ranked classic game that counts, for the tests to take things away from
+    fn game(did: &str, name: &str, size: usize, score: i64) -> Game {
+        Game {
+            uri: format!("at://{}/blue.2048.game/{}", did, name),
+            did: did.to_string(),
+            seed: 1,
+            size,
+            score,
+            won: score >= 2000,
+            moves: 100,
+            mode: None,
+            hints_used: 0,
+            undo_count: 0,
+            ranked: true,
+            eligible: true,
+            seeded_recording: String::new(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn store(tx: &Tx, game: &Game, verified: bool) -> Result<(), StorageError> {
+        tx.players().upsert(&Player {
+            did: game.did.clone(),
+            handle: None,
+            updated_at: Utc::now(),
+        })?;
+        tx.games().upsert(game)?;
+        tx.verifications().record(&Verification {
+            game_uri: game.uri.clone(),
+            verified,
+            replayed_score: Some(game.score),
+            error: None,
+            verified_at: Utc::now(),
+        })
+    }
+
+    async fn seeded_database() -> Database {
+        let database = Database::in_memory().unwrap();
+        database
+            .transaction(|tx| {
+                store(tx, &game("did:plc:alice", "a", 4, 1000), true)?;
+                store(tx, &game("did:plc:alice", "b", 4, 3000), true)?;
+                store(tx, &game("did:plc:alice", "c", 5, 500), true)?;
+                store(tx, &game("did:plc:alice", "unverified", 4, 9000), false)?;
+                let mut unranked = game("did:plc:alice", "unranked", 4, 8000);
+                unranked.ranked = false;
+                store(tx, &unranked, true)?;
+                let mut ineligible = game("did:plc:alice", "ineligible", 4, 7000);
+                ineligible.eligible = false;
+                store(tx, &ineligible, true)?;
+                let mut hinted = game("did:plc:alice", "hinted", 4, 6000);
+                hinted.hints_used = 1;
+                store(tx, &hinted, true)?;
+                let mut undone = game("did:plc:alice", "undone", 4, 5000);
+                undone.undo_count = 2;
+                store(tx, &undone, true)?;
+                let mut timed = game("did:plc:alice", "timed", 4, 4500);
+                timed.mode = Some("timeAttack".to_string());
+                store(tx, &timed, true)?;
+                let mut classic = game("did:plc:bob", "a", 4, 2000);
+                classic.mode = Some("classic".to_string());
+                store(tx, &classic, true)?;
+                let mut unranked = game("did:plc:carol", "a", 4, 100_000);
+                unranked.ranked = false;
+                store(tx, &unranked, true)?;
+                for did in ["did:plc:alice", "did:plc:bob", "did:plc:carol"] {
+                    tx.stats().refresh(did, Utc::now())?;
+                }
+                Ok(())
+            })
+            .await
+            .unwrap();
+        database
+    }
+
+    #[tokio::test]
+    async fn only_games_that_count_make_the_leaderboard() {
+        let database = seeded_database().await;
+        let leaderboard = database
+            .transaction(|tx| tx.stats().leaderboard(4, 10))
+            .await
+            .unwrap();
+        let players: Vec<(&str, i64)> = leaderboard
+            .iter()
+            .map(|stats| (stats.did.as_str(), stats.best_score))
+            .collect();
+        assert_eq!(players, [("did:plc:alice", 3000), ("did:plc:bob", 2000)]);
+
+        let alice = &leaderboard[0];
+        assert_eq!(alice.size, 4);
+        assert_eq!(alice.games, 2);
+        assert_eq!(alice.wins, 1);
+        assert_eq!(alice.total_score, 4000);
+        assert_eq!(alice.best_game_uri, "at://did:plc:alice/blue.2048.game/b");
+    }
+
+    #[tokio::test]
+    async fn leaderboards_are_by_board_size() {
+        let database = seeded_database().await;
+        let (fives, sixes, top) = database
+            .transaction(|tx| {
+                let stats = tx.stats();
+                Ok((
+                    stats.leaderboard(5, 10)?,
+                    stats.leaderboard(6, 10)?,
+                    stats.leaderboard(4, 1)?,
+                ))
+            })
+            .await
+            .unwrap();
+        assert_eq!(fives.len(), 1);
+        assert_eq!(fives[0].did, "did:plc:alice");
+        assert_eq!(fives[0].best_score, 500);
+        assert!(sixes.is_empty());
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].did, "did:plc:alice");
+    }
+
+    #[tokio::test]
+    async fn refreshing_drops_games_that_stopped_counting() {
+        let database = seeded_database().await;
+        let leaderboard = database
+            .transaction(|tx| {
+                let mut best = game("did:plc:alice", "b", 4, 3000);
+                best.eligible = false;
+                tx.games().upsert(&best)?;
+                let mut small = game("did:plc:alice", "c", 5, 500);
+                small.ranked = false;
+                tx.games().upsert(&small)?;
+                tx.stats().refresh("did:plc:alice", Utc::now())?;
+                Ok((
+                    tx.stats().leaderboard(4, 10)?,
+                    tx.stats().leaderboard(5, 10)?,
+                ))
+            })
+            .await
+            .unwrap();
+        let (fours, fives) = leaderboard;
+        assert_eq!(fours[0].did, "did:plc:bob");
+        assert_eq!(fours[1].did, "did:plc:alice");
+        assert_eq!(fours[1].best_score, 1000);
+        assert_eq!(fours[1].games, 1);
+        assert!(fives.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refreshing_everyone_picks_up_every_players_changes() {
+        let database = seeded_database().await;
+        let (refreshed, leaderboard) = database
+            .transaction(|tx| {
+                let mut best = game("did:plc:alice", "b", 4, 3000);
+                best.eligible = false;
+                tx.games().upsert(&best)?;
+                let mut unranked = game("did:plc:carol", "a", 4, 100_000);
+                unranked.ranked = true;
+                tx.games().upsert(&unranked)?;
+                Ok((
+                    tx.stats().refresh_all(Utc::now())?,
+                    tx.stats().leaderboard(4, 10)?,
+                ))
+            })
+            .await
+            .unwrap();
+        assert_eq!(refreshed, 3);
+        let players: Vec<(&str, i64)> = leaderboard
+            .iter()
+            .map(|stats| (stats.did.as_str(), stats.best_score))
+            .collect();
+        assert_eq!(
+            players,
+            [
+                ("did:plc:carol", 100_000),
+                ("did:plc:bob", 2000),
+                ("did:plc:alice", 1000)
+            ]
+        );
+    }
+}
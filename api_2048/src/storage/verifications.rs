@@ -0,0 +1,71 @@
+use super::StorageError;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Whether a game's recording replays to the score it claims
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct Verification {
+    pub game_uri: String,
+    pub verified: bool,
+    /// The score the replay ended on, missing when it could not be replayed
+    pub replayed_score: Option<i64>,
+    /// Why it did not verify
+    pub error: Option<String>,
+    pub verified_at: DateTime<Utc>,
+}
+
+pub struct Verifications<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Verifications<'a> {
+    pub(super) fn new(conn: &'a Connection) -> Self {
+        Verifications { conn }
+    }
+
+    /// Keeps the newest result for the game, which has to be stored already
+    pub fn record(&self, verification: &Verification) -> Result<(), StorageError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO verifications (game_uri, verified, replayed_score, error, verified_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                verification.game_uri,
+                verification.verified,
+                verification.replayed_score,
+                verification.error,
+                verification.verified_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get(&self, game_uri: &str) -> Result<Option<Verification>, StorageError> {
+        let verification = self
+            .conn
+            .query_row(
+                "SELECT game_uri, verified, replayed_score, error, verified_at FROM verifications WHERE game_uri = ?1",
+                params![game_uri],
+                |row| {
+                    Ok(Verification {
+                        game_uri: row.get(0)?,
+                        verified: row.get(1)?,
+                        replayed_score: row.get(2)?,
+                        error: row.get(3)?,
+                        verified_at: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(verification)
+    }
+
+    /// How many there are, for the metrics
+    pub fn count(&self) -> Result<u64, StorageError> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT count(*) FROM verifications", [], |row| row.get(0))?;
+        Ok(count as u64)
+    }
+}
@@ -0,0 +1,350 @@
+use crate::tournaments::{
+    PublicTournament, RoundFormat, Tournament, TournamentDefinition, TournamentError,
+    TournamentResults,
+};
+use crate::{ApiContext, require_bearer_token};
+use atrium_api::types::Collection;
+use dropshot::{
+    Body as DropshotBody, ClientErrorStatusCode, HttpError, HttpResponseCreated, HttpResponseOk,
+    Path, RequestContext, TypedBody,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use types_2048::blue;
+use types_2048::record::KnownRecord;
+
+impl From<TournamentError> for HttpError {
+    fn from(err: TournamentError) -> Self {
+        match err {
+            TournamentError::Invalid(_) => HttpError::for_bad_request(None, err.to_string()),
+            TournamentError::NotFound(_) => HttpError::for_not_found(None, err.to_string()),
+            TournamentError::Conflict(_) => {
+                HttpError::for_client_error(None, ClientErrorStatusCode::CONFLICT, err.to_string())
+            }
+            TournamentError::Storage(_) => HttpError::for_internal_error(err.to_string()),
+            TournamentError::Atproto(_) => HttpError::for_unavail(None, err.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct TournamentPath {
+    pub id: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct PublishedTournament {
+    pub uri: String,
+    pub cid: String,
+}
+
+// Changing tournaments needs the organiser token as a bearer token
+fn require_organiser(rqctx: &RequestContext<ApiContext>) -> Result<(), HttpError> {
+    require_bearer_token(
+        rqctx,
+        rqctx.context().config.tokens.organiser.as_deref(),
+        "Tournaments can not be organised on this server",
+    )
+}
+
+/// Every tournament hosted on this server, without the seeds of rounds that have not opened
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/tournaments",
+}]
+pub async fn list_tournaments(
+    rqctx: RequestContext<ApiContext>,
+) -> Result<HttpResponseOk<Vec<PublicTournament>>, HttpError> {
+    let now = chrono::Utc::now();
+    let tournaments = rqctx.context().tournaments.list().await;
+    Ok(HttpResponseOk(
+        tournaments
+            .iter()
+            .map(|tournament| tournament.public(now))
+            .collect(),
+    ))
+}
+
+/// Create a tournament. Participants can be handles, they are stored as DIDs
+#[dropshot::endpoint {
+    method = POST,
+    path = "/api/tournaments",
+}]
+pub async fn create_tournament(
+    rqctx: RequestContext<ApiContext>,
+    body: TypedBody<TournamentDefinition>,
+) -> Result<HttpResponseCreated<Tournament>, HttpError> {
+    require_organiser(&rqctx)?;
+    let api_context = rqctx.context();
+    let tournament =
+        Tournament::from_definition(&api_context.atproto, body.into_inner(), chrono::Utc::now())
+            .await?;
+    api_context.tournaments.insert(tournament.clone()).await?;
+    Ok(HttpResponseCreated(tournament))
+}
+
+/// Change a tournament's rounds or participants. The id in the path wins over the body's
+#[dropshot::endpoint {
+    method = PUT,
+    path = "/api/tournaments/{id}",
+}]
+pub async fn update_tournament(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<TournamentPath>,
+    body: TypedBody<TournamentDefinition>,
+) -> Result<HttpResponseOk<Tournament>, HttpError> {
+    require_organiser(&rqctx)?;
+    let api_context = rqctx.context();
+    let existing = api_context.tournaments.get(&path.into_inner().id).await?;
+    let mut definition = body.into_inner();
+    definition.id = Some(existing.id.clone());
+    let mut tournament =
+        Tournament::from_definition(&api_context.atproto, definition, existing.created_at).await?;
+    tournament.published = existing.published;
+    api_context.tournaments.update(tournament.clone()).await?;
+    Ok(HttpResponseOk(tournament))
+}
+
+/// Standings, who advanced and the bracket, from the participants' games on each round's seed
+#[dropshot::endpoint {
+    method = GET,
+    path = "/api/tournaments/{id}",
+}]
+pub async fn get_tournament_results(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<TournamentPath>,
+) -> Result<HttpResponseOk<TournamentResults>, HttpError> {
+    let api_context = rqctx.context();
+    let results = api_context
+        .tournaments
+        .results(
+            &api_context.atproto,
+            &api_context.anticheat,
+            &api_context.moderation,
+            &path.into_inner().id,
+        )
+        .await?;
+    Ok(HttpResponseOk(results))
+}
+
+/// Write the current results to the authority repo as a blue.2048.tournament record keyed by
+/// the tournament's id. Publishing again replaces the record
+#[dropshot::endpoint {
+    method = POST,
+    path = "/api/tournaments/{id}/publish",
+}]
+pub async fn publish_tournament_results(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<TournamentPath>,
+) -> Result<HttpResponseOk<PublishedTournament>, HttpError> {
+    require_organiser(&rqctx)?;
+    let api_context = rqctx.context();
+    let Some(authority) = &api_context.authority else {
+        return Err(HttpError::for_unavail(
+            None,
+            "AUTHORITY_IDENTIFIER and AUTHORITY_APP_PASSWORD are not set".to_string(),
+        ));
+    };
+    let id = path.into_inner().id;
+    let results = api_context
+        .tournaments
+        .results(
+            &api_context.atproto,
+            &api_context.anticheat,
+            &api_context.moderation,
+            &id,
+        )
+        .await?;
+    // Results missing someone's games would put the wrong players through on the record
+    if !results.unreachable.is_empty() {
+        return Err(HttpError::for_unavail(
+            None,
+            format!(
+                "Could not read the games of {}",
+                results.unreachable.join(", ")
+            ),
+        ));
+    }
+
+    let record = KnownRecord::from(results.to_record()?);
+    let output = api_context
+        .atproto
+        .put_authority_record(authority, blue::_2048::Tournament::NSID, &id, &record)
+        .await
+        .map_err(TournamentError::from)?;
+
+    let mut tournament = api_context.tournaments.get(&id).await?;
+    tournament.published = Some(output.uri.clone());
+    api_context.tournaments.update(tournament).await?;
+    Ok(HttpResponseOk(PublishedTournament {
+        uri: output.uri,
+        cid: output.cid,
+    }))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn player_link(did: &str) -> String {
+    format!(
+        r#"<a href="https://bsky.app/profile/{0}">{0}</a>"#,
+        escape_html(did)
+    )
+}
+
+fn render_results(results: &TournamentResults, app_url: &str) -> String {
+    let tournament = &results.tournament;
+    let mut body = format!("<h1>{}</h1>", escape_html(&tournament.name));
+    if let Some(description) = &tournament.description {
+        body.push_str(&format!("<p>{}</p>", escape_html(description)));
+    }
+    if let Some(champion) = &results.champion {
+        body.push_str(&format!(
+            "<p><strong>Champion:</strong> {}</p>",
+            player_link(champion)
+        ));
+    }
+    if !results.unreachable.is_empty() {
+        body.push_str(&format!(
+            "<p>Could not read the games of {} right now, their scores may be missing.</p>",
+            results
+                .unreachable
+                .iter()
+                .map(|did| escape_html(did))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    for round in &results.rounds {
+        let status = match (round.closed, round.decided) {
+            (true, _) => "Closed",
+            (false, true) => "Decided",
+            (false, false) => "Open",
+        };
+        let seed = match round.seed {
+            Some(seed) => format!(
+                r#"Seed {} on a {}x{} board · Deadline {} · <a href="{}/seed/{}?size={}">Play the seed</a>"#,
+                seed,
+                round.size,
+                round.size,
+                round.deadline.format("%Y-%m-%d %H:%M UTC"),
+                app_url,
+                seed,
+                round.size
+            ),
+            None => format!(
+                "The seed is shown when the round opens, on a {}x{} board · Deadline {}",
+                round.size,
+                round.size,
+                round.deadline.format("%Y-%m-%d %H:%M UTC")
+            ),
+        };
+        body.push_str(&format!(
+            "<section><h2>{}</h2><p>{} · {}</p>",
+            escape_html(&round.name),
+            status,
+            seed
+        ));
+        if round.standings.is_empty() {
+            // Decided with nobody in it means no one made it through the round before
+            body.push_str(if round.decided {
+                "<p>Nobody made it to this round.</p></section>"
+            } else {
+                "<p>Waiting on the round before.</p></section>"
+            });
+            continue;
+        }
+        if round.format == RoundFormat::Bracket {
+            body.push_str("<ul>");
+            for bracket_match in &round.matches {
+                let players = bracket_match
+                    .players
+                    .iter()
+                    .map(|player| {
+                        let score = round
+                            .standings
+                            .iter()
+                            .find(|standing| &standing.player == player)
+                            .and_then(|standing| standing.score)
+                            .map_or("-".to_string(), |score| score.to_string());
+                        if bracket_match.winner.as_ref() == Some(player) {
+                            format!("<strong>{} ({})</strong>", player_link(player), score)
+                        } else {
+                            format!("{} ({})", player_link(player), score)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                match players.as_slice() {
+                    [bye] => body.push_str(&format!("<li>{} has a bye</li>", bye)),
+                    _ => body.push_str(&format!("<li>{}</li>", players.join(" vs "))),
+                }
+            }
+            body.push_str("</ul>");
+        }
+        body.push_str("<table><tr><th>#</th><th>Player</th><th>Score</th><th></th></tr>");
+        for (place, standing) in round.standings.iter().enumerate() {
+            body.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                place + 1,
+                player_link(&standing.player),
+                standing
+                    .score
+                    .map_or("-".to_string(), |score| score.to_string()),
+                if standing.advanced { "Advanced" } else { "" }
+            ));
+        }
+        body.push_str("</table></section>");
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta charset="utf-8">
+            <title>{} - at://2048 tournament</title>
+            <meta property="og:title" content="{}">
+            <meta property="og:description" content="A 2048 tournament played on fixed seeds">
+        </head>
+        <body>
+            {}
+            <p><a href="/api/tournaments/{}">JSON</a></p>
+        </body>
+        </html>"#,
+        escape_html(&tournament.name),
+        escape_html(&tournament.name),
+        body,
+        urlencoding::encode(&tournament.id)
+    )
+}
+
+#[dropshot::endpoint {
+    method = GET,
+    path = "/tournaments/{id}",
+}]
+pub async fn serve_tournament_page(
+    rqctx: RequestContext<ApiContext>,
+    path: Path<TournamentPath>,
+) -> Result<http::Response<DropshotBody>, HttpError> {
+    let api_context = rqctx.context();
+    let results = api_context
+        .tournaments
+        .results(
+            &api_context.atproto,
+            &api_context.anticheat,
+            &api_context.moderation,
+            &path.into_inner().id,
+        )
+        .await?;
+    let html_content = render_results(&results, &api_context.config.site.app_url);
+    http::Response::builder()
+        .status(http::StatusCode::OK)
+        .header(http::header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(DropshotBody::from(html_content))
+        .map_err(|e| HttpError::for_internal_error(format!("Failed to build response: {}", e)))
+}
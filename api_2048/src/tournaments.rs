@@ -0,0 +1,1021 @@
+use crate::anticheat::{AnticheatStore, PlayerHistory};
+use crate::atproto::{AtprotoClient, AtprotoError};
+use crate::moderation::ModerationStore;
+use atrium_api::types::Collection;
+use atrium_api::types::string::{Datetime, Did};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinSet;
+use twothousand_forty_eight::{unified::game::GameState, v2::recording::SeededRecording};
+use types_2048::blue;
+use types_2048::blue::_2048::{game, tournament};
+
+// Rounds without a size are played on a classic 4x4 board
+const DEFAULT_BOARD_SIZE: usize = 4;
+const MIN_BOARD_SIZE: usize = 3;
+const MAX_BOARD_SIZE: usize = twothousand_forty_eight::board::MAX_WIDTH;
+// Standings pull every player's games from their PDS, so they are reused for a bit
+const RESULTS_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum TournamentError {
+    Invalid(String),
+    NotFound(String),
+    Conflict(String),
+    Storage(String),
+    Atproto(AtprotoError),
+}
+
+impl std::fmt::Display for TournamentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TournamentError::Invalid(err) => write!(f, "Invalid tournament: {}", err),
+            TournamentError::NotFound(id) => write!(f, "No tournament called {}", id),
+            TournamentError::Conflict(id) => write!(f, "A tournament called {} already exists", id),
+            TournamentError::Storage(err) => write!(f, "Could not save the tournaments: {}", err),
+            TournamentError::Atproto(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<AtprotoError> for TournamentError {
+    fn from(err: AtprotoError) -> Self {
+        TournamentError::Atproto(err)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundFormat {
+    /// The top scores on the seed move on
+    Standings,
+    /// Players are paired off by seeding and the higher score of each pair moves on
+    Bracket,
+}
+
+impl RoundFormat {
+    pub fn id(&self) -> &'static str {
+        match self {
+            RoundFormat::Standings => "standings",
+            RoundFormat::Bracket => "bracket",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RoundDefinition {
+    pub name: String,
+    pub seed: u32,
+    /// Width and height of the board, 4 when missing
+    pub size: Option<usize>,
+    /// Games created after this do not count. A round opens when the one before it closes
+    pub deadline: DateTime<Utc>,
+    pub format: RoundFormat,
+    /// How many players a standings round sends on. Everyone who played when missing, or just
+    /// the winner in the final round
+    pub advance: Option<usize>,
+}
+
+impl RoundDefinition {
+    pub fn board_size(&self) -> usize {
+        self.size.unwrap_or(DEFAULT_BOARD_SIZE)
+    }
+}
+
+/// What an organiser sends to create or change a tournament
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct TournamentDefinition {
+    /// Used in the tournament's urls and as the record key of its published results. Made from
+    /// the name when missing
+    pub id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    /// Handles or DIDs, in seeding order
+    pub participants: Vec<String>,
+    pub rounds: Vec<RoundDefinition>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Tournament {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// DIDs, in seeding order
+    pub participants: Vec<String>,
+    pub rounds: Vec<RoundDefinition>,
+    pub created_at: DateTime<Utc>,
+    /// The at://uri of the results last published to the authority repo
+    pub published: Option<String>,
+}
+
+/// Lowercase letters, numbers and dashes, which is also a valid record key
+fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+impl Tournament {
+    /// Checks the definition and resolves the participants' handles to DIDs
+    pub async fn from_definition(
+        client: &AtprotoClient,
+        definition: TournamentDefinition,
+        created_at: DateTime<Utc>,
+    ) -> Result<Self, TournamentError> {
+        let id = slugify(definition.id.as_deref().unwrap_or(&definition.name));
+        if id.is_empty() {
+            return Err(TournamentError::Invalid(
+                "the id needs a letter or number".to_string(),
+            ));
+        }
+        if definition.rounds.is_empty() {
+            return Err(TournamentError::Invalid(
+                "it needs at least one round".to_string(),
+            ));
+        }
+        for round in &definition.rounds {
+            if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&round.board_size()) {
+                return Err(TournamentError::Invalid(format!(
+                    "{} is played on a board size that does not exist",
+                    round.name
+                )));
+            }
+        }
+        if definition
+            .rounds
+            .windows(2)
+            .any(|pair| pair[0].deadline >= pair[1].deadline)
+        {
+            return Err(TournamentError::Invalid(
+                "each round has to end after the one before it".to_string(),
+            ));
+        }
+
+        let mut participants: Vec<String> = vec![];
+        for participant in &definition.participants {
+            let did = client
+                .resolve_player(participant)
+                .await?
+                .as_str()
+                .to_string();
+            if !participants.contains(&did) {
+                participants.push(did);
+            }
+        }
+        if participants.is_empty() {
+            return Err(TournamentError::Invalid(
+                "it needs at least one participant".to_string(),
+            ));
+        }
+
+        Ok(Tournament {
+            id,
+            name: definition.name,
+            description: definition.description,
+            participants,
+            rounds: definition.rounds,
+            created_at,
+            published: None,
+        })
+    }
+
+    /// When a round opens, which is when the round before it closes. The first round opens as
+    /// the tournament is created
+    pub fn opens_at(&self, index: usize) -> DateTime<Utc> {
+        match index.checked_sub(1) {
+            Some(previous) => self.rounds[previous].deadline,
+            None => self.created_at,
+        }
+    }
+
+    /// The tournament as anyone can see it, without the seeds of rounds that have not opened
+    pub fn public(&self, now: DateTime<Utc>) -> PublicTournament {
+        PublicTournament {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            participants: self.participants.clone(),
+            rounds: self
+                .rounds
+                .iter()
+                .enumerate()
+                .map(|(index, round)| PublicRound {
+                    name: round.name.clone(),
+                    seed: (now >= self.opens_at(index)).then_some(round.seed),
+                    size: round.board_size(),
+                    deadline: round.deadline,
+                    format: round.format,
+                    advance: round.advance,
+                })
+                .collect(),
+            created_at: self.created_at,
+            published: self.published.clone(),
+        }
+    }
+}
+
+/// A round as it is shown to players
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct PublicRound {
+    pub name: String,
+    /// Missing till the round opens, so nobody can practise it ahead of time
+    pub seed: Option<u32>,
+    pub size: usize,
+    pub deadline: DateTime<Utc>,
+    pub format: RoundFormat,
+    pub advance: Option<usize>,
+}
+
+/// A tournament as it is shown to players
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct PublicTournament {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// DIDs, in seeding order
+    pub participants: Vec<String>,
+    pub rounds: Vec<PublicRound>,
+    pub created_at: DateTime<Utc>,
+    /// The at://uri of the results last published to the authority repo
+    pub published: Option<String>,
+}
+
+/// A player's place in a round
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct Standing {
+    pub player: String,
+    /// Missing when the player has not played the seed in the round's window
+    pub score: Option<i64>,
+    /// The at://uri of the blue.2048.game that counted
+    pub game: Option<String>,
+    pub played_at: Option<DateTime<Utc>>,
+    /// Made it to the next round, or won the final round
+    pub advanced: bool,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct BracketMatch {
+    /// One player when it was a bye
+    pub players: Vec<String>,
+    pub winner: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct RoundResult {
+    pub name: String,
+    /// Missing till the round opens
+    pub seed: Option<u32>,
+    pub size: usize,
+    pub deadline: DateTime<Utc>,
+    pub format: RoundFormat,
+    /// Past the deadline
+    pub closed: bool,
+    /// Everyone who moves on is known. Later rounds have no players till this is true
+    pub decided: bool,
+    /// Best first, players who have not played yet last
+    pub standings: Vec<Standing>,
+    pub matches: Vec<BracketMatch>,
+}
+
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct TournamentResults {
+    pub tournament: PublicTournament,
+    pub rounds: Vec<RoundResult>,
+    pub champion: Option<String>,
+    /// Players whose PDS could not be read, their games are missing from the standings
+    pub unreachable: Vec<String>,
+    pub computed_at: DateTime<Utc>,
+}
+
+/// A game that replays to the score it claims
+#[derive(Clone, Debug)]
+struct TournamentGame {
+    uri: String,
+    seed: u32,
+    size: usize,
+    score: i64,
+    created_at: DateTime<Utc>,
+}
+
+fn verify_game(
+    uri: String,
+    game: &game::RecordData,
+    seeds: &HashSet<(u32, usize)>,
+) -> Option<TournamentGame> {
+    // Timed, move limited and assisted games are not played under tournament rules
+    let classic = game.mode.as_deref().is_none_or(|mode| mode == "classic");
+    if !classic
+        || !game.completed
+        || game.hints_used.unwrap_or(0) > 0
+        || game.undo_count.unwrap_or(0) > 0
+    {
+        return None;
+    }
+    let recording: SeededRecording = game.seeded_recording.parse().ok()?;
+    if recording.width != recording.height || !seeds.contains(&(recording.seed, recording.width)) {
+        return None;
+    }
+    // A record claiming a score its moves do not add up to was edited after the fact
+    let gamestate = GameState::from_reconstructable_ruleset(&recording).ok()?;
+    if gamestate.score_current as i64 != game.current_score {
+        return None;
+    }
+    Some(TournamentGame {
+        uri,
+        seed: recording.seed,
+        size: recording.width,
+        score: game.current_score,
+        created_at: game.created_at.as_ref().with_timezone(&Utc),
+    })
+}
+
+async fn fetch_player_games(
+    client: &AtprotoClient,
+    anticheat: &AnticheatStore,
+    moderation: &ModerationStore,
+    player: &str,
+    seeds: &HashSet<(u32, usize)>,
+) -> Result<Vec<TournamentGame>, AtprotoError> {
+    let did: Did = player
+        .parse()
+        .map_err(|e: &str| AtprotoError::Resolve(e.to_string()))?;
+    // Banned and hidden players stay in the bracket with no games, rather than vanishing from it
+    let exclusions = moderation.exclusions(player).await;
+    if exclusions.excludes_player() {
+        return Ok(vec![]);
+    }
+    // Read loosely so one odd record does not hide the rest of the player's games
+    let records = client
+        .list_records::<serde_json::Value>(&did, blue::_2048::Game::NSID)
+        .await?;
+    let games: Vec<(String, game::RecordData)> = records
+        .into_iter()
+        .filter_map(|(uri, value)| Some((uri, serde_json::from_value(value).ok()?)))
+        .collect();
+    let verified: Vec<(TournamentGame, &game::RecordData)> = games
+        .iter()
+        .filter(|(uri, _)| !exclusions.excludes(uri))
+        .filter_map(|(uri, game)| Some((verify_game(uri.clone(), game, seeds)?, game)))
+        .collect();
+
+    // Games that look played by a bot, or that a moderator found cheating, do not count
+    let history = PlayerHistory::new(games.iter().map(|(_, game)| game))
+        .with_dealt_seeds(seeds.iter().copied());
+    let candidates: Vec<(String, game::RecordData)> = verified
+        .iter()
+        .map(|(verified, game)| (verified.uri.clone(), (*game).clone()))
+        .collect();
+    let ineligible: HashSet<String> = anticheat
+        .screen(player, &history, &candidates)
+        .await
+        .into_iter()
+        .filter(|report| !report.is_eligible())
+        .map(|report| report.uri)
+        .collect();
+    Ok(verified
+        .into_iter()
+        .map(|(verified, _)| verified)
+        .filter(|verified| !ineligible.contains(&verified.uri))
+        .collect())
+}
+
+/// Every participant's verified games on the tournament's seeds, and who could not be reached
+async fn collect_games(
+    client: &AtprotoClient,
+    anticheat: &Arc<AnticheatStore>,
+    moderation: &Arc<ModerationStore>,
+    tournament: &Tournament,
+) -> (HashMap<String, Vec<TournamentGame>>, Vec<String>) {
+    let seeds: HashSet<(u32, usize)> = tournament
+        .rounds
+        .iter()
+        .map(|round| (round.seed, round.board_size()))
+        .collect();
+    let mut fetches = JoinSet::new();
+    for player in tournament.participants.clone() {
+        let client = client.clone();
+        let anticheat = anticheat.clone();
+        let moderation = moderation.clone();
+        let seeds = seeds.clone();
+        fetches.spawn(async move {
+            let games = fetch_player_games(&client, &anticheat, &moderation, &player, &seeds).await;
+            (player, games)
+        });
+    }
+
+    let mut games = HashMap::new();
+    let mut unreachable = vec![];
+    while let Some(fetched) = fetches.join_next().await {
+        match fetched {
+            Ok((player, Ok(player_games))) => {
+                games.insert(player, player_games);
+            }
+            Ok((player, Err(e))) => {
+                log::warn!(
+                    "Could not read {}'s games for tournament {}: {}",
+                    player,
+                    tournament.id,
+                    e
+                );
+                unreachable.push(player);
+            }
+            Err(e) => log::error!(
+                "Fetching games for tournament {} panicked: {}",
+                tournament.id,
+                e
+            ),
+        }
+    }
+    (games, unreachable)
+}
+
+/// Best score first, the earlier game wins a tie and anyone who has not played goes last.
+/// The sort is stable so seeding breaks anything left
+fn rank(standings: &mut [Standing]) {
+    standings.sort_by(|a, b| match (a.score, b.score) {
+        (Some(a_score), Some(b_score)) => b_score.cmp(&a_score).then(a.played_at.cmp(&b.played_at)),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+}
+
+/// Top seed against bottom seed and so on inwards. With an odd number of players the top
+/// seed gets a bye
+fn pair_off(standings: &[Standing]) -> Vec<Vec<&Standing>> {
+    let mut players: Vec<&Standing> = standings.iter().collect();
+    let mut pairs = vec![];
+    if players.len() % 2 == 1 {
+        pairs.push(vec![players.remove(0)]);
+    }
+    while players.len() >= 2 {
+        let high = players.remove(0);
+        let low = players.pop().expect("two players are left");
+        pairs.push(vec![high, low]);
+    }
+    pairs
+}
+
+/// A match is over once both players have played, or when the round closes
+fn match_winner(players: &[&Standing], closed: bool) -> Option<String> {
+    match players {
+        [bye] => Some(bye.player.clone()),
+        [high, low] => match (high.score, low.score) {
+            (Some(_), Some(_)) => {
+                let mut both = [(*high).clone(), (*low).clone()];
+                rank(&mut both);
+                Some(both[0].player.clone())
+            }
+            (Some(_), None) if closed => Some(high.player.clone()),
+            (None, Some(_)) if closed => Some(low.player.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn compute_results(
+    tournament: &Tournament,
+    games: &HashMap<String, Vec<TournamentGame>>,
+    unreachable: Vec<String>,
+    now: DateTime<Utc>,
+) -> TournamentResults {
+    let mut entrants = tournament.participants.clone();
+    let mut opens_at = tournament.created_at;
+    let mut decided = true;
+    let mut rounds = vec![];
+    let last_round = tournament.rounds.len().saturating_sub(1);
+
+    for (index, round) in tournament.rounds.iter().enumerate() {
+        let size = round.board_size();
+        let closed = now >= round.deadline;
+        // Only the first game on the seed inside the round's window counts. The order comes from
+        // the createdAt the player's client wrote, which the server can not check. Hiding the
+        // seed till the round opens stops games played before the window, but a player who plays
+        // the seed again inside it can backdate the better game and have that one count
+        let mut standings: Vec<Standing> = if decided {
+            entrants
+                .iter()
+                .map(|player| {
+                    let first_game = games.get(player).and_then(|player_games| {
+                        player_games
+                            .iter()
+                            .filter(|game| game.seed == round.seed && game.size == size)
+                            .filter(|game| {
+                                game.created_at > opens_at && game.created_at <= round.deadline
+                            })
+                            .min_by_key(|game| game.created_at)
+                    });
+                    Standing {
+                        player: player.clone(),
+                        score: first_game.map(|game| game.score),
+                        game: first_game.map(|game| game.uri.clone()),
+                        played_at: first_game.map(|game| game.created_at),
+                        advanced: false,
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let mut matches = vec![];
+        let mut advancing: Vec<String> = vec![];
+        match round.format {
+            RoundFormat::Standings => {
+                rank(&mut standings);
+                if closed {
+                    let advance = round.advance.unwrap_or(if index == last_round {
+                        1
+                    } else {
+                        standings.len()
+                    });
+                    advancing = standings
+                        .iter()
+                        .take(advance)
+                        .filter(|standing| standing.score.is_some())
+                        .map(|standing| standing.player.clone())
+                        .collect();
+                }
+                decided = decided && closed;
+            }
+            RoundFormat::Bracket => {
+                matches = pair_off(&standings)
+                    .into_iter()
+                    .map(|players| BracketMatch {
+                        winner: match_winner(&players, closed),
+                        players: players
+                            .iter()
+                            .map(|standing| standing.player.clone())
+                            .collect(),
+                    })
+                    .collect();
+                advancing = matches
+                    .iter()
+                    .filter_map(|bracket_match| bracket_match.winner.clone())
+                    .collect();
+                decided = decided
+                    && (closed
+                        || matches
+                            .iter()
+                            .all(|bracket_match| bracket_match.winner.is_some()));
+                rank(&mut standings);
+            }
+        }
+        for standing in standings.iter_mut() {
+            standing.advanced = advancing.contains(&standing.player);
+        }
+
+        rounds.push(RoundResult {
+            name: round.name.clone(),
+            seed: (now >= opens_at).then_some(round.seed),
+            size,
+            deadline: round.deadline,
+            format: round.format,
+            closed,
+            decided,
+            standings,
+            matches,
+        });
+        entrants = advancing;
+        opens_at = round.deadline;
+    }
+
+    let champion = match entrants.as_slice() {
+        [champion] if decided => Some(champion.clone()),
+        _ => None,
+    };
+    TournamentResults {
+        tournament: tournament.public(now),
+        rounds,
+        champion,
+        unreachable,
+        computed_at: now,
+    }
+}
+
+impl TournamentResults {
+    /// The results as a blue.2048.tournament record for the authority repo. Rounds that have not
+    /// opened are left off so the record does not give their seeds away
+    pub fn to_record(&self) -> Result<tournament::RecordData, TournamentError> {
+        let did = |player: &str| -> Result<Did, TournamentError> {
+            player
+                .parse()
+                .map_err(|e: &str| TournamentError::Invalid(format!("{}: {}", player, e)))
+        };
+        let mut rounds = vec![];
+        for round in &self.rounds {
+            let Some(seed) = round.seed else {
+                continue;
+            };
+            let mut standings = vec![];
+            for standing in &round.standings {
+                standings.push(
+                    tournament::StandingData {
+                        advanced: standing.advanced,
+                        game: standing.game.clone(),
+                        player: did(&standing.player)?,
+                        score: standing.score,
+                    }
+                    .into(),
+                );
+            }
+            let mut matches = vec![];
+            for bracket_match in &round.matches {
+                matches.push(
+                    tournament::MatchData {
+                        players: bracket_match
+                            .players
+                            .iter()
+                            .map(|player| did(player))
+                            .collect::<Result<_, _>>()?,
+                        winner: bracket_match.winner.as_deref().map(did).transpose()?,
+                    }
+                    .into(),
+                );
+            }
+            rounds.push(
+                tournament::RoundData {
+                    deadline: Datetime::new(round.deadline.fixed_offset()),
+                    format: round.format.id().to_string(),
+                    matches: (round.format == RoundFormat::Bracket).then_some(matches),
+                    name: round.name.clone(),
+                    seed: seed as i64,
+                    size: Some(round.size as i64),
+                    standings,
+                }
+                .into(),
+            );
+        }
+        Ok(tournament::RecordData {
+            champion: self.champion.as_deref().map(did).transpose()?,
+            created_at: Datetime::new(self.tournament.created_at.fixed_offset()),
+            description: self.tournament.description.clone(),
+            name: self.tournament.name.clone(),
+            rounds,
+        })
+    }
+}
+
+/// Tournaments kept in a JSON file next to the server
+pub struct TournamentStore {
+    path: PathBuf,
+    tournaments: RwLock<Vec<Tournament>>,
+    results: Mutex<HashMap<String, (Instant, TournamentResults)>>,
+}
+
+impl TournamentStore {
+    /// Starts empty when the file does not exist yet
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let tournaments = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("Error parsing {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(format!("Error reading {}: {}", path.display(), e)),
+        };
+        Ok(TournamentStore {
+            path,
+            tournaments: RwLock::new(tournaments),
+            results: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn save(&self, tournaments: &[Tournament]) -> Result<(), TournamentError> {
+        let contents = serde_json::to_string_pretty(tournaments)
+            .map_err(|e| TournamentError::Storage(e.to_string()))?;
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| TournamentError::Storage(e.to_string()))?;
+        }
+        // Written next to the real file first so a crash mid write can not lose every tournament
+        let temp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&temp_path, contents)
+            .await
+            .map_err(|e| TournamentError::Storage(e.to_string()))?;
+        tokio::fs::rename(&temp_path, &self.path)
+            .await
+            .map_err(|e| TournamentError::Storage(e.to_string()))
+    }
+
+    pub async fn list(&self) -> Vec<Tournament> {
+        self.tournaments.read().await.clone()
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Tournament, TournamentError> {
+        self.tournaments
+            .read()
+            .await
+            .iter()
+            .find(|tournament| tournament.id == id)
+            .cloned()
+            .ok_or_else(|| TournamentError::NotFound(id.to_string()))
+    }
+
+    pub async fn insert(&self, tournament: Tournament) -> Result<(), TournamentError> {
+        let mut tournaments = self.tournaments.write().await;
+        if tournaments
+            .iter()
+            .any(|existing| existing.id == tournament.id)
+        {
+            return Err(TournamentError::Conflict(tournament.id));
+        }
+        tournaments.push(tournament);
+        self.save(&tournaments).await
+    }
+
+    /// Replaces the tournament with the same id and drops its cached results
+    pub async fn update(&self, tournament: Tournament) -> Result<(), TournamentError> {
+        let mut tournaments = self.tournaments.write().await;
+        let existing = tournaments
+            .iter_mut()
+            .find(|existing| existing.id == tournament.id)
+            .ok_or_else(|| TournamentError::NotFound(tournament.id.clone()))?;
+        *existing = tournament.clone();
+        self.save(&tournaments).await?;
+        self.results.lock().await.remove(&tournament.id);
+        Ok(())
+    }
+
+    /// Standings as of now, recomputed from the players' PDSs once the cached ones are stale
+    pub async fn results(
+        &self,
+        client: &AtprotoClient,
+        anticheat: &Arc<AnticheatStore>,
+        moderation: &Arc<ModerationStore>,
+        id: &str,
+    ) -> Result<TournamentResults, TournamentError> {
+        if let Some((computed, results)) = self.results.lock().await.get(id)
+            && computed.elapsed() < RESULTS_TTL
+        {
+            return Ok(results.clone());
+        }
+        let tournament = self.get(id).await?;
+        let (games, unreachable) = collect_games(client, anticheat, moderation, &tournament).await;
+        let results = compute_results(&tournament, &games, unreachable, Utc::now());
+        self.results
+            .lock()
+            .await
+            .insert(id.to_string(), (Instant::now(), results.clone()));
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32) -> DateTime<Utc> {
+        format!("2026-03-{:02}T12:00:00Z", day).parse().unwrap()
+    }
+
+    fn round(name: &str, seed: u32, deadline: u32, format: RoundFormat) -> RoundDefinition {
+        RoundDefinition {
+            name: name.to_string(),
+            seed,
+            size: None,
+            deadline: at(deadline),
+            format,
+            advance: None,
+        }
+    }
+
+    fn tournament(participants: &[&str], rounds: Vec<RoundDefinition>) -> Tournament {
+        Tournament {
+            id: "spring".to_string(),
+            name: "Spring".to_string(),
+            description: None,
+            participants: participants
+                .iter()
+                .map(|player| player.to_string())
+                .collect(),
+            rounds,
+            created_at: at(1),
+            published: None,
+        }
+    }
+
+    fn game(seed: u32, score: i64, day: u32) -> TournamentGame {
+        TournamentGame {
+            uri: format!("at://game/{}/{}", seed, day),
+            seed,
+            size: DEFAULT_BOARD_SIZE,
+            score,
+            created_at: at(day),
+        }
+    }
+
+    fn games(played: &[(&str, TournamentGame)]) -> HashMap<String, Vec<TournamentGame>> {
+        let mut games: HashMap<String, Vec<TournamentGame>> = HashMap::new();
+        for (player, game) in played {
+            games
+                .entry(player.to_string())
+                .or_default()
+                .push(game.clone());
+        }
+        games
+    }
+
+    fn players(standings: &[Standing]) -> Vec<&str> {
+        standings
+            .iter()
+            .map(|standing| standing.player.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn the_first_game_inside_the_window_counts() {
+        let tournament = tournament(
+            &["a", "b", "c"],
+            vec![round("Final", 7, 10, RoundFormat::Standings)],
+        );
+        let games = games(&[
+            // Before the tournament opened
+            ("a", game(7, 9000, 1)),
+            ("a", game(7, 500, 3)),
+            // A replay after the first try does not replace it
+            ("a", game(7, 8000, 4)),
+            ("b", game(7, 1000, 5)),
+            // Another seed
+            ("c", game(8, 4000, 5)),
+        ]);
+        let results = compute_results(&tournament, &games, vec![], at(6));
+        let standings = &results.rounds[0].standings;
+        assert_eq!(players(standings), ["b", "a", "c"]);
+        assert_eq!(standings[0].score, Some(1000));
+        assert_eq!(standings[1].score, Some(500));
+        assert_eq!(standings[2].score, None);
+        assert!(!results.rounds[0].closed);
+        assert!(!results.rounds[0].decided);
+        assert_eq!(results.champion, None);
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_game() {
+        let tournament = tournament(
+            &["a", "b"],
+            vec![round("Final", 7, 10, RoundFormat::Standings)],
+        );
+        let games = games(&[("a", game(7, 1000, 5)), ("b", game(7, 1000, 4))]);
+        let results = compute_results(&tournament, &games, vec![], at(6));
+        assert_eq!(players(&results.rounds[0].standings), ["b", "a"]);
+    }
+
+    #[test]
+    fn standings_send_the_top_players_on_once_closed() {
+        let mut heats = round("Heats", 7, 5, RoundFormat::Standings);
+        heats.advance = Some(2);
+        let tournament = tournament(
+            &["a", "b", "c"],
+            vec![heats, round("Final", 8, 10, RoundFormat::Standings)],
+        );
+        let games = games(&[
+            ("a", game(7, 300, 2)),
+            ("b", game(7, 200, 2)),
+            ("c", game(7, 100, 2)),
+            ("b", game(8, 900, 6)),
+            ("a", game(8, 800, 6)),
+        ]);
+
+        let open = compute_results(&tournament, &games, vec![], at(4));
+        assert!(
+            open.rounds[0]
+                .standings
+                .iter()
+                .all(|standing| !standing.advanced)
+        );
+        assert!(open.rounds[1].standings.is_empty());
+
+        let results = compute_results(&tournament, &games, vec![], at(11));
+        let heats = &results.rounds[0];
+        assert!(heats.decided);
+        assert_eq!(
+            heats
+                .standings
+                .iter()
+                .map(|standing| standing.advanced)
+                .collect::<Vec<_>>(),
+            [true, true, false]
+        );
+        assert_eq!(players(&results.rounds[1].standings), ["b", "a"]);
+        assert_eq!(results.champion.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn players_who_did_not_play_do_not_advance() {
+        let tournament = tournament(
+            &["a", "b"],
+            vec![
+                round("Heats", 7, 5, RoundFormat::Standings),
+                round("Final", 8, 10, RoundFormat::Standings),
+            ],
+        );
+        let games = games(&[("a", game(7, 300, 2))]);
+        let results = compute_results(&tournament, &games, vec![], at(6));
+        assert_eq!(players(&results.rounds[1].standings), ["a"]);
+    }
+
+    #[test]
+    fn brackets_pair_top_seed_against_bottom_seed() {
+        let tournament = tournament(
+            &["a", "b", "c", "d", "e"],
+            vec![round("Semis", 7, 10, RoundFormat::Bracket)],
+        );
+        let games = games(&[
+            ("b", game(7, 100, 2)),
+            ("e", game(7, 200, 2)),
+            ("c", game(7, 300, 2)),
+        ]);
+        let results = compute_results(&tournament, &games, vec![], at(6));
+        let matches: Vec<(Vec<&str>, Option<&str>)> = results.rounds[0]
+            .matches
+            .iter()
+            .map(|bracket_match| {
+                (
+                    bracket_match.players.iter().map(String::as_str).collect(),
+                    bracket_match.winner.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            matches,
+            [
+                (vec!["a"], Some("a")),
+                (vec!["b", "e"], Some("e")),
+                // d has not played and the round is still open
+                (vec!["c", "d"], None),
+            ]
+        );
+        assert!(!results.rounds[0].decided);
+    }
+
+    #[test]
+    fn a_bracket_is_decided_once_every_match_is() {
+        let tournament = tournament(
+            &["a", "b", "c", "d"],
+            vec![
+                round("Semis", 7, 5, RoundFormat::Bracket),
+                round("Final", 8, 10, RoundFormat::Bracket),
+            ],
+        );
+        let games = games(&[
+            ("a", game(7, 100, 2)),
+            ("d", game(7, 200, 2)),
+            ("b", game(7, 300, 2)),
+            ("c", game(7, 400, 2)),
+            ("d", game(8, 600, 6)),
+            ("c", game(8, 500, 6)),
+        ]);
+
+        // Both semis have been played before their deadline
+        let semis = compute_results(&tournament, &games, vec![], at(3));
+        assert!(semis.rounds[0].decided);
+        assert_eq!(players(&semis.rounds[1].standings), ["d", "c"]);
+
+        let results = compute_results(&tournament, &games, vec![], at(7));
+        assert_eq!(results.rounds[1].matches[0].winner.as_deref(), Some("d"));
+        assert_eq!(results.champion.as_deref(), Some("d"));
+    }
+
+    #[test]
+    fn a_closed_match_goes_to_whoever_played() {
+        let tournament = tournament(
+            &["a", "b"],
+            vec![round("Final", 7, 5, RoundFormat::Bracket)],
+        );
+        let games = games(&[("b", game(7, 100, 2))]);
+        let open = compute_results(&tournament, &games, vec![], at(4));
+        assert_eq!(open.rounds[0].matches[0].winner, None);
+        let closed = compute_results(&tournament, &games, vec![], at(6));
+        assert_eq!(closed.rounds[0].matches[0].winner.as_deref(), Some("b"));
+        assert_eq!(closed.champion.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn seeds_stay_hidden_till_the_round_opens() {
+        let tournament = tournament(
+            &["did:plc:a"],
+            vec![
+                round("Heats", 7, 5, RoundFormat::Standings),
+                round("Final", 8, 10, RoundFormat::Standings),
+            ],
+        );
+        let results = compute_results(&tournament, &HashMap::new(), vec![], at(4));
+        assert_eq!(results.rounds[0].seed, Some(7));
+        assert_eq!(results.rounds[1].seed, None);
+        assert_eq!(results.tournament.rounds[1].seed, None);
+        assert_eq!(results.to_record().unwrap().rounds.len(), 1);
+
+        let results = compute_results(&tournament, &HashMap::new(), vec![], at(5));
+        assert_eq!(results.rounds[1].seed, Some(8));
+        assert_eq!(tournament.public(at(5)).rounds[1].seed, Some(8));
+    }
+}
@@ -0,0 +1,115 @@
+use twothousand_forty_eight::board::Board;
+use twothousand_forty_eight::direction::Direction;
+
+/// Moves a 2048 has to be found in for [Achievement::WinInUnderAThousandMoves]
+pub const WIN_IN_UNDER_MOVES: usize = 1000;
+/// Days in a row needed for [Achievement::SevenDaysInARow]
+pub const DAYS_IN_A_ROW: i64 = 7;
+
+/// Every achievement a player can unlock. The ids are the known values of blue.2048.player.achievement
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Achievement {
+    ReachFortyNinetySix,
+    WinWithoutMovingUp,
+    WinInUnderAThousandMoves,
+    SevenDaysInARow,
+    BeatASharedSeed,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 5] = [
+        Achievement::ReachFortyNinetySix,
+        Achievement::WinWithoutMovingUp,
+        Achievement::WinInUnderAThousandMoves,
+        Achievement::SevenDaysInARow,
+        Achievement::BeatASharedSeed,
+    ];
+
+    /// The id used as the record key and the achievement field of the record
+    pub fn id(&self) -> &'static str {
+        match self {
+            Achievement::ReachFortyNinetySix => "reach4096",
+            Achievement::WinWithoutMovingUp => "winWithoutMovingUp",
+            Achievement::WinInUnderAThousandMoves => "winInUnderAThousandMoves",
+            Achievement::SevenDaysInARow => "sevenDaysInARow",
+            Achievement::BeatASharedSeed => "beatASharedSeed",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Achievement::ReachFortyNinetySix => "Double Down",
+            Achievement::WinWithoutMovingUp => "Never Look Up",
+            Achievement::WinInUnderAThousandMoves => "Speed Run",
+            Achievement::SevenDaysInARow => "Week Streak",
+            Achievement::BeatASharedSeed => "Show Off",
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Achievement::ReachFortyNinetySix => "Reach a 4096 block".to_string(),
+            Achievement::WinWithoutMovingUp => "Find a 2048 without ever moving up".to_string(),
+            Achievement::WinInUnderAThousandMoves => {
+                format!("Find a 2048 in under {} moves", WIN_IN_UNDER_MOVES)
+            }
+            Achievement::SevenDaysInARow => format!("Play {} days in a row", DAYS_IN_A_ROW),
+            Achievement::BeatASharedSeed => {
+                "Beat the score of a seed someone shared with you".to_string()
+            }
+        }
+    }
+}
+
+/// What the engine looks at once a game is completed
+pub struct CompletedGame<'a> {
+    pub moves: &'a [Direction],
+    /// The reconstructed board after every move, starting with the empty board
+    pub history: &'a [Board],
+    pub score: i64,
+    pub score_to_beat: Option<i64>,
+    /// From the player's stats after this game was counted
+    pub consecutive_days_played: i64,
+}
+
+fn highest_tile(board: &Board) -> usize {
+    board
+        .tiles
+        .iter()
+        .flatten()
+        .filter_map(|tile| *tile)
+        .map(|tile| tile.value)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Every achievement this game earned, whether or not the player already has it
+pub fn evaluate(game: &CompletedGame) -> Vec<Achievement> {
+    //The first board is before any move was made, so the index is also the moves made
+    let moves_to_twenty_forty_eight = game
+        .history
+        .iter()
+        .position(|board| highest_tile(board) >= 2048);
+    let highest_tile = game.history.iter().map(highest_tile).max().unwrap_or(0);
+
+    Achievement::ALL
+        .into_iter()
+        .filter(|achievement| match achievement {
+            Achievement::ReachFortyNinetySix => highest_tile >= 4096,
+            Achievement::WinWithoutMovingUp => moves_to_twenty_forty_eight.is_some_and(|moves| {
+                !game
+                    .moves
+                    .iter()
+                    .take(moves)
+                    .any(|direction| *direction == Direction::UP)
+            }),
+            Achievement::WinInUnderAThousandMoves => {
+                moves_to_twenty_forty_eight.is_some_and(|moves| moves < WIN_IN_UNDER_MOVES)
+            }
+            Achievement::SevenDaysInARow => game.consecutive_days_played >= DAYS_IN_A_ROW,
+            Achievement::BeatASharedSeed => game
+                .score_to_beat
+                .is_some_and(|score_to_beat| game.score > score_to_beat),
+        })
+        .collect()
+}
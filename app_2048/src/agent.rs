@@ -1,19 +1,30 @@
+use crate::achievements::{Achievement, CompletedGame};
 use crate::at_repo_sync::AtRepoSync;
-use crate::idb::{DB_NAME, GAME_STORE, RecordStorageWrapper, StorageError, object_get_index};
+use crate::idb::{
+    DB_NAME, GAME_STORE, RecordStorageWrapper, StorageError, object_get_all, object_get_index,
+};
 use crate::oauth_client::oauth_client;
+use crate::store::{DEFAULT_BOARD_SIZE, GameMode};
+use ai_2048::analysis::GameAnalysis;
+use ai_2048::{Hint, SearchConfig};
 use atrium_api::agent::Agent;
 use atrium_api::types::LimitedU32;
 use atrium_api::types::string::{Datetime, Did, Tid};
 use indexed_db_futures::database::Database;
 use js_sys::Uint8Array;
 use serde::{Deserialize, Serialize};
+use twothousand_forty_eight::board::Board;
+use twothousand_forty_eight::direction::Direction;
 use twothousand_forty_eight::unified::game::GameState;
 use twothousand_forty_eight::unified::hash::Hashable;
 use twothousand_forty_eight::unified::reconstruction::Reconstructable;
 use twothousand_forty_eight::v2::recording::SeededRecording;
 use types_2048::blue;
-use types_2048::blue::_2048::defs::SyncStatusData;
+use types_2048::blue::_2048::defs::{
+    BoardSizeStatsData, DirectionCountsData, ModeStatsData, SyncStatusData,
+};
 use types_2048::blue::_2048::game;
+use types_2048::blue::_2048::player;
 use wasm_bindgen::JsValue;
 use yew_agent::Codec;
 use yew_agent::prelude::*;
@@ -39,19 +50,69 @@ impl Codec for Postcard {
     }
 }
 
+/// Timing captured by the board while the game was being played
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameTiming {
+    /// Time in milliseconds spent actively playing
+    pub play_time_ms: u64,
+    /// Play time in milliseconds when the first 2048 tile showed up
+    pub time_to_twenty_forty_eight_ms: Option<u64>,
+    /// Play time in milliseconds when each move was made
+    pub move_times: Vec<u64>,
+}
+
+/// Everything the board knows about a game that is not in its seeded recording
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameDetails {
+    pub timing: GameTiming,
+    /// Set when the game was started from a seed someone shared along with their score
+    pub score_to_beat: Option<i64>,
+    /// Set when the server dealt the seed for a ranked game
+    pub seed_issued_at: Option<Datetime>,
+    /// When the first move was made
+    pub started_at: Option<Datetime>,
+    /// How many moves were taken back with undo
+    pub undo_count: u32,
+    /// How many hints were asked for, including autoplay moves
+    pub hints_used: u32,
+    pub mode: GameMode,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum StorageRequest {
     // GameCompleted(RecordStorageWrapper<game::RecordData>),
-    GameCompleted(String, Option<Did>),
+    /// The game's history, the player if logged in and the details the board kept track of
+    GameCompleted(String, Option<Did>, GameDetails),
+    /// The move the engine would make on the board
+    BestMove {
+        board: Box<Board>,
+        search: SearchConfig,
+    },
+    /// Every move of a game scored against the engine
+    AnalyzeGame {
+        seeded_recording: String,
+        search: SearchConfig,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum StorageResponse {
     Success,
     AlreadySynced,
+    BestMove(Option<Hint>),
+    Analysis(GameAnalysis),
     Error(StorageError),
 }
 
+impl StorageResponse {
+    pub fn into_hint(self) -> Option<Hint> {
+        match self {
+            StorageResponse::BestMove(hint) => hint,
+            _ => None,
+        }
+    }
+}
+
 #[oneshot]
 pub async fn StorageTask(request: StorageRequest) -> StorageResponse {
     let _db = match Database::open(DB_NAME).await {
@@ -62,18 +123,139 @@ pub async fn StorageTask(request: StorageRequest) -> StorageResponse {
     };
 
     let response = match request {
-        StorageRequest::GameCompleted(game_history, did) => {
+        StorageRequest::GameCompleted(game_history, did, details) => {
             // transaction_put(db, game, GAME_STORE, None).await
-            handle_game_completed(game_history, did).await
+            handle_game_completed(game_history, did, details).await
+        }
+        //The search runs here so the board stays responsive while the engine thinks
+        StorageRequest::BestMove { board, search } => Ok(StorageResponse::BestMove(
+            ai_2048::best_move(&board, &search),
+        )),
+        StorageRequest::AnalyzeGame {
+            seeded_recording,
+            search,
+        } => crate::hint::analyze_game(&seeded_recording, &search).map(StorageResponse::Analysis),
+    };
+    response.unwrap_or_else(StorageResponse::Error)
+}
+
+/// The median of the scores, rounded down when it falls between two games
+pub(crate) fn median_score(mut scores: Vec<i64>) -> Option<i64> {
+    if scores.is_empty() {
+        return None;
+    }
+    scores.sort_unstable();
+    let middle = scores.len() / 2;
+    if scores.len().is_multiple_of(2) {
+        Some((scores[middle - 1] + scores[middle]) / 2)
+    } else {
+        Some(scores[middle])
+    }
+}
+
+/// Whether a saved game counts towards the main stats, which only cover classic 4x4 games
+pub(crate) fn is_classic_four_by_four(game: &game::RecordData) -> bool {
+    GameMode::from_record(game.mode.as_deref(), game.mode_limit) == GameMode::Classic
+        && game
+            .seeded_recording
+            .parse::<SeededRecording>()
+            .is_ok_and(|seeded_recording| seeded_recording.width == DEFAULT_BOARD_SIZE)
+}
+
+/// The player's local calendar day for a datetime, using the browser's timezone offset
+pub(crate) fn local_day(datetime: &Datetime) -> i64 {
+    let offset_minutes = js_sys::Date::new_0().get_timezone_offset() as i64;
+    (datetime.as_ref().timestamp() - offset_minutes * 60).div_euclid(86_400)
+}
+
+/// Saves any of the earned achievements the player does not already have
+async fn unlock_achievements(
+    at_repo_sync: &AtRepoSync,
+    earned_achievements: Vec<Achievement>,
+    game_key: Tid,
+) -> Result<(), StorageError> {
+    let unlocked = at_repo_sync
+        .get_local_achievements()
+        .await
+        .map_err(|err| StorageError::Error(err.to_string()))?;
+    for achievement in earned_achievements {
+        if unlocked
+            .iter()
+            .any(|unlocked| unlocked.achievement == achievement.id())
+        {
+            continue;
+        }
+        let new_achievement = player::achievement::RecordData {
+            achievement: achievement.id().to_string(),
+            created_at: Datetime::now(),
+            game: game_key.parse().ok(),
+            sync_status: SyncStatusData {
+                created_at: Datetime::now(),
+                hash: "".to_string(),
+                synced_with_at_repo: false,
+                updated_at: Datetime::now(),
+            }
+            .into(),
+        };
+        at_repo_sync
+            .create_a_new_achievement(new_achievement)
+            .await
+            .map_err(|err| StorageError::Error(err.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Adds a timed or move limited game to the bucket for its mode, limit and board size
+fn count_mode_game(
+    stats: &mut player::stats::RecordData,
+    mode: GameMode,
+    limit: i64,
+    seeded_recording: &SeededRecording,
+    score: i64,
+    highest_block: i64,
+) {
+    let board_size = seeded_recording.width as i64;
+    let modes = stats.modes.get_or_insert_with(Vec::new);
+    let mode_stats = match modes.iter_mut().position(|mode_stats| {
+        mode_stats.mode == mode.id() && mode_stats.limit == limit && mode_stats.size == board_size
+    }) {
+        Some(index) => &mut modes[index],
+        None => {
+            modes.push(
+                ModeStatsData {
+                    games_played: 0,
+                    highest_number_block: 0,
+                    highest_score: 0,
+                    limit,
+                    mode: mode.id().to_string(),
+                    size: board_size,
+                    total_score: 0,
+                }
+                .into(),
+            );
+            modes.last_mut().unwrap()
         }
     };
-    response.unwrap_or_else(|e| StorageResponse::Error(e))
+    mode_stats.games_played += 1;
+    mode_stats.total_score += score;
+    mode_stats.highest_score = mode_stats.highest_score.max(score);
+    mode_stats.highest_number_block = mode_stats.highest_number_block.max(highest_block);
 }
 
 pub async fn handle_game_completed(
     game_history: String,
     did: Option<Did>,
+    details: GameDetails,
 ) -> Result<StorageResponse, StorageError> {
+    let GameDetails {
+        timing,
+        score_to_beat,
+        seed_issued_at,
+        started_at,
+        undo_count,
+        hints_used,
+        mode,
+    } = details;
     let seeded_recording: SeededRecording = match game_history.clone().parse() {
         Ok(seeded_recording) => seeded_recording,
         Err(err) => {
@@ -106,7 +288,7 @@ pub async fn handle_game_completed(
     };
 
     let already_saved: Option<RecordStorageWrapper<game::RecordData>> =
-        object_get_index(db, GAME_STORE, &seeded_recording.game_hash())
+        object_get_index(db.clone(), GAME_STORE, &seeded_recording.game_hash())
             .await
             .map_err(|err| StorageError::Error(err.to_string()))?;
     if let Some(already_saved) = already_saved {
@@ -128,11 +310,49 @@ pub async fn handle_game_completed(
         }
     };
 
+    let reconstruction = match seeded_recording.reconstruct() {
+        Ok(reconstruction) => reconstruction,
+        Err(err) => {
+            return Err(StorageError::Error(err.to_string()));
+        }
+    };
+
+    let highest_block_this_game = gamestate
+        .board
+        .tiles
+        .iter()
+        .flatten()
+        .filter_map(|tile| *tile)
+        .map(|x| x.value)
+        .max()
+        .unwrap_or(0) as i64;
+
+    //history[n] is the board after n moves
+    let won_at_move = reconstruction.history.iter().position(|board| {
+        board
+            .get_occupied_tiles()
+            .iter()
+            .any(|tile| tile.value >= 2048)
+    });
+
     let record = blue::_2048::game::RecordData {
-        completed: gamestate.over,
+        //Timed and move limited games are done once their limit is hit, and won games can be
+        //ended from the win modal, both with moves left
+        completed: gamestate.over || won_at_move.is_some() || mode != GameMode::Classic,
         created_at: Datetime::now(),
         current_score: gamestate.score_current as i64,
+        hints_used: Some(hints_used as i64),
+        max_tile: Some(highest_block_this_game),
+        mode: Some(mode.id().to_string()),
+        mode_limit: mode.limit(),
+        //Games started before move times were recorded would not line up with their moves
+        move_times: (timing.move_times.len() == seeded_recording.moves.len())
+            .then(|| timing.move_times.iter().map(|ms| *ms as i64).collect()),
+        play_time: Some(timing.play_time_ms as i64),
+        score_to_beat,
+        seed_issued_at,
         seeded_recording: game_history,
+        started_at,
         sync_status: SyncStatusData {
             created_at: Datetime::now(),
             hash: "".to_string(),
@@ -141,7 +361,10 @@ pub async fn handle_game_completed(
             updated_at: Datetime::now(),
         }
         .into(),
+        time_to_find_twenty_forty_eight: timing.time_to_twenty_forty_eight_ms.map(|ms| ms as i64),
+        undo_count: Some(undo_count as i64),
         won: gamestate.won,
+        won_at_move: won_at_move.map(|move_number| move_number as i64),
     };
 
     // if at_repo_sync.can_remote_sync() {
@@ -168,63 +391,170 @@ pub async fn handle_game_completed(
         }
     };
 
-    let highest_block_this_game = gamestate
-        .board
-        .tiles
-        .iter()
-        .flatten()
-        .filter_map(|tile| *tile)
-        .map(|x| x.value)
-        .max()
-        .unwrap_or(0) as i64;
+    //Scores are only comparable between games on the same size board, so the main stats keep
+    //counting classic 4x4 games like they always have and every size gets its own bucket below.
+    //Timed and move limited games only count towards their own mode's stats
+    let board_size = seeded_recording.width as i64;
+    let counts_in_main_stats = mode == GameMode::Classic && board_size == DEFAULT_BOARD_SIZE as i64;
+    if counts_in_main_stats {
+        //Update the stats
+        stats.games_played += 1;
+        stats.total_score += gamestate.score_current as i64;
+        stats.average_score = stats.total_score / stats.games_played;
+        if highest_block_this_game > stats.highest_number_block {
+            stats.highest_number_block = highest_block_this_game;
+        }
 
-    //Update the stats
-    stats.games_played += 1;
-    stats.total_score += gamestate.score_current as i64;
-    stats.average_score = stats.total_score / stats.games_played;
-    if highest_block_this_game > stats.highest_number_block {
-        stats.highest_number_block = highest_block_this_game;
-    }
+        if gamestate.score_current as i64 > stats.highest_score {
+            stats.highest_score = gamestate.score_current as i64;
+        }
 
-    if gamestate.score_current as i64 > stats.highest_score {
-        stats.highest_score = gamestate.score_current as i64;
+        //The record only keeps totals, so the median comes from the games saved on this device
+        let mut scores: Vec<i64> =
+            object_get_all::<RecordStorageWrapper<game::RecordData>>(db.clone(), GAME_STORE)
+                .await?
+                .into_iter()
+                .filter(|game| game.record.completed && is_classic_four_by_four(&game.record))
+                .map(|game| game.record.current_score)
+                .collect();
+        scores.push(gamestate.score_current as i64);
+        stats.median_score = median_score(scores);
+
+        if let Some(time_to_twenty_forty_eight) = timing.time_to_twenty_forty_eight_ms {
+            let time_to_twenty_forty_eight = time_to_twenty_forty_eight as i64;
+            if stats
+                .fastest_time_to_find_twenty_forty_eight
+                .is_none_or(|fastest| time_to_twenty_forty_eight < fastest)
+            {
+                stats.fastest_time_to_find_twenty_forty_eight = Some(time_to_twenty_forty_eight);
+            }
+        }
     }
 
-    let reconstruction = match seeded_recording.reconstruct() {
-        Ok(reconstruction) => reconstruction,
-        Err(err) => {
-            return Err(StorageError::Error(err.to_string()));
+    //How much someone plays counts classic games on any size board
+    if mode == GameMode::Classic {
+        let direction_counts = stats.direction_counts.get_or_insert_with(|| {
+            DirectionCountsData {
+                down: 0,
+                left: 0,
+                right: 0,
+                up: 0,
+            }
+            .into()
+        });
+        let mut moves_this_game = 0;
+        for direction in &seeded_recording.moves {
+            match direction {
+                Direction::UP => direction_counts.up += 1,
+                Direction::DOWN => direction_counts.down += 1,
+                Direction::LEFT => direction_counts.left += 1,
+                Direction::RIGHT => direction_counts.right += 1,
+                //Breaks and the start/end markers are not swipes
+                _ => continue,
+            }
+            moves_this_game += 1;
         }
+        stats.total_moves = Some(stats.total_moves.unwrap_or(0) + moves_this_game);
+
+        stats.total_time_played =
+            Some(stats.total_time_played.unwrap_or(0) + timing.play_time_ms as i64);
+    }
+
+    let now = Datetime::now();
+    let consecutive_days_played = match &stats.last_played_at {
+        Some(last_played_at) => match local_day(&now) - local_day(last_played_at) {
+            0 => stats.consecutive_days_played.unwrap_or(1).max(1),
+            1 => stats.consecutive_days_played.unwrap_or(0) + 1,
+            _ => 1,
+        },
+        None => 1,
     };
+    stats.consecutive_days_played = Some(consecutive_days_played);
+    stats.longest_consecutive_days_played = Some(
+        stats
+            .longest_consecutive_days_played
+            .unwrap_or(0)
+            .max(consecutive_days_played),
+    );
+    stats.last_played_at = Some(now);
 
-    let mut twenty_48_this_game: Vec<usize> = vec![];
-    let mut turns_till_2048 = 0;
-    let mut turns = 0;
-    for board_in_the_moment in reconstruction.history {
-        turns += 1;
+    let earned_achievements = crate::achievements::evaluate(&CompletedGame {
+        moves: &seeded_recording.moves,
+        history: &reconstruction.history,
+        score: gamestate.score_current as i64,
+        score_to_beat,
+        consecutive_days_played,
+    });
 
-        for tile in board_in_the_moment
-            .tiles
-            .iter()
-            .flatten()
-            .filter_map(|tile| *tile)
-        {
-            if tile.value as i64 > stats.highest_number_block {
-                stats.highest_number_block = tile.value as i64;
+    if mode == GameMode::Classic {
+        let mut twenty_48_this_game: Vec<usize> = vec![];
+        for board_in_the_moment in reconstruction.history {
+            for tile in board_in_the_moment
+                .tiles
+                .iter()
+                .flatten()
+                .filter_map(|tile| *tile)
+            {
+                if tile.value as i64 == 2048 && !twenty_48_this_game.contains(&tile.id) {
+                    twenty_48_this_game.push(tile.id);
+                }
             }
+        }
 
-            if tile.value as i64 == 2048 && twenty_48_this_game.contains(&tile.id) == false {
-                if turns_till_2048 == 0 {
-                    turns_till_2048 = turns;
-                    if turns < stats.least_moves_to_find_twenty_forty_eight {
-                        stats.least_moves_to_find_twenty_forty_eight = turns;
-                    }
-                    // stats.least_moves_to_find_twenty_forty_eight
-                }
-                stats.times_twenty_forty_eight_been_found += 1;
-                twenty_48_this_game.push(tile.id);
+        if counts_in_main_stats {
+            stats.times_twenty_forty_eight_been_found += twenty_48_this_game.len() as i64;
+        }
+        if let Some(won_at_move) = won_at_move.filter(|_| counts_in_main_stats) {
+            let won_at_move = won_at_move as i64;
+            //0 means a 2048 has never been found
+            if stats.least_moves_to_find_twenty_forty_eight == 0
+                || won_at_move < stats.least_moves_to_find_twenty_forty_eight
+            {
+                stats.least_moves_to_find_twenty_forty_eight = won_at_move;
+            }
+            stats.games_won = Some(stats.games_won.unwrap_or(0) + 1);
+            if seeded_recording.moves.len() as i64 > won_at_move {
+                stats.games_continued_after_win =
+                    Some(stats.games_continued_after_win.unwrap_or(0) + 1);
             }
         }
+
+        let mut board_sizes = stats.board_sizes.take().unwrap_or_default();
+        let size_stats = match board_sizes
+            .iter_mut()
+            .position(|size_stats| size_stats.size == board_size)
+        {
+            Some(index) => &mut board_sizes[index],
+            None => {
+                board_sizes.push(
+                    BoardSizeStatsData {
+                        games_played: 0,
+                        highest_number_block: 0,
+                        highest_score: 0,
+                        size: board_size,
+                        times_twenty_forty_eight_been_found: 0,
+                    }
+                    .into(),
+                );
+                board_sizes.last_mut().unwrap()
+            }
+        };
+        size_stats.games_played += 1;
+        size_stats.highest_score = size_stats.highest_score.max(gamestate.score_current as i64);
+        size_stats.highest_number_block =
+            size_stats.highest_number_block.max(highest_block_this_game);
+        size_stats.times_twenty_forty_eight_been_found += twenty_48_this_game.len() as i64;
+        board_sizes.sort_by_key(|size_stats| size_stats.size);
+        stats.board_sizes = Some(board_sizes);
+    } else if let Some(limit) = mode.limit() {
+        count_mode_game(
+            &mut stats,
+            mode,
+            limit,
+            &seeded_recording,
+            gamestate.score_current as i64,
+            highest_block_this_game,
+        );
     }
 
     at_repo_sync
@@ -234,11 +564,13 @@ pub async fn handle_game_completed(
 
     let tid = Tid::now(LimitedU32::MIN);
     let result = at_repo_sync
-        .create_a_new_game(record, tid, seeded_recording.game_hash())
+        .create_a_new_game(record, tid.clone(), seeded_recording.game_hash())
         .await
         .map_err(|err| StorageError::Error(err.to_string()));
     if result.is_err() {
         return Err(StorageError::Error(result.err().unwrap().to_string()));
     }
+
+    unlock_achievements(&at_repo_sync, earned_achievements, tid).await?;
     Ok(StorageResponse::Success)
 }
@@ -0,0 +1,65 @@
+use crate::oauth_client::oauth_client;
+use atrium_api::agent::Agent;
+use atrium_api::com::atproto::server::get_service_auth;
+use atrium_api::types::string::Did;
+use client_2048::Client;
+
+fn origin() -> String {
+    gloo::utils::window()
+        .location()
+        .origin()
+        .unwrap_or_default()
+}
+
+/// The api's client. The api is served from the same origin as the app, behind the same proxy
+pub fn client() -> Client {
+    Client::new(&origin())
+}
+
+/// The did:web of the origin the api shares with the app, which service auth tokens for it are
+/// made out to
+fn service_did() -> Result<Did, String> {
+    let host = gloo::utils::window().location().host().unwrap_or_default();
+    format!("did:web:{}", host.replace(':', "%3A"))
+        .parse()
+        .map_err(|err: &str| err.to_string())
+}
+
+/// The api's client signed in as the player for one method. The player's PDS signs a short lived
+/// service auth token for it, which the api checks against the player's DID document
+pub async fn player_client(did: &Did, method: &str) -> Result<Client, String> {
+    let session = oauth_client()
+        .await
+        .restore(did)
+        .await
+        .map_err(|err| err.to_string())?;
+    let token = Agent::new(session)
+        .api
+        .com
+        .atproto
+        .server
+        .get_service_auth(
+            get_service_auth::ParametersData {
+                aud: service_did()?,
+                exp: None,
+                lxm: Some(method.parse().map_err(|err: &str| err.to_string())?),
+            }
+            .into(),
+        )
+        .await
+        .map_err(|err| err.to_string())?
+        .data
+        .token;
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::AUTHORIZATION,
+        format!("Bearer {}", token)
+            .parse()
+            .map_err(|err: reqwest::header::InvalidHeaderValue| err.to_string())?,
+    );
+    let http = reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(|err| err.to_string())?;
+    Ok(Client::new_with_client(&origin(), http))
+}
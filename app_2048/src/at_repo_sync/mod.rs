@@ -1,7 +1,8 @@
 use crate::atrium_stores::IndexDBSessionStore;
+use crate::duels::{StoredChallenge, save_challenge};
 use crate::idb::{
-    DB_NAME, GAME_STORE, PROFILE_STORE, RecordStorageWrapper, SELF_KEY, STATS_STORE, StorageError,
-    object_get, transaction_put,
+    ACHIEVEMENT_STORE, DB_NAME, GAME_STORE, PROFILE_STORE, RecordStorageWrapper, SELF_KEY,
+    STATS_STORE, StorageError, object_get, object_get_all, transaction_put,
 };
 use crate::resolver::ApiDNSTxtResolver;
 use atrium_api::agent::Agent;
@@ -254,9 +255,7 @@ impl AtRepoSync
 
         match object_get::<player::profile::RecordData>(db, PROFILE_STORE, SELF_KEY).await {
             Ok(profile) => Ok(profile),
-            Err(err) => {
-                return Err(AtRepoSyncError::ThereWasAnError(err.to_string()));
-            }
+            Err(err) => Err(AtRepoSyncError::ThereWasAnError(err.to_string())),
         }
     }
 
@@ -319,11 +318,29 @@ impl AtRepoSync
     ) -> Result<player::stats::RecordData, AtRepoSyncError> {
         let mut new_player_stats = player::stats::RecordData {
             average_score: 0,
+            board_sizes: Some(vec![]),
+            consecutive_days_played: Some(0),
             created_at: Datetime::now(),
+            direction_counts: Some(
+                _2048::defs::DirectionCountsData {
+                    down: 0,
+                    left: 0,
+                    right: 0,
+                    up: 0,
+                }
+                .into(),
+            ),
+            fastest_time_to_find_twenty_forty_eight: None,
+            games_continued_after_win: Some(0),
             games_played: 0,
+            games_won: Some(0),
             highest_number_block: 0,
             highest_score: 0,
+            last_played_at: None,
             least_moves_to_find_twenty_forty_eight: 0,
+            longest_consecutive_days_played: Some(0),
+            median_score: None,
+            modes: Some(vec![]),
             sync_status: _2048::defs::SyncStatusData {
                 created_at: Datetime::now(),
                 hash: "".to_string(),
@@ -332,7 +349,9 @@ impl AtRepoSync
             }
             .into(),
             times_twenty_forty_eight_been_found: 0,
+            total_moves: Some(0),
             total_score: 0,
+            total_time_played: Some(0),
         };
         let string_data = serde_json::to_string(&new_player_stats).unwrap();
         let hash = const_xxh3(string_data.as_bytes());
@@ -615,6 +634,219 @@ impl AtRepoSync
         }
     }
 
+    pub async fn get_local_achievements(
+        &self,
+    ) -> Result<Vec<player::achievement::RecordData>, AtRepoSyncError> {
+        let db = match Database::open(DB_NAME).await {
+            Ok(db) => db,
+            Err(err) => {
+                return Err(AtRepoSyncError::ThereWasAnError(err.to_string()));
+            }
+        };
+
+        match object_get_all::<player::achievement::RecordData>(db, ACHIEVEMENT_STORE).await {
+            Ok(achievements) => Ok(achievements),
+            Err(err) => Err(AtRepoSyncError::ThereWasAnError(err.to_string())),
+        }
+    }
+
+    /// Saves a newly unlocked achievement locally and to the AT repo. The achievement id is the record key
+    pub async fn create_a_new_achievement(
+        &self,
+        mut new_achievement: player::achievement::RecordData,
+    ) -> Result<player::achievement::RecordData, AtRepoSyncError> {
+        let record_key: RecordKey = match new_achievement.achievement.parse() {
+            Ok(record_key) => record_key,
+            Err(err) => {
+                return Err(AtRepoSyncError::ThereWasAnError(err.to_string()));
+            }
+        };
+
+        let mut synced_with_at_repo = false;
+        match &self.client {
+            None => {}
+            Some(client) => {
+                let create_request = client
+                    .api
+                    .com
+                    .atproto
+                    .repo
+                    .put_record(
+                        atrium_api::com::atproto::repo::put_record::InputData {
+                            collection: blue::_2048::player::Achievement::NSID.parse().unwrap(),
+                            record: KnownRecord::from(new_achievement.clone()).into(),
+                            //TODO unwrap is not best, but ideally if we have a client we should have a did
+                            repo: AtIdentifier::Did(self.users_did.clone().unwrap()),
+                            rkey: record_key.clone(),
+                            swap_commit: None,
+                            swap_record: None,
+                            validate: None,
+                        }
+                        .into(),
+                    )
+                    .await;
+                match create_request {
+                    Ok(_) => {
+                        synced_with_at_repo = true;
+                    }
+                    Err(err) => {
+                        //Just going to log errors "quietly" as I figure out how to handle them
+                        log::error!("{:?}", err);
+                    }
+                }
+            }
+        }
+
+        let db = match Database::open(DB_NAME).await {
+            Ok(db) => db,
+            Err(err) => {
+                return Err(AtRepoSyncError::ThereWasAnError(err.to_string()));
+            }
+        };
+
+        new_achievement.sync_status.synced_with_at_repo = synced_with_at_repo;
+        new_achievement.sync_status.updated_at = Datetime::now();
+        let string_data = serde_json::to_string(&new_achievement).unwrap();
+        let hash = const_xxh3(string_data.as_bytes());
+        new_achievement.sync_status.hash = format!("{:x}", hash);
+        match transaction_put(
+            db,
+            new_achievement.clone(),
+            ACHIEVEMENT_STORE,
+            Some(record_key.to_string()),
+        )
+        .await
+        {
+            Ok(_) => Ok(new_achievement),
+            Err(err) => Err(AtRepoSyncError::ThereWasAnError(err.to_string())),
+        }
+    }
+
+    /// Pulls down any achievements unlocked on another device and pushes up any that never made it to the AT repo
+    pub async fn sync_achievements(&self) -> Result<(), AtRepoSyncError> {
+        let client = match &self.client {
+            None => return Ok(()),
+            Some(client) => client,
+        };
+        let local_achievements = self.get_local_achievements().await?;
+
+        let mut remote_achievements: Vec<player::achievement::RecordData> = vec![];
+        let mut cursor = None;
+        loop {
+            let page = client
+                .api
+                .com
+                .atproto
+                .repo
+                .list_records(
+                    atrium_api::com::atproto::repo::list_records::ParametersData {
+                        collection: blue::_2048::player::Achievement::NSID.parse().unwrap(),
+                        cursor: cursor.clone(),
+                        limit: None,
+                        repo: AtIdentifier::Did(self.users_did.clone().unwrap()),
+                        reverse: None,
+                    }
+                    .into(),
+                )
+                .await
+                .map_err(|err| AtRepoSyncError::AtRepoCallError(err.to_string()))?;
+            remote_achievements.extend(
+                page.records
+                    .iter()
+                    .map(|record| record.value.clone().into()),
+            );
+            match page.cursor.clone() {
+                Some(next) if !page.records.is_empty() => cursor = Some(next),
+                _ => break,
+            }
+        }
+
+        let db = match Database::open(DB_NAME).await {
+            Ok(db) => db,
+            Err(err) => {
+                return Err(AtRepoSyncError::ThereWasAnError(err.to_string()));
+            }
+        };
+
+        for remote_achievement in &remote_achievements {
+            let is_local = local_achievements
+                .iter()
+                .any(|local| local.achievement == remote_achievement.achievement);
+            if !is_local {
+                transaction_put(
+                    db.clone(),
+                    remote_achievement.clone(),
+                    ACHIEVEMENT_STORE,
+                    Some(remote_achievement.achievement.clone()),
+                )
+                .await
+                .map_err(|err| AtRepoSyncError::LocalRepoError(err.to_string()))?;
+            }
+        }
+
+        for local_achievement in local_achievements {
+            let is_remote = remote_achievements
+                .iter()
+                .any(|remote| remote.achievement == local_achievement.achievement);
+            if !is_remote {
+                self.create_a_new_achievement(local_achievement).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a challenge to the AT repo and keeps a copy locally. The opponent reads it from the
+    /// challenger's repo, so sending one needs to be logged in
+    pub async fn create_a_new_challenge(
+        &self,
+        mut new_challenge: blue::_2048::challenge::RecordData,
+        key: Tid,
+    ) -> Result<StoredChallenge, AtRepoSyncError> {
+        let (client, did) = match (&self.client, &self.users_did) {
+            (Some(client), Some(did)) => (client, did),
+            _ => {
+                return Err(AtRepoSyncError::ThereWasAnError(
+                    "You need to be logged in to send a challenge".to_string(),
+                ));
+            }
+        };
+        let record_key: RecordKey = key.parse().unwrap();
+        new_challenge.sync_status.synced_with_at_repo = true;
+        new_challenge.sync_status.updated_at = Datetime::now();
+        let string_data = serde_json::to_string(&new_challenge).unwrap();
+        let hash = const_xxh3(string_data.as_bytes());
+        new_challenge.sync_status.hash = format!("{:x}", hash);
+
+        client
+            .api
+            .com
+            .atproto
+            .repo
+            .create_record(
+                atrium_api::com::atproto::repo::create_record::InputData {
+                    collection: blue::_2048::Challenge::NSID.parse().unwrap(),
+                    record: KnownRecord::from(new_challenge.clone()).into(),
+                    repo: AtIdentifier::Did(did.clone()),
+                    rkey: Some(record_key.clone()),
+                    swap_commit: None,
+                    validate: None,
+                }
+                .into(),
+            )
+            .await
+            .map_err(|err| AtRepoSyncError::AtRepoCallError(err.to_string()))?;
+
+        let stored_challenge = StoredChallenge {
+            challenger: did.clone(),
+            rkey: record_key,
+            record: new_challenge,
+        };
+        save_challenge(&stored_challenge)
+            .await
+            .map_err(|err| AtRepoSyncError::LocalRepoError(err.to_string()))?;
+        Ok(stored_challenge)
+    }
+
     //TODO just scraping the current game sync for now. Dont think it is needed
     // pub async fn get_current_game(&self) -> Result<game::RecordData, AtRepoSyncError> {
     //     //TODO change to be same as ATProto repo where we get current game from player profile and not local profile
@@ -0,0 +1,174 @@
+use yew::{Html, Properties, function_component, html};
+
+const CHART_WIDTH: f64 = 320.0;
+const CHART_HEIGHT: f64 = 160.0;
+/// Room on the left and bottom of the chart for the axis labels
+const CHART_PADDING: f64 = 28.0;
+
+fn empty_chart() -> Html {
+    html! {
+        <p class="text-base-content/70 text-center py-8">
+            { "Finish a game to start filling this chart in" }
+        </p>
+    }
+}
+
+fn axes(y_max: f64, y_suffix: &str) -> Html {
+    html! {
+        <>
+            <line
+                x1={CHART_PADDING.to_string()}
+                y1="0"
+                x2={CHART_PADDING.to_string()}
+                y2={(CHART_HEIGHT - CHART_PADDING).to_string()}
+                class="stroke-base-content/30"
+            />
+            <line
+                x1={CHART_PADDING.to_string()}
+                y1={(CHART_HEIGHT - CHART_PADDING).to_string()}
+                x2={CHART_WIDTH.to_string()}
+                y2={(CHART_HEIGHT - CHART_PADDING).to_string()}
+                class="stroke-base-content/30"
+            />
+            <text
+                x={(CHART_PADDING - 4.0).to_string()}
+                y="8"
+                text-anchor="end"
+                class="fill-base-content text-[8px]"
+            >
+                { format!("{}{}", y_max.round(), y_suffix) }
+            </text>
+            <text
+                x={(CHART_PADDING - 4.0).to_string()}
+                y={(CHART_HEIGHT - CHART_PADDING).to_string()}
+                text-anchor="end"
+                class="fill-base-content text-[8px]"
+            >
+                { format!("0{}", y_suffix) }
+            </text>
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct LineChartProps {
+    /// Values plotted left to right in the order given
+    pub values: Vec<f64>,
+    /// Fixes the top of the y axis, otherwise the largest value is used
+    #[prop_or_default]
+    pub y_max: Option<f64>,
+    /// Shown after the y axis labels, like "%"
+    #[prop_or_default]
+    pub y_suffix: String,
+}
+
+#[function_component(LineChart)]
+pub fn line_chart(props: &LineChartProps) -> Html {
+    if props.values.is_empty() {
+        return empty_chart();
+    }
+
+    let y_max = props
+        .y_max
+        .unwrap_or_else(|| props.values.iter().cloned().fold(0.0, f64::max))
+        .max(1.0);
+    //A single value still gets a flat line across the chart
+    let values = if props.values.len() == 1 {
+        vec![props.values[0]; 2]
+    } else {
+        props.values.clone()
+    };
+    let plot_width = CHART_WIDTH - CHART_PADDING;
+    let plot_height = CHART_HEIGHT - CHART_PADDING;
+    let x_step = plot_width / (values.len() - 1) as f64;
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let x = CHART_PADDING + i as f64 * x_step;
+            let y = plot_height - (value / y_max) * plot_height;
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    html! {
+        <svg
+            class="w-full h-auto"
+            viewBox={format!("0 0 {} {}", CHART_WIDTH, CHART_HEIGHT)}
+            xmlns="http://www.w3.org/2000/svg"
+        >
+            { axes(y_max, &props.y_suffix) }
+            <polyline
+                points={points}
+                fill="none"
+                stroke-width="2"
+                stroke-linejoin="round"
+                class="stroke-primary"
+            />
+        </svg>
+    }
+}
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct BarChartProps {
+    /// The label and value of each bar, drawn left to right
+    pub bars: Vec<(String, f64)>,
+}
+
+#[function_component(BarChart)]
+pub fn bar_chart(props: &BarChartProps) -> Html {
+    if props.bars.iter().all(|(_, value)| *value == 0.0) {
+        return empty_chart();
+    }
+
+    let y_max = props
+        .bars
+        .iter()
+        .map(|(_, value)| *value)
+        .fold(0.0, f64::max)
+        .max(1.0);
+    let plot_width = CHART_WIDTH - CHART_PADDING;
+    let plot_height = CHART_HEIGHT - CHART_PADDING;
+    let slot_width = plot_width / props.bars.len() as f64;
+    let bar_width = (slot_width * 0.8).max(1.0);
+    //Keeps the labels from running into each other on charts with a lot of bars
+    let label_every = props.bars.len().div_ceil(8);
+
+    html! {
+        <svg
+            class="w-full h-auto"
+            viewBox={format!("0 0 {} {}", CHART_WIDTH, CHART_HEIGHT)}
+            xmlns="http://www.w3.org/2000/svg"
+        >
+            { axes(y_max, "") }
+            { props.bars.iter().enumerate().map(|(i, (label, value))| {
+                    let height = (value / y_max) * plot_height;
+                    let x = CHART_PADDING + i as f64 * slot_width + (slot_width - bar_width) / 2.0;
+                    html! {
+                        <g key={i}>
+                            <rect
+                                x={format!("{:.1}", x)}
+                                y={format!("{:.1}", plot_height - height)}
+                                width={format!("{:.1}", bar_width)}
+                                height={format!("{:.1}", height)}
+                                class="fill-primary"
+                            >
+                                <title>{ format!("{}: {}", label, value) }</title>
+                            </rect>
+                            if i % label_every == 0 {
+                                <text
+                                    x={format!("{:.1}", x + bar_width / 2.0)}
+                                    y={(CHART_HEIGHT - CHART_PADDING + 12.0).to_string()}
+                                    text-anchor="middle"
+                                    class="fill-base-content text-[8px]"
+                                >
+                                    { label }
+                                </text>
+                            }
+                        </g>
+                    }
+                }).collect::<Html>() }
+        </svg>
+    }
+}
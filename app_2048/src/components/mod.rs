@@ -1 +1,2 @@
+pub mod charts;
 pub mod theme_picker;
@@ -0,0 +1,303 @@
+use crate::idb::{CHALLENGE_STORE, DB_NAME, StorageError, object_get_all, transaction_put};
+use crate::resolver::ApiDNSTxtResolver;
+use atrium_api::types::Collection;
+use atrium_api::types::string::{Datetime, Did, Handle, RecordKey};
+use atrium_common::resolver::Resolver;
+use atrium_identity::did::{CommonDidResolver, CommonDidResolverConfig, DEFAULT_PLC_DIRECTORY_URL};
+use atrium_identity::handle::{AtprotoHandleResolver, AtprotoHandleResolverConfig};
+use atrium_oauth::DefaultHttpClient;
+use gloo::net::http::Request;
+use indexed_db_futures::database::Database;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use twothousand_forty_eight::unified::game::GameState;
+use twothousand_forty_eight::v2::recording::SeededRecording;
+use types_2048::blue;
+use types_2048::blue::_2048::{challenge, game};
+
+/// Most records a PDS hands back per listRecords page
+const LIST_RECORDS_LIMIT: usize = 100;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DuelError {
+    /// The handle or DID could not be turned into a PDS to read from
+    Resolve(String),
+    /// The PDS did not give back the record
+    Fetch(String),
+}
+
+impl std::fmt::Display for DuelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DuelError::Resolve(err) => write!(f, "Could not find that player: {}", err),
+            DuelError::Fetch(err) => write!(f, "Could not load the duel: {}", err),
+        }
+    }
+}
+
+/// A challenge sent or received, kept locally so the duels page can list them
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredChallenge {
+    /// The repo the challenge lives in
+    pub challenger: Did,
+    pub rkey: RecordKey,
+    pub record: challenge::RecordData,
+}
+
+impl StoredChallenge {
+    pub fn uri(&self) -> String {
+        record_uri(&self.challenger, blue::_2048::Challenge::NSID, &self.rkey)
+    }
+}
+
+pub fn record_uri(did: &Did, collection: &str, rkey: &RecordKey) -> String {
+    format!("at://{}/{}/{}", did.as_str(), collection, rkey.as_str())
+}
+
+/// Splits an at:// uri into its repo, collection and record key
+fn parse_record_uri(uri: &str) -> Option<(Did, String, RecordKey)> {
+    let mut parts = uri.strip_prefix("at://")?.split('/');
+    let did = parts.next()?.parse().ok()?;
+    let collection = parts.next()?.to_string();
+    let rkey = parts.next()?.parse().ok()?;
+    Some((did, collection, rkey))
+}
+
+pub async fn save_challenge(challenge: &StoredChallenge) -> Result<(), StorageError> {
+    let db = Database::open(DB_NAME)
+        .await
+        .map_err(|err| StorageError::OpenDbError(err.to_string()))?;
+    transaction_put(
+        db,
+        challenge.clone(),
+        CHALLENGE_STORE,
+        Some(challenge.uri()),
+    )
+    .await
+}
+
+pub async fn get_local_challenges() -> Result<Vec<StoredChallenge>, StorageError> {
+    let db = Database::open(DB_NAME)
+        .await
+        .map_err(|err| StorageError::OpenDbError(err.to_string()))?;
+    object_get_all::<StoredChallenge>(db, CHALLENGE_STORE).await
+}
+
+/// Turns a handle (with or without the @) or a DID into a DID
+pub async fn resolve_player(handle_or_did: &str) -> Result<Did, DuelError> {
+    let handle_or_did = handle_or_did.trim().trim_start_matches('@');
+    if let Ok(did) = handle_or_did.parse::<Did>() {
+        return Ok(did);
+    }
+    let handle: Handle = handle_or_did
+        .parse()
+        .map_err(|err: &str| DuelError::Resolve(err.to_string()))?;
+    let http_client = Arc::new(DefaultHttpClient::default());
+    AtprotoHandleResolver::new(AtprotoHandleResolverConfig {
+        dns_txt_resolver: ApiDNSTxtResolver,
+        http_client,
+    })
+    .resolve(&handle)
+    .await
+    .map_err(|err| DuelError::Resolve(err.to_string()))
+}
+
+/// The PDS a player's records are read from. Reads are public so no session is needed
+async fn pds_endpoint(did: &Did) -> Result<String, DuelError> {
+    let http_client = Arc::new(DefaultHttpClient::default());
+    let did_document = CommonDidResolver::new(CommonDidResolverConfig {
+        plc_directory_url: DEFAULT_PLC_DIRECTORY_URL.to_string(),
+        http_client,
+    })
+    .resolve(did)
+    .await
+    .map_err(|err| DuelError::Resolve(err.to_string()))?;
+    did_document
+        .get_pds_endpoint()
+        .ok_or_else(|| DuelError::Resolve(format!("{} has no PDS", did.as_str())))
+}
+
+#[derive(Deserialize)]
+struct PublicRecord<R> {
+    uri: String,
+    value: R,
+}
+
+#[derive(Deserialize)]
+struct PublicRecordPage<R> {
+    cursor: Option<String>,
+    records: Vec<PublicRecord<R>>,
+}
+
+async fn fetch_json<T: DeserializeOwned>(url: &str) -> Result<T, DuelError> {
+    let response = Request::get(url)
+        .send()
+        .await
+        .map_err(|err| DuelError::Fetch(err.to_string()))?;
+    if !response.ok() {
+        return Err(DuelError::Fetch(format!(
+            "{} {}",
+            response.status(),
+            response.status_text()
+        )));
+    }
+    response
+        .json::<T>()
+        .await
+        .map_err(|err| DuelError::Fetch(err.to_string()))
+}
+
+/// Reads a record straight from the owner's PDS
+pub async fn get_public_record<R: DeserializeOwned>(
+    did: &Did,
+    collection: &str,
+    rkey: &RecordKey,
+) -> Result<R, DuelError> {
+    let pds = pds_endpoint(did).await?;
+    let url = format!(
+        "{}/xrpc/com.atproto.repo.getRecord?repo={}&collection={}&rkey={}",
+        pds.trim_end_matches('/'),
+        urlencoding::encode(did.as_str()),
+        collection,
+        urlencoding::encode(rkey.as_str())
+    );
+    let record: PublicRecord<R> = fetch_json(&url).await?;
+    Ok(record.value)
+}
+
+/// Every record in one of a player's collections, with its at:// uri
+async fn list_public_records<R: DeserializeOwned>(
+    did: &Did,
+    collection: &str,
+) -> Result<Vec<(String, R)>, DuelError> {
+    let pds = pds_endpoint(did).await?;
+    let mut records = vec![];
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut url = format!(
+            "{}/xrpc/com.atproto.repo.listRecords?repo={}&collection={}&limit={}",
+            pds.trim_end_matches('/'),
+            urlencoding::encode(did.as_str()),
+            collection,
+            LIST_RECORDS_LIMIT
+        );
+        if let Some(cursor) = &cursor {
+            url.push_str(&format!("&cursor={}", urlencoding::encode(cursor)));
+        }
+        let page: PublicRecordPage<R> = fetch_json(&url).await?;
+        let page_size = page.records.len();
+        records.extend(
+            page.records
+                .into_iter()
+                .map(|record| (record.uri, record.value)),
+        );
+        match page.cursor {
+            Some(next) if page_size > 0 => cursor = Some(next),
+            _ => break,
+        }
+    }
+    Ok(records)
+}
+
+/// A game on the duel's seed whose recording replays to the score it claims
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifiedGame {
+    pub uri: String,
+    pub score: i64,
+    pub created_at: Datetime,
+}
+
+/// Replays the game and checks it was played on the challenge's seed and board
+fn verify_game(
+    uri: String,
+    game: &game::RecordData,
+    seed: u32,
+    size: usize,
+) -> Option<VerifiedGame> {
+    let recording: SeededRecording = game.seeded_recording.parse().ok()?;
+    if recording.seed != seed || recording.width != size || recording.height != size {
+        return None;
+    }
+    let gamestate = GameState::from_reconstructable_ruleset(&recording).ok()?;
+    //A record claiming a score its moves do not add up to was edited after the fact
+    if gamestate.score_current as i64 != game.current_score || !game.completed {
+        return None;
+    }
+    Some(VerifiedGame {
+        uri,
+        score: game.current_score,
+        created_at: game.created_at.clone(),
+    })
+}
+
+/// A player's first verified game on the seed since the challenge was sent. Only the first one
+/// counts so the seed can not be replayed till it goes well
+async fn first_game_on_seed(
+    did: &Did,
+    challenge: &challenge::RecordData,
+) -> Result<Option<VerifiedGame>, DuelError> {
+    let seed = challenge.seed as u32;
+    let size = challenge_size(challenge);
+    let games = list_public_records::<game::RecordData>(did, blue::_2048::Game::NSID).await?;
+    Ok(games
+        .into_iter()
+        .filter(|(_, game)| game.created_at >= challenge.created_at)
+        .filter_map(|(uri, game)| verify_game(uri, &game, seed, size))
+        .min_by(|a, b| a.created_at.cmp(&b.created_at)))
+}
+
+pub fn challenge_size(challenge: &challenge::RecordData) -> usize {
+    challenge.size.unwrap_or(4) as usize
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuelOutcome {
+    /// One or both players still have to play the seed
+    Waiting,
+    ChallengerWon,
+    OpponentWon,
+    Tie,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuelResult {
+    pub challenger_game: Option<VerifiedGame>,
+    pub opponent_game: Option<VerifiedGame>,
+}
+
+impl DuelResult {
+    pub fn outcome(&self) -> DuelOutcome {
+        match (&self.challenger_game, &self.opponent_game) {
+            (Some(challenger), Some(opponent)) => match challenger.score.cmp(&opponent.score) {
+                std::cmp::Ordering::Greater => DuelOutcome::ChallengerWon,
+                std::cmp::Ordering::Less => DuelOutcome::OpponentWon,
+                std::cmp::Ordering::Equal => DuelOutcome::Tie,
+            },
+            _ => DuelOutcome::Waiting,
+        }
+    }
+}
+
+/// Looks up both players' games on the seed and checks them. The challenger's game is the one
+/// the challenge points to, or their first game on the seed after it when it points to none
+pub async fn decide(
+    challenger: &Did,
+    challenge: &challenge::RecordData,
+) -> Result<DuelResult, DuelError> {
+    let seed = challenge.seed as u32;
+    let size = challenge_size(challenge);
+    let challenger_game = match challenge.game.as_deref().and_then(parse_record_uri) {
+        Some((did, collection, rkey)) if did == *challenger => {
+            let game: game::RecordData = get_public_record(&did, &collection, &rkey).await?;
+            let uri = record_uri(&did, &collection, &rkey);
+            verify_game(uri, &game, seed, size)
+        }
+        _ => first_game_on_seed(challenger, challenge).await?,
+    };
+    let opponent_game = first_game_on_seed(&challenge.opponent, challenge).await?;
+    Ok(DuelResult {
+        challenger_game,
+        opponent_game,
+    })
+}
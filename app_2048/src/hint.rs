@@ -0,0 +1,23 @@
+use crate::idb::StorageError;
+use ai_2048::SearchConfig;
+use ai_2048::analysis::GameAnalysis;
+use twothousand_forty_eight::v2::recording::SeededRecording;
+
+/// Kept quick since every move of the game gets searched
+pub const ANALYSIS_SEARCH: SearchConfig = SearchConfig {
+    depth: 2,
+    time_budget_ms: 50,
+};
+
+/// Every move of a game scored against the engine. Runs in the storage worker so the page stays
+/// responsive while it thinks
+pub fn analyze_game(
+    seeded_recording: &str,
+    search: &SearchConfig,
+) -> Result<GameAnalysis, StorageError> {
+    let recording: SeededRecording = seeded_recording
+        .parse()
+        .map_err(|err| StorageError::Error(format!("{:?}", err)))?;
+    ai_2048::analysis::analyze(&recording, search)
+        .map_err(|err| StorageError::Error(err.to_string()))
+}
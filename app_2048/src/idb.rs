@@ -17,6 +17,10 @@ pub const CURRENT_GAME_STORE: &str = "current_game";
 pub const STATS_STORE: &str = "stats";
 /// Store for the user profile(blue.2048.player.profile) self as the key
 pub const PROFILE_STORE: &str = "profile";
+/// Store for the user's unlocked achievements(blue.2048.player.achievement), keys are the achievement ids
+pub const ACHIEVEMENT_STORE: &str = "achievements";
+/// Store for duels sent or received(blue.2048.challenge), keys are the challenge's at:// uri
+pub const CHALLENGE_STORE: &str = "challenges";
 /// Store for did:keys like blue.2048.key.game or blue.2048.key.player.stats
 pub const KEY_STORE: &str = "did:keys";
 /// did resolver store
@@ -31,31 +35,35 @@ pub const SELF_KEY: &str = "self";
 
 pub async fn create_database() -> Result<Database, OpenDbError> {
     let db = Database::open(DB_NAME)
-        .with_version(1u8)
+        .with_version(3u8)
         .with_on_blocked(|event| {
             log::debug!("DB upgrade blocked: {:?}", event);
             Ok(())
         })
         .with_on_upgrade_needed_fut(|event, db| async move {
-            match (event.old_version(), event.new_version()) {
-                (0.0, Some(1.0)) => {
-                    let record_key_path = KeyPath::from("rkey");
-                    let game_store = db
-                        .create_object_store(GAME_STORE)
-                        .with_key_path(record_key_path.clone())
-                        .build()?;
-                    game_store
-                        .create_index("index_hash", KeyPath::from("index_hash"))
-                        .build()?;
-                    db.create_object_store(CURRENT_GAME_STORE).build()?;
-                    db.create_object_store(STATS_STORE).build()?;
-                    db.create_object_store(PROFILE_STORE).build()?;
-                    db.create_object_store(KEY_STORE).build()?;
-                    db.create_object_store(DID_RESOLVER_STORE).build()?;
-                    db.create_object_store(STATE_STORE).build()?;
-                    db.create_object_store(SESSIONS_STORE).build()?;
-                }
-                _ => {}
+            let old_version = event.old_version();
+            if old_version < 1.0 {
+                let record_key_path = KeyPath::from("rkey");
+                let game_store = db
+                    .create_object_store(GAME_STORE)
+                    .with_key_path(record_key_path.clone())
+                    .build()?;
+                game_store
+                    .create_index("index_hash", KeyPath::from("index_hash"))
+                    .build()?;
+                db.create_object_store(CURRENT_GAME_STORE).build()?;
+                db.create_object_store(STATS_STORE).build()?;
+                db.create_object_store(PROFILE_STORE).build()?;
+                db.create_object_store(KEY_STORE).build()?;
+                db.create_object_store(DID_RESOLVER_STORE).build()?;
+                db.create_object_store(STATE_STORE).build()?;
+                db.create_object_store(SESSIONS_STORE).build()?;
+            }
+            if old_version < 2.0 {
+                db.create_object_store(ACHIEVEMENT_STORE).build()?;
+            }
+            if old_version < 3.0 {
+                db.create_object_store(CHALLENGE_STORE).build()?;
             }
 
             Ok(())
@@ -169,6 +177,38 @@ where
     }
 }
 
+pub async fn object_get_all<T>(db: Database, store: &str) -> Result<Vec<T>, StorageError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let transaction = match db
+        .transaction(store)
+        .with_mode(TransactionMode::Readonly)
+        .build()
+    {
+        Ok(transaction) => transaction,
+        Err(err) => {
+            return Err(StorageError::Error(err.to_string()));
+        }
+    };
+
+    let store = match transaction.object_store(store) {
+        Ok(store) => store,
+        Err(err) => {
+            return Err(StorageError::Error(err.to_string()));
+        }
+    };
+    match store.get_all().serde() {
+        Ok(action) => match action.await {
+            Ok(records) => records
+                .collect::<Result<Vec<T>, _>>()
+                .map_err(|err| StorageError::Error(err.to_string())),
+            Err(err) => Err(StorageError::Error(err.to_string())),
+        },
+        Err(err) => Err(StorageError::Error(err.to_string())),
+    }
+}
+
 pub async fn object_get_index<T>(
     db: Database,
     store: &str,
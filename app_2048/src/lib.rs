@@ -4,10 +4,15 @@ use crate::components::theme_picker::ThemePicker;
 use crate::idb::{DB_NAME, SESSIONS_STORE, object_delete};
 use crate::oauth_client::oauth_client;
 use crate::pages::callback::CallbackPage;
+use crate::pages::duels::{DuelPage, DuelsPage};
 use crate::pages::game::GamePage;
 use crate::pages::login::LoginPage;
+use crate::pages::profile::ProfilePage;
+use crate::pages::race::{RacePage, RacesPage};
+use crate::pages::replay::ReplayPage;
 use crate::pages::seed::SeedPage;
 use crate::pages::stats::StatsPage;
+use crate::pages::watch::WatchPage;
 use crate::store::UserStore;
 use atrium_api::agent::Agent;
 use gloo_utils::document;
@@ -21,13 +26,20 @@ use yew_hooks::use_effect_once;
 use yew_router::prelude::*;
 use yewdux::use_store;
 
+pub mod achievements;
 pub mod agent;
+pub mod api;
 pub mod at_repo_sync;
 mod atrium_stores;
 mod components;
+pub mod duels;
+pub mod hint;
 pub mod idb;
+pub mod live;
 pub mod oauth_client;
 mod pages;
+pub mod race;
+pub mod ranked;
 mod resolver;
 pub mod store;
 
@@ -41,10 +53,24 @@ enum Route {
     CallbackPage,
     #[at("/stats")]
     StatsPage,
+    #[at("/profile")]
+    ProfilePage,
     #[at("/seed/:seed")]
     SeedPage { seed: u32 },
     #[at("/seed")]
     SeedPageNoSeed,
+    #[at("/replay")]
+    ReplayPage,
+    #[at("/duels")]
+    DuelsPage,
+    #[at("/duel/:did/:rkey")]
+    DuelPage { did: String, rkey: String },
+    #[at("/watch/:did")]
+    WatchPage { did: String },
+    #[at("/race")]
+    RacesPage,
+    #[at("/race/:id")]
+    RacePage { id: String },
     #[not_found]
     #[at("/404")]
     NotFound,
@@ -56,8 +82,15 @@ fn switch(routes: Route) -> Html {
         Route::LoginPage => html! { <LoginPage /> },
         Route::CallbackPage => html! { <CallbackPage /> },
         Route::StatsPage => html! { <StatsPage /> },
+        Route::ProfilePage => html! { <ProfilePage /> },
         Route::SeedPage { seed } => html! { <SeedPage starting_seed={seed} /> },
         Route::SeedPageNoSeed => html! { <SeedPage starting_seed={None} /> },
+        Route::ReplayPage => html! { <ReplayPage /> },
+        Route::DuelsPage => html! { <DuelsPage /> },
+        Route::DuelPage { did, rkey } => html! { <DuelPage did={did} rkey={rkey} /> },
+        Route::WatchPage { did } => html! { <WatchPage did={did} /> },
+        Route::RacesPage => html! { <RacesPage /> },
+        Route::RacePage { id } => html! { <RacePage id={id} /> },
         Route::NotFound => html! { <h1>{ "404" }</h1> },
     }
 }
@@ -137,6 +170,12 @@ fn Main() -> Html {
                                 log::error!("Error syncing stats: {:?}", err.to_string());
                             }
                         }
+                        match at_repo_sync.sync_achievements().await {
+                            Ok(_) => {}
+                            Err(err) => {
+                                log::error!("Error syncing achievements: {:?}", err.to_string());
+                            }
+                        }
                     }
                 }
             });
@@ -148,6 +187,9 @@ fn Main() -> Html {
     let mut links: Vec<Html> = vec![
         html! {<li key=1 onclick={menu_entry_onclick.clone()}><Link<Route> to={Route::GamePage}>{ "Play" }</Link<Route>></li>},
         html! {<li key=2 onclick={menu_entry_onclick.clone()}><Link<Route> to={Route::StatsPage}>{ "Stats" }</Link<Route>></li>},
+        html! {<li key=5 onclick={menu_entry_onclick.clone()}><Link<Route> to={Route::ProfilePage}>{ "Profile" }</Link<Route>></li>},
+        html! {<li key=6 onclick={menu_entry_onclick.clone()}><Link<Route> to={Route::DuelsPage}>{ "Duels" }</Link<Route>></li>},
+        html! {<li key=7 onclick={menu_entry_onclick.clone()}><Link<Route> to={Route::RacesPage}>{ "Race" }</Link<Route>></li>},
     ];
 
     if user_store.did.is_some() {
@@ -0,0 +1,105 @@
+use atrium_api::types::string::Did;
+use client_2048::types::LiveUpdate;
+use gloo::storage::{SessionStorage, Storage};
+use serde::Deserialize;
+use std::cell::RefCell;
+use yew::platform::spawn_local;
+
+/// Where the stream's token is kept so a reloaded tab can keep streaming
+const LIVE_TOKEN_KEY: &str = "live_stream_token";
+/// The method the service auth token that starts a stream is for, matching the api
+const UPDATE_LIVE_GAME_METHOD: &str = "blue.2048.live.updateGame";
+
+/// The game a player is streaming, as the api sends it to spectators
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct LiveGame {
+    pub did: String,
+    pub seeded_recording: String,
+    pub score: usize,
+    pub finished: bool,
+}
+
+/// Only one update is in flight at a time. Moves made while it is out replace each other and the
+/// newest one goes next, every update has the whole recording so nothing is lost
+#[derive(Default)]
+struct Outbox {
+    sending: bool,
+    pending: Option<(Did, String, bool)>,
+}
+
+thread_local! {
+    static OUTBOX: RefCell<Outbox> = RefCell::new(Outbox::default());
+}
+
+/// The websocket spectators connect to, on the same host the app is served from
+pub fn watch_socket_url(did: &str) -> String {
+    let location = gloo::utils::window().location();
+    let scheme = match location.protocol().as_deref() {
+        Ok("https:") => "wss",
+        _ => "ws",
+    };
+    format!(
+        "{}://{}/api/live/{}/watch",
+        scheme,
+        location.host().unwrap_or_default(),
+        did
+    )
+}
+
+/// The stream's token stands in for the player once a stream has started. Starting one, or
+/// starting over after it went idle, signs in as the player
+async fn send_update(did: &Did, seeded_recording: String, finished: bool) -> Result<(), String> {
+    let mut token: Option<String> = SessionStorage::get(LIVE_TOKEN_KEY).ok();
+    loop {
+        let client = match token {
+            Some(_) => crate::api::client(),
+            None => crate::api::player_client(did, UPDATE_LIVE_GAME_METHOD).await?,
+        };
+        let update = LiveUpdate {
+            token: token.clone(),
+            seeded_recording: seeded_recording.clone(),
+            finished,
+        };
+        match client.update_live_game(did.as_str(), &update).await {
+            Ok(live_token) => {
+                return SessionStorage::set(LIVE_TOKEN_KEY, live_token.into_inner().token)
+                    .map_err(|err| err.to_string());
+            }
+            Err(err)
+                if token.is_some() && err.status() == Some(reqwest::StatusCode::UNAUTHORIZED) =>
+            {
+                token = None;
+            }
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+}
+
+/// Sends the game so far to the api so spectators on /watch/{did} see the move
+pub fn stream_move(did: Did, seeded_recording: String, finished: bool) {
+    let send_now = OUTBOX.with_borrow_mut(|outbox| {
+        if outbox.sending {
+            outbox.pending = Some((did.clone(), seeded_recording.clone(), finished));
+            false
+        } else {
+            outbox.sending = true;
+            true
+        }
+    });
+    if !send_now {
+        return;
+    }
+    spawn_local(async move {
+        let mut next = Some((did, seeded_recording, finished));
+        while let Some((did, seeded_recording, finished)) = next {
+            if let Err(err) = send_update(&did, seeded_recording, finished).await {
+                log::error!("Error streaming the move: {}", err);
+            }
+            next = OUTBOX.with_borrow_mut(|outbox| {
+                let pending = outbox.pending.take();
+                outbox.sending = pending.is_some();
+                pending
+            });
+        }
+    });
+}
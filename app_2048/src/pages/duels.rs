@@ -0,0 +1,551 @@
+use crate::Route;
+use crate::at_repo_sync::AtRepoSync;
+use crate::duels::{
+    DuelOutcome, DuelResult, StoredChallenge, challenge_size, decide, get_local_challenges,
+    get_public_record, record_uri, resolve_player, save_challenge,
+};
+use crate::idb::{DB_NAME, GAME_STORE, RecordStorageWrapper, object_get_all};
+use crate::oauth_client::oauth_client;
+use crate::store::{MAX_BOARD_SIZE, MIN_BOARD_SIZE, UserStore};
+use atrium_api::agent::Agent;
+use atrium_api::types::Collection;
+use atrium_api::types::LimitedU32;
+use atrium_api::types::string::{Datetime, Did, RecordKey, Tid};
+use indexed_db_futures::database::Database;
+use js_sys::encode_uri_component;
+use serde::{Deserialize, Serialize};
+use twothousand_forty_eight::v2::recording::SeededRecording;
+use types_2048::blue;
+use types_2048::blue::_2048::defs::SyncStatusData;
+use types_2048::blue::_2048::{challenge, game};
+use web_sys::{Event, HtmlInputElement, HtmlSelectElement, InputEvent, SubmitEvent};
+use yew::platform::spawn_local;
+use yew::{
+    Callback, Html, Properties, TargetCast, function_component, html, use_effect_with, use_state,
+    use_state_eq,
+};
+use yew_hooks::use_effect_once;
+use yew_router::components::Link;
+use yew_router::hooks::use_location;
+use yewdux::use_store;
+
+/// Query params on a link to the duels page, filled in from a finished game
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct DuelsQuery {
+    pub seed: Option<u32>,
+    pub size: Option<usize>,
+}
+
+fn duel_url(challenge: &StoredChallenge) -> String {
+    format!(
+        "https://2048.symm.app/duel/{}/{}",
+        challenge.challenger.as_str(),
+        challenge.rkey.as_str()
+    )
+}
+
+/// The player's best game on the seed that made it to their AT repo, so the opponent can read it
+async fn best_synced_game_on_seed(did: &Did, seed: u32, size: usize) -> Option<String> {
+    let db = Database::open(DB_NAME).await.ok()?;
+    let games = object_get_all::<RecordStorageWrapper<game::RecordData>>(db, GAME_STORE)
+        .await
+        .ok()?;
+    games
+        .into_iter()
+        .filter(|game| game.record.sync_status.synced_with_at_repo)
+        .filter(|game| {
+            game.record
+                .seeded_recording
+                .parse::<SeededRecording>()
+                .is_ok_and(|recording| recording.seed == seed && recording.width == size)
+        })
+        .max_by_key(|game| game.record.current_score)
+        .map(|game| record_uri(did, blue::_2048::Game::NSID, &game.rkey))
+}
+
+async fn send_challenge(
+    did: Did,
+    opponent: &str,
+    seed: u32,
+    size: usize,
+) -> Result<StoredChallenge, String> {
+    let opponent = resolve_player(opponent)
+        .await
+        .map_err(|err| err.to_string())?;
+    if opponent == did {
+        return Err("You can not challenge yourself".to_string());
+    }
+    let new_challenge = challenge::RecordData {
+        created_at: Datetime::now(),
+        game: best_synced_game_on_seed(&did, seed, size).await,
+        opponent,
+        seed: seed as i64,
+        size: Some(size as i64),
+        sync_status: SyncStatusData {
+            created_at: Datetime::now(),
+            hash: "".to_string(),
+            synced_with_at_repo: false,
+            updated_at: Datetime::now(),
+        }
+        .into(),
+    };
+    let session = oauth_client()
+        .await
+        .restore(&did)
+        .await
+        .map_err(|err| err.to_string())?;
+    AtRepoSync::new_logged_in_repo(Agent::new(session), did)
+        .create_a_new_challenge(new_challenge, Tid::now(LimitedU32::MIN))
+        .await
+        .map_err(|err| err.to_string())
+}
+
+fn challenge_row(challenge: &StoredChallenge, other_player: &Did) -> Html {
+    let size = challenge_size(&challenge.record);
+    html! {
+        <tr key={challenge.uri()}>
+            <td class="max-w-40 truncate">{ other_player.as_str() }</td>
+            <td>{ challenge.record.seed }</td>
+            <td>{ format!("{}x{}", size, size) }</td>
+            <td>
+                <Link<Route>
+                    to={Route::DuelPage { did: challenge.challenger.to_string(), rkey: challenge.rkey.to_string() }}
+                    classes="btn btn-xs btn-outline"
+                >
+                    { "View" }
+                </Link<Route>>
+            </td>
+        </tr>
+    }
+}
+
+fn challenge_table(title: &str, empty: &str, rows: Vec<Html>) -> Html {
+    html! {
+        <div class="card bg-base-100 shadow-xl">
+            <div class="card-body">
+                <h3 class="card-title">{ title }</h3>
+                if rows.is_empty() {
+                    <p class="text-base-content/70">{ empty }</p>
+                } else {
+                    <div class="overflow-x-auto">
+                        <table class="table table-sm">
+                            <thead>
+                                <tr>
+                                    <th>{ "Player" }</th>
+                                    <th>{ "Seed" }</th>
+                                    <th>{ "Size" }</th>
+                                    <th />
+                                </tr>
+                            </thead>
+                            <tbody>{ for rows }</tbody>
+                        </table>
+                    </div>
+                }
+            </div>
+        </div>
+    }
+}
+
+#[function_component(DuelsPage)]
+pub fn duels() -> Html {
+    let (user_store, _) = use_store::<UserStore>();
+    let duels_query = use_location()
+        .and_then(|location| location.query::<DuelsQuery>().ok())
+        .unwrap_or_default();
+    let opponent = use_state_eq(String::new);
+    let seed = use_state_eq(|| duels_query.seed.unwrap_or(0));
+    let board_size = use_state_eq(|| {
+        duels_query
+            .size
+            .filter(|size| (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(size))
+            .unwrap_or(4)
+    });
+    let sending = use_state_eq(|| false);
+    let error = use_state_eq(|| None::<String>);
+    //The challenge that was just sent and who it went to, so it can be posted
+    let sent = use_state(|| None::<(StoredChallenge, String)>);
+    let challenges = use_state(|| None::<Vec<StoredChallenge>>);
+
+    {
+        let challenges = challenges.clone();
+        use_effect_with(sent.is_some(), move |_| {
+            spawn_local(async move {
+                match get_local_challenges().await {
+                    Ok(local_challenges) => challenges.set(Some(local_challenges)),
+                    Err(err) => {
+                        log::error!("Error getting local challenges: {:?}", err.to_string());
+                        challenges.set(Some(vec![]));
+                    }
+                }
+            });
+            || ()
+        });
+    }
+
+    let Some(did) = user_store.did.clone() else {
+        return html! {
+            <div class="flex flex-col items-center justify-center h-screen bg-base-200">
+                <h1 class="text-3xl font-bold">{ "Duels" }</h1>
+                <p class="text-base-content/70 my-4">
+                    { "Log in with your AT Protocol account to challenge other players on a seed" }
+                </p>
+                <Link<Route> to={Route::LoginPage} classes="btn btn-primary">{ "Login" }</Link<Route>>
+            </div>
+        };
+    };
+
+    let on_opponent_input = {
+        let opponent = opponent.clone();
+        Callback::from(move |event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            opponent.set(input.value());
+        })
+    };
+    let on_seed_input = {
+        let seed = seed.clone();
+        let error = error.clone();
+        Callback::from(move |event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            match input.value().parse::<u32>() {
+                Ok(value) => seed.set(value),
+                Err(_) => error.set(Some("Seed must be a number".to_string())),
+            }
+        })
+    };
+    let on_board_size_change = {
+        let board_size = board_size.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            if let Ok(size) = select.value().parse::<usize>() {
+                board_size.set(size);
+            }
+        })
+    };
+    let onsubmit = {
+        let did = did.clone();
+        let opponent = opponent.clone();
+        let seed = seed.clone();
+        let board_size = board_size.clone();
+        let sending = sending.clone();
+        let error = error.clone();
+        let sent = sent.clone();
+        Callback::from(move |event: SubmitEvent| {
+            event.prevent_default();
+            if opponent.trim().is_empty() {
+                error.set(Some(
+                    "Enter the handle of the player to challenge".to_string(),
+                ));
+                return;
+            }
+            error.set(None);
+            sending.set(true);
+            let did = did.clone();
+            let opponent = (*opponent).clone();
+            let seed = *seed;
+            let size = *board_size;
+            let sending = sending.clone();
+            let error = error.clone();
+            let sent = sent.clone();
+            spawn_local(async move {
+                match send_challenge(did, &opponent, seed, size).await {
+                    Ok(challenge) => sent.set(Some((challenge, opponent))),
+                    Err(err) => {
+                        log::error!("Error sending challenge: {:?}", err);
+                        error.set(Some(err));
+                    }
+                }
+                sending.set(false);
+            });
+        })
+    };
+
+    let local_challenges = (*challenges).clone().unwrap_or_default();
+    let sent_rows = local_challenges
+        .iter()
+        .filter(|challenge| challenge.challenger == did)
+        .map(|challenge| challenge_row(challenge, &challenge.record.opponent))
+        .collect::<Vec<_>>();
+    let incoming_rows = local_challenges
+        .iter()
+        .filter(|challenge| challenge.record.opponent == did)
+        .map(|challenge| challenge_row(challenge, &challenge.challenger))
+        .collect::<Vec<_>>();
+
+    html! {
+        <div class="min-h-screen bg-base-200 p-4">
+            <div class="max-w-4xl mx-auto space-y-4">
+                <div class="card bg-base-100 shadow-xl">
+                    <div class="card-body">
+                        <h2 class="card-title text-3xl font-bold">{ "Duels" }</h2>
+                        <p class="text-base-content/70">
+                            { "Challenge a player to a seed. You both play it once and the higher score wins." }
+                        </p>
+                        <form class="flex flex-col gap-2" {onsubmit}>
+                            <input
+                                type="text"
+                                class="input input-bordered w-full"
+                                placeholder="Their handle, like 2048.blue"
+                                value={(*opponent).clone()}
+                                oninput={on_opponent_input}
+                            />
+                            <div class="flex gap-2">
+                                <input
+                                    type="number"
+                                    class="input input-bordered w-full"
+                                    placeholder="Seed"
+                                    value={seed.to_string()}
+                                    oninput={on_seed_input}
+                                />
+                                <select
+                                    onchange={on_board_size_change}
+                                    class="select select-bordered"
+                                    aria-label="Board size"
+                                >
+                                    { for (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).map(|size| html! {
+                                        <option value={size.to_string()} selected={size == *board_size}>
+                                            { format!("{}x{}", size, size) }
+                                        </option>
+                                    }) }
+                                </select>
+                            </div>
+                            <button type="submit" class="btn btn-primary" disabled={*sending}>
+                                if *sending {
+                                    <span class="loading loading-spinner loading-sm" />
+                                }
+                                { "Send challenge" }
+                            </button>
+                        </form>
+                        if let Some(error) = (*error).clone() {
+                            <p class="text-error">{ error }</p>
+                        }
+                        if let Some((challenge, opponent)) = (*sent).clone() {
+                            <div class="alert alert-success flex flex-col items-start">
+                                <span>{ "Challenge sent! Let them know so they can play it." }</span>
+                                <a
+                                    class="btn btn-sm btn-accent"
+                                    href={format!(
+                                        "https://bsky.app/intent/compose?text={}",
+                                        encode_uri_component(&format!(
+                                            "@{} I challenge you to a duel on seed {} of at://2048. Think you can beat me? {} \n @2048.blue",
+                                            opponent.trim().trim_start_matches('@'),
+                                            challenge.record.seed,
+                                            duel_url(&challenge)
+                                        ))
+                                    )}
+                                    target="_blank"
+                                    rel="noopener noreferrer"
+                                >
+                                    { "Post the challenge" }
+                                </a>
+                            </div>
+                        }
+                    </div>
+                </div>
+                { challenge_table("Incoming", "Challenges sent to you show up here once you open their link", incoming_rows) }
+                { challenge_table("Sent", "You have not challenged anyone yet", sent_rows) }
+            </div>
+        </div>
+    }
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct DuelProps {
+    pub did: String,
+    pub rkey: String,
+}
+
+fn player_score(label: &str, game: Option<i64>) -> Html {
+    html! {
+        <div class="stat">
+            <div class="stat-title">{ label }</div>
+            <div class="stat-value">
+                { game.map_or("-".to_string(), |score| score.to_string()) }
+            </div>
+            if game.is_none() {
+                <div class="stat-desc">{ "Not played yet" }</div>
+            }
+        </div>
+    }
+}
+
+#[function_component(DuelPage)]
+pub fn duel(props: &DuelProps) -> Html {
+    let (user_store, _) = use_store::<UserStore>();
+    let challenge = use_state(|| None::<Result<StoredChallenge, String>>);
+    let result = use_state(|| None::<Result<DuelResult, String>>);
+    //Bumped by the refresh button to look for new games
+    let refresh = use_state_eq(|| 0_u32);
+
+    {
+        let challenge = challenge.clone();
+        let viewer = user_store.did.clone();
+        let props = props.clone();
+        use_effect_once(move || {
+            spawn_local(async move {
+                let (Ok(challenger), Ok(rkey)) =
+                    (props.did.parse::<Did>(), props.rkey.parse::<RecordKey>())
+                else {
+                    challenge.set(Some(Err("This is not a valid duel link".to_string())));
+                    return;
+                };
+                let record = get_public_record::<challenge::RecordData>(
+                    &challenger,
+                    blue::_2048::Challenge::NSID,
+                    &rkey,
+                )
+                .await;
+                match record {
+                    Ok(record) => {
+                        let stored_challenge = StoredChallenge {
+                            challenger,
+                            rkey,
+                            record,
+                        };
+                        //Keeps the duel on the duels page for both players
+                        let is_player = viewer.is_some_and(|viewer| {
+                            viewer == stored_challenge.challenger
+                                || viewer == stored_challenge.record.opponent
+                        });
+                        if is_player && let Err(err) = save_challenge(&stored_challenge).await {
+                            log::error!("Error saving challenge: {:?}", err.to_string());
+                        }
+                        challenge.set(Some(Ok(stored_challenge)));
+                    }
+                    Err(err) => challenge.set(Some(Err(err.to_string()))),
+                }
+            });
+            || ()
+        });
+    }
+
+    {
+        let result = result.clone();
+        let stored_challenge = match &*challenge {
+            Some(Ok(stored_challenge)) => Some(stored_challenge.clone()),
+            _ => None,
+        };
+        use_effect_with(
+            (stored_challenge, *refresh),
+            move |(stored_challenge, _)| {
+                if let Some(stored_challenge) = stored_challenge.clone() {
+                    result.set(None);
+                    spawn_local(async move {
+                        let duel_result =
+                            decide(&stored_challenge.challenger, &stored_challenge.record)
+                                .await
+                                .map_err(|err| err.to_string());
+                        result.set(Some(duel_result));
+                    });
+                }
+                || ()
+            },
+        );
+    }
+
+    let stored_challenge = match &*challenge {
+        None => {
+            return html! {
+                <div class="flex flex-col items-center justify-center h-screen bg-base-200">
+                    <div class="flex items-center justify-center">
+                        <span class="loading loading-spinner loading-lg" />
+                        <h1 class="ml-4 text-3xl font-bold">{ "Loading the duel..." }</h1>
+                    </div>
+                </div>
+            };
+        }
+        Some(Err(err)) => {
+            return html! {
+                <div class="flex flex-col items-center justify-center h-screen bg-base-200">
+                    <h1 class="text-3xl font-bold">{ "This duel could not be found" }</h1>
+                    <p class="text-base-content/70">{ err.clone() }</p>
+                </div>
+            };
+        }
+        Some(Ok(stored_challenge)) => stored_challenge.clone(),
+    };
+
+    let size = challenge_size(&stored_challenge.record);
+    let viewer = user_store.did.clone();
+    let viewer_is_challenger = viewer.as_ref() == Some(&stored_challenge.challenger);
+    let viewer_is_opponent = viewer.as_ref() == Some(&stored_challenge.record.opponent);
+    let on_refresh = {
+        let refresh = refresh.clone();
+        move |_| refresh.set(*refresh + 1)
+    };
+
+    let result_view = match &*result {
+        None => html! {
+            <div class="flex items-center justify-center">
+                <span class="loading loading-spinner loading-md" />
+                <span class="ml-2">{ "Checking both games..." }</span>
+            </div>
+        },
+        Some(Err(err)) => html! {
+            <p class="text-error text-center">{ format!("Could not check the games: {}", err) }</p>
+        },
+        Some(Ok(duel_result)) => {
+            let challenger_score = duel_result.challenger_game.as_ref().map(|game| game.score);
+            let opponent_score = duel_result.opponent_game.as_ref().map(|game| game.score);
+            let outcome = match (
+                duel_result.outcome(),
+                viewer_is_challenger,
+                viewer_is_opponent,
+            ) {
+                (DuelOutcome::Waiting, _, true) if opponent_score.is_none() => "Your turn",
+                (DuelOutcome::Waiting, true, _) if challenger_score.is_none() => "Your turn",
+                (DuelOutcome::Waiting, _, _) => "Waiting on a game",
+                (DuelOutcome::Tie, _, _) => "It's a tie",
+                (DuelOutcome::ChallengerWon, true, _) | (DuelOutcome::OpponentWon, _, true) => {
+                    "You won!"
+                }
+                (DuelOutcome::ChallengerWon, _, true) | (DuelOutcome::OpponentWon, true, _) => {
+                    "You lost"
+                }
+                (DuelOutcome::ChallengerWon, _, _) => "The challenger won",
+                (DuelOutcome::OpponentWon, _, _) => "The opponent won",
+            };
+            //Whoever still has to play gets sent to the seed with the other score to beat
+            let play_link = match (viewer_is_challenger, viewer_is_opponent) {
+                (true, _) if challenger_score.is_none() => Some(opponent_score),
+                (_, true) if opponent_score.is_none() => Some(challenger_score),
+                _ => None,
+            }
+            .map(|score_to_beat| {
+                let mut url = format!("/seed/{}?size={}", stored_challenge.record.seed, size);
+                if let Some(score_to_beat) = score_to_beat {
+                    url.push_str(&format!("&score={}", score_to_beat));
+                }
+                url
+            });
+            html! {
+                <>
+                    <h3 class="text-2xl font-bold text-center">{ outcome }</h3>
+                    <div class="stats stats-vertical md:stats-horizontal shadow">
+                        { player_score(if viewer_is_challenger { "You" } else { "Challenger" }, challenger_score) }
+                        { player_score(if viewer_is_opponent { "You" } else { "Opponent" }, opponent_score) }
+                    </div>
+                    if let Some(play_link) = play_link {
+                        <a class="btn btn-primary" href={play_link}>{ "Play the seed" }</a>
+                    }
+                </>
+            }
+        }
+    };
+
+    html! {
+        <div class="min-h-screen bg-base-200 p-4">
+            <div class="max-w-4xl mx-auto space-y-4">
+                <div class="card bg-base-100 shadow-xl">
+                    <div class="card-body items-center">
+                        <h2 class="card-title text-3xl font-bold">{ "Duel" }</h2>
+                        <p class="text-base-content/70">
+                            { format!("Seed {} on a {}x{} board. Only the first game on the seed after the challenge counts.", stored_challenge.record.seed, size, size) }
+                        </p>
+                        { result_view }
+                        <button class="btn btn-sm btn-outline" onclick={on_refresh}>{ "Refresh" }</button>
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -1,12 +1,21 @@
-use crate::agent::{StorageRequest, StorageResponse, StorageTask};
+use crate::Route;
+use crate::agent::{GameDetails, GameTiming, StorageRequest, StorageResponse, StorageTask};
 use crate::idb::{
     CURRENT_GAME_STORE, DB_NAME, SELF_KEY, STATS_STORE, object_delete, object_get, transaction_put,
 };
-use crate::store::UserStore;
+use crate::live;
+use crate::pages::duels::DuelsQuery;
+use crate::pages::replay::ReplayQuery;
+use crate::store::{
+    DEFAULT_BOARD_SIZE, GAME_MODE_OPTIONS, GameMode, MAX_BOARD_SIZE, MIN_BOARD_SIZE, SettingsStore,
+    UndoLimit, UserStore,
+};
+use ai_2048::Hint;
 use atrium_api::types::string::Datetime;
 use gloo::dialogs::alert;
 use gloo::events::EventListener;
-use gloo::timers::callback::Timeout;
+use gloo::timers::callback::{Interval, Timeout};
+use gloo::timers::future::TimeoutFuture;
 use indexed_db_futures::database::Database;
 use js_sys::encode_uri_component;
 use numfmt::{Formatter, Precision};
@@ -17,17 +26,22 @@ use twothousand_forty_eight::direction::Direction;
 use twothousand_forty_eight::{unified::game::GameState, v2::recording::SeededRecording};
 use types_2048::blue;
 use types_2048::blue::_2048::defs::SyncStatusData;
-use web_sys::{HtmlElement, wasm_bindgen::JsCast, wasm_bindgen::closure::Closure};
+use web_sys::{
+    Event, HtmlElement, HtmlSelectElement, wasm_bindgen::JsCast, wasm_bindgen::closure::Closure,
+};
 use yew::platform::spawn_local;
 use yew::{
-    Callback, Html, Properties, Reducible, function_component, html, use_effect_with, use_mut_ref,
-    use_node_ref, use_reducer, use_state, use_state_eq,
+    Callback, Html, Properties, Reducible, TargetCast, classes, function_component, html,
+    use_effect_with, use_force_update, use_mut_ref, use_node_ref, use_reducer, use_state,
+    use_state_eq,
 };
 use yew_agent::oneshot::use_oneshot_runner;
 use yew_hooks::use_effect_once;
+use yew_router::prelude::Link;
 use yewdux::use_store;
-use urlencoding;
-use gloo_console::log as gloo_log;
+
+/// How long the autoplay demo waits between moves so they can be followed
+const AUTOPLAY_MOVE_DELAY_MS: u32 = 300;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct State {
@@ -35,9 +49,80 @@ pub(crate) struct State {
     history: SeededRecording,
     message: String,
     hiscore: usize,
+    clock: PlayClock,
+    /// When the first move was made. Time attack games are timed from here on the wall clock,
+    /// pausing the play clock only pauses the play time shown
+    started_at: Option<Datetime>,
+    /// Play time when the first 2048 tile showed up
+    time_to_twenty_forty_eight_ms: Option<u64>,
+    /// Play time when each move in the history was made
+    move_times: Vec<u64>,
+    /// Set when the game was started from a seed someone shared along with their score
+    score_to_beat: Option<i64>,
+    /// Set when the server dealt the seed for a ranked game
+    seed_issued_at: Option<Datetime>,
+    /// How many moves have been taken back this game
+    undo_count: u32,
+    /// How many hints have been shown this game, autoplay moves included
+    hints_used: u32,
+    /// Comes from the player's settings, not saved with the game
+    #[serde(skip)]
+    undo_limit: UndoLimit,
+    mode: GameMode,
+    /// The move that made the first 2048 tile
+    won_at_move: Option<usize>,
+    /// The player picked keep playing when they won
+    keep_playing: bool,
+    /// The player picked end game when they won
+    ended: bool,
+    /// The game has been wrapped up by [State::finish], so ticks past the time limit leave it be
+    #[serde(skip)]
+    wrapped_up: bool,
     // current_game: game::RecordData,
 }
 
+/// Keeps track of active play time. Only runs while the board is visible and focused, so it is
+/// what the player is shown as play time but not what a time attack game is timed on
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct PlayClock {
+    /// Play time from the stretches before the current one
+    banked_ms: u64,
+    /// When the clock was last resumed, from `Date.now()`. `None` while paused
+    running_since: Option<f64>,
+}
+
+impl PlayClock {
+    fn starting_at(banked_ms: u64) -> Self {
+        Self {
+            banked_ms,
+            running_since: None,
+        }
+    }
+
+    /// Total play time including the current running stretch
+    pub fn elapsed_ms(&self) -> u64 {
+        let running_ms = self
+            .running_since
+            .map_or(0.0, |since| (js_sys::Date::now() - since).max(0.0));
+        self.banked_ms + running_ms as u64
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running_since.is_some()
+    }
+
+    fn resume(&mut self) {
+        if self.running_since.is_none() {
+            self.running_since = Some(js_sys::Date::now());
+        }
+    }
+
+    fn pause(&mut self) {
+        self.banked_ms = self.elapsed_ms();
+        self.running_since = None;
+    }
+}
+
 impl PartialEq for State {
     fn eq(&self, other: &Self) -> bool {
         self.history == other.history
@@ -48,6 +133,21 @@ impl PartialEq for State {
 
 pub enum Action {
     Move(Direction),
+    /// Take back the last move if the undo limit allows it
+    Undo,
+    SetUndoLimit(UndoLimit),
+    /// Show the move the engine suggests and count it against the game
+    Hint(Direction),
+    /// The board gained focus or became visible again, start counting play time
+    ResumeClock,
+    /// The board lost focus or was hidden, stop counting play time
+    PauseClock,
+    /// Checks if a time attack game has run out of time
+    Tick,
+    /// Close the win modal and keep going past 2048
+    KeepPlaying,
+    /// Finish the game from the win modal
+    EndGame,
 }
 
 impl Reducible for State {
@@ -56,72 +156,377 @@ impl Reducible for State {
     fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
         match action {
             Action::Move(direction) => {
+                if self.mode_limit_reached() {
+                    return Rc::new(self.finish());
+                }
+                //Nothing moves till the player picks an option in the win modal
+                if self.awaiting_win_choice() {
+                    return self;
+                }
                 if !self.gamestate.allowed_moves.contains(&direction) {
                     let mut message = "".to_string();
                     if self.gamestate.over {
                         message = "Game over".to_string();
                     }
                     return Rc::new(State {
-                        gamestate: self.gamestate.clone(),
-                        history: self.history.clone(),
                         message,
-                        hiscore: self.hiscore,
+                        ..(*self).clone()
                     });
                 }
                 let mut new_history = self.history.clone();
                 new_history.moves.push(direction);
                 let history_string: String = (&new_history).into();
+                let move_time = self.clock.elapsed_ms();
 
                 let mut state = match history_string.parse::<SeededRecording>() {
                     Ok(history) => match GameState::from_reconstructable_ruleset(&history) {
-                        Ok(gamestate) => State {
-                            gamestate: gamestate.clone(),
-                            history,
-                            message: String::new(),
-                            hiscore: self.hiscore.max(gamestate.score_max),
-                        },
+                        Ok(gamestate) => {
+                            let mut move_times = self.move_times.clone();
+                            move_times.push(move_time);
+                            State {
+                                gamestate: gamestate.clone(),
+                                history,
+                                message: String::new(),
+                                hiscore: self.hiscore.max(gamestate.score_max),
+                                clock: self.clock.clone(),
+                                started_at: self
+                                    .started_at
+                                    .clone()
+                                    .or_else(|| Some(Datetime::now())),
+                                time_to_twenty_forty_eight_ms: self
+                                    .time_to_twenty_forty_eight_ms
+                                    .or(gamestate.won.then_some(move_time)),
+                                move_times,
+                                score_to_beat: self.score_to_beat,
+                                seed_issued_at: self.seed_issued_at.clone(),
+                                undo_count: self.undo_count,
+                                hints_used: self.hints_used,
+                                undo_limit: self.undo_limit,
+                                mode: self.mode,
+                                won_at_move: self
+                                    .won_at_move
+                                    .or(gamestate.won.then_some(new_history.moves.len())),
+                                keep_playing: self.keep_playing,
+                                ended: self.ended,
+                                wrapped_up: self.wrapped_up,
+                            }
+                        }
                         Err(e) => {
                             log::error!("{:?}", e);
                             State {
-                                gamestate: self.gamestate.clone(),
-                                history: self.history.clone(),
                                 message: format!("{:?}", e),
-                                hiscore: self.hiscore,
+                                ..(*self).clone()
                             }
                         }
                     },
                     Err(e) => State {
-                        gamestate: self.gamestate.clone(),
-                        history: self.history.clone(),
                         message: format!("{:?}", e),
-                        hiscore: self.hiscore,
+                        ..(*self).clone()
                     },
                 };
+                if state.is_finished() {
+                    state.clock.pause();
+                }
                 let mut state_clone = state.clone();
                 spawn_local(async move {
                     state_clone.save().await;
                 });
-                if state.gamestate.over {
-                    state.message = "Game over".to_string();
+                if state.is_finished() {
+                    state.message = state.finished_message().to_string();
                 }
 
                 //TODO spawn off to a worker to calculate new stats and save them
                 Rc::new(state)
             }
+            Action::Undo => {
+                if self.is_finished() {
+                    return Rc::new(State {
+                        message: self.finished_message().to_string(),
+                        ..(*self).clone()
+                    });
+                }
+                if !self.undo_limit.allows_undo(self.undo_count) {
+                    return Rc::new(State {
+                        message: "No undos left".to_string(),
+                        ..(*self).clone()
+                    });
+                }
+                let mut history = self.history.clone();
+                if history.moves.pop().is_none() {
+                    return Rc::new(State {
+                        message: "Nothing to undo".to_string(),
+                        ..(*self).clone()
+                    });
+                }
+                let gamestate = match GameState::from_reconstructable_ruleset(&history) {
+                    Ok(gamestate) => gamestate,
+                    Err(e) => {
+                        log::error!("{:?}", e);
+                        return Rc::new(State {
+                            message: format!("{:?}", e),
+                            ..(*self).clone()
+                        });
+                    }
+                };
+                let mut move_times = self.move_times.clone();
+                move_times.pop();
+                let mut state = State {
+                    gamestate: gamestate.clone(),
+                    history,
+                    message: String::new(),
+                    //The 2048 does not count if the move that found it was taken back
+                    time_to_twenty_forty_eight_ms: self
+                        .time_to_twenty_forty_eight_ms
+                        .filter(|_| gamestate.won),
+                    won_at_move: self.won_at_move.filter(|_| gamestate.won),
+                    keep_playing: self.keep_playing && gamestate.won,
+                    move_times,
+                    undo_count: self.undo_count + 1,
+                    ..(*self).clone()
+                };
+                spawn_local({
+                    let mut state = state.clone();
+                    async move {
+                        state.save().await;
+                    }
+                });
+                if let Some(remaining) = state.undo_limit.remaining(state.undo_count) {
+                    state.message = format!("{} undos left", remaining);
+                }
+                Rc::new(state)
+            }
+            Action::SetUndoLimit(undo_limit) => Rc::new(State {
+                undo_limit,
+                ..(*self).clone()
+            }),
+            Action::Hint(direction) => {
+                let state = State {
+                    message: format!("Hint: try moving {}", direction_name(direction)),
+                    hints_used: self.hints_used + 1,
+                    ..(*self).clone()
+                };
+                spawn_local({
+                    let mut state = state.clone();
+                    async move {
+                        state.save().await;
+                    }
+                });
+                Rc::new(state)
+            }
+            Action::ResumeClock => {
+                if self.is_finished() || self.clock.is_running() {
+                    return self;
+                }
+                let mut clock = self.clock.clone();
+                clock.resume();
+                Rc::new(State {
+                    clock,
+                    ..(*self).clone()
+                })
+            }
+            Action::PauseClock => {
+                if !self.clock.is_running() {
+                    return self;
+                }
+                let mut clock = self.clock.clone();
+                clock.pause();
+                let state = State {
+                    clock,
+                    ..(*self).clone()
+                };
+                //Keeps the banked play time if the tab is closed while hidden
+                let mut state_clone = state.clone();
+                spawn_local(async move {
+                    state_clone.save().await;
+                });
+                Rc::new(state)
+            }
+            Action::Tick => {
+                //The limit is on the wall clock, so time can run out while the board is hidden
+                //and the play clock is paused
+                if !self.wrapped_up && self.mode_limit_reached() {
+                    Rc::new(self.finish())
+                } else {
+                    self
+                }
+            }
+            Action::KeepPlaying => {
+                let state = State {
+                    keep_playing: true,
+                    message: "Keep going!".to_string(),
+                    ..(*self).clone()
+                };
+                spawn_local({
+                    let mut state = state.clone();
+                    async move {
+                        state.save().await;
+                    }
+                });
+                Rc::new(state)
+            }
+            Action::EndGame => {
+                if !self.awaiting_win_choice() {
+                    return self;
+                }
+                Rc::new(
+                    State {
+                        ended: true,
+                        ..(*self).clone()
+                    }
+                    .finish(),
+                )
+            }
         }
     }
 }
 
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::UP => "up",
+        Direction::DOWN => "down",
+        Direction::LEFT => "left",
+        Direction::RIGHT => "right",
+        _ => "nowhere",
+    }
+}
+
+/// The best move the engine found that the game also allows
+fn hinted_direction(hint: Option<Hint>, allowed_moves: &[Direction]) -> Option<Direction> {
+    hint?
+        .evaluations
+        .into_iter()
+        .map(|(direction, _)| direction)
+        .find(|direction| allowed_moves.contains(direction))
+}
+
 impl State {
-    pub fn new() -> Self {
+    /// A new game on a `board_size` by `board_size` board
+    pub fn new(board_size: usize, mode: GameMode) -> Self {
+        let board_size = board_size.clamp(MIN_BOARD_SIZE, MAX_BOARD_SIZE);
         let random_seed = rand::random();
-        let history = SeededRecording::empty(random_seed, 4, 4);
+        let history = SeededRecording::empty(random_seed, board_size, board_size);
         let gamestate = GameState::from_reconstructable_ruleset(&history).unwrap();
         Self {
             gamestate,
             history,
             message: "".to_string(),
             hiscore: 0,
+            clock: PlayClock::default(),
+            started_at: None,
+            time_to_twenty_forty_eight_ms: None,
+            move_times: vec![],
+            score_to_beat: None,
+            seed_issued_at: None,
+            undo_count: 0,
+            hints_used: 0,
+            undo_limit: UndoLimit::default(),
+            mode,
+            won_at_move: None,
+            keep_playing: false,
+            ended: false,
+            wrapped_up: false,
+        }
+    }
+
+    /// Wall time since the first move, which is what a time attack game is timed on
+    fn time_since_start_ms(&self) -> u64 {
+        self.started_at.as_ref().map_or(0, |started_at| {
+            (js_sys::Date::now() as i64 - started_at.as_ref().timestamp_millis()).max(0) as u64
+        })
+    }
+
+    /// The time or move limit of the game's mode has been hit
+    fn mode_limit_reached(&self) -> bool {
+        match self.mode {
+            GameMode::Classic => false,
+            GameMode::TimeAttack { seconds } => self.time_since_start_ms() >= seconds as u64 * 1000,
+            GameMode::MoveLimit { moves } => self.history.moves.len() >= moves as usize,
+        }
+    }
+
+    /// No more moves can be made, either the board is full, the mode's limit was hit or the
+    /// player ended the game after winning
+    pub fn is_finished(&self) -> bool {
+        self.gamestate.over || self.ended || self.mode_limit_reached()
+    }
+
+    /// A 2048 tile was just made and the player has not picked keep playing or end game yet
+    pub fn awaiting_win_choice(&self) -> bool {
+        self.won_at_move.is_some() && !self.keep_playing && !self.is_finished()
+    }
+
+    /// The highest tile on the board
+    fn max_tile(&self) -> usize {
+        self.gamestate
+            .board
+            .get_occupied_tiles()
+            .iter()
+            .map(|tile| tile.value)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn finished_message(&self) -> &'static str {
+        match self.mode {
+            _ if self.ended && !self.gamestate.over => "You won!",
+            GameMode::TimeAttack { .. } if self.mode_limit_reached() => "Time's up",
+            GameMode::MoveLimit { .. } if self.mode_limit_reached() => "Out of moves",
+            _ => "Game over",
+        }
+    }
+
+    /// Stops the clock and saves once the mode's limit is hit
+    fn finish(&self) -> State {
+        let mut state = State {
+            message: self.finished_message().to_string(),
+            wrapped_up: true,
+            ..self.clone()
+        };
+        state.clock.pause();
+        spawn_local({
+            let mut state = state.clone();
+            async move {
+                state.save().await;
+            }
+        });
+        state
+    }
+
+    /// What is left of a timed or move limited game, shown under the score
+    fn mode_status(&self) -> Option<String> {
+        match self.mode {
+            GameMode::Classic => None,
+            GameMode::TimeAttack { seconds } => {
+                let remaining_seconds = (seconds as u64 * 1000)
+                    .saturating_sub(self.time_since_start_ms())
+                    .div_ceil(1000);
+                Some(format!(
+                    "{}:{:02} left",
+                    remaining_seconds / 60,
+                    remaining_seconds % 60
+                ))
+            }
+            GameMode::MoveLimit { moves } => Some(format!(
+                "{} moves left",
+                (moves as usize).saturating_sub(self.history.moves.len())
+            )),
+        }
+    }
+
+    /// The details captured so far, sent along when the game is completed
+    pub fn details(&self) -> GameDetails {
+        GameDetails {
+            timing: GameTiming {
+                play_time_ms: self.clock.elapsed_ms(),
+                time_to_twenty_forty_eight_ms: self.time_to_twenty_forty_eight_ms,
+                move_times: self.move_times.clone(),
+            },
+            score_to_beat: self.score_to_beat,
+            seed_issued_at: self.seed_issued_at.clone(),
+            started_at: self.started_at.clone(),
+            undo_count: self.undo_count,
+            hints_used: self.hints_used,
+            mode: self.mode,
         }
     }
 
@@ -146,10 +551,19 @@ impl State {
                 Some(current_game) => current_game,
                 None => {
                     let current_game = blue::_2048::game::RecordData {
-                        completed: self.gamestate.over,
+                        completed: self.is_finished(),
                         created_at: Datetime::now(),
                         current_score: self.gamestate.score_current as i64,
+                        hints_used: Some(self.hints_used as i64),
+                        max_tile: Some(self.max_tile() as i64),
+                        mode: Some(self.mode.id().to_string()),
+                        mode_limit: self.mode.limit(),
+                        move_times: Some(self.move_times.iter().map(|ms| *ms as i64).collect()),
+                        play_time: Some(self.clock.elapsed_ms() as i64),
+                        score_to_beat: self.score_to_beat,
+                        seed_issued_at: self.seed_issued_at.clone(),
                         seeded_recording: history_string,
+                        started_at: self.started_at.clone(),
                         sync_status: SyncStatusData {
                             created_at: Datetime::now(),
                             hash: "".to_string(),
@@ -157,7 +571,12 @@ impl State {
                             updated_at: Datetime::now(),
                         }
                         .into(),
+                        time_to_find_twenty_forty_eight: self
+                            .time_to_twenty_forty_eight_ms
+                            .map(|ms| ms as i64),
+                        undo_count: Some(self.undo_count as i64),
                         won: self.gamestate.won,
+                        won_at_move: self.won_at_move.map(|move_number| move_number as i64),
                     };
                     let result = transaction_put(
                         db.clone(),
@@ -183,7 +602,18 @@ impl State {
         };
 
         current_game.current_score = self.gamestate.score_current as i64;
+        current_game.completed = self.is_finished();
         current_game.seeded_recording = history_string;
+        current_game.started_at = self.started_at.clone();
+        current_game.play_time = Some(self.clock.elapsed_ms() as i64);
+        current_game.move_times = Some(self.move_times.iter().map(|ms| *ms as i64).collect());
+        current_game.time_to_find_twenty_forty_eight =
+            self.time_to_twenty_forty_eight_ms.map(|ms| ms as i64);
+        current_game.undo_count = Some(self.undo_count as i64);
+        current_game.hints_used = Some(self.hints_used as i64);
+        current_game.won = self.gamestate.won;
+        current_game.won_at_move = self.won_at_move.map(|move_number| move_number as i64);
+        current_game.max_tile = Some(self.max_tile() as i64);
 
         let result = transaction_put(
             db.clone(),
@@ -242,12 +672,42 @@ impl State {
             }
         };
         let hiscore = gamestate.score_max;
-        Some(Self {
+        let history_len = history.moves.len();
+        let won_at_move = current_game
+            .won_at_move
+            .map(|move_number| move_number as usize);
+        let mut state = Self {
             history,
             message: "".to_string(),
             gamestate,
             hiscore,
-        })
+            clock: PlayClock::starting_at(current_game.play_time.unwrap_or(0) as u64),
+            started_at: current_game.started_at.clone(),
+            time_to_twenty_forty_eight_ms: current_game
+                .time_to_find_twenty_forty_eight
+                .map(|ms| ms as u64),
+            //Games saved before move times were recorded have none to restore
+            move_times: current_game
+                .move_times
+                .unwrap_or_default()
+                .into_iter()
+                .map(|ms| ms as u64)
+                .collect(),
+            score_to_beat: current_game.score_to_beat,
+            seed_issued_at: current_game.seed_issued_at.clone(),
+            undo_count: current_game.undo_count.unwrap_or(0) as u32,
+            hints_used: current_game.hints_used.unwrap_or(0) as u32,
+            undo_limit: UndoLimit::default(),
+            mode: GameMode::from_record(current_game.mode.as_deref(), current_game.mode_limit),
+            won_at_move,
+            //Any move after the win means keep playing was picked
+            keep_playing: won_at_move.is_some_and(|won_at_move| history_len > won_at_move),
+            ended: false,
+            wrapped_up: false,
+        };
+        //A game saved as completed with moves still left was ended from the win modal
+        state.ended = current_game.completed && !state.is_finished();
+        Some(state)
     }
 }
 
@@ -255,6 +715,9 @@ fn get_position_class(row_start: usize, col_start: usize, size: usize) -> String
     //Have to do this or tailwindcss does not pick up and send the css it seems
     let row_class = match row_start {
         0 => "top-0",
+        //3x3
+        1 if size == 3 => "top-1/3",
+        2 if size == 3 => "top-2/3",
         //4x4
         1 if size == 4 => "top-1/4",
         2 if size == 4 => "top-2/4",
@@ -275,6 +738,9 @@ fn get_position_class(row_start: usize, col_start: usize, size: usize) -> String
 
     let col_class = match col_start {
         0 => "left-0",
+        //3x3
+        1 if size == 3 => "left-1/3",
+        2 if size == 3 => "left-2/3",
         //4x4
         1 if size == 4 => "left-1/4",
         2 if size == 4 => "left-2/4",
@@ -312,17 +778,34 @@ fn get_bg_color_and_text_color<'a>(tile_size: usize) -> &'a str {
         512 => "bg-light-grid-cell-512 text-light-grid-cell-text-512",
         1024 => "bg-light-grid-cell-1024 text-light-grid-cell-text-1024",
         //If it's over just keep to the same color as 2048
-        2048 | _ => "bg-light-grid-cell-2048 text-light-grid-cell-text-2048",
+        _ => "bg-light-grid-cell-2048 text-light-grid-cell-text-2048",
+    }
+}
+
+/// Width and height of one cell, same reason as get_position_class for spelling them out
+fn get_cell_size_class(size: usize) -> &'static str {
+    match size {
+        3 => "w-1/3 h-1/3",
+        5 => "w-1/5 h-1/5",
+        6 => "w-1/6 h-1/6",
+        _ => "w-1/4 h-1/4",
     }
 }
 
-fn get_font_size(text: &str) -> String {
-    let font_size = match text.len() {
-        1 => "text-[4rem] md:text-[4rem]",
-        2 => "text-[3.2rem] md:text-[4rem] lg:text-[4rem]",
-        3 => "text-[2.5rem] md:text-[4rem] lg:text-[4rem]",
+fn get_font_size(text: &str, size: usize) -> String {
+    let font_size = match (text.len(), size) {
+        //Bigger boards have smaller tiles
+        (1 | 2, 5) => "text-[2.4rem] md:text-[3.2rem]",
+        (3, 5) => "text-[1.8rem] md:text-[2.8rem]",
+        (_, 5) => "text-[1.2rem] md:text-[2.4rem]",
+        (1 | 2, 6) => "text-[2rem] md:text-[2.6rem]",
+        (3, 6) => "text-[1.5rem] md:text-[2.2rem]",
+        (_, 6) => "text-[1rem] md:text-[1.8rem]",
+        (1, _) => "text-[4rem] md:text-[4rem]",
+        (2, _) => "text-[3.2rem] md:text-[4rem] lg:text-[4rem]",
+        (3, _) => "text-[2.5rem] md:text-[4rem] lg:text-[4rem]",
         //If over 4 just keep to same size
-        4 | _ => "text-[1.5rem] md:text-[3.6rem] lg:text-[3.4rem]",
+        _ => "text-[1.5rem] md:text-[3.6rem] lg:text-[3.4rem]",
     };
     font_size.to_string()
 }
@@ -339,7 +822,7 @@ pub fn grid(props: &GridProps) -> Html {
     let position_class = get_position_class(position / size, position % size, *size);
     html! {
         <div
-            class={format!("absolute w-1/4 h-1/4 p-1 flex items-center justify-center {}",position_class)}
+            class={format!("absolute {} p-1 flex items-center justify-center {}", get_cell_size_class(*size), position_class)}
         >
             <div
                 class="flex items-center justify-center w-full h-full bg-light-grid-cell-0 rounded-[5px]"
@@ -375,7 +858,7 @@ pub fn tile(props: &TileProps) -> Html {
     let position_class = get_position_class(*y, *x, *size);
 
     let tile_class = get_bg_color_and_text_color(*tile_value_ref);
-    let font_size = get_font_size(&text);
+    let font_size = get_font_size(&text, *size);
 
     let new_tile_animation = if *new_tile_ref && *tile_value_ref != 0 {
         "animate-spawn eink:animate-none duration-500s"
@@ -386,7 +869,7 @@ pub fn tile(props: &TileProps) -> Html {
 
     html! {
         <div
-            class={format!("absolute w-1/4 h-1/4 {} p-1 flex items-center justify-center {} {}", position_class, new_tile_animation, move_animation)}
+            class={format!("absolute {} {} p-1 flex items-center justify-center {} {}", get_cell_size_class(*size), position_class, new_tile_animation, move_animation)}
         >
             <div
                 class={format!(
@@ -402,6 +885,9 @@ pub fn tile(props: &TileProps) -> Html {
 
 pub enum ScoreBoardAction {
     NewGame,
+    Undo,
+    Hint,
+    ToggleAutoplay,
 }
 
 #[derive(Properties, PartialEq, Clone)]
@@ -410,6 +896,13 @@ pub struct ScoreboardProps {
     pub hiscore: usize,
     pub message: String,
     pub action: Callback<ScoreBoardAction>,
+    pub undo_limit: UndoLimit,
+    pub undo_count: u32,
+    /// The engine is still looking for a hint
+    pub hint_loading: bool,
+    pub autoplay: bool,
+    /// Time or moves left in a timed or move limited game
+    pub mode_status: Option<String>,
 }
 
 #[function_component(ScoreBoard)]
@@ -419,14 +912,59 @@ pub fn scoreboard(props: &ScoreboardProps) -> Html {
         hiscore,
         message,
         action,
+        undo_limit,
+        undo_count,
+        hint_loading,
+        autoplay,
+        mode_status,
     } = props.clone();
     let hiscore_to_display = if score > hiscore { score } else { hiscore };
 
+    let undo_onclick = {
+        let action = action.clone();
+        move |_| {
+            action.emit(ScoreBoardAction::Undo);
+        }
+    };
+    let hint_onclick = {
+        let action = action.clone();
+        move |_| {
+            action.emit(ScoreBoardAction::Hint);
+        }
+    };
+    let autoplay_onclick = {
+        let action = action.clone();
+        move |_| {
+            action.emit(ScoreBoardAction::ToggleAutoplay);
+        }
+    };
+    let undo_label = match undo_limit.remaining(undo_count) {
+        Some(remaining) => format!("Undo ({})", remaining),
+        None => "Undo".to_string(),
+    };
+
     let onclick = {
         move |_| {
             action.emit(ScoreBoardAction::NewGame);
         }
     };
+    let (settings, settings_dispatch) = use_store::<SettingsStore>();
+    let on_board_size_change = {
+        let settings_dispatch = settings_dispatch.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            if let Ok(board_size) = select.value().parse::<usize>() {
+                settings_dispatch.reduce_mut(|settings| settings.board_size = board_size);
+            }
+        })
+    };
+    let on_game_mode_change = Callback::from(move |event: Event| {
+        let select: HtmlSelectElement = event.target_unchecked_into();
+        if let Some(game_mode) = GAME_MODE_OPTIONS.get(select.selected_index() as usize) {
+            let game_mode = *game_mode;
+            settings_dispatch.reduce_mut(|settings| settings.game_mode = game_mode);
+        }
+    });
     let mut number_formatter = Formatter::new()
         .precision(Precision::Decimals(0))
         .separator(',')
@@ -450,9 +988,61 @@ pub fn scoreboard(props: &ScoreboardProps) -> Html {
                 </div>
                 <div class="flex flex-col items-center justify-center mx-5">
                     <button {onclick} class="btn btn-outline btn-sm">{ "New game" }</button>
+                    <select
+                        onchange={on_board_size_change}
+                        class="select select-sm mt-2 w-auto"
+                        aria-label="Board size for new games"
+                    >
+                        { for (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).map(|board_size| html! {
+                            <option
+                                value={board_size.to_string()}
+                                selected={board_size == settings.board_size}
+                            >
+                                { format!("{}x{}", board_size, board_size) }
+                            </option>
+                        }) }
+                    </select>
+                    <select
+                        onchange={on_game_mode_change}
+                        class="select select-sm mt-2 w-auto"
+                        aria-label="Mode for new games"
+                    >
+                        { for GAME_MODE_OPTIONS.iter().map(|game_mode| html! {
+                            <option selected={*game_mode == settings.game_mode}>
+                                { game_mode.label() }
+                            </option>
+                        }) }
+                    </select>
+                    if undo_limit != UndoLimit::Off {
+                        <button
+                            onclick={undo_onclick}
+                            class="btn btn-outline btn-sm mt-2"
+                            disabled={!undo_limit.allows_undo(undo_count)}
+                        >
+                            { undo_label }
+                        </button>
+                    }
+                    <div class="join mt-2">
+                        <button
+                            onclick={hint_onclick}
+                            class="btn btn-outline btn-sm join-item"
+                            disabled={hint_loading || autoplay}
+                        >
+                            { "Hint" }
+                        </button>
+                        <button
+                            onclick={autoplay_onclick}
+                            class={classes!("btn", "btn-sm", "join-item", if autoplay { "btn-primary" } else { "btn-outline" })}
+                        >
+                            { if autoplay { "Stop demo" } else { "Demo" } }
+                        </button>
+                    </div>
                 </div>
             </div>
             <div class="text-center md:mt-4 mt-2">
+                if let Some(mode_status) = mode_status {
+                    <h2 class="text-lg font-bold">{ mode_status }</h2>
+                }
                 <h2 class="text-lg">{ message }</h2>
             </div>
         </>
@@ -474,7 +1064,7 @@ fn _emoji_board(tile_values: Vec<usize>) -> String {
         };
         emoji_board.push(emoji);
         if column_count == 3 {
-            emoji_board.push_str("\n");
+            emoji_board.push('\n');
             column_count = 0;
         } else {
             column_count += 1;
@@ -483,10 +1073,44 @@ fn _emoji_board(tile_values: Vec<usize>) -> String {
     emoji_board
 }
 
+#[derive(Properties, PartialEq, Clone)]
+struct WinModalProps {
+    won_at_move: usize,
+    /// `true` to keep playing, `false` to end the game
+    action: Callback<bool>,
+}
+
+#[function_component(WinModal)]
+fn win_modal(props: &WinModalProps) -> Html {
+    let keep_playing = {
+        let action = props.action.clone();
+        move |_| action.emit(true)
+    };
+    let end_game = {
+        let action = props.action.clone();
+        move |_| action.emit(false)
+    };
+    html! {
+        <div class="modal modal-open" role="dialog">
+            <div class="modal-box text-center">
+                <h3 class="text-2xl font-bold">{ "You made 2048!" }</h3>
+                <p class="py-4">{ format!("It took you {} moves", props.won_at_move) }</p>
+                <div class="modal-action justify-center">
+                    <button onclick={keep_playing} class="btn btn-primary">{ "Keep playing" }</button>
+                    <button onclick={end_game} class="btn btn-outline">{ "End game" }</button>
+                </div>
+            </div>
+        </div>
+    }
+}
+
 #[derive(Properties, PartialEq, Clone)]
 struct ShareButtonProps {
     score: usize,
     seed: u32,
+    size: usize,
+    won_at_move: Option<usize>,
+    max_tile: usize,
     // emoji_board: String,
 }
 
@@ -499,19 +1123,36 @@ fn bsky_buttons(props: &ShareButtonProps) -> Html {
         .separator(',')
         .expect("Could not build the number formatter.");
     let score = number_formatter.fmt2(props.score).to_string();
-    
+    //Won games lead with the win and how far past it the player got
+    let outcome = match props.won_at_move {
+        Some(won_at_move) if props.max_tile > 2048 => format!(
+            "I reached 2048 in {} moves, kept going to a {} tile and scored {}",
+            won_at_move, props.max_tile, score
+        ),
+        Some(won_at_move) => format!(
+            "I reached 2048 in {} moves and scored {}",
+            won_at_move, score
+        ),
+        None => format!("I just scored {}", score),
+    };
+
     let normal_share_display_text = format!(
-        "I just scored {} on a game of at://2048.\nThink you can do better? Join in on the fun with @2048.blue.\n\nhttps://{}",
-        score.clone(),
+        "{} on a game of at://2048.\nThink you can do better? Join in on the fun with @2048.blue.\n\nhttps://{}",
+        outcome,
         app_domain // Use the new domain here
     );
 
-    let seed_redirect_url = format!("https://{}/seed/{}", app_domain, props.seed.to_string()); // Use the new domain here
+    let seed_redirect_url = format!(
+        "https://{}/seed/{}?score={}&size={}",
+        app_domain, props.seed, props.score, props.size
+    ); // Use the new domain here
 
     let seeded_share = format!(
-        "I just scored {} on a game of at://2048 with a starting seed of {}.\nThink you can do better with this exact same seed? Try it out here {} \n @2048.blue",
-        score.clone(),
-        props.seed.to_string(),
+        "{} on a {}x{} game of at://2048 with a starting seed of {}.\nThink you can do better with this exact same seed? Try it out here {} \n @2048.blue",
+        outcome,
+        props.size,
+        props.size,
+        props.seed,
         seed_redirect_url.clone()
     );
 
@@ -555,6 +1196,13 @@ fn bsky_buttons(props: &ShareButtonProps) -> Html {
                 { "Share" }
                 { bsky_logo_svg }
             </a>
+            <Link<Route, DuelsQuery>
+                to={Route::DuelsPage}
+                query={Some(DuelsQuery { seed: Some(props.seed), size: Some(props.size) })}
+                classes="btn btn-sm btn-outline ml-2"
+            >
+                { "Challenge" }
+            </Link<Route, DuelsQuery>>
         </div>
     )
 }
@@ -573,7 +1221,22 @@ pub fn board(game_props: &GameProps) -> Html {
     let storage_task = use_oneshot_runner::<StorageTask>();
     let storage_agent = storage_task.clone();
     let hiscore_handle = use_state_eq(|| state.hiscore);
+    let (settings, _) = use_store::<SettingsStore>();
+    let hint_loading = use_state_eq(|| false);
+    let autoplay = use_state_eq(|| false);
+    //Bumped whenever the board changes so a demo move worked out for an old board is dropped
+    let autoplay_generation = use_mut_ref(|| 0_u32);
 
+    {
+        let state = state.clone();
+        use_effect_with(settings.undo_limit, move |undo_limit| {
+            state.dispatch(Action::SetUndoLimit(*undo_limit));
+            || ()
+        });
+    }
+
+    let board_size = state.history.width;
+    let game_mode = state.mode;
     use_effect_with(hiscore_handle.clone(), move |h_handle| {
         let current_hiscore_handle = h_handle.clone();
         spawn_local(async move {
@@ -583,32 +1246,71 @@ pub fn board(game_props: &GameProps) -> Html {
                     panic!("Error opening database: {:?}", err);
                 }
             };
-            match object_get::<blue::_2048::player::stats::RecordData>(db, STATS_STORE, SELF_KEY)
-                .await
+            if let Ok(stats_opt) =
+                object_get::<blue::_2048::player::stats::RecordData>(db, STATS_STORE, SELF_KEY)
+                    .await
+                && let Some(stats_data) = stats_opt
             {
-                Ok(stats_opt) => match stats_opt {
-                    Some(stats_data) => {
-                        current_hiscore_handle.set(stats_data.highest_score as usize);
-                    }
-                    None => {}
-                },
-                Err(_) => {}
+                if let Some(limit) = game_mode.limit() {
+                    //Timed and move limited games are compared only with games of the same mode
+                    let mode_stats =
+                        stats_data
+                            .modes
+                            .unwrap_or_default()
+                            .into_iter()
+                            .find(|mode_stats| {
+                                mode_stats.mode == game_mode.id()
+                                    && mode_stats.limit == limit
+                                    && mode_stats.size == board_size as i64
+                            });
+                    current_hiscore_handle
+                        .set(mode_stats.map_or(0, |mode_stats| mode_stats.highest_score) as usize);
+                    return;
+                }
+                let size_stats = stats_data
+                    .board_sizes
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|size_stats| size_stats.size == board_size as i64);
+                let highest_score = match size_stats {
+                    Some(size_stats) => size_stats.highest_score,
+                    //Stats from before board sizes were split out are all from 4x4 games
+                    None if board_size == DEFAULT_BOARD_SIZE => stats_data.highest_score,
+                    None => 0,
+                };
+                current_hiscore_handle.set(highest_score as usize);
             }
         });
         || ()
     });
 
+    //Time attack games need to notice the clock running out between moves
+    let force_update = use_force_update();
+    {
+        let state = state.clone();
+        use_effect_with(state.mode, move |mode| {
+            let interval = matches!(mode, GameMode::TimeAttack { .. }).then(|| {
+                Interval::new(250, move || {
+                    state.dispatch(Action::Tick);
+                    force_update.force_update();
+                })
+            });
+            move || drop(interval)
+        });
+    }
+
     let game_over_state_reducer = state.clone();
     let user_store_for_effect = user_store.clone();
     let storage_agent_for_effect = storage_agent.clone();
 
-    use_effect_with(state.gamestate.over, move |gameover| {
+    use_effect_with(state.is_finished(), move |gameover| {
         if *gameover {
-            let history_string: String = (&(*game_over_state_reducer).history.clone()).into();
+            let history_string: String = (&game_over_state_reducer.history.clone()).into();
+            let details = game_over_state_reducer.details();
             let did = user_store_for_effect.did.clone();
-            
+
             spawn_local(async move {
-                let request = StorageRequest::GameCompleted(history_string, did);
+                let request = StorageRequest::GameCompleted(history_string, did, details);
                 let result = storage_agent_for_effect.run(request).await;
                 match result {
                     StorageResponse::Error(err) => {
@@ -622,15 +1324,72 @@ pub fn board(game_props: &GameProps) -> Html {
                 }
             });
         }
-        ||()
+        || ()
     });
 
+    //Spectators on /watch/{did} follow the game move by move when the player streams it
+    {
+        let history_string: String = (&state.history).into();
+        let did = user_store.did.clone().filter(|_| settings.live_streaming);
+        use_effect_with(
+            (history_string, state.is_finished(), did),
+            |(history_string, finished, did)| {
+                if let Some(did) = did {
+                    live::stream_move(did.clone(), history_string.clone(), *finished);
+                }
+                || ()
+            },
+        );
+    }
+
+    {
+        let state = state.clone();
+        use_effect_with((), move |_| {
+            let document = gloo::utils::document();
+            let window = gloo::utils::window();
+            let update_clock = {
+                let document = document.clone();
+                move || {
+                    let has_focus = document.has_focus().unwrap_or(false);
+                    if document.hidden() || !has_focus {
+                        state.dispatch(Action::PauseClock);
+                    } else {
+                        state.dispatch(Action::ResumeClock);
+                    }
+                }
+            };
+            update_clock();
+            let listeners = [
+                EventListener::new(&document, "visibilitychange", {
+                    let update_clock = update_clock.clone();
+                    move |_| update_clock()
+                }),
+                EventListener::new(&window, "focus", {
+                    let update_clock = update_clock.clone();
+                    move |_| update_clock()
+                }),
+                EventListener::new(&window, "blur", move |_| update_clock()),
+            ];
+            move || drop(listeners)
+        });
+    }
+
     use_effect_with(state.clone(), {
         let move_delay = move_delay.clone();
         move |state| {
             let state = state.clone();
             let listener = EventListener::new(&gloo::utils::document(), "keydown", move |event| {
                 if let Some(event) = event.dyn_ref::<web_sys::KeyboardEvent>() {
+                    let is_undo = match event.key().as_str() {
+                        "u" | "Backspace" => true,
+                        "z" | "Z" => event.ctrl_key() || event.meta_key(),
+                        _ => false,
+                    };
+                    if is_undo {
+                        event.prevent_default();
+                        state.dispatch(Action::Undo);
+                        return;
+                    }
                     let direction = match event.key().as_str() {
                         "k" | "w" | "ArrowUp" => Direction::UP,
                         "j" | "s" | "ArrowDown" => Direction::DOWN,
@@ -653,11 +1412,57 @@ pub fn board(game_props: &GameProps) -> Html {
         }
     });
 
+    {
+        let state = state.clone();
+        let hint_task = storage_task.clone();
+        let search = settings.hint_search;
+        let autoplay_generation = autoplay_generation.clone();
+        use_effect_with(
+            (
+                state.history.moves.len(),
+                state.is_finished() || state.awaiting_win_choice(),
+                *autoplay,
+            ),
+            move |(_, over, autoplay)| {
+                let generation = {
+                    let mut autoplay_generation = autoplay_generation.borrow_mut();
+                    *autoplay_generation += 1;
+                    *autoplay_generation
+                };
+                if *autoplay && !*over {
+                    let request = StorageRequest::BestMove {
+                        board: Box::new(state.gamestate.board),
+                        search,
+                    };
+                    let autoplay_generation = autoplay_generation.clone();
+                    spawn_local(async move {
+                        let hint = hint_task.run(request).await.into_hint();
+                        TimeoutFuture::new(AUTOPLAY_MOVE_DELAY_MS).await;
+                        if *autoplay_generation.borrow() != generation {
+                            return;
+                        }
+                        if let Some(direction) =
+                            hinted_direction(hint, &state.gamestate.allowed_moves)
+                        {
+                            state.dispatch(Action::Hint(direction));
+                            state.dispatch(Action::Move(direction));
+                        }
+                    });
+                }
+                move || *autoplay_generation.borrow_mut() += 1
+            },
+        );
+    }
+
     let board_ref = use_node_ref();
     let touch_start = use_mut_ref(|| (0, 0));
+    //Set by a two finger tap so lifting those fingers is not read as a swipe
+    let undo_touch = use_mut_ref(|| false);
 
     {
         let touch_start = touch_start.clone();
+        let undo_touch = undo_touch.clone();
+        let state = state.clone();
         use_effect_with(board_ref.clone(), move |board_ref| {
             let board = board_ref
                 .cast::<HtmlElement>()
@@ -665,12 +1470,20 @@ pub fn board(game_props: &GameProps) -> Html {
 
             let callback = Closure::wrap(Box::new(move |event: web_sys::Event| {
                 event.prevent_default();
-                if let Some(event) = event.dyn_ref::<web_sys::TouchEvent>() {
-                    if let Some(touch) = event.changed_touches().item(0) {
-                        let x = touch.client_x();
-                        let y = touch.client_y();
-                        *touch_start.borrow_mut() = (x, y);
-                    }
+                //A two finger tap takes back the last move
+                if let Some(event) = event.dyn_ref::<web_sys::TouchEvent>()
+                    && event.touches().length() == 2
+                {
+                    *undo_touch.borrow_mut() = true;
+                    state.dispatch(Action::Undo);
+                    return;
+                }
+                if let Some(event) = event.dyn_ref::<web_sys::TouchEvent>()
+                    && let Some(touch) = event.changed_touches().item(0)
+                {
+                    let x = touch.client_x();
+                    let y = touch.client_y();
+                    *touch_start.borrow_mut() = (x, y);
                 }
             }) as Box<dyn FnMut(_)>);
 
@@ -685,6 +1498,7 @@ pub fn board(game_props: &GameProps) -> Html {
 
     {
         let touch_start = touch_start.clone();
+        let undo_touch = undo_touch.clone();
         let move_delay = move_delay.clone();
         let state = state.clone();
 
@@ -696,42 +1510,44 @@ pub fn board(game_props: &GameProps) -> Html {
             let callback = Closure::wrap(Box::new(move |event: web_sys::Event| {
                 event.prevent_default();
 
-                if let Some(event) = event.dyn_ref::<web_sys::TouchEvent>() {
-                    if let Some(touch) = event.changed_touches().item(0) {
-                        let touch_end_x = touch.client_x();
-                        let touch_end_y = touch.client_y();
+                if let Some(event) = event.dyn_ref::<web_sys::TouchEvent>()
+                    && event.touches().length() == 0
+                    && !undo_touch.replace(false)
+                    && let Some(touch) = event.changed_touches().item(0)
+                {
+                    let touch_end_x = touch.client_x();
+                    let touch_end_y = touch.client_y();
 
-                        let (start_x, start_y) = *touch_start.borrow();
+                    let (start_x, start_y) = *touch_start.borrow();
 
-                        let delta_x = touch_end_x - start_x;
-                        let delta_y = touch_end_y - start_y;
-                        if delta_x.abs() < 10 && delta_y.abs() < 10 {
-                            return;
-                        }
+                    let delta_x = touch_end_x - start_x;
+                    let delta_y = touch_end_y - start_y;
+                    if delta_x.abs() < 10 && delta_y.abs() < 10 {
+                        return;
+                    }
 
-                        let direction = if delta_x.abs() > delta_y.abs() {
-                            if delta_x > 0 {
-                                Direction::RIGHT
-                            } else {
-                                Direction::LEFT
-                            }
+                    let direction = if delta_x.abs() > delta_y.abs() {
+                        if delta_x > 0 {
+                            Direction::RIGHT
                         } else {
-                            if delta_y > 0 {
-                                Direction::DOWN
-                            } else {
-                                Direction::UP
-                            }
-                        };
-
-                        *move_delay.borrow_mut() = Some(Timeout::new(150, {
-                            let cloned_state = state.clone();
-                            let move_delay = move_delay.clone();
-                            move || {
-                                move_delay.borrow_mut().take();
-                                cloned_state.dispatch(Action::Move(direction));
-                            }
-                        }));
-                    }
+                            Direction::LEFT
+                        }
+                    } else {
+                        if delta_y > 0 {
+                            Direction::DOWN
+                        } else {
+                            Direction::UP
+                        }
+                    };
+
+                    *move_delay.borrow_mut() = Some(Timeout::new(150, {
+                        let cloned_state = state.clone();
+                        let move_delay = move_delay.clone();
+                        move || {
+                            move_delay.borrow_mut().take();
+                            cloned_state.dispatch(Action::Move(direction));
+                        }
+                    }));
                 }
             }) as Box<dyn FnMut(_)>);
 
@@ -757,12 +1573,54 @@ pub fn board(game_props: &GameProps) -> Html {
         .filter_map(|tile| *tile)
         .collect::<Vec<_>>();
 
+    let win_modal_callback = {
+        let state = state.clone();
+        Callback::from(move |keep_playing: bool| {
+            if keep_playing {
+                state.dispatch(Action::KeepPlaying);
+            } else {
+                state.dispatch(Action::EndGame);
+            }
+        })
+    };
+
     let action = game_props.action.clone();
+    let undo_state = state.clone();
+    let autoplay_toggle = autoplay.clone();
+    let hint_loading_handle = hint_loading.clone();
+    let search = settings.hint_search;
     let score_board_callback =
         Callback::from(move |board_action: ScoreBoardAction| match board_action {
             ScoreBoardAction::NewGame => {
                 action.emit(ScoreBoardAction::NewGame);
             }
+            ScoreBoardAction::Undo => {
+                undo_state.dispatch(Action::Undo);
+            }
+            ScoreBoardAction::Hint => {
+                if undo_state.is_finished() {
+                    return;
+                }
+                let state = undo_state.clone();
+                let hint_task = storage_task.clone();
+                let hint_loading = hint_loading_handle.clone();
+                hint_loading.set(true);
+                spawn_local(async move {
+                    let request = StorageRequest::BestMove {
+                        board: Box::new(state.gamestate.board),
+                        search,
+                    };
+                    let hint = hint_task.run(request).await.into_hint();
+                    hint_loading.set(false);
+                    if let Some(direction) = hinted_direction(hint, &state.gamestate.allowed_moves)
+                    {
+                        state.dispatch(Action::Hint(direction));
+                    }
+                });
+            }
+            ScoreBoardAction::ToggleAutoplay => {
+                autoplay_toggle.set(!*autoplay_toggle);
+            }
         });
     html! {
         <div class="flex flex-col ">
@@ -771,14 +1629,44 @@ pub fn board(game_props: &GameProps) -> Html {
                 hiscore={*hiscore_handle as usize}
                 message={state.message.clone()}
                 action={score_board_callback.clone()}
+                undo_limit={state.undo_limit}
+                undo_count={state.undo_count}
+                hint_loading={*hint_loading}
+                autoplay={*autoplay}
+                mode_status={state.mode_status()}
             />
             {
-                if state.gamestate.over {
-                    html! { <ShareGameButtons score={state.hiscore} seed={state.history.seed}/> }
+                if state.is_finished() {
+                    html! {
+                        <>
+                            <ShareGameButtons
+                                score={state.hiscore}
+                                seed={state.history.seed}
+                                size={state.history.width}
+                                won_at_move={state.won_at_move}
+                                max_tile={state.max_tile()}
+                            />
+                            <div class="flex justify-center">
+                                <Link<Route, ReplayQuery>
+                                    to={Route::ReplayPage}
+                                    query={Some(ReplayQuery { recording: (&state.history).into() })}
+                                    classes="btn btn-sm btn-outline"
+                                >
+                                    { "Review game" }
+                                </Link<Route, ReplayQuery>>
+                            </div>
+                        </>
+                    }
                 } else {
                     html! {}
                 }
             }
+            if state.awaiting_win_choice() {
+                <WinModal
+                    won_at_move={state.won_at_move.unwrap_or_default()}
+                    action={win_modal_callback}
+                />
+            }
             <div
                 ref={board_ref}
                 id="game-board"
@@ -802,12 +1690,17 @@ pub fn board(game_props: &GameProps) -> Html {
 #[function_component(GamePage)]
 pub fn game() -> Html {
     let current_game_state = use_state(|| None);
+    let (settings, _) = use_store::<SettingsStore>();
+    let board_size = settings.board_size;
+    let game_mode = settings.game_mode;
     let current_game_state_clone = current_game_state.clone();
     let cloned_state_for_callback = current_game_state_clone.clone();
 
     let score_board_callback = {
         let cloned_state = cloned_state_for_callback.clone();
         Callback::from(move |action: ScoreBoardAction| match action {
+            //The board takes care of its own undos and hints
+            ScoreBoardAction::Undo | ScoreBoardAction::Hint | ScoreBoardAction::ToggleAutoplay => {}
             ScoreBoardAction::NewGame => {
                 let cloned_state = cloned_state.clone();
                 cloned_state.set(None);
@@ -819,7 +1712,7 @@ pub fn game() -> Html {
                         }
                     };
                     let _ = object_delete(db, CURRENT_GAME_STORE, SELF_KEY).await;
-                    cloned_state.set(Some(State::new()));
+                    cloned_state.set(Some(State::new(board_size, game_mode)));
                 })
             }
         })
@@ -829,7 +1722,7 @@ pub fn game() -> Html {
         spawn_local(async move {
             match State::load().await {
                 None => {
-                    current_game_state_clone.set(Some(State::new()));
+                    current_game_state_clone.set(Some(State::new(board_size, game_mode)));
                 }
                 Some(current_game) => {
                     current_game_state_clone.set(Some(current_game));
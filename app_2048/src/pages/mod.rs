@@ -1,5 +1,10 @@
 pub mod callback;
+pub mod duels;
 pub mod game;
 pub mod login;
+pub mod profile;
+pub mod race;
+pub mod replay;
 pub mod seed;
 pub mod stats;
+pub mod watch;
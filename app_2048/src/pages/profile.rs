@@ -0,0 +1,236 @@
+use crate::Route;
+use crate::achievements::Achievement;
+use crate::at_repo_sync::AtRepoSync;
+use crate::store::{SettingsStore, UndoLimit, UserStore};
+use ai_2048::MAX_DEPTH;
+use atrium_api::agent::Agent;
+use types_2048::blue::_2048::player;
+use web_sys::{Event, HtmlInputElement, HtmlSelectElement};
+use yew::platform::spawn_local;
+use yew::{Callback, Html, TargetCast, classes, function_component, html, use_state};
+use yew_hooks::use_effect_once;
+use yew_router::components::Link;
+use yewdux::prelude::*;
+
+/// The undo limits a player can pick from and what they are called in the settings
+const UNDO_LIMIT_OPTIONS: [(UndoLimit, &str); 5] = [
+    (UndoLimit::Off, "Off"),
+    (UndoLimit::PerGame(1), "1 per game"),
+    (UndoLimit::PerGame(3), "3 per game"),
+    (UndoLimit::PerGame(5), "5 per game"),
+    (UndoLimit::Unlimited, "Unlimited"),
+];
+
+/// How long the engine may think about a hint, in milliseconds
+const HINT_TIME_BUDGET_OPTIONS: [u64; 5] = [100, 250, 500, 1000, 2000];
+
+#[function_component(ProfilePage)]
+pub fn profile() -> Html {
+    let (user_store, _) = use_store::<UserStore>();
+    let (settings, settings_dispatch) = use_store::<SettingsStore>();
+    let achievements_state = use_state(|| None::<Vec<player::achievement::RecordData>>);
+    let user_store_clone = user_store.clone();
+    let achievements_state_clone = achievements_state.clone();
+
+    use_effect_once(move || {
+        spawn_local(async move {
+            let at_repo_sync = match user_store_clone.did.clone() {
+                None => AtRepoSync::new_local_repo(),
+                Some(did) => {
+                    let oauth_client = crate::oauth_client::oauth_client().await;
+                    match oauth_client.restore(&did).await {
+                        Ok(session) => AtRepoSync::new_logged_in_repo(Agent::new(session), did),
+                        Err(err) => {
+                            log::error!("{:?}", err);
+                            AtRepoSync::new_local_repo()
+                        }
+                    }
+                }
+            };
+            if let Err(err) = at_repo_sync.sync_achievements().await {
+                log::error!("Error syncing achievements: {:?}", err.to_string());
+            }
+            match at_repo_sync.get_local_achievements().await {
+                Ok(achievements) => achievements_state_clone.set(Some(achievements)),
+                Err(err) => {
+                    log::error!("Error getting local achievements: {:?}", err.to_string());
+                    achievements_state_clone.set(Some(vec![]));
+                }
+            }
+        });
+        || ()
+    });
+
+    let Some(unlocked) = (*achievements_state).clone() else {
+        return html! {
+            <div class="flex flex-col items-center justify-center h-screen bg-base-200">
+                <div class="flex items-center justify-center">
+                    <span class="loading loading-spinner loading-lg" />
+                    <h1 class="ml-4 text-3xl font-bold">{ "Loading Profile..." }</h1>
+                </div>
+            </div>
+        };
+    };
+
+    let on_undo_limit_change = {
+        let settings_dispatch = settings_dispatch.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            if let Some((undo_limit, _)) = UNDO_LIMIT_OPTIONS.get(select.selected_index() as usize)
+            {
+                let undo_limit = *undo_limit;
+                settings_dispatch.reduce_mut(|settings| settings.undo_limit = undo_limit);
+            }
+        })
+    };
+    let on_hint_depth_change = {
+        let settings_dispatch = settings_dispatch.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            if let Ok(depth) = select.value().parse::<u8>() {
+                settings_dispatch.reduce_mut(|settings| settings.hint_search.depth = depth);
+            }
+        })
+    };
+    let on_hint_time_budget_change = {
+        let settings_dispatch = settings_dispatch.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            if let Ok(time_budget_ms) = select.value().parse::<u64>() {
+                settings_dispatch
+                    .reduce_mut(|settings| settings.hint_search.time_budget_ms = time_budget_ms);
+            }
+        })
+    };
+    let on_live_streaming_change = Callback::from(move |event: Event| {
+        let input: HtmlInputElement = event.target_unchecked_into();
+        let live_streaming = input.checked();
+        settings_dispatch.reduce_mut(|settings| settings.live_streaming = live_streaming);
+    });
+
+    let unlocked_count = Achievement::ALL
+        .iter()
+        .filter(|achievement| {
+            unlocked
+                .iter()
+                .any(|record| record.achievement == achievement.id())
+        })
+        .count();
+
+    html! {
+        <div class="min-h-screen bg-base-200 p-4">
+            <div class="max-w-4xl mx-auto space-y-4">
+                // Header
+                <div class="card bg-base-100 shadow-xl">
+                    <div class="card-body">
+                        <h2 class="card-title text-3xl font-bold">
+                            { "Your at://2048 Profile" }
+                        </h2>
+                        <p class="text-base-content/70 break-all">
+                            { match &user_store.did {
+                                Some(did) => did.to_string(),
+                                None => "Playing locally. Login to sync your achievements to your PDS".to_string(),
+                            } }
+                        </p>
+                    </div>
+                </div>
+                // Settings
+                <div class="card bg-base-100 shadow-xl">
+                    <div class="card-body">
+                        <h3 class="card-title">{ "Game Settings" }</h3>
+                        <fieldset class="fieldset">
+                            <legend class="fieldset-legend">{ "Undo limit" }</legend>
+                            <select onchange={on_undo_limit_change} class="select">
+                                { for UNDO_LIMIT_OPTIONS.iter().map(|(undo_limit, label)| html! {
+                                    <option selected={*undo_limit == settings.undo_limit}>{ *label }</option>
+                                }) }
+                            </select>
+                            <p class="label text-wrap">
+                                { "Undo with the button under New game, u, Ctrl+Z or a two finger tap. Games where you used an undo are ranked separately." }
+                            </p>
+                        </fieldset>
+                        <fieldset class="fieldset">
+                            <legend class="fieldset-legend">{ "Hint search depth" }</legend>
+                            <select onchange={on_hint_depth_change} class="select">
+                                { for (1..=MAX_DEPTH).map(|depth| html! {
+                                    <option value={depth.to_string()} selected={depth == settings.hint_search.depth}>
+                                        { format!("{} moves ahead", depth) }
+                                    </option>
+                                }) }
+                            </select>
+                            <legend class="fieldset-legend">{ "Hint thinking time" }</legend>
+                            <select onchange={on_hint_time_budget_change} class="select">
+                                { for HINT_TIME_BUDGET_OPTIONS.iter().map(|time_budget_ms| html! {
+                                    <option
+                                        value={time_budget_ms.to_string()}
+                                        selected={*time_budget_ms == settings.hint_search.time_budget_ms}
+                                    >
+                                        { format!("{} ms", time_budget_ms) }
+                                    </option>
+                                }) }
+                            </select>
+                            <p class="label text-wrap">
+                                { "Deeper searches play better but take longer. The engine stops at the thinking time even if it has not looked that far ahead. Games where you used a hint or the demo are not ranked." }
+                            </p>
+                        </fieldset>
+                        <fieldset class="fieldset">
+                            <legend class="fieldset-legend">{ "Live spectating" }</legend>
+                            <label class="label">
+                                <input
+                                    type="checkbox"
+                                    class="toggle"
+                                    checked={settings.live_streaming}
+                                    disabled={user_store.did.is_none()}
+                                    onchange={on_live_streaming_change}
+                                />
+                                { "Stream my games while I play" }
+                            </label>
+                            <p class="label text-wrap">
+                                { match &user_store.did {
+                                    Some(_) => "Every move is sent as you make it so anyone with the link can watch.",
+                                    None => "Login to let others watch your games live.",
+                                } }
+                            </p>
+                            if let Some(did) = user_store.did.as_ref().filter(|_| settings.live_streaming) {
+                                <Link<Route> to={Route::WatchPage { did: did.to_string() }} classes="link link-primary">
+                                    { "Your watch page" }
+                                </Link<Route>>
+                            }
+                        </fieldset>
+                    </div>
+                </div>
+                // Achievements
+                <div class="card bg-base-100 shadow-xl">
+                    <div class="card-body">
+                        <h3 class="card-title">
+                            { format!("Achievements {}/{}", unlocked_count, Achievement::ALL.len()) }
+                        </h3>
+                        <div class="grid grid-cols-1 md:grid-cols-2 gap-4">
+                            { Achievement::ALL.iter().map(|achievement| {
+                                let unlocked_at = unlocked
+                                    .iter()
+                                    .find(|record| record.achievement == achievement.id())
+                                    .map(|record| record.created_at.as_ref().format("%B %-d, %Y").to_string());
+                                html! {
+                                    <div
+                                        key={achievement.id()}
+                                        class={classes!("stat", "shadow", "rounded-box", unlocked_at.is_none().then_some("opacity-50"))}
+                                    >
+                                        <div class="stat-title">{ achievement.description() }</div>
+                                        <div class="stat-value text-2xl">{ achievement.title() }</div>
+                                        <div class="stat-desc">
+                                            { match unlocked_at {
+                                                Some(unlocked_at) => format!("Unlocked {}", unlocked_at),
+                                                None => "Locked".to_string(),
+                                            } }
+                                        </div>
+                                    </div>
+                                }
+                            }).collect::<Html>() }
+                        </div>
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
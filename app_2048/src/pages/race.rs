@@ -0,0 +1,638 @@
+use crate::Route;
+use crate::pages::replay::replay_board;
+use crate::race::{
+    FinishReason, MAX_RACE_MINUTES, RaceCommand, RaceMessage, RacePhase, RacePlayer, RaceView,
+    create_race, ms_until, open_races, race_socket_url,
+};
+use crate::store::{MAX_BOARD_SIZE, MIN_BOARD_SIZE, UserStore};
+use futures::channel::mpsc;
+use futures::future::FutureExt;
+use futures::{SinkExt, StreamExt};
+use gloo::events::EventListener;
+use gloo::net::websocket::Message;
+use gloo::net::websocket::futures::WebSocket;
+use gloo::timers::callback::Interval;
+use twothousand_forty_eight::direction::Direction;
+use twothousand_forty_eight::{unified::game::GameState, v2::recording::SeededRecording};
+use wasm_bindgen::JsCast;
+use web_sys::{Event, HtmlInputElement, HtmlSelectElement, InputEvent, SubmitEvent};
+use yew::platform::spawn_local;
+use yew::{
+    Callback, Html, Properties, TargetCast, classes, function_component, html, use_effect_with,
+    use_force_update, use_mut_ref, use_state, use_state_eq,
+};
+use yew_hooks::use_effect_once;
+use yew_router::components::Link;
+use yew_router::hooks::use_navigator;
+use yewdux::use_store;
+
+/// Names longer than this are cut by the api
+const MAX_NAME_LENGTH: usize = 40;
+
+/// The player's own game, kept in step with what was sent to the race
+#[derive(Clone)]
+struct LocalGame {
+    recording: SeededRecording,
+    gamestate: GameState,
+}
+
+impl LocalGame {
+    fn new(seed: u32, size: usize) -> Option<Self> {
+        let recording = SeededRecording::empty(seed, size, size);
+        let gamestate = GameState::from_reconstructable_ruleset(&recording).ok()?;
+        Some(LocalGame {
+            recording,
+            gamestate,
+        })
+    }
+
+    /// The game after the move, or nothing when the move does not change the board
+    fn play(&self, direction: Direction) -> Option<Self> {
+        if !self.gamestate.allowed_moves.contains(&direction) {
+            return None;
+        }
+        let mut recording = self.recording.clone();
+        recording.moves.push(direction);
+        let gamestate = GameState::from_reconstructable_ruleset(&recording).ok()?;
+        Some(LocalGame {
+            recording,
+            gamestate,
+        })
+    }
+}
+
+fn seconds_left(time: &str) -> u64 {
+    (ms_until(time) / 1000.0).ceil() as u64
+}
+
+fn phase_badge(phase: &RacePhase) -> Html {
+    let (label, class) = match phase {
+        RacePhase::Lobby => ("Lobby".to_string(), "badge-ghost"),
+        RacePhase::Countdown { starts_at } => (
+            format!("Starting in {}", seconds_left(starts_at)),
+            "badge-warning",
+        ),
+        RacePhase::Racing { ends_at } => {
+            let left = seconds_left(ends_at);
+            (
+                format!("{}:{:02} left", left / 60, left % 60),
+                "badge-error",
+            )
+        }
+        RacePhase::Finished { .. } => ("Finished".to_string(), "badge-neutral"),
+    };
+    html! { <span class={classes!("badge", class)}>{ label }</span> }
+}
+
+fn finish_message(view: &RaceView) -> Option<String> {
+    let RacePhase::Finished { winner, reason } = &view.phase else {
+        return None;
+    };
+    let winner = winner
+        .as_ref()
+        .and_then(|winner| view.players.iter().find(|player| &player.id == winner));
+    let how = match reason {
+        FinishReason::TwentyFortyEight => "made 2048 first",
+        FinishReason::Time => "had the best score when time ran out",
+        FinishReason::AllOut => "had the best score when everyone ran out of moves",
+    };
+    Some(match winner {
+        Some(winner) => format!("{} wins! They {}.", winner.name, how),
+        None => "It's a tie at the top!".to_string(),
+    })
+}
+
+/// A small read only board for the other players in the race
+fn mini_board(player: &RacePlayer, is_you: bool, is_winner: bool) -> Html {
+    let size = player.board.len().max(1);
+    html! {
+        <div
+            key={player.id.clone()}
+            class={classes!("card", "bg-base-100", "shadow", is_winner.then_some("ring-2 ring-success"))}
+        >
+            <div class="card-body p-3 gap-2">
+                <div class="flex items-center justify-between gap-2">
+                    <span class="font-bold truncate">
+                        { player.name.clone() }
+                        if is_you {
+                            <span class="text-base-content/70">{ " (you)" }</span>
+                        }
+                    </span>
+                    if !player.connected {
+                        <span class="badge badge-ghost badge-sm">{ "Left" }</span>
+                    } else if player.over {
+                        <span class="badge badge-neutral badge-sm">{ "Out of moves" }</span>
+                    } else if player.ready {
+                        <span class="badge badge-success badge-sm">{ "Ready" }</span>
+                    }
+                </div>
+                <div class="text-sm">{ format!("Score {} · {} moves", player.score, player.moves) }</div>
+                <div
+                    class="grid gap-1 bg-light-board-background rounded p-1"
+                    style={format!("grid-template-columns: repeat({}, minmax(0, 1fr));", size)}
+                >
+                    { for player.board.iter().flatten().map(|value| html! {
+                        <div class="aspect-square rounded bg-base-200 flex items-center justify-center text-xs font-bold">
+                            if *value > 0 {
+                                { value }
+                            }
+                        </div>
+                    }) }
+                </div>
+            </div>
+        </div>
+    }
+}
+
+#[function_component(RacesPage)]
+pub fn races() -> Html {
+    let navigator = use_navigator().unwrap();
+    let lobbies = use_state(|| None::<Vec<RaceView>>);
+    let minutes = use_state_eq(|| 3u32);
+    let board_size = use_state_eq(|| 4usize);
+    let creating = use_state_eq(|| false);
+    let error = use_state_eq(|| None::<String>);
+
+    {
+        let lobbies = lobbies.clone();
+        use_effect_once(move || {
+            spawn_local(async move {
+                match open_races().await {
+                    Ok(open) => lobbies.set(Some(open)),
+                    Err(err) => {
+                        log::error!("Error getting open races: {}", err);
+                        lobbies.set(Some(vec![]));
+                    }
+                }
+            });
+            || ()
+        });
+    }
+
+    let on_minutes_input = {
+        let minutes = minutes.clone();
+        Callback::from(move |event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            if let Ok(value) = input.value().parse::<u32>() {
+                minutes.set(value.clamp(1, MAX_RACE_MINUTES));
+            }
+        })
+    };
+    let on_board_size_change = {
+        let board_size = board_size.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            if let Ok(size) = select.value().parse::<usize>() {
+                board_size.set(size);
+            }
+        })
+    };
+    let onsubmit = {
+        let minutes = minutes.clone();
+        let board_size = board_size.clone();
+        let creating = creating.clone();
+        let error = error.clone();
+        Callback::from(move |event: SubmitEvent| {
+            event.prevent_default();
+            error.set(None);
+            creating.set(true);
+            let minutes = *minutes;
+            let size = *board_size;
+            let creating = creating.clone();
+            let error = error.clone();
+            let navigator = navigator.clone();
+            spawn_local(async move {
+                match create_race(minutes, size).await {
+                    Ok(race) => navigator.push(&Route::RacePage { id: race.id }),
+                    Err(err) => {
+                        log::error!("Error creating race: {}", err);
+                        error.set(Some(err));
+                    }
+                }
+                creating.set(false);
+            });
+        })
+    };
+
+    html! {
+        <div class="min-h-screen bg-base-200 p-4">
+            <div class="max-w-4xl mx-auto space-y-4">
+                <div class="card bg-base-100 shadow-xl">
+                    <div class="card-body">
+                        <h2 class="card-title text-3xl font-bold">{ "Race" }</h2>
+                        <p class="text-base-content/70">
+                            { "Race 1 to 3 others on the same seed. The first to make 2048 wins, or the best score when time runs out." }
+                        </p>
+                        <form class="flex flex-col gap-2" {onsubmit}>
+                            <div class="flex gap-2">
+                                <label class="input input-bordered flex items-center gap-2 w-full">
+                                    <input
+                                        type="number"
+                                        class="grow"
+                                        min="1"
+                                        max={MAX_RACE_MINUTES.to_string()}
+                                        value={minutes.to_string()}
+                                        oninput={on_minutes_input}
+                                    />
+                                    { "minutes" }
+                                </label>
+                                <select
+                                    onchange={on_board_size_change}
+                                    class="select select-bordered"
+                                    aria-label="Board size"
+                                >
+                                    { for (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).map(|size| html! {
+                                        <option value={size.to_string()} selected={size == *board_size}>
+                                            { format!("{}x{}", size, size) }
+                                        </option>
+                                    }) }
+                                </select>
+                            </div>
+                            <button type="submit" class="btn btn-primary" disabled={*creating}>
+                                if *creating {
+                                    <span class="loading loading-spinner loading-sm" />
+                                }
+                                { "Open a lobby" }
+                            </button>
+                        </form>
+                        if let Some(error) = (*error).clone() {
+                            <p class="text-error">{ error }</p>
+                        }
+                    </div>
+                </div>
+                <div class="card bg-base-100 shadow-xl">
+                    <div class="card-body">
+                        <h3 class="card-title">{ "Open lobbies" }</h3>
+                        if let Some(lobbies) = &*lobbies {
+                            if lobbies.is_empty() {
+                                <p class="text-base-content/70">{ "Nobody is waiting for a race. Open a lobby and share the link." }</p>
+                            } else {
+                                <div class="overflow-x-auto">
+                                    <table class="table table-sm">
+                                        <thead>
+                                            <tr>
+                                                <th>{ "Players" }</th>
+                                                <th>{ "Size" }</th>
+                                                <th>{ "Minutes" }</th>
+                                                <th />
+                                            </tr>
+                                        </thead>
+                                        <tbody>
+                                            { for lobbies.iter().map(|race| html! {
+                                                <tr key={race.id.clone()}>
+                                                    <td class="max-w-40 truncate">
+                                                        { race.players.iter().map(|player| player.name.clone()).collect::<Vec<_>>().join(", ") }
+                                                    </td>
+                                                    <td>{ format!("{}x{}", race.size, race.size) }</td>
+                                                    <td>{ race.minutes }</td>
+                                                    <td>
+                                                        <Link<Route> to={Route::RacePage { id: race.id.clone() }} classes="btn btn-xs btn-outline">
+                                                            { "Join" }
+                                                        </Link<Route>>
+                                                    </td>
+                                                </tr>
+                                            }) }
+                                        </tbody>
+                                    </table>
+                                </div>
+                            }
+                        } else {
+                            <span class="loading loading-spinner loading-md" />
+                        }
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct RaceProps {
+    pub id: String,
+}
+
+#[function_component(RacePage)]
+pub fn race(props: &RaceProps) -> Html {
+    let (user_store, _) = use_store::<UserStore>();
+    let name = use_state_eq(|| {
+        user_store
+            .did
+            .as_ref()
+            .map(|did| did.to_string())
+            .unwrap_or_else(|| "Guest".to_string())
+    });
+    //Set once the player joins, the socket is opened again under this name
+    let joined_as = use_state_eq(|| None::<String>);
+    let view = use_state_eq(|| None::<RaceView>);
+    let player_id = use_state_eq(|| None::<String>);
+    let error = use_state_eq(|| None::<String>);
+    let closed = use_state_eq(|| false);
+    let game = use_state(|| None::<LocalGame>);
+    //Commands go through here to the task that owns the socket
+    let outgoing = use_mut_ref(|| None::<mpsc::UnboundedSender<String>>);
+    let force_update = use_force_update();
+
+    {
+        let view = view.clone();
+        let player_id = player_id.clone();
+        let error = error.clone();
+        let closed = closed.clone();
+        let game = game.clone();
+        let outgoing = outgoing.clone();
+        use_effect_with(
+            (props.id.clone(), (*joined_as).clone()),
+            move |(id, joined_as)| {
+                let url = race_socket_url(id, joined_as.as_deref());
+                let (sender, mut commands) = mpsc::unbounded::<String>();
+                *outgoing.borrow_mut() = Some(sender);
+                //Dropping stop ends the reading task so an old socket does not touch the page
+                let (stop, stopped) = futures::channel::oneshot::channel::<()>();
+                let stopped = stopped.shared();
+                game.set(None);
+                closed.set(false);
+                match WebSocket::open(&url) {
+                    Ok(socket) => {
+                        let (mut write, read) = socket.split();
+                        spawn_local(async move {
+                            while let Some(command) = commands.next().await {
+                                if let Err(err) = write.send(Message::Text(command)).await {
+                                    log::error!("Error sending to the race: {}", err);
+                                    break;
+                                }
+                            }
+                        });
+                        spawn_local(async move {
+                            let mut messages = read.take_until(stopped.clone());
+                            while let Some(message) = messages.next().await {
+                                let text = match message {
+                                    Ok(Message::Text(text)) => text,
+                                    Ok(Message::Bytes(_)) => continue,
+                                    Err(err) => {
+                                        log::error!("Race socket closed: {}", err);
+                                        break;
+                                    }
+                                };
+                                match serde_json::from_str::<RaceMessage>(&text) {
+                                    Ok(RaceMessage::Joined { player_id: id }) => {
+                                        player_id.set(Some(id))
+                                    }
+                                    Ok(RaceMessage::Race(race)) => view.set(Some(race)),
+                                    Ok(RaceMessage::Error { message }) => error.set(Some(message)),
+                                    Err(err) => log::error!("Error reading the race: {}", err),
+                                }
+                            }
+                            if stopped.peek().is_none() {
+                                closed.set(true);
+                            }
+                        });
+                    }
+                    Err(err) => {
+                        log::error!("Error opening the race socket: {}", err);
+                        closed.set(true);
+                    }
+                }
+                //With both tasks done the socket is dropped and closes
+                move || {
+                    drop(stop);
+                    drop(outgoing.borrow_mut().take());
+                }
+            },
+        );
+    }
+
+    let send = {
+        let outgoing = outgoing.clone();
+        move |command: RaceCommand| {
+            if let (Some(sender), Ok(text)) = (&*outgoing.borrow(), serde_json::to_string(&command))
+            {
+                let _ = sender.unbounded_send(text);
+            }
+        }
+    };
+
+    let racing = matches!(
+        (*view).as_ref().map(|view| &view.phase),
+        Some(RacePhase::Racing { .. })
+    );
+    let in_race = player_id.is_some();
+
+    //The player's board starts when the race does
+    {
+        let game = game.clone();
+        use_effect_with(
+            (
+                racing && in_race,
+                (*view)
+                    .as_ref()
+                    .and_then(|view| Some((view.seed?, view.size))),
+            ),
+            move |(playing, seed_size)| {
+                if *playing
+                    && game.is_none()
+                    && let Some((seed, size)) = seed_size
+                {
+                    game.set(LocalGame::new(*seed, *size));
+                }
+                || ()
+            },
+        );
+    }
+
+    let on_move = {
+        let game = game.clone();
+        let send = send.clone();
+        Callback::from(move |direction: Direction| {
+            if !racing {
+                return;
+            }
+            let Some(next) = (*game).as_ref().and_then(|game| game.play(direction)) else {
+                return;
+            };
+            send(RaceCommand::Move {
+                seeded_recording: (&next.recording).into(),
+            });
+            game.set(Some(next));
+        })
+    };
+
+    {
+        let on_move = on_move.clone();
+        use_effect_with(
+            (*game).as_ref().map(|game| game.recording.clone()),
+            move |_| {
+                let listener =
+                    EventListener::new(&gloo::utils::document(), "keydown", move |event| {
+                        if let Some(event) = event.dyn_ref::<web_sys::KeyboardEvent>() {
+                            let direction = match event.key().as_str() {
+                                "k" | "w" | "ArrowUp" => Direction::UP,
+                                "j" | "s" | "ArrowDown" => Direction::DOWN,
+                                "h" | "a" | "ArrowLeft" => Direction::LEFT,
+                                "l" | "d" | "ArrowRight" => Direction::RIGHT,
+                                _ => return,
+                            };
+                            event.prevent_default();
+                            on_move.emit(direction);
+                        }
+                    });
+                move || drop(listener)
+            },
+        );
+    }
+
+    //Redraws the countdown and the time left
+    {
+        let ticking = matches!(
+            (*view).as_ref().map(|view| &view.phase),
+            Some(RacePhase::Countdown { .. } | RacePhase::Racing { .. })
+        );
+        use_effect_with(ticking, move |ticking| {
+            let interval = ticking.then(|| Interval::new(500, move || force_update.force_update()));
+            move || drop(interval)
+        });
+    }
+
+    let on_name_input = {
+        let name = name.clone();
+        Callback::from(move |event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            name.set(input.value());
+        })
+    };
+    let on_join = {
+        let name = name.clone();
+        let joined_as = joined_as.clone();
+        let error = error.clone();
+        Callback::from(move |event: SubmitEvent| {
+            event.prevent_default();
+            let trimmed: String = name.trim().chars().take(MAX_NAME_LENGTH).collect();
+            if trimmed.is_empty() {
+                error.set(Some("Enter a name to race under".to_string()));
+                return;
+            }
+            error.set(None);
+            joined_as.set(Some(trimmed));
+        })
+    };
+
+    let Some(race) = (*view).clone() else {
+        return html! {
+            <div class="flex flex-col items-center justify-center h-screen bg-base-200">
+                if *closed {
+                    <h1 class="text-3xl font-bold">{ "Race not found" }</h1>
+                    <p class="text-base-content/70 my-4">{ "It may have finished a while ago." }</p>
+                    <Link<Route> to={Route::RacesPage} classes="btn btn-primary">{ "Find a race" }</Link<Route>>
+                } else {
+                    <span class="loading loading-spinner loading-lg" />
+                }
+            </div>
+        };
+    };
+
+    let you = (*player_id)
+        .as_ref()
+        .and_then(|id| race.players.iter().find(|player| &player.id == id));
+    let winner = match &race.phase {
+        RacePhase::Finished { winner, .. } => winner.clone(),
+        _ => None,
+    };
+    let on_ready = {
+        let send = send.clone();
+        let ready = you.is_some_and(|player| player.ready);
+        Callback::from(move |_| send(RaceCommand::Ready { ready: !ready }))
+    };
+    let arrow = |direction: Direction, label: &'static str| {
+        let on_move = on_move.clone();
+        html! {
+            <button class="btn btn-square" onclick={Callback::from(move |_| on_move.emit(direction))}>
+                { label }
+            </button>
+        }
+    };
+
+    html! {
+        <div class="min-h-screen bg-base-200 p-4">
+            <div class="max-w-5xl mx-auto space-y-4">
+                <div class="card bg-base-100 shadow-xl">
+                    <div class="card-body">
+                        <h2 class="card-title text-2xl">
+                            { "Race" }
+                            { phase_badge(&race.phase) }
+                        </h2>
+                        <p class="text-base-content/70">
+                            { match race.seed {
+                                Some(seed) => format!("Seed {} on a {}x{} board, {} minutes", seed, race.size, race.size, race.minutes),
+                                None => format!("A {}x{} board, {} minutes. The seed is dealt when the race starts", race.size, race.size, race.minutes),
+                            } }
+                        </p>
+                        if let Some(message) = finish_message(&race) {
+                            <div class="alert alert-success">{ message }</div>
+                        }
+                        if *closed {
+                            <div class="alert alert-warning">{ "Lost the connection to the race" }</div>
+                        }
+                        if let Some(error) = (*error).clone() {
+                            <p class="text-error">{ error }</p>
+                        }
+                        if race.phase == RacePhase::Lobby {
+                            if let Some(you) = you {
+                                <button
+                                    class={classes!("btn", if you.ready { "btn-outline" } else { "btn-primary" })}
+                                    onclick={on_ready}
+                                >
+                                    { if you.ready { "Not ready" } else { "Ready" } }
+                                </button>
+                                <p class="text-base-content/70 text-sm">
+                                    { "The countdown starts once at least 2 players are in and everyone is ready. Share this page's link to invite others." }
+                                </p>
+                            } else if joined_as.is_none() {
+                                <form class="flex gap-2" onsubmit={on_join}>
+                                    <input
+                                        type="text"
+                                        class="input input-bordered w-full"
+                                        placeholder="Your name"
+                                        maxlength={MAX_NAME_LENGTH.to_string()}
+                                        value={(*name).clone()}
+                                        oninput={on_name_input}
+                                    />
+                                    <button type="submit" class="btn btn-primary">{ "Join" }</button>
+                                </form>
+                            }
+                        }
+                    </div>
+                </div>
+                if let Some(game) = &*game {
+                    <div class="flex flex-col items-center gap-2">
+                        <div class="stats shadow">
+                            <div class="stat">
+                                <div class="stat-title">{ "Score" }</div>
+                                <div class="stat-value">{ game.gamestate.score_current }</div>
+                            </div>
+                            <div class="stat">
+                                <div class="stat-title">{ "Moves" }</div>
+                                <div class="stat-value">{ game.recording.moves.len() }</div>
+                            </div>
+                        </div>
+                        { replay_board(&game.gamestate.board) }
+                        if racing {
+                            <div class="grid grid-cols-3 gap-1">
+                                <div />
+                                { arrow(Direction::UP, "↑") }
+                                <div />
+                                { arrow(Direction::LEFT, "←") }
+                                { arrow(Direction::DOWN, "↓") }
+                                { arrow(Direction::RIGHT, "→") }
+                            </div>
+                        }
+                    </div>
+                }
+                <div class="grid grid-cols-1 sm:grid-cols-2 lg:grid-cols-4 gap-4">
+                    { for race.players.iter().map(|player| mini_board(
+                        player,
+                        player_id.as_ref() == Some(&player.id),
+                        winner.as_ref() == Some(&player.id),
+                    )) }
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,285 @@
+use crate::agent::{StorageRequest, StorageResponse, StorageTask};
+use crate::hint::ANALYSIS_SEARCH;
+use crate::pages::game::{Grid, Tile};
+use ai_2048::analysis::{GameAnalysis, Verdict};
+use js_sys::encode_uri_component;
+use serde::{Deserialize, Serialize};
+use twothousand_forty_eight::board::Board;
+use twothousand_forty_eight::direction::Direction;
+use twothousand_forty_eight::unified::reconstruction::Reconstructable;
+use twothousand_forty_eight::v2::recording::SeededRecording;
+use web_sys::{HtmlInputElement, InputEvent};
+use yew::platform::spawn_local;
+use yew::{Callback, Html, TargetCast, classes, function_component, html, use_state, use_state_eq};
+use yew_agent::oneshot::use_oneshot_runner;
+use yew_hooks::use_effect_once;
+use yew_router::hooks::use_location;
+
+/// Query params on a replay link
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct ReplayQuery {
+    pub recording: String,
+}
+
+fn direction_arrow(direction: Direction) -> &'static str {
+    match direction {
+        Direction::UP => "↑",
+        Direction::DOWN => "↓",
+        Direction::LEFT => "←",
+        Direction::RIGHT => "→",
+        Direction::BREAK => "Break",
+        _ => "",
+    }
+}
+
+fn verdict_label(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::Best => "Best move",
+        Verdict::Good => "Good",
+        Verdict::Inaccuracy => "Inaccuracy",
+        Verdict::Mistake => "Mistake",
+        Verdict::Blunder => "Blunder",
+    }
+}
+
+fn verdict_badge(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::Best => "badge-success",
+        Verdict::Good => "badge-info",
+        Verdict::Inaccuracy => "badge-warning",
+        Verdict::Mistake | Verdict::Blunder => "badge-error",
+    }
+}
+
+pub(crate) fn replay_board(board: &Board) -> Html {
+    let size = board.width;
+    html! {
+        <div
+            class="mx-auto p-4 w-90 md:w-3/4 lg:w-1/2 xl:w-120 bg-light-board-background shadow-2xl rounded-md"
+        >
+            <div class="aspect-square p-2 flex flex-col rounded-md w-full relative">
+                { (0..size * board.height).map(|i| {
+                        html! { <Grid key={format!("grid-parent-{}", i)} position={i} size={size} /> }
+                    }).collect::<Html>() }
+                { board.get_occupied_tiles().into_iter().map(|tile| {
+                        html! { <Tile key={tile.id} tile_value={tile.value} new_tile={tile.new} x={tile.x} y={tile.y} size={size} /> }
+                    }).collect::<Html>() }
+            </div>
+        </div>
+    }
+}
+
+fn analysis_summary(analysis: &GameAnalysis, on_jump: Callback<usize>) -> Html {
+    let blunders = analysis
+        .moves
+        .iter()
+        .filter(|analysis| analysis.verdict == Verdict::Blunder)
+        .map(|analysis| analysis.move_index);
+    html! {
+        <div class="card bg-base-100 shadow-xl">
+            <div class="card-body">
+                <h3 class="card-title">{ "Analysis" }</h3>
+                <div class="stats stats-vertical md:stats-horizontal shadow">
+                    <div class="stat">
+                        <div class="stat-title">{ "Accuracy" }</div>
+                        <div class="stat-value">{ format!("{:.1}%", analysis.accuracy) }</div>
+                    </div>
+                    { for [Verdict::Inaccuracy, Verdict::Mistake, Verdict::Blunder].into_iter().map(|verdict| html! {
+                        <div class="stat">
+                            <div class="stat-title">{ format!("{}s", verdict_label(verdict)) }</div>
+                            <div class="stat-value">{ analysis.count(verdict) }</div>
+                        </div>
+                    }) }
+                </div>
+                if analysis.count(Verdict::Blunder) > 0 {
+                    <p class="text-base-content/70">{ "Jump to a blunder" }</p>
+                    <div class="flex flex-wrap gap-2">
+                        { for blunders.map(|move_index| {
+                            let on_jump = on_jump.clone();
+                            html! {
+                                <button
+                                    class="btn btn-xs btn-error btn-outline"
+                                    onclick={move |_| on_jump.emit(move_index + 1)}
+                                >
+                                    { format!("Move {}", move_index + 1) }
+                                </button>
+                            }
+                        }) }
+                    </div>
+                }
+            </div>
+        </div>
+    }
+}
+
+#[function_component(ReplayPage)]
+pub fn replay() -> Html {
+    let recording_string = use_location()
+        .and_then(|location| location.query::<ReplayQuery>().ok())
+        .unwrap_or_default()
+        .recording;
+    let hint_task = use_oneshot_runner::<StorageTask>();
+    //The number of moves shown, 0 is the board before the first move
+    let step = use_state_eq(|| 0_usize);
+    let analysis = use_state(|| None::<Result<GameAnalysis, String>>);
+
+    {
+        let analysis = analysis.clone();
+        let seeded_recording = recording_string.clone();
+        use_effect_once(move || {
+            spawn_local(async move {
+                let request = StorageRequest::AnalyzeGame {
+                    seeded_recording,
+                    search: ANALYSIS_SEARCH,
+                };
+                match hint_task.run(request).await {
+                    StorageResponse::Analysis(game_analysis) => {
+                        analysis.set(Some(Ok(game_analysis)))
+                    }
+                    StorageResponse::Error(err) => {
+                        log::error!("Error analyzing the game: {:?}", err.to_string());
+                        analysis.set(Some(Err(err.to_string())));
+                    }
+                    _ => {}
+                }
+            });
+            || ()
+        });
+    }
+
+    let reconstruction = recording_string
+        .parse::<SeededRecording>()
+        .map_err(|err| format!("{:?}", err))
+        .and_then(|recording| {
+            recording
+                .reconstruct()
+                .map(|reconstruction| (recording, reconstruction.history))
+                .map_err(|err| err.to_string())
+        });
+    let (recording, history) = match reconstruction {
+        Ok(reconstruction) => reconstruction,
+        Err(err) => {
+            return html! {
+                <div class="flex flex-col items-center justify-center h-screen bg-base-200">
+                    <h1 class="text-3xl font-bold">{ "This game can not be replayed" }</h1>
+                    <p class="text-base-content/70">{ err }</p>
+                </div>
+            };
+        }
+    };
+
+    let last_step = recording.moves.len();
+    let current_step = (*step).min(last_step);
+    let go_to = {
+        let step = step.clone();
+        Callback::from(move |new_step: usize| step.set(new_step.min(last_step)))
+    };
+    let on_slider_input = {
+        let go_to = go_to.clone();
+        Callback::from(move |event: InputEvent| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            if let Ok(new_step) = input.value().parse::<usize>() {
+                go_to.emit(new_step);
+            }
+        })
+    };
+
+    //The move that led to the board being shown
+    let move_analysis = match (&*analysis, current_step) {
+        (Some(Ok(analysis)), 1..) => analysis
+            .moves
+            .iter()
+            .find(|analysis| analysis.move_index == current_step - 1),
+        _ => None,
+    };
+    let move_description = match current_step {
+        0 => html! { <span>{ "Starting board" }</span> },
+        _ => html! {
+            <span>
+                { format!("Move {}: {}", current_step, direction_arrow(recording.moves[current_step - 1])) }
+                if let Some(move_analysis) = move_analysis {
+                    <span class={classes!("badge", "ml-2", verdict_badge(move_analysis.verdict))}>
+                        { verdict_label(move_analysis.verdict) }
+                    </span>
+                    if move_analysis.best != move_analysis.played {
+                        <span class="ml-2 text-base-content/70">
+                            { format!("The engine liked {}", direction_arrow(move_analysis.best)) }
+                        </span>
+                    }
+                }
+            </span>
+        },
+    };
+
+    let share_text = match &*analysis {
+        Some(Ok(analysis)) => Some(format!(
+            "I played a game of at://2048 with {:.1}% accuracy and {} blunders. See every move here https://2048.symm.app/replay?recording={} \n @2048.blue",
+            analysis.accuracy,
+            analysis.count(Verdict::Blunder),
+            encode_uri_component(&recording_string)
+        )),
+        _ => None,
+    };
+
+    html! {
+        <div class="min-h-screen bg-base-200 p-4">
+            <div class="max-w-4xl mx-auto space-y-4">
+                <div class="card bg-base-100 shadow-xl">
+                    <div class="card-body items-center">
+                        <h2 class="card-title text-3xl font-bold">{ "Replay" }</h2>
+                        <p class="text-lg">{ move_description }</p>
+                        { replay_board(&history[current_step]) }
+                        <div class="join mt-4">
+                            <button class="btn join-item" onclick={let go_to = go_to.clone(); move |_| go_to.emit(0)}>{ "«" }</button>
+                            <button
+                                class="btn join-item"
+                                onclick={let go_to = go_to.clone(); move |_| go_to.emit(current_step.saturating_sub(1))}
+                            >
+                                { "‹" }
+                            </button>
+                            <button
+                                class="btn join-item"
+                                onclick={let go_to = go_to.clone(); move |_| go_to.emit(current_step + 1)}
+                            >
+                                { "›" }
+                            </button>
+                            <button class="btn join-item" onclick={let go_to = go_to.clone(); move |_| go_to.emit(last_step)}>{ "»" }</button>
+                        </div>
+                        <input
+                            type="range"
+                            class="range range-sm w-full mt-2"
+                            min="0"
+                            max={last_step.to_string()}
+                            value={current_step.to_string()}
+                            oninput={on_slider_input}
+                        />
+                    </div>
+                </div>
+                { match &*analysis {
+                    None => html! {
+                        <div class="flex items-center justify-center">
+                            <span class="loading loading-spinner loading-md" />
+                            <span class="ml-2">{ "The engine is going over every move..." }</span>
+                        </div>
+                    },
+                    Some(Err(err)) => html! {
+                        <p class="text-error text-center">{ format!("Could not analyze this game: {}", err) }</p>
+                    },
+                    Some(Ok(analysis)) => analysis_summary(analysis, go_to.clone()),
+                } }
+                if let Some(share_text) = share_text {
+                    <div class="flex justify-center">
+                        <a
+                            class="btn btn-sm btn-accent"
+                            href={format!("https://bsky.app/intent/compose?text={}", encode_uri_component(&share_text))}
+                            target="_blank"
+                            rel="noopener noreferrer"
+                        >
+                            { "Share analysis" }
+                        </a>
+                    </div>
+                }
+            </div>
+        </div>
+    }
+}
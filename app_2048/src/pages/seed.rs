@@ -1,16 +1,81 @@
 use crate::Route;
 use crate::idb::{CURRENT_GAME_STORE, DB_NAME, SELF_KEY, transaction_put};
+use crate::ranked::issue_ranked_seed;
+use crate::store::{GameMode, MAX_BOARD_SIZE, MIN_BOARD_SIZE, SettingsStore, UserStore};
 use atrium_api::types::string::Datetime;
 use indexed_db_futures::database::Database;
+use serde::Deserialize;
 use twothousand_forty_eight::v2::recording::SeededRecording;
 use types_2048::blue;
 use types_2048::blue::_2048::defs::SyncStatusData;
-use web_sys::{HtmlInputElement, InputEvent, SubmitEvent};
+use web_sys::{Event, HtmlInputElement, HtmlSelectElement, InputEvent, SubmitEvent};
 use yew::platform::spawn_local;
 use yew::{
     Callback, Html, Properties, TargetCast, classes, function_component, html, use_state_eq,
 };
-use yew_router::hooks::use_navigator;
+use yew_router::components::Link;
+use yew_router::hooks::{use_location, use_navigator};
+use yewdux::use_store;
+
+/// Query params on a shared seed link
+#[derive(Deserialize, Default)]
+struct SeedQuery {
+    /// The score of the player who shared the seed
+    score: Option<i64>,
+    /// The board size the seed was played on, links from before sizes were picked are 4x4
+    size: Option<usize>,
+}
+
+/// Replaces the current game with a fresh one on the seed
+async fn start_game(
+    seed: u32,
+    size: usize,
+    score_to_beat: Option<i64>,
+    seed_issued_at: Option<Datetime>,
+) -> Result<(), String> {
+    let history = SeededRecording::empty(seed, size, size);
+    let history_string: String = (&history).into();
+
+    let db = Database::open(DB_NAME)
+        .await
+        .map_err(|err| format!("Error opening database: {:?}", err))?;
+    let current_game = blue::_2048::game::RecordData {
+        completed: false,
+        created_at: Datetime::now(),
+        current_score: 0,
+        hints_used: Some(0),
+        max_tile: None,
+        //Seeds are shared from classic games, and ranked games are classic
+        mode: Some(GameMode::Classic.id().to_string()),
+        mode_limit: None,
+        move_times: Some(vec![]),
+        play_time: Some(0),
+        score_to_beat,
+        seed_issued_at,
+        seeded_recording: history_string,
+        started_at: None,
+        sync_status: SyncStatusData {
+            created_at: Datetime::now(),
+            hash: "".to_string(),
+            synced_with_at_repo: false,
+            updated_at: Datetime::now(),
+        }
+        .into(),
+        time_to_find_twenty_forty_eight: None,
+        undo_count: Some(0),
+        won: false,
+        won_at_move: None,
+    };
+    transaction_put(
+        db.clone(),
+        current_game.clone(),
+        CURRENT_GAME_STORE,
+        Some(SELF_KEY.to_string()),
+    )
+    .await
+    .map(|_| ())
+    .map_err(|e| format!("{:?} {:?}", current_game, e.to_string()))
+}
 
 #[derive(Properties, Clone, PartialEq)]
 pub struct SeedProps {
@@ -22,6 +87,31 @@ pub fn seed(props: &SeedProps) -> Html {
     let seed_input = use_state_eq(|| props.starting_seed.unwrap_or(0));
     let error = use_state_eq(|| None);
     let navigator = use_navigator().unwrap();
+    let (settings, _) = use_store::<SettingsStore>();
+    let (user_store, _) = use_store::<UserStore>();
+    let ranked_error = use_state_eq(|| None::<&'static str>);
+    let seed_query = use_location()
+        .and_then(|location| location.query::<SeedQuery>().ok())
+        .unwrap_or_default();
+    let shared_score = seed_query.score;
+    let shared_size = seed_query
+        .size
+        .filter(|size| (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(size));
+    let board_size = use_state_eq(|| match (props.starting_seed, shared_size) {
+        (_, Some(size)) => size,
+        //A shared seed without a size is from a 4x4 game
+        (Some(_), None) => 4,
+        (None, None) => settings.board_size,
+    });
+    let on_board_size_change = {
+        let board_size = board_size.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            if let Ok(size) = select.value().parse::<usize>() {
+                board_size.set(size);
+            }
+        })
+    };
     let on_input_handle = seed_input.clone();
     let error_input = error.clone();
 
@@ -36,11 +126,45 @@ pub fn seed(props: &SeedProps) -> Html {
             }
         }
     });
+    let on_play_ranked = {
+        let navigator = navigator.clone();
+        let board_size = board_size.clone();
+        let ranked_error = ranked_error.clone();
+        let did = user_store.did.clone();
+        Callback::from(move |_| {
+            let Some(did) = did.clone() else {
+                return;
+            };
+            ranked_error.set(None);
+            let size = *board_size;
+            let navigator = navigator.clone();
+            let ranked_error = ranked_error.clone();
+            spawn_local(async move {
+                let started = match issue_ranked_seed(&did, size).await {
+                    Ok(issued) => {
+                        // Saved on the game as `seed_issued_at` so the api can check the seed was dealt
+                        let issued_at = Datetime::new(issued.issued_at.fixed_offset());
+                        start_game(issued.seed, issued.size as usize, None, Some(issued_at)).await
+                    }
+                    Err(err) => Err(err),
+                };
+                match started {
+                    Ok(_) => navigator.push(&Route::GamePage),
+                    Err(err) => {
+                        log::error!("Error starting a ranked game: {}", err);
+                        ranked_error.set(Some("Could not get a ranked seed, try again soon"));
+                    }
+                }
+            });
+        })
+    };
     let error_view_clone = error.clone();
     let onsubmit = {
         let seed_input = seed_input.clone();
         let error_input = error.clone();
         let navigator = navigator.clone();
+        let starting_seed = props.starting_seed;
+        let board_size = board_size.clone();
         Callback::from(move |event: SubmitEvent| {
             let error_callback_clone = error_input.clone();
             error_callback_clone.set(None);
@@ -48,45 +172,18 @@ pub fn seed(props: &SeedProps) -> Html {
             let seed_value = *seed_input;
             let error_spawn = error_input.clone();
             let nav = navigator.clone();
+            let size = *board_size;
+            //The shared score only counts if they are still playing the seed that was shared
+            let score_to_beat = shared_score
+                .filter(|_| starting_seed == Some(seed_value) && shared_size.unwrap_or(4) == size);
             spawn_local(async move {
-                let history = SeededRecording::empty(seed_value, 4, 4);
-                let history_string: String = (&history).into();
-
-                let db = match Database::open(DB_NAME).await {
-                    Ok(db) => db,
-                    Err(err) => {
-                        panic!("Error opening database: {:?}", err);
-                    }
-                };
-                let current_game = blue::_2048::game::RecordData {
-                    completed: false,
-                    created_at: Datetime::now(),
-                    current_score: 0,
-                    seeded_recording: history_string,
-                    sync_status: SyncStatusData {
-                        created_at: Datetime::now(),
-                        hash: "".to_string(),
-                        synced_with_at_repo: false,
-                        updated_at: Datetime::now(),
-                    }
-                    .into(),
-                    won: false,
-                };
-                let result = transaction_put(
-                    db.clone(),
-                    current_game.clone(),
-                    CURRENT_GAME_STORE,
-                    Some(SELF_KEY.to_string()),
-                )
-                .await;
-                match result {
+                match start_game(seed_value, size, score_to_beat, None).await {
                     Ok(_) => nav.push(&Route::GamePage),
                     Err(e) => {
-                        log::info!("{:?}", current_game);
-                        log::error!("{:?}", e.to_string());
+                        log::error!("{}", e);
                         error_spawn.set(Some("Error creating a new game from that seed"));
                     }
-                };
+                }
             });
         })
     };
@@ -104,11 +201,14 @@ pub fn seed(props: &SeedProps) -> Html {
                 <p class="text-lg mb-4">
                     { "Someone share a starting seed with you? Type it here to replace your current game with that seed and see if you can do better than your friends!" }
                 </p>
+                if let Some(score) = shared_score {
+                    <p class="text-lg mb-4 font-bold">{ format!("Score to beat: {}", score) }</p>
+                }
                 <form {onsubmit} class="w-full flex flex-col items-center pt-1">
                     <div class="join w-full">
                         <div class="w-full">
                             <label
-                                class={classes!("w-full", "input",  "join-item", error_view_clone.is_none().then(|| Some("dark:input-primary eink:input-neutral")), error_view_clone.is_some().then(|| Some("input-error")))}
+                                class={classes!("w-full", "input",  "join-item", error_view_clone.is_none().then_some(Some("dark:input-primary eink:input-neutral")), error_view_clone.is_some().then_some(Some("input-error")))}
                             >
                                 <input
                                     {oninput}
@@ -122,6 +222,17 @@ pub fn seed(props: &SeedProps) -> Html {
                                 <div class="text-error">{ error_message }</div>
                             }
                         </div>
+                        <select
+                            onchange={on_board_size_change}
+                            class="select join-item w-auto"
+                            aria-label="Board size"
+                        >
+                            { for (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).map(|size| html! {
+                                <option value={size.to_string()} selected={size == *board_size}>
+                                    { format!("{}x{}", size, size) }
+                                </option>
+                            }) }
+                        </select>
                         <button
                             type="submit"
                             class="btn btn-neutral eink:btn-outline dark:btn-primary join-item"
@@ -131,6 +242,29 @@ pub fn seed(props: &SeedProps) -> Html {
                     </div>
                 </form>
             </div>
+            <div
+                class="backdrop-blur-md bg-base-200/50 p-6 rounded-lg shadow-lg mb-8 max-w-md w-full"
+            >
+                <h2 class="text-2xl font-bold mb-2">{ "Ranked" }</h2>
+                <p class="mb-4">
+                    { "Only games on a seed dealt by at://2048 count for the global leaderboards. You get a new seed every hour, and only your first game on each one counts. Ranked games are classic games with no hints or undos." }
+                </p>
+                if user_store.did.is_some() {
+                    <button
+                        class="btn btn-neutral eink:btn-outline dark:btn-primary w-full"
+                        onclick={on_play_ranked}
+                    >
+                        { format!("Play ranked {}x{}", *board_size, *board_size) }
+                    </button>
+                } else {
+                    <Link<Route> to={Route::LoginPage} classes="btn btn-outline w-full">
+                        { "Log in to play ranked" }
+                    </Link<Route>>
+                }
+                if let Some(error_message) = *ranked_error {
+                    <div class="text-error mt-2">{ error_message }</div>
+                }
+            </div>
             <div class="container mx-auto p-4" />
         </div>
     }
@@ -1,12 +1,27 @@
+use crate::agent::{is_classic_four_by_four, local_day, median_score};
 use crate::at_repo_sync::AtRepoSync;
-use crate::store::UserStore;
+use crate::components::charts::{BarChart, LineChart};
+use crate::idb::{DB_NAME, GAME_STORE, RecordStorageWrapper, object_get_all};
+use crate::store::{GameMode, UserStore};
 use atrium_api::agent::Agent;
+use indexed_db_futures::database::Database;
 use js_sys::encode_uri_component;
 use numfmt::{Formatter, Precision};
+use std::collections::BTreeMap;
+use twothousand_forty_eight::unified::game::GameState;
+use twothousand_forty_eight::v2::recording::SeededRecording;
+use types_2048::blue::_2048::game;
+use wasm_bindgen::JsValue;
 use yew::platform::spawn_local;
 use yew::{Html, Properties, function_component, html, use_effect_with, use_state};
+use yew_hooks::use_effect_once;
 use yewdux::prelude::*;
 
+/// How many days back the games per day chart goes, including today
+const GAMES_PER_DAY_RANGE: i64 = 14;
+/// How many of the most recent games each point on the win rate trend covers
+const WIN_RATE_WINDOW: usize = 10;
+
 #[derive(Properties, PartialEq)]
 pub struct BSkyButtonProps {
     pub text: String,
@@ -41,6 +56,116 @@ pub fn bsky_button(props: &BSkyButtonProps) -> Html {
     )
 }
 
+/// Formats milliseconds of play time as hours, minutes and seconds
+fn format_play_time(ms: i64) -> String {
+    let total_seconds = ms / 1000;
+    let hours = total_seconds / 3600;
+    let minutes = (total_seconds % 3600) / 60;
+    let seconds = total_seconds % 60;
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Everything the charts on the stats page need, built from the games saved on this device
+#[derive(Clone, PartialEq, Default)]
+struct ChartData {
+    scores: Vec<f64>,
+    highest_tiles: Vec<(String, f64)>,
+    games_per_day: Vec<(String, f64)>,
+    win_rate_trend: Vec<f64>,
+    /// Of the classic 4x4 games, like the main stats. Worked out from the games on this device,
+    /// the same way the medianScore synced in the stats record is
+    median_score: Option<i64>,
+}
+
+/// Month/day label for one of the local days from [local_day]
+fn day_label(day: i64) -> String {
+    let offset_minutes = js_sys::Date::new_0().get_timezone_offset() as i64;
+    let date = js_sys::Date::new(&JsValue::from_f64(
+        ((day * 86_400 + offset_minutes * 60) * 1000) as f64,
+    ));
+    format!("{}/{}", date.get_month() + 1, date.get_date())
+}
+
+fn build_chart_data(mut games: Vec<game::RecordData>) -> ChartData {
+    games.retain(|game| game.completed);
+    games.sort_by(|a, b| a.created_at.as_ref().cmp(b.created_at.as_ref()));
+
+    let scores = games.iter().map(|game| game.current_score as f64).collect();
+
+    let mut highest_tiles: BTreeMap<usize, f64> = BTreeMap::new();
+    let mut classic_scores = vec![];
+    for game in &games {
+        let seeded_recording: SeededRecording = match game.seeded_recording.parse() {
+            Ok(seeded_recording) => seeded_recording,
+            Err(err) => {
+                log::error!("Error parsing a saved game for the charts: {:?}", err);
+                continue;
+            }
+        };
+        if is_classic_four_by_four(game) {
+            classic_scores.push(game.current_score);
+        }
+        let gamestate = match GameState::from_reconstructable_ruleset(&seeded_recording) {
+            Ok(gamestate) => gamestate,
+            Err(err) => {
+                log::error!(
+                    "Error reconstructing a saved game for the charts: {:?}",
+                    err
+                );
+                continue;
+            }
+        };
+        let highest_tile = gamestate
+            .board
+            .tiles
+            .iter()
+            .flatten()
+            .filter_map(|tile| *tile)
+            .map(|tile| tile.value)
+            .max()
+            .unwrap_or(0);
+        *highest_tiles.entry(highest_tile).or_default() += 1.0;
+    }
+
+    let today = local_day(&atrium_api::types::string::Datetime::now());
+    let mut games_per_day: BTreeMap<i64, f64> = (today - GAMES_PER_DAY_RANGE + 1..=today)
+        .map(|day| (day, 0.0))
+        .collect();
+    for game in &games {
+        if let Some(count) = games_per_day.get_mut(&local_day(&game.created_at)) {
+            *count += 1.0;
+        }
+    }
+
+    let win_rate_trend = (1..=games.len())
+        .map(|end| {
+            let window = &games[end.saturating_sub(WIN_RATE_WINDOW)..end];
+            let wins = window.iter().filter(|game| game.won).count();
+            wins as f64 / window.len() as f64 * 100.0
+        })
+        .collect();
+
+    ChartData {
+        scores,
+        highest_tiles: highest_tiles
+            .into_iter()
+            .map(|(tile, count)| (tile.to_string(), count))
+            .collect(),
+        games_per_day: games_per_day
+            .into_iter()
+            .map(|(day, count)| (day_label(day), count))
+            .collect(),
+        win_rate_trend,
+        median_score: median_score(classic_scores),
+    }
+}
+
 #[function_component(StatsPage)]
 pub fn stats() -> Html {
     let (user_store, _) = use_store::<UserStore>();
@@ -50,6 +175,29 @@ pub fn stats() -> Html {
         .separator(',')
         .expect("Could not build the number formatter.");
     let user_store_clone = user_store.clone();
+    let chart_state = use_state(ChartData::default);
+
+    let chart_state_clone = chart_state.clone();
+    use_effect_once(move || {
+        spawn_local(async move {
+            let db = match Database::open(DB_NAME).await {
+                Ok(db) => db,
+                Err(err) => {
+                    log::error!("Error opening the database for the charts: {:?}", err);
+                    return;
+                }
+            };
+            match object_get_all::<RecordStorageWrapper<game::RecordData>>(db, GAME_STORE).await {
+                Ok(games) => chart_state_clone.set(build_chart_data(
+                    games.into_iter().map(|game| game.record).collect(),
+                )),
+                Err(err) => {
+                    log::error!("Error loading games for the charts: {:?}", err.to_string());
+                }
+            }
+        });
+        || ()
+    });
 
     use_effect_with(stats_state.clone(), move |stats_state| {
         let stats_state = stats_state.clone();
@@ -109,10 +257,10 @@ pub fn stats() -> Html {
     if let Some(stats_state) = (*stats_state).clone() {
         //HACK I am very sorry to who ever finds this. I don't have an explanation other than I gave up. Will comeback later...
         let mut formatter = number_formatter.clone();
-        let high_score_formatted = formatter.fmt2(stats_state.highest_score.clone());
+        let high_score_formatted = formatter.fmt2(stats_state.highest_score);
 
         let mut formatter = number_formatter.clone();
-        let average_score_formatted = formatter.fmt2(stats_state.average_score.clone());
+        let average_score_formatted = formatter.fmt2(stats_state.average_score);
 
         let mut formatter = number_formatter.clone();
         let total_score_formatted = formatter.fmt2(stats_state.total_score);
@@ -128,9 +276,47 @@ pub fn stats() -> Html {
         let lowest_turns_till_2048_formatted =
             formatter.fmt2(stats_state.least_moves_to_find_twenty_forty_eight);
 
+        let mut formatter = number_formatter.clone();
+        let games_won_formatted = formatter.fmt2(stats_state.games_won.unwrap_or(0));
+
+        let mut formatter = number_formatter.clone();
+        let games_continued_after_win_formatted =
+            formatter.fmt2(stats_state.games_continued_after_win.unwrap_or(0));
+
         let mut formatter = number_formatter.clone();
         let total_games_formatted = formatter.fmt2(stats_state.games_played);
 
+        let mut formatter = number_formatter.clone();
+        let median_score_formatted = chart_state
+            .median_score
+            .map_or("-".to_string(), |median_score| {
+                formatter.fmt2(median_score).to_string()
+            });
+
+        let mut formatter = number_formatter.clone();
+        let total_moves_formatted = formatter.fmt2(stats_state.total_moves.unwrap_or(0));
+
+        let direction_counts_formatted = match &stats_state.direction_counts {
+            Some(counts) => format!(
+                "↑ {} ↓ {} ← {} → {}",
+                counts.up, counts.down, counts.left, counts.right
+            ),
+            None => "No moves yet".to_string(),
+        };
+
+        let total_time_played_formatted =
+            format_play_time(stats_state.total_time_played.unwrap_or(0));
+
+        let fastest_twenty_forty_eight_formatted = stats_state
+            .fastest_time_to_find_twenty_forty_eight
+            .map_or("-".to_string(), format_play_time);
+
+        let consecutive_days_played = stats_state.consecutive_days_played.unwrap_or(0);
+        let board_sizes = stats_state.board_sizes.clone().unwrap_or_default();
+        let modes = stats_state.modes.clone().unwrap_or_default();
+        let longest_consecutive_days_played =
+            stats_state.longest_consecutive_days_played.unwrap_or(0);
+
         html! {
             <div class="min-h-screen bg-base-200 p-4">
                 <div class="max-w-4xl mx-auto space-y-4">
@@ -151,6 +337,7 @@ pub fn stats() -> Html {
                         <div class="card shadow-xl">
                             <div class="card-body">
                                 <h3 class="card-title">{ "Score Statistics" }</h3>
+                                <p class="text-sm text-base-content/70">{ "Classic games on a 4x4 board" }</p>
                                 <div class="stats stats-vertical shadow">
                                     <div class="stat">
                                         <div class="stat-figure">
@@ -197,16 +384,22 @@ pub fn stats() -> Html {
                                         <div class="stat-title">{ "Total Score" }</div>
                                         <div class="stat-value">{ total_score_formatted }</div>
                                     </div>
+                                    <div class="stat">
+                                        <div class="stat-title">{ "Median Score" }</div>
+                                        <div class="stat-value">{ median_score_formatted.clone() }</div>
+                                        <div class="stat-desc">{ "Of the games on this device" }</div>
+                                    </div>
                                 </div>
                             </div>
                             <BSkyButton
-                                text={format!("High Score: {}\nAverage Score: {}\nTotal Score: {}\n",high_score_formatted, average_score_formatted, total_score_formatted);}
+                                text={format!("High Score: {}\nAverage Score: {}\nMedian Score: {}\nTotal Score: {}\n",high_score_formatted, average_score_formatted, median_score_formatted, total_score_formatted);}
                             />
                         </div>
                         // Achievement Stats Card
                         <div class="card  shadow-xl">
                             <div class="card-body">
                                 <h3 class="card-title">{ "Achievements" }</h3>
+                                <p class="text-sm text-base-content/70">{ "Classic games on a 4x4 board" }</p>
                                 <div class="stats stats-vertical shadow">
                                     <div class="stat">
                                         <div class="stat-figure ">
@@ -254,10 +447,24 @@ pub fn stats() -> Html {
                                         </div>
                                         <div class="stat-desc">{ "moves" }</div>
                                     </div>
+                                    <div class="stat">
+                                        <div class="stat-title">{ "Fastest 2048" }</div>
+                                        <div class="stat-value">
+                                            { fastest_twenty_forty_eight_formatted.clone() }
+                                        </div>
+                                        <div class="stat-desc">{ "of play time" }</div>
+                                    </div>
+                                    <div class="stat">
+                                        <div class="stat-title">{ "Games Won" }</div>
+                                        <div class="stat-value">{ games_won_formatted }</div>
+                                        <div class="stat-desc">
+                                            { format!("kept going past 2048 in {}", games_continued_after_win_formatted) }
+                                        </div>
+                                    </div>
                                 </div>
                             </div>
                             <BSkyButton
-                                text={format!("Highest Block: {}\nTimes 2048 found: {}\nLowest turns to 2048: {}\n",highest_number_block_formatted, times_twenty_forty_eight_been_found_formatted, lowest_turns_till_2048_formatted);}
+                                text={format!("Highest Block: {}\nTimes 2048 found: {}\nLowest turns to 2048: {}\nFastest 2048: {}\nGames won: {} (kept going in {})\n",highest_number_block_formatted, times_twenty_forty_eight_been_found_formatted, lowest_turns_till_2048_formatted, fastest_twenty_forty_eight_formatted, games_won_formatted, games_continued_after_win_formatted);}
                             />
                         </div>
                         // Game History Card
@@ -306,6 +513,140 @@ pub fn stats() -> Html {
                                 text={format!("I've played {} games of at://2048", total_games_formatted);}
                             />
                         </div>
+                        // Play Time Card
+                        <div class="card shadow-xl">
+                            <div class="card-body">
+                                <h3 class="card-title">{ "Play Time" }</h3>
+                                <div class="stats stats-vertical shadow">
+                                    <div class="stat">
+                                        <div class="stat-title">{ "Total Time Played" }</div>
+                                        <div class="stat-value">
+                                            { total_time_played_formatted.clone() }
+                                        </div>
+                                    </div>
+                                    <div class="stat">
+                                        <div class="stat-title">{ "Total Moves" }</div>
+                                        <div class="stat-value">{ total_moves_formatted }</div>
+                                        <div class="stat-desc">{ direction_counts_formatted }</div>
+                                    </div>
+                                    <div class="stat">
+                                        <div class="stat-title">{ "Days In A Row" }</div>
+                                        <div class="stat-value">{ consecutive_days_played }</div>
+                                        <div class="stat-desc">
+                                            { format!("Longest streak: {} days", longest_consecutive_days_played) }
+                                        </div>
+                                    </div>
+                                </div>
+                            </div>
+                            <BSkyButton
+                                text={format!("I've spent {} playing at://2048 over {} moves and played {} days in a row", total_time_played_formatted, total_moves_formatted, consecutive_days_played);}
+                            />
+                        </div>
+                        // Board Size Card
+                        <div class="card shadow-xl">
+                            <div class="card-body">
+                                <h3 class="card-title">{ "By Board Size" }</h3>
+                                if board_sizes.is_empty() {
+                                    <p class="text-base-content/70">
+                                        { "Finish a game to see your stats for each board size" }
+                                    </p>
+                                } else {
+                                    <div class="overflow-x-auto">
+                                        <table class="table table-sm">
+                                            <thead>
+                                                <tr>
+                                                    <th>{ "Size" }</th>
+                                                    <th>{ "Games" }</th>
+                                                    <th>{ "Best" }</th>
+                                                    <th>{ "Block" }</th>
+                                                    <th>{ "2048s" }</th>
+                                                </tr>
+                                            </thead>
+                                            <tbody>
+                                                { for board_sizes.iter().map(|size_stats| html! {
+                                                    <tr key={size_stats.size}>
+                                                        <td>{ format!("{}x{}", size_stats.size, size_stats.size) }</td>
+                                                        <td>{ size_stats.games_played }</td>
+                                                        <td>{ size_stats.highest_score }</td>
+                                                        <td>{ size_stats.highest_number_block }</td>
+                                                        <td>{ size_stats.times_twenty_forty_eight_been_found }</td>
+                                                    </tr>
+                                                }) }
+                                            </tbody>
+                                        </table>
+                                    </div>
+                                }
+                            </div>
+                        </div>
+                        if !modes.is_empty() {
+                            // Mode Card
+                            <div class="card shadow-xl">
+                                <div class="card-body">
+                                    <h3 class="card-title">{ "By Mode" }</h3>
+                                    <div class="overflow-x-auto">
+                                        <table class="table table-sm">
+                                            <thead>
+                                                <tr>
+                                                    <th>{ "Mode" }</th>
+                                                    <th>{ "Size" }</th>
+                                                    <th>{ "Games" }</th>
+                                                    <th>{ "Best" }</th>
+                                                    <th>{ "Block" }</th>
+                                                </tr>
+                                            </thead>
+                                            <tbody>
+                                                { for modes.iter().map(|mode_stats| {
+                                                    let mode = GameMode::from_record(Some(&mode_stats.mode), Some(mode_stats.limit));
+                                                    html! {
+                                                        <tr key={format!("{}-{}-{}", mode_stats.mode, mode_stats.limit, mode_stats.size)}>
+                                                            <td>{ mode.label() }</td>
+                                                            <td>{ format!("{}x{}", mode_stats.size, mode_stats.size) }</td>
+                                                            <td>{ mode_stats.games_played }</td>
+                                                            <td>{ mode_stats.highest_score }</td>
+                                                            <td>{ mode_stats.highest_number_block }</td>
+                                                        </tr>
+                                                    }
+                                                }) }
+                                            </tbody>
+                                        </table>
+                                    </div>
+                                </div>
+                            </div>
+                        }
+                    </div>
+                    // Charts
+                    <div class="grid grid-cols-1 md:grid-cols-2 gap-4">
+                        <div class="card bg-base-100 shadow-xl">
+                            <div class="card-body">
+                                <h3 class="card-title">{ "Score Over Time" }</h3>
+                                <LineChart values={chart_state.scores.clone()} />
+                            </div>
+                        </div>
+                        <div class="card bg-base-100 shadow-xl">
+                            <div class="card-body">
+                                <h3 class="card-title">{ "Highest Tiles" }</h3>
+                                <BarChart bars={chart_state.highest_tiles.clone()} />
+                            </div>
+                        </div>
+                        <div class="card bg-base-100 shadow-xl">
+                            <div class="card-body">
+                                <h3 class="card-title">{ "Games Per Day" }</h3>
+                                <BarChart bars={chart_state.games_per_day.clone()} />
+                            </div>
+                        </div>
+                        <div class="card bg-base-100 shadow-xl">
+                            <div class="card-body">
+                                <h3 class="card-title">{ "Win Rate Trend" }</h3>
+                                <LineChart
+                                    values={chart_state.win_rate_trend.clone()}
+                                    y_max={Some(100.0)}
+                                    y_suffix="%"
+                                />
+                                <p class="text-sm text-base-content/70">
+                                    { format!("Over your last {} games at each point", WIN_RATE_WINDOW) }
+                                </p>
+                            </div>
+                        </div>
                     </div>
                 </div>
             </div>
@@ -0,0 +1,139 @@
+use crate::live::{LiveGame, watch_socket_url};
+use crate::pages::replay::replay_board;
+use futures::StreamExt;
+use futures::future::{Either, FutureExt, select};
+use gloo::net::websocket::Message;
+use gloo::net::websocket::futures::WebSocket;
+use gloo::timers::future::TimeoutFuture;
+use twothousand_forty_eight::unified::game::GameState;
+use twothousand_forty_eight::v2::recording::SeededRecording;
+use yew::platform::spawn_local;
+use yew::{Html, Properties, classes, function_component, html, use_effect_with, use_state_eq};
+
+/// How long to wait before trying the socket again after it drops
+const RECONNECT_DELAY_MS: u32 = 2000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Connection {
+    Connecting,
+    Connected,
+    Reconnecting,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct WatchProps {
+    pub did: String,
+}
+
+/// Replays the recording so far to get the board the player is looking at and how many moves
+/// it took
+fn live_gamestate(live_game: &LiveGame) -> Option<(GameState, usize)> {
+    let recording: SeededRecording = live_game.seeded_recording.parse().ok()?;
+    let gamestate = GameState::from_reconstructable_ruleset(&recording).ok()?;
+    Some((gamestate, recording.moves.len()))
+}
+
+#[function_component(WatchPage)]
+pub fn watch(props: &WatchProps) -> Html {
+    let live_game = use_state_eq(|| None::<LiveGame>);
+    let connection = use_state_eq(|| Connection::Connecting);
+
+    {
+        let live_game = live_game.clone();
+        let connection = connection.clone();
+        use_effect_with(props.did.clone(), move |did| {
+            let url = watch_socket_url(did);
+            //Dropping stop when the page goes away closes the socket and ends the reconnect loop
+            let (stop, stopped) = futures::channel::oneshot::channel::<()>();
+            let stopped = stopped.shared();
+            spawn_local(async move {
+                loop {
+                    match WebSocket::open(&url) {
+                        Ok(socket) => {
+                            connection.set(Connection::Connected);
+                            //The api sends the whole recording so far first, so a reconnect catches up
+                            let mut messages = socket.take_until(stopped.clone());
+                            while let Some(message) = messages.next().await {
+                                match message {
+                                    Ok(Message::Text(text)) => {
+                                        match serde_json::from_str::<LiveGame>(&text) {
+                                            Ok(update) => live_game.set(Some(update)),
+                                            Err(err) => {
+                                                log::error!("Error reading live game: {}", err)
+                                            }
+                                        }
+                                    }
+                                    Ok(Message::Bytes(_)) => {}
+                                    Err(err) => {
+                                        log::error!("Live game socket closed: {}", err);
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                        Err(err) => log::error!("Error opening live game socket: {}", err),
+                    }
+                    if stopped.peek().is_some() {
+                        break;
+                    }
+                    connection.set(Connection::Reconnecting);
+                    let delay = TimeoutFuture::new(RECONNECT_DELAY_MS);
+                    if let Either::Right(_) = select(delay, stopped.clone()).await {
+                        break;
+                    }
+                }
+            });
+            move || drop(stop)
+        });
+    }
+
+    let gamestate = (*live_game).as_ref().and_then(live_gamestate);
+    let status = match (&*live_game, *connection) {
+        (_, Connection::Reconnecting) => ("Reconnecting", "badge-warning"),
+        (_, Connection::Connecting) => ("Connecting", "badge-ghost"),
+        (Some(game), _) if game.finished => ("Finished", "badge-neutral"),
+        (Some(_), _) => ("Live", "badge-error"),
+        (None, _) => ("Not playing", "badge-ghost"),
+    };
+
+    html! {
+        <div class="min-h-screen bg-base-200 p-4">
+            <div class="max-w-4xl mx-auto space-y-4">
+                <div class="card bg-base-100 shadow-xl">
+                    <div class="card-body">
+                        <h2 class="card-title text-2xl">
+                            { "Watching" }
+                            <span class={classes!("badge", status.1)}>{ status.0 }</span>
+                        </h2>
+                        <a
+                            class="link text-base-content/70 break-all"
+                            href={format!("https://bsky.app/profile/{}", props.did)}
+                            target="_blank"
+                        >
+                            { props.did.clone() }
+                        </a>
+                        if let Some((gamestate, moves)) = &gamestate {
+                            <div class="stats shadow">
+                                <div class="stat">
+                                    <div class="stat-title">{ "Score" }</div>
+                                    <div class="stat-value">{ gamestate.score_current }</div>
+                                </div>
+                                <div class="stat">
+                                    <div class="stat-title">{ "Moves" }</div>
+                                    <div class="stat-value">{ moves }</div>
+                                </div>
+                            </div>
+                        } else {
+                            <p class="text-base-content/70">
+                                { "Nothing is being streamed right now. The board shows up here as soon as they make a move." }
+                            </p>
+                        }
+                    </div>
+                </div>
+                if let Some((gamestate, _)) = &gamestate {
+                    { replay_board(&gamestate.board) }
+                }
+            </div>
+        </div>
+    }
+}
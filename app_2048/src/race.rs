@@ -0,0 +1,125 @@
+use gloo::net::http::Request;
+use serde::{Deserialize, Serialize};
+
+/// How long a race can be set to last, matching the api
+pub const MAX_RACE_MINUTES: u32 = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    TwentyFortyEight,
+    Time,
+    AllOut,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RacePhase {
+    Lobby,
+    Countdown {
+        starts_at: String,
+    },
+    Racing {
+        ends_at: String,
+    },
+    Finished {
+        winner: Option<String>,
+        reason: FinishReason,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RacePlayer {
+    pub id: String,
+    pub name: String,
+    pub ready: bool,
+    pub connected: bool,
+    pub score: usize,
+    pub moves: usize,
+    pub board: Vec<Vec<usize>>,
+    pub over: bool,
+}
+
+/// The whole race as the api sends it after every change
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RaceView {
+    pub id: String,
+    /// Missing till the race starts
+    pub seed: Option<u32>,
+    pub size: usize,
+    pub minutes: u32,
+    pub phase: RacePhase,
+    pub players: Vec<RacePlayer>,
+}
+
+/// What the race's socket sends
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RaceMessage {
+    Joined { player_id: String },
+    Race(RaceView),
+    Error { message: String },
+}
+
+/// What a player sends on the race's socket
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RaceCommand {
+    Ready { ready: bool },
+    Move { seeded_recording: String },
+}
+
+#[derive(Serialize)]
+struct NewRace {
+    minutes: u32,
+    size: usize,
+}
+
+/// Milliseconds till an RFC 3339 time the api sent, never below 0
+pub fn ms_until(time: &str) -> f64 {
+    (js_sys::Date::parse(time) - js_sys::Date::now()).max(0.0)
+}
+
+/// The websocket to play or watch a race on. Without a name it only watches
+pub fn race_socket_url(id: &str, name: Option<&str>) -> String {
+    let location = gloo::utils::window().location();
+    let scheme = match location.protocol().as_deref() {
+        Ok("https:") => "wss",
+        _ => "ws",
+    };
+    let query = name
+        .map(|name| format!("?name={}", js_sys::encode_uri_component(name)))
+        .unwrap_or_default();
+    format!(
+        "{}://{}/api/races/{}/play{}",
+        scheme,
+        location.host().unwrap_or_default(),
+        id,
+        query
+    )
+}
+
+/// Races still waiting for players
+pub async fn open_races() -> Result<Vec<RaceView>, String> {
+    let response = Request::get("/api/races")
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if !response.ok() {
+        return Err(format!("{} {}", response.status(), response.status_text()));
+    }
+    response.json().await.map_err(|err| err.to_string())
+}
+
+pub async fn create_race(minutes: u32, size: usize) -> Result<RaceView, String> {
+    let response = Request::post("/api/races")
+        .json(&NewRace { minutes, size })
+        .map_err(|err| err.to_string())?
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if !response.ok() {
+        return Err(format!("{} {}", response.status(), response.status_text()));
+    }
+    response.json().await.map_err(|err| err.to_string())
+}
@@ -0,0 +1,19 @@
+use atrium_api::types::string::Did;
+use client_2048::types::{IssuedSeed, SeedRequest};
+
+/// The method the service auth token for a ranked seed is for, matching the api
+const ISSUE_RANKED_SEED_METHOD: &str = "blue.2048.ranked.issueSeed";
+
+/// Asks the api for the player's ranked seed on a board of this size, signed in as the player
+pub async fn issue_ranked_seed(did: &Did, size: usize) -> Result<IssuedSeed, String> {
+    let request = SeedRequest {
+        did: did.as_str().to_string(),
+        size: Some(size as u32),
+    };
+    crate::api::player_client(did, ISSUE_RANKED_SEED_METHOD)
+        .await?
+        .issue_ranked_seed(&request)
+        .await
+        .map(|response| response.into_inner())
+        .map_err(|err| err.to_string())
+}
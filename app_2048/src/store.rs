@@ -1,3 +1,4 @@
+use ai_2048::SearchConfig;
 use atrium_api::types::string::Did;
 use serde::{Deserialize, Serialize};
 use yewdux::prelude::*;
@@ -9,6 +10,124 @@ pub struct UserStore {
     pub did: Option<Did>,
 }
 
+/// How many moves a player can take back each game
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum UndoLimit {
+    #[default]
+    Off,
+    PerGame(u32),
+    Unlimited,
+}
+
+impl UndoLimit {
+    /// How many undos are left after using `used` of them. `None` means there is no limit
+    pub fn remaining(&self, used: u32) -> Option<u32> {
+        match self {
+            UndoLimit::Off => Some(0),
+            UndoLimit::PerGame(limit) => Some(limit.saturating_sub(used)),
+            UndoLimit::Unlimited => None,
+        }
+    }
+
+    pub fn allows_undo(&self, used: u32) -> bool {
+        self.remaining(used).is_none_or(|remaining| remaining > 0)
+    }
+}
+
+/// The rules a game is played under
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    /// Highest score before the play clock hits the limit
+    TimeAttack { seconds: u32 },
+    /// Highest score in a set number of moves
+    MoveLimit { moves: u32 },
+}
+
+impl GameMode {
+    /// The mode value on blue.2048.game records
+    pub fn id(&self) -> &'static str {
+        match self {
+            GameMode::Classic => "classic",
+            GameMode::TimeAttack { .. } => "timeAttack",
+            GameMode::MoveLimit { .. } => "moveLimit",
+        }
+    }
+
+    /// The modeLimit value on blue.2048.game records, seconds or moves
+    pub fn limit(&self) -> Option<i64> {
+        match self {
+            GameMode::Classic => None,
+            GameMode::TimeAttack { seconds } => Some(*seconds as i64),
+            GameMode::MoveLimit { moves } => Some(*moves as i64),
+        }
+    }
+
+    /// Reads the mode back off a record. Unknown modes and missing limits fall back to classic
+    pub fn from_record(mode: Option<&str>, limit: Option<i64>) -> Self {
+        let limit = limit
+            .and_then(|limit| u32::try_from(limit).ok())
+            .filter(|limit| *limit > 0);
+        match (mode, limit) {
+            (Some("timeAttack"), Some(seconds)) => GameMode::TimeAttack { seconds },
+            (Some("moveLimit"), Some(moves)) => GameMode::MoveLimit { moves },
+            _ => GameMode::Classic,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            GameMode::Classic => "Classic".to_string(),
+            GameMode::TimeAttack { seconds } => format!("{} minute time attack", seconds / 60),
+            GameMode::MoveLimit { moves } => format!("{} moves", moves),
+        }
+    }
+}
+
+/// The modes a player can pick from for new games
+pub const GAME_MODE_OPTIONS: [GameMode; 5] = [
+    GameMode::Classic,
+    GameMode::TimeAttack { seconds: 3 * 60 },
+    GameMode::TimeAttack { seconds: 5 * 60 },
+    GameMode::MoveLimit { moves: 100 },
+    GameMode::MoveLimit { moves: 250 },
+];
+
+/// Smallest board a game can be started on
+pub const MIN_BOARD_SIZE: usize = 3;
+/// Largest board a game can be started on. The game engine keeps its tiles in a fixed 6x6 grid
+pub const MAX_BOARD_SIZE: usize = twothousand_forty_eight::board::MAX_WIDTH;
+/// The classic 4x4 board
+pub const DEFAULT_BOARD_SIZE: usize = 4;
+
+#[derive(PartialEq, Serialize, Deserialize, Store, Clone)]
+#[store(storage = "local")]
+#[serde(default)]
+pub struct SettingsStore {
+    pub undo_limit: UndoLimit,
+    /// The width and height of the board new games are started with
+    pub board_size: usize,
+    /// How hard the engine looks when asked for a hint or playing the demo
+    pub hint_search: SearchConfig,
+    /// The mode new games are started in
+    pub game_mode: GameMode,
+    /// Streams each move of the game in progress so others can watch on /watch/{did}
+    pub live_streaming: bool,
+}
+
+impl Default for SettingsStore {
+    fn default() -> Self {
+        Self {
+            undo_limit: UndoLimit::default(),
+            board_size: DEFAULT_BOARD_SIZE,
+            hint_search: SearchConfig::default(),
+            game_mode: GameMode::default(),
+            live_streaming: false,
+        }
+    }
+}
+
 //Incase I need a debug listener later
 // #[store(storage = "local", listener(LogListener))]
 // struct LogListener;
@@ -0,0 +1,39 @@
+use std::path::Path;
+
+// The document api_2048 writes with `api_2048 openapi`
+const SPEC: &str = "../api_2048/openapi.json";
+
+// Drops the websocket endpoints. The generated code for them needs an upgraded connection,
+// which browsers do not hand out, so the app opens those sockets itself
+fn without_websockets(spec: &mut serde_json::Value) {
+    let Some(paths) = spec
+        .get_mut("paths")
+        .and_then(|paths| paths.as_object_mut())
+    else {
+        return;
+    };
+    for item in paths.values_mut() {
+        if let Some(item) = item.as_object_mut() {
+            item.retain(|_, operation| operation.get("x-dropshot-websocket").is_none());
+        }
+    }
+    paths.retain(|_, item| item.as_object().is_some_and(|item| !item.is_empty()));
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SPEC);
+    let file =
+        std::fs::File::open(SPEC).unwrap_or_else(|e| panic!("Error reading {}: {}", SPEC, e));
+    let mut spec: serde_json::Value =
+        serde_json::from_reader(file).unwrap_or_else(|e| panic!("Error parsing {}: {}", SPEC, e));
+    without_websockets(&mut spec);
+    let spec: openapiv3::OpenAPI =
+        serde_json::from_value(spec).unwrap_or_else(|e| panic!("{} is not OpenAPI: {}", SPEC, e));
+
+    let tokens = progenitor::Generator::default()
+        .generate_tokens(&spec)
+        .unwrap_or_else(|e| panic!("Error generating the client: {}", e));
+    let ast = syn::parse2(tokens).expect("the generated client parses");
+    let out = Path::new(&std::env::var("OUT_DIR").expect("cargo sets OUT_DIR")).join("client.rs");
+    std::fs::write(out, prettyplease::unparse(&ast)).expect("the generated client can be written");
+}
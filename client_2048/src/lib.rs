@@ -0,0 +1,4 @@
+//! A typed client for api_2048, generated at build time from the OpenAPI document it commits.
+//! Regenerate the document with `api_2048 openapi` when the endpoints change
+
+include!(concat!(env!("OUT_DIR"), "/client.rs"));
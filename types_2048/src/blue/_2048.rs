@@ -1,13 +1,34 @@
 // @generated - This file is generated by esquema-codegen (forked from atrium-codegen). DO NOT EDIT.
 //!Definitions for the `blue.2048` namespace.
+pub mod ban;
+pub mod challenge;
 pub mod defs;
 pub mod game;
 pub mod key;
 pub mod player;
+pub mod tournament;
 pub mod verification;
 #[derive(Debug)]
+pub struct Ban;
+impl atrium_api::types::Collection for Ban {
+    const NSID: &'static str = "blue.2048.ban";
+    type Record = ban::Record;
+}
+#[derive(Debug)]
+pub struct Challenge;
+impl atrium_api::types::Collection for Challenge {
+    const NSID: &'static str = "blue.2048.challenge";
+    type Record = challenge::Record;
+}
+#[derive(Debug)]
 pub struct Game;
 impl atrium_api::types::Collection for Game {
     const NSID: &'static str = "blue.2048.game";
     type Record = game::Record;
 }
+#[derive(Debug)]
+pub struct Tournament;
+impl atrium_api::types::Collection for Tournament {
+    const NSID: &'static str = "blue.2048.tournament";
+    type Record = tournament::Record;
+}
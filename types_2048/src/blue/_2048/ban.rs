@@ -0,0 +1,24 @@
+// @generated - This file is generated by esquema-codegen (forked from atrium-codegen). DO NOT EDIT.
+//!Definitions for the `blue.2048.ban` namespace.
+use atrium_api::types::TryFromUnknown;
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordData {
+    pub created_at: atrium_api::types::string::Datetime,
+    ///The at://uri of the banned blue.2048.game. Without one every game by the player is banned
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub game: core::option::Option<String>,
+    ///Why the ban was made
+    pub reason: String,
+    ///DID of the banned player, or of the player whose game is banned
+    pub subject: atrium_api::types::string::Did,
+}
+pub type Record = atrium_api::types::Object<RecordData>;
+impl From<atrium_api::types::Unknown> for RecordData {
+    fn from(value: atrium_api::types::Unknown) -> Self {
+        Self::try_from_unknown(value).unwrap()
+    }
+}
@@ -0,0 +1,32 @@
+// @generated - This file is generated by esquema-codegen (forked from atrium-codegen). DO NOT EDIT.
+//!Definitions for the `blue.2048.challenge` namespace.
+use atrium_api::types::TryFromUnknown;
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordData {
+    pub created_at: atrium_api::types::string::Datetime,
+    ///The at://uri of the challenger's blue.2048.game on the seed. Without one the challenger's first game on the seed after the challenge counts
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub game: core::option::Option<String>,
+    ///DID of the player being challenged
+    pub opponent: atrium_api::types::string::Did,
+    ///The starting seed both players play
+    pub seed: i64,
+    ///Width and height of the board the seed is played on
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub size: core::option::Option<i64>,
+    ///The sync status of this record with the users AT Protocol repo.
+    pub sync_status: crate::blue::_2048::defs::SyncStatus,
+}
+pub type Record = atrium_api::types::Object<RecordData>;
+impl From<atrium_api::types::Unknown> for RecordData {
+    fn from(value: atrium_api::types::Unknown) -> Self {
+        Self::try_from_unknown(value).unwrap()
+    }
+}
@@ -1,6 +1,43 @@
 // @generated - This file is generated by esquema-codegen (forked from atrium-codegen). DO NOT EDIT.
 //!Definitions for the `blue.2048.defs` namespace.
 //!Reusable types for blue.2048 lexicons
+///A player's stats for games played on one board size.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BoardSizeStatsData {
+    pub games_played: i64,
+    pub highest_number_block: i64,
+    pub highest_score: i64,
+    ///The width and height of the board, 4 for a 4x4 board
+    pub size: i64,
+    pub times_twenty_forty_eight_been_found: i64,
+}
+pub type BoardSizeStats = atrium_api::types::Object<BoardSizeStatsData>;
+///How many times the player has moved in each direction.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectionCountsData {
+    pub down: i64,
+    pub left: i64,
+    pub right: i64,
+    pub up: i64,
+}
+pub type DirectionCounts = atrium_api::types::Object<DirectionCountsData>;
+///A player's stats for one timed or move limited game mode on one board size. Classic games are only counted in the main stats.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ModeStatsData {
+    pub games_played: i64,
+    pub highest_number_block: i64,
+    pub highest_score: i64,
+    ///Seconds for timeAttack, moves for moveLimit
+    pub limit: i64,
+    pub mode: String,
+    ///The width and height of the board, 4 for a 4x4 board
+    pub size: i64,
+    pub total_score: i64,
+}
+pub type ModeStats = atrium_api::types::Object<ModeStatsData>;
 ///The sync status for a record used to help sync between your ATProto record and local record.
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
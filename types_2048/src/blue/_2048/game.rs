@@ -9,12 +9,84 @@ pub struct RecordData {
     pub created_at: atrium_api::types::string::Datetime,
     ///The game's current score
     pub current_score: i64,
+    ///How many hints the player asked for, counting every move made by the autoplay demo. Games with hints should not be ranked
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub hints_used: core::option::Option<i64>,
+    ///The highest block on the board when the game ended
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub max_tile: core::option::Option<i64>,
+    ///The rules the game was played under. Games without a mode are classic games
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub mode: core::option::Option<String>,
+    ///Seconds for a timeAttack game, moves for a moveLimit game
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub mode_limit: core::option::Option<i64>,
+    ///Play time in milliseconds when each move in the seededRecording was made, in the same order as the moves
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub move_times: core::option::Option<Vec<i64>>,
+    ///Time in milliseconds the player spent actively playing the game
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub play_time: core::option::Option<i64>,
+    ///The score the player is trying to beat when playing a seed someone shared with them
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub score_to_beat: core::option::Option<i64>,
+    ///When the server issued the game's seed for ranked play. Only games on an issued seed count for global leaderboards, ones on a seed the player picked are unranked
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub seed_issued_at: core::option::Option<atrium_api::types::string::Datetime>,
     ///This is the recording of the game. Like chess notation, but for 2048
     pub seeded_recording: String,
+    ///When the first move was made. A timeAttack game's time runs from here whether or not the game is on screen
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub started_at: core::option::Option<atrium_api::types::string::Datetime>,
     ///The sync status of this record with the users AT Protocol repo.
     pub sync_status: crate::blue::_2048::defs::SyncStatus,
+    ///Play time in milliseconds it took to find the first 2048 block
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub time_to_find_twenty_forty_eight: core::option::Option<i64>,
+    ///How many moves the player took back with undo. Games with undos should be ranked separately from ones without
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub undo_count: core::option::Option<i64>,
     ///The player has found a 2048 tile (they have won)
     pub won: bool,
+    ///The move number that made the first 2048 block. Moves after it were played once the player chose to keep going
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub won_at_move: core::option::Option<i64>,
 }
 pub type Record = atrium_api::types::Object<RecordData>;
 impl From<atrium_api::types::Unknown> for RecordData {
@@ -0,0 +1,24 @@
+// @generated - This file is generated by esquema-codegen (forked from atrium-codegen). DO NOT EDIT.
+//!Definitions for the `blue.2048.player.achievement` namespace.
+use atrium_api::types::TryFromUnknown;
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordData {
+    ///The id of the achievement that was unlocked
+    pub achievement: String,
+    pub created_at: atrium_api::types::string::Datetime,
+    ///The record key of the blue.2048.game that unlocked the achievement
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub game: core::option::Option<atrium_api::types::string::RecordKey>,
+    ///The sync status of this record with the users AT Protocol repo.
+    pub sync_status: crate::blue::_2048::defs::SyncStatus,
+}
+pub type Record = atrium_api::types::Object<RecordData>;
+impl From<atrium_api::types::Unknown> for RecordData {
+    fn from(value: atrium_api::types::Unknown) -> Self {
+        Self::try_from_unknown(value).unwrap()
+    }
+}
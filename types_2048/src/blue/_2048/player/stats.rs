@@ -4,23 +4,95 @@ use atrium_api::types::TryFromUnknown;
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct RecordData {
-    ///Average score across all games
+    ///Average score across all classic 4x4 games
     pub average_score: i64,
+    ///Classic stats split out by the size of board the games were played on, 4x4 included
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub board_sizes: core::option::Option<Vec<crate::blue::_2048::defs::BoardSizeStats>>,
+    ///The number of days in a row the player has finished a game, ending on the day of lastPlayedAt
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub consecutive_days_played: core::option::Option<i64>,
     pub created_at: atrium_api::types::string::Datetime,
-    ///Total numbers of games the user has played
+    ///Total moves made in each direction across all games
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub direction_counts: core::option::Option<crate::blue::_2048::defs::DirectionCounts>,
+    ///The shortest play time in milliseconds it has taken to find the 2048 block on a 4x4 board
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub fastest_time_to_find_twenty_forty_eight: core::option::Option<i64>,
+    ///Won classic 4x4 games the player kept playing after reaching the 2048 block
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub games_continued_after_win: core::option::Option<i64>,
+    ///Total numbers of classic 4x4 games the user has played
     pub games_played: i64,
-    ///The highest number block the player has fround. example 128, 256, etc
+    ///Classic 4x4 games where the 2048 block was reached
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub games_won: core::option::Option<i64>,
+    ///The highest number block the player has fround in a classic 4x4 game. example 128, 256, etc
     pub highest_number_block: i64,
-    ///The highest score the user has gotten in a game
+    ///The highest score the user has gotten in a classic 4x4 game
     pub highest_score: i64,
-    ///The smallest number of moves to get the 2048 block
+    ///When the player last finished a game
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub last_played_at: core::option::Option<atrium_api::types::string::Datetime>,
+    ///The smallest number of moves to get the 2048 block on a 4x4 board
     pub least_moves_to_find_twenty_forty_eight: i64,
+    ///The most days in a row the player has finished a game
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub longest_consecutive_days_played: core::option::Option<i64>,
+    ///Median score of the classic 4x4 games saved on the device that last updated the record. The record does not keep every score, so games only played on other devices are left out
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub median_score: core::option::Option<i64>,
+    ///Stats for timed and move limited games, kept apart so they don't change the classic stats
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub modes: core::option::Option<Vec<crate::blue::_2048::defs::ModeStats>>,
     ///The sync status of this record with the users AT Protocol repo.
     pub sync_status: crate::blue::_2048::defs::SyncStatus,
-    ///Times the 2048 block has been found also count as wins
+    ///Times the 2048 block has been found in classic 4x4 games also count as wins
     pub times_twenty_forty_eight_been_found: i64,
-    ///Total score across all games
+    ///Total number of moves (swipes) made across all games
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub total_moves: core::option::Option<i64>,
+    ///Total score across all classic 4x4 games
     pub total_score: i64,
+    ///Total play time in milliseconds across all games
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub total_time_played: core::option::Option<i64>,
 }
 pub type Record = atrium_api::types::Object<RecordData>;
 impl From<atrium_api::types::Unknown> for RecordData {
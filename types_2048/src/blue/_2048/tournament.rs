@@ -0,0 +1,86 @@
+// @generated - This file is generated by esquema-codegen (forked from atrium-codegen). DO NOT EDIT.
+//!Definitions for the `blue.2048.tournament` namespace.
+use atrium_api::types::TryFromUnknown;
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordData {
+    ///DID of the player who won the tournament, missing until the final round is decided
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub champion: core::option::Option<atrium_api::types::string::Did>,
+    pub created_at: atrium_api::types::string::Datetime,
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub description: core::option::Option<String>,
+    pub name: String,
+    pub rounds: Vec<Round>,
+}
+pub type Record = atrium_api::types::Object<RecordData>;
+impl From<atrium_api::types::Unknown> for RecordData {
+    fn from(value: atrium_api::types::Unknown) -> Self {
+        Self::try_from_unknown(value).unwrap()
+    }
+}
+///Two players paired off in a bracket round. A single player had a bye
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchData {
+    pub players: Vec<atrium_api::types::string::Did>,
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub winner: core::option::Option<atrium_api::types::string::Did>,
+}
+pub type Match = atrium_api::types::Object<MatchData>;
+///One round of a tournament and how the players in it did
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundData {
+    ///Games created after this do not count for the round
+    pub deadline: atrium_api::types::string::Datetime,
+    ///standings advances the top scores, bracket pairs players off and advances the winner of each match
+    pub format: String,
+    ///The pairings of a bracket round
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub matches: core::option::Option<Vec<Match>>,
+    pub name: String,
+    ///The starting seed every player in the round plays
+    pub seed: i64,
+    ///Width and height of the board the seed is played on
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub size: core::option::Option<i64>,
+    pub standings: Vec<Standing>,
+}
+pub type Round = atrium_api::types::Object<RoundData>;
+///A player's place in a round
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StandingData {
+    ///If the player made it to the next round, or won the final
+    pub advanced: bool,
+    ///The at://uri of the blue.2048.game that counted
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub game: core::option::Option<String>,
+    pub player: atrium_api::types::string::Did,
+    ///Score of the game that counted, missing when the player did not play the seed in time
+    #[cfg_attr(
+        feature = "skip_serializing",
+        serde(skip_serializing_if = "core::option::Option::is_none")
+    )]
+    pub score: core::option::Option<i64>,
+}
+pub type Standing = atrium_api::types::Object<StandingData>;
@@ -3,21 +3,49 @@
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(tag = "$type")]
 pub enum KnownRecord {
+    #[serde(rename = "blue.2048.ban")]
+    Blue2048Ban(Box<crate::blue::_2048::ban::Record>),
+    #[serde(rename = "blue.2048.challenge")]
+    Blue2048Challenge(Box<crate::blue::_2048::challenge::Record>),
     #[serde(rename = "blue.2048.game")]
     Blue2048Game(Box<crate::blue::_2048::game::Record>),
     #[serde(rename = "blue.2048.key.game")]
     Blue2048KeyGame(Box<crate::blue::_2048::key::game::Record>),
     #[serde(rename = "blue.2048.key.player.stats")]
     Blue2048KeyPlayerStats(Box<crate::blue::_2048::key::player::stats::Record>),
+    #[serde(rename = "blue.2048.player.achievement")]
+    Blue2048PlayerAchievement(Box<crate::blue::_2048::player::achievement::Record>),
     #[serde(rename = "blue.2048.player.profile")]
     Blue2048PlayerProfile(Box<crate::blue::_2048::player::profile::Record>),
     #[serde(rename = "blue.2048.player.stats")]
     Blue2048PlayerStats(Box<crate::blue::_2048::player::stats::Record>),
+    #[serde(rename = "blue.2048.tournament")]
+    Blue2048Tournament(Box<crate::blue::_2048::tournament::Record>),
     #[serde(rename = "blue.2048.verification.game")]
     Blue2048VerificationGame(Box<crate::blue::_2048::verification::game::Record>),
     #[serde(rename = "blue.2048.verification.stats")]
     Blue2048VerificationStats(Box<crate::blue::_2048::verification::stats::Record>),
 }
+impl From<crate::blue::_2048::ban::Record> for KnownRecord {
+    fn from(record: crate::blue::_2048::ban::Record) -> Self {
+        KnownRecord::Blue2048Ban(Box::new(record))
+    }
+}
+impl From<crate::blue::_2048::ban::RecordData> for KnownRecord {
+    fn from(record_data: crate::blue::_2048::ban::RecordData) -> Self {
+        KnownRecord::Blue2048Ban(Box::new(record_data.into()))
+    }
+}
+impl From<crate::blue::_2048::challenge::Record> for KnownRecord {
+    fn from(record: crate::blue::_2048::challenge::Record) -> Self {
+        KnownRecord::Blue2048Challenge(Box::new(record))
+    }
+}
+impl From<crate::blue::_2048::challenge::RecordData> for KnownRecord {
+    fn from(record_data: crate::blue::_2048::challenge::RecordData) -> Self {
+        KnownRecord::Blue2048Challenge(Box::new(record_data.into()))
+    }
+}
 impl From<crate::blue::_2048::game::Record> for KnownRecord {
     fn from(record: crate::blue::_2048::game::Record) -> Self {
         KnownRecord::Blue2048Game(Box::new(record))
@@ -48,6 +76,16 @@ impl From<crate::blue::_2048::key::player::stats::RecordData> for KnownRecord {
         KnownRecord::Blue2048KeyPlayerStats(Box::new(record_data.into()))
     }
 }
+impl From<crate::blue::_2048::player::achievement::Record> for KnownRecord {
+    fn from(record: crate::blue::_2048::player::achievement::Record) -> Self {
+        KnownRecord::Blue2048PlayerAchievement(Box::new(record))
+    }
+}
+impl From<crate::blue::_2048::player::achievement::RecordData> for KnownRecord {
+    fn from(record_data: crate::blue::_2048::player::achievement::RecordData) -> Self {
+        KnownRecord::Blue2048PlayerAchievement(Box::new(record_data.into()))
+    }
+}
 impl From<crate::blue::_2048::player::profile::Record> for KnownRecord {
     fn from(record: crate::blue::_2048::player::profile::Record) -> Self {
         KnownRecord::Blue2048PlayerProfile(Box::new(record))
@@ -68,6 +106,16 @@ impl From<crate::blue::_2048::player::stats::RecordData> for KnownRecord {
         KnownRecord::Blue2048PlayerStats(Box::new(record_data.into()))
     }
 }
+impl From<crate::blue::_2048::tournament::Record> for KnownRecord {
+    fn from(record: crate::blue::_2048::tournament::Record) -> Self {
+        KnownRecord::Blue2048Tournament(Box::new(record))
+    }
+}
+impl From<crate::blue::_2048::tournament::RecordData> for KnownRecord {
+    fn from(record_data: crate::blue::_2048::tournament::RecordData) -> Self {
+        KnownRecord::Blue2048Tournament(Box::new(record_data.into()))
+    }
+}
 impl From<crate::blue::_2048::verification::game::Record> for KnownRecord {
     fn from(record: crate::blue::_2048::verification::game::Record) -> Self {
         KnownRecord::Blue2048VerificationGame(Box::new(record))